@@ -0,0 +1,2 @@
+ALTER TABLE service ADD COLUMN pre_deploy_cmd TEXT;
+ALTER TABLE service ADD COLUMN post_deploy_cmd TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE service_env (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    UNIQUE(service_id, key)
+);
@@ -0,0 +1 @@
+ALTER TABLE service_env ADD COLUMN secret bool NOT NULL DEFAULT false;
@@ -0,0 +1,8 @@
+CREATE TABLE deploy_key (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    public_key TEXT NOT NULL,
+    key_path TEXT NOT NULL
+);
+
+ALTER TABLE service ADD COLUMN deploy_key_id INTEGER REFERENCES deploy_key(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN archived bool NOT NULL DEFAULT false;
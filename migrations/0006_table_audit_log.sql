@@ -0,0 +1,11 @@
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    service_id INTEGER,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    source_ip TEXT,
+    changes TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX audit_log_service_id ON audit_log(service_id);
@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN schedule TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE service ADD COLUMN poll_interval_secs INTEGER;
+ALTER TABLE service ADD COLUMN last_commit TEXT;
@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN build_before_up bool NOT NULL DEFAULT false;
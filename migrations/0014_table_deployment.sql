@@ -0,0 +1,11 @@
+ALTER TABLE service ADD COLUMN push_image bool NOT NULL DEFAULT false;
+
+CREATE TABLE deployment (
+    id INTEGER PRIMARY KEY,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    service_id INTEGER NOT NULL,
+    commit_sha TEXT,
+    images TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX deployment_service_id ON deployment(service_id);
@@ -0,0 +1,9 @@
+CREATE TABLE node (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    hostname TEXT,
+    last_seen TEXT
+);
+
+ALTER TABLE service ADD COLUMN node_id INTEGER REFERENCES node(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN health_grace_secs INTEGER;
@@ -0,0 +1,3 @@
+ALTER TABLE service ADD COLUMN last_status TEXT;
+ALTER TABLE service ADD COLUMN last_status_detail TEXT;
+ALTER TABLE service ADD COLUMN last_status_at TEXT;
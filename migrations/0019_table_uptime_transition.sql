@@ -0,0 +1,8 @@
+CREATE TABLE uptime_transition (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    up BOOLEAN NOT NULL,
+    at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX uptime_transition_service_at ON uptime_transition(service_id, at);
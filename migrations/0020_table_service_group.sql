@@ -0,0 +1,6 @@
+CREATE TABLE service_group (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL
+);
+
+ALTER TABLE service ADD COLUMN group_id INTEGER REFERENCES service_group(id) ON DELETE SET NULL;
@@ -0,0 +1,5 @@
+ALTER TABLE service ADD COLUMN environment TEXT;
+ALTER TABLE service ADD COLUMN branch TEXT;
+
+-- a repo is registered at most once per environment
+CREATE UNIQUE INDEX service_repo_environment ON service(repo_url, environment);
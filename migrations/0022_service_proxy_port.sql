@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN proxy_port INTEGER;
@@ -0,0 +1,8 @@
+ALTER TABLE service ADD COLUMN review_required bool NOT NULL DEFAULT false;
+
+CREATE TABLE deploy_review (
+    service_id INTEGER PRIMARY KEY REFERENCES service(id) ON DELETE CASCADE,
+    commit_sha TEXT,
+    diff TEXT NOT NULL,
+    requested_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN watch_paths TEXT;
@@ -0,0 +1,5 @@
+CREATE TABLE git_credential (
+    service_id INTEGER PRIMARY KEY REFERENCES service(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    token TEXT NOT NULL
+);
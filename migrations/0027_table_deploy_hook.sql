@@ -0,0 +1,5 @@
+CREATE TABLE deploy_hook (
+    service_id INTEGER PRIMARY KEY REFERENCES service(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
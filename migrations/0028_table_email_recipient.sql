@@ -0,0 +1,7 @@
+CREATE TABLE email_recipient (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(service_id, address)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE push_target (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    channel TEXT NOT NULL CHECK (channel IN ('ntfy', 'pushover')),
+    target TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(service_id, channel, target)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE notification_rule (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    channel TEXT NOT NULL CHECK (channel IN ('email', 'ntfy', 'pushover')),
+    deployed INTEGER NOT NULL DEFAULT 1,
+    deploy_failed INTEGER NOT NULL DEFAULT 1,
+    health INTEGER NOT NULL DEFAULT 1,
+    UNIQUE(service_id, channel)
+);
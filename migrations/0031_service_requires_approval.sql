@@ -0,0 +1,7 @@
+ALTER TABLE service ADD COLUMN requires_approval bool NOT NULL DEFAULT false;
+
+CREATE TABLE deploy_approval (
+    service_id INTEGER PRIMARY KEY REFERENCES service(id) ON DELETE CASCADE,
+    trigger TEXT NOT NULL,
+    requested_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- compose lowercases project names, so names differing only in case share a project
+UPDATE service SET name = name || '-' || id
+WHERE id NOT IN (SELECT MIN(id) FROM service GROUP BY lower(name));
+
+CREATE UNIQUE INDEX service_name_nocase ON service(name COLLATE NOCASE);
@@ -0,0 +1,3 @@
+-- NULL for deployments recorded before they were timed
+ALTER TABLE deployment ADD COLUMN duration_ms INTEGER;
+ALTER TABLE deployment ADD COLUMN steps TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- comma-separated compose volume names snapshotted before each deploy
+ALTER TABLE service ADD COLUMN backup_volumes TEXT;
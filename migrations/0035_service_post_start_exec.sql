@@ -0,0 +1,3 @@
+-- run with `compose exec` in one of the service's containers once it's up
+ALTER TABLE service ADD COLUMN post_start_exec TEXT;
+ALTER TABLE service ADD COLUMN post_start_container TEXT;
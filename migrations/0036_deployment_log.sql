@@ -0,0 +1,4 @@
+-- failed and cancelled deploys are kept too, with what they logged
+ALTER TABLE deployment ADD COLUMN outcome TEXT NOT NULL DEFAULT 'succeeded';
+ALTER TABLE deployment ADD COLUMN log TEXT NOT NULL DEFAULT '';
+ALTER TABLE deployment ADD COLUMN compose_file TEXT;
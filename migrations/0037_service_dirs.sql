@@ -0,0 +1,4 @@
+-- directories holding the service's clone and live dir instead of SERVICE_REPO_PATH and
+-- SERVICE_LIVE_PATH, for services that must live on a specific mount
+ALTER TABLE service ADD COLUMN repo_dir TEXT;
+ALTER TABLE service ADD COLUMN live_dir TEXT;
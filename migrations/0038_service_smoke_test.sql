@@ -0,0 +1,5 @@
+-- requested after each deploy's containers are up; the deploy fails unless it answers with
+-- smoke_status (any 2xx when unset) and a body matching smoke_regex
+ALTER TABLE service ADD COLUMN smoke_url TEXT;
+ALTER TABLE service ADD COLUMN smoke_status INTEGER;
+ALTER TABLE service ADD COLUMN smoke_regex TEXT;
@@ -0,0 +1,29 @@
+-- commands run in a service's containers on a cron schedule, with `compose exec` in a
+-- running container or `compose run --rm` in a fresh one
+CREATE TABLE service_job (
+    id INTEGER PRIMARY KEY,
+    service_id INTEGER NOT NULL REFERENCES service(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    schedule TEXT NOT NULL,
+    mode TEXT NOT NULL DEFAULT 'exec' CHECK (mode IN ('exec', 'run')),
+    container TEXT,
+    command TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(service_id, name)
+);
+
+CREATE TABLE job_run (
+    id INTEGER PRIMARY KEY,
+    job_id INTEGER NOT NULL REFERENCES service_job(id) ON DELETE CASCADE,
+    trigger TEXT NOT NULL,
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    duration_ms INTEGER NOT NULL,
+    ok INTEGER NOT NULL,
+    exit_code INTEGER,
+    output TEXT NOT NULL
+);
+
+CREATE INDEX job_run_job_id ON job_run(job_id);
+
+ALTER TABLE notification_rule ADD COLUMN job_failed INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,3 @@
+-- a one-off job: deploys run this compose service to completion with `compose run --rm`
+-- instead of `up -d`, and succeed when it exits 0
+ALTER TABLE service ADD COLUMN one_off TEXT;
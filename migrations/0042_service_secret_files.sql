@@ -0,0 +1 @@
+ALTER TABLE service ADD COLUMN secret_files bool NOT NULL DEFAULT false;
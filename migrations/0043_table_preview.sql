@@ -0,0 +1,9 @@
+ALTER TABLE service ADD COLUMN previews bool NOT NULL DEFAULT false;
+ALTER TABLE service ADD COLUMN preview_of INTEGER REFERENCES service(id) ON DELETE SET NULL;
+
+-- the pull request a preview service was opened for; see `service::preview`
+CREATE TABLE preview (
+    service_id INTEGER PRIMARY KEY REFERENCES service(id) ON DELETE CASCADE,
+    pr_number INTEGER NOT NULL,
+    expires_at DATETIME NOT NULL
+);
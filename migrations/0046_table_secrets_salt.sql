@@ -0,0 +1,4 @@
+CREATE TABLE secrets_salt (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    salt TEXT NOT NULL
+);
@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+use crate::modules::service::ServiceStatus;
+
+const POLL: Duration = Duration::from_secs(2);
+
+#[derive(Parser)]
+#[command(version, about = "A CI/CD for what brennanxyz needs right now.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Address of the running instance, by default APP_HOST and APP_PORT
+    #[arg(long, global = true, env = "WRAUT_URL")]
+    url: Option<String>,
+    /// User to sign in as, once the instance has users
+    #[arg(long, global = true, env = "WRAUT_USER")]
+    user: Option<String>,
+    #[arg(long, global = true, env = "WRAUT_PASSWORD", hide = true)]
+    password: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the dashboard and API (the default)
+    Serve,
+    /// Run pipelines for a central instance
+    Agent,
+    /// List services
+    List {
+        /// List archived services instead
+        #[arg(long)]
+        archived: bool,
+    },
+    /// Show whether services are running and how their last pipeline went
+    Status {
+        /// Only this service, by name or id
+        service: Option<String>,
+    },
+    /// Deploy a service and wait for it to finish
+    Deploy {
+        /// The service, by name or id
+        service: String,
+        /// Return once the deploy is queued
+        #[arg(long)]
+        no_wait: bool,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("Unable to reach wraut | {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("wraut answered {0} | {1}")]
+    Api(StatusCode, String),
+    #[error("No service is named '{0}'")]
+    NotFound(String),
+    #[error("More than one service is named '{0}'; use its id ({1})")]
+    Ambiguous(String, String),
+    #[error("Deploy of {0} ended {1}")]
+    Failed(String, String),
+}
+
+// a service as the API lists it; viewers get only its status, without `repo_url`
+#[derive(Deserialize)]
+struct ServiceView {
+    id: i64,
+    name: String,
+    environment: Option<String>,
+    repo_url: Option<String>,
+    last_status: Option<String>,
+    last_status_detail: Option<String>,
+    last_status_at: Option<String>,
+    running: Option<bool>,
+}
+
+impl ServiceView {
+    fn recorded_status(&self) -> Option<ServiceStatus> {
+        ServiceStatus::from_record(
+            self.last_status.as_deref()?,
+            self.last_status_detail.as_deref(),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error: String,
+}
+
+struct Api {
+    client: Client,
+    url: String,
+    credentials: Option<(String, Option<String>)>,
+}
+
+impl Api {
+    fn new(cli: Cli) -> Self {
+        let url = cli.url.unwrap_or_else(|| {
+            let host = std::env::var("APP_HOST").unwrap_or("127.0.0.1".to_string());
+            let port = std::env::var("APP_PORT").unwrap_or("8080".to_string());
+            // a wildcard bind is reached through loopback
+            let host = match host.as_str() {
+                "0.0.0.0" | "::" => "127.0.0.1".to_string(),
+                _ => host,
+            };
+            format!("http://{}:{}", host, port)
+        });
+        Api {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            credentials: cli.user.map(|user| (user, cli.password)),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.client.request(method, format!("{}{}", self.url, path));
+        match &self.credentials {
+            Some((user, password)) => request.basic_auth(user, password.as_ref()),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, CliError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.json().await?);
+        }
+        let message = match response.json::<ApiError>().await {
+            Ok(e) => e.error,
+            Err(_) => status.canonical_reason().unwrap_or("error").to_string(),
+        };
+        Err(CliError::Api(status, message))
+    }
+
+    async fn services(&self, archived: bool) -> Result<Vec<ServiceView>, CliError> {
+        let path = format!("/api/v1/services?archived={}", archived);
+        self.send(self.request(Method::GET, &path)).await
+    }
+
+    async fn service(&self, id: i64) -> Result<ServiceView, CliError> {
+        self.send(self.request(Method::GET, &format!("/api/v1/services/{}", id)))
+            .await
+    }
+
+    // by id, or by name when that's unambiguous
+    async fn find(&self, service: &str) -> Result<ServiceView, CliError> {
+        let mut matching: Vec<ServiceView> = self
+            .services(false)
+            .await?
+            .into_iter()
+            .filter(|s| s.name == service || s.id.to_string() == service)
+            .collect();
+        match matching.len() {
+            0 => Err(CliError::NotFound(service.to_string())),
+            1 => Ok(matching.remove(0)),
+            _ => Err(CliError::Ambiguous(
+                service.to_string(),
+                matching
+                    .iter()
+                    .map(|s| s.id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        }
+    }
+}
+
+fn status_text(service: &ServiceView) -> String {
+    match (&service.last_status, &service.last_status_detail) {
+        (Some(kind), Some(detail)) => format!("{} ({})", kind, detail),
+        (Some(kind), None) => kind.clone(),
+        _ => "-".to_string(),
+    }
+}
+
+fn running_text(running: Option<bool>) -> &'static str {
+    match running {
+        Some(true) => "up",
+        Some(false) => "down",
+        None => "?",
+    }
+}
+
+fn list(services: Vec<ServiceView>) {
+    println!("{:<6}{:<28}{:<16}REPO", "ID", "NAME", "ENVIRONMENT");
+    for service in services {
+        println!(
+            "{:<6}{:<28}{:<16}{}",
+            service.id,
+            service.name,
+            service.environment.as_deref().unwrap_or("-"),
+            service.repo_url.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+fn status(services: Vec<ServiceView>) {
+    println!(
+        "{:<6}{:<28}{:<8}{:<22}LAST STATUS",
+        "ID", "NAME", "STATE", "SINCE"
+    );
+    for service in services {
+        println!(
+            "{:<6}{:<28}{:<8}{:<22}{}",
+            service.id,
+            service.name,
+            running_text(service.running),
+            service.last_status_at.as_deref().unwrap_or("-"),
+            status_text(&service)
+        );
+    }
+}
+
+// the pipeline has stopped, one way or another
+fn settled(status: &ServiceStatus) -> bool {
+    status.is_failure()
+        || matches!(
+            status,
+            ServiceStatus::Running
+                | ServiceStatus::Completed
+                | ServiceStatus::Degraded(_)
+                | ServiceStatus::Inactive
+                | ServiceStatus::AwaitingReview
+                | ServiceStatus::Declined
+        )
+}
+
+async fn deploy(api: &Api, service: &str, wait: bool) -> Result<(), CliError> {
+    let service = api.find(service).await?;
+    let before = service.last_status_at.clone();
+    let _: serde_json::Value = api
+        .send(api.request(
+            Method::POST,
+            &format!("/api/v1/services/{}/deploy", service.id),
+        ))
+        .await?;
+    println!("Deploying {}", service.name);
+    if !wait {
+        return Ok(());
+    }
+
+    let mut shown = None;
+    loop {
+        tokio::time::sleep(POLL).await;
+        let service = api.service(service.id).await?;
+        // nothing recorded since the request yet
+        if service.last_status_at == before {
+            continue;
+        }
+        let Some(status) = service.recorded_status() else {
+            continue;
+        };
+        let text = status_text(&service);
+        if shown.as_ref() != Some(&text) {
+            println!("  {}", text);
+            shown = Some(text.clone());
+        }
+        if !settled(&status) {
+            continue;
+        }
+        return match status.is_failure() || matches!(status, ServiceStatus::Declined) {
+            true => Err(CliError::Failed(service.name, text)),
+            false => Ok(()),
+        };
+    }
+}
+
+// runs a client subcommand against the running instance, returning the exit code
+pub async fn run(cli: Cli, command: Command) -> i32 {
+    let api = Api::new(cli);
+    let result = match command {
+        Command::List { archived } => api.services(archived).await.map(list),
+        Command::Status { service: None } => api.services(false).await.map(status),
+        Command::Status {
+            service: Some(service),
+        } => api.find(&service).await.map(|s| status(vec![s])),
+        Command::Deploy { service, no_wait } => deploy(&api, &service, !no_wait).await,
+        // started by main
+        Command::Serve | Command::Agent => Ok(()),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
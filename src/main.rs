@@ -1,24 +1,130 @@
 //! A CI/CD for what brennanxyz needs right now.
+mod cli;
 mod modules;
 mod routes;
 
-use modules::{AppState, Config, ServiceBroadcast};
+use clap::Parser;
+use cli::{Cli, Command};
+use modules::{
+    AppState, Config, LogFormat, ServiceBroadcast,
+    agent::{Agents, worker},
+    backup, db,
+    maintenance::{self, logs::LOG_FILE},
+    manifest, network, notify, request_id, scheduler, secrets,
+    service::{ServiceEvent, cache::DockerCache, job::RunningJobs, process::InFlight},
+    tools, uptime,
+    user::{self, Role},
+};
 use routes::{
-    add_new_service, all_status_request, app, deactivate_service, delete_service, deploy_service,
-    edit_existing_service, edit_service_form, live_services, new_service_form, status,
+    activity_feed, add_email_recipient, add_group, add_new_service, add_node, add_push_target,
+    add_service_job, adopt_form, adopt_panel, all_status_request, api, app, approve_deploy,
+    approve_review, archive_service, archived_services, atom_feed, audit_log,
+    auth::{self, require},
+    badge, cancel_deploy, create_user, csrf, deactivate_service, decline_deploy, decline_review,
+    delete_deploy_key, delete_email_recipient, delete_group, delete_node, delete_push_target,
+    delete_service, delete_service_env, delete_service_hook, delete_service_job, delete_user,
+    deploy_all, deploy_group, deploy_keys, deploy_service, duplicate_service_form,
+    edit_existing_service, edit_service_form, generate_deploy_key, graphql, groups, live_service,
+    live_services, live_statuspage, maintenance_panel, new_service_form, nodes, promote_service,
+    prune, remove_orphans, restart_service, restore_service, restore_volumes, rotate_service_hook,
+    run_service_job, save_compose_override, save_notification_rules, service_compose,
+    service_deployments, service_env_editor, service_files, service_hook, service_jobs,
+    service_notifications, service_review, service_terminal, service_volumes, set_service_env,
+    set_service_job_active, start_service, static_asset, status, statuspage, stop_service,
+    tear_down_service, users, ws,
 };
 
 use axum::{
-    Router,
+    Router, middleware,
     routing::{delete, get, post, put},
 };
 use sqlx::{Pool, sqlite::Sqlite};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{signal, sync::broadcast};
+use tokio_util::task::TaskTracker;
 use tracing::{Level, event};
-use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_appender::rolling::RollingFileAppender;
+use tracing_subscriber::{filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+async fn shutdown_signal(broadcaster: broadcast::Sender<ServiceEvent>) {
+    let ctrl_c = async {
+        if let Err(e) = signal::ctrl_c().await {
+            event!(Level::ERROR, "Failed to listen for SIGINT | {}", e);
+        }
+    };
+
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Failed to listen for SIGTERM | {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = ctrl_c => event!(Level::INFO, "Received SIGINT, shutting down..."),
+        _ = terminate => event!(Level::INFO, "Received SIGTERM, shutting down..."),
+    }
+
+    // close open SSE streams so the server can finish draining connections
+    let _ = broadcaster.send(ServiceEvent::Shutdown);
+}
+
+// creates the first admin from ADMIN_PASSWORD; with no users at all, access control stays off
+async fn bootstrap_admin(config: &Config, pool: &Pool<Sqlite>) {
+    match db::count_users(pool).await {
+        Ok(0) => (),
+        Ok(_) => return,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to check users | {}", e);
+            return;
+        }
+    }
+
+    let Some(password) = &config.admin_password else {
+        event!(
+            Level::WARN,
+            "No users configured; access control is disabled. Set ADMIN_PASSWORD or add a user."
+        );
+        return;
+    };
+
+    let created = match user::hash_password(password) {
+        Ok(hash) => db::new_user(pool, config.admin_username.clone(), hash, Role::Admin)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match created {
+        Ok(_) => event!(
+            Level::INFO,
+            "Created admin user '{}'.",
+            config.admin_username
+        ),
+        Err(e) => event!(Level::ERROR, "Unable to create admin user | {}", e),
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    let config = match Config::new() {
+    dotenv::dotenv().ok();
+    let mut cli = Cli::parse();
+    // `wraut agent` runs pipelines for a central instance instead of serving the dashboard;
+    // the other subcommands are clients of a running instance
+    let agent_mode = match cli.command.take() {
+        None | Some(Command::Serve) => false,
+        Some(Command::Agent) => true,
+        Some(command) => std::process::exit(cli::run(cli, command).await),
+    };
+    let loaded = match agent_mode {
+        true => Config::agent(),
+        false => Config::new(),
+    };
+    let mut config = match loaded {
         Ok(c) => {
             event!(Level::INFO, "Loaded configuration info.");
             c
@@ -30,17 +136,49 @@ async fn main() {
     };
 
     // setup logging
-    let logfile = tracing_appender::rolling::hourly(
-        config.logs_dir.to_string_lossy().to_string(),
-        "wraut.log",
-    );
-    let stdout = std::io::stdout.with_max_level(tracing::Level::INFO);
-    tracing_subscriber::fmt()
-        .pretty()
-        .with_writer(stdout.and(logfile))
+    let logfile = match RollingFileAppender::builder()
+        .rotation(config.log_rotation.clone())
+        .filename_prefix(LOG_FILE)
+        .build(&config.logs_dir)
+    {
+        Ok(appender) => appender,
+        Err(e) => panic!("Failed to open the log directory | {}", e),
+    };
+    let (pretty_file, json_file) = match config.log_format {
+        LogFormat::Pretty => (Some(fmt::layer().pretty().with_writer(logfile)), None),
+        LogFormat::Json => (
+            None,
+            Some(fmt::layer().json().with_ansi(false).with_writer(logfile)),
+        ),
+    };
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(fmt::layer().pretty().with_writer(std::io::stdout))
+        .with(pretty_file)
+        .with(json_file)
         .init();
     event!(Level::INFO, "Launching...");
+    tools::init(&config);
+    if let Err(e) = tools::preflight() {
+        event!(Level::ERROR, "{}", e);
+        panic!("{}", e);
+    }
+    tokio::spawn(maintenance::logs::retain(config.clone()));
+
+    if agent_mode {
+        event!(
+            Level::INFO,
+            "Running as an agent of {:?}",
+            config.agent_server
+        );
+        worker::run(config).await;
+        return;
+    }
     let db_string = &config.db_url;
+    if let Err(e) = backup::apply_staged(db_string) {
+        event!(Level::ERROR, "Unable to restore the staged backup | {}", e);
+        panic!("Unable to restore the staged backup | {}", e);
+    }
 
     // TODO: get or create
     let pool = match Pool::<Sqlite>::connect(db_string).await {
@@ -65,24 +203,409 @@ async fn main() {
         }
     };
 
+    if let Some(passphrase) = &config.secrets_passphrase {
+        let key = match secrets::unlock(&pool, passphrase).await {
+            Ok(key) => key,
+            Err(e) => {
+                event!(Level::ERROR, "Unable to derive the secrets key | {}", e);
+                panic!("Unable to derive the secrets key | {}", e);
+            }
+        };
+        if let Err(e) = secrets::upgrade(&pool, &key).await {
+            event!(Level::ERROR, "Unable to re-encrypt stored secrets | {}", e);
+        }
+        config.secrets_key = Some(key);
+    }
+
+    bootstrap_admin(&config, &pool).await;
+
     let app_state = AppState {
         config: config.clone(),
         pool,
-        service_broadcast: ServiceBroadcast::new(),
+        service_broadcast: ServiceBroadcast::new(config.broadcast_capacity),
+        deployments: TaskTracker::new(),
+        csrf_token: csrf::new_token(),
+        agents: Agents::default(),
+        in_flight: InFlight::default(),
+        running_jobs: RunningJobs::default(),
+        docker_cache: DockerCache::new(config.docker_cache_ttl),
+        deploy_slots: modules::deploy_slots(config.max_concurrent_deploys),
     };
+    let broadcaster = app_state.service_broadcast.broadcaster.clone();
+    tokio::spawn(
+        app_state
+            .service_broadcast
+            .clone()
+            .record(app_state.pool.clone()),
+    );
+    tokio::spawn(app_state.service_broadcast.clone().sequence());
+    tokio::spawn(scheduler::run(app_state.clone()));
+    tokio::spawn(scheduler::poll(app_state.clone()));
+    tokio::spawn(scheduler::usage(app_state.clone()));
+    tokio::spawn(scheduler::reconcile(app_state.clone()));
+    tokio::spawn(scheduler::watchdog(app_state.clone()));
+    tokio::spawn(scheduler::jobs(app_state.clone()));
+    tokio::spawn(scheduler::previews(app_state.clone()));
+    tokio::spawn(uptime::track(app_state.clone()));
+    tokio::spawn(notify::run(app_state.clone()));
+    tokio::spawn(notify::github::run(app_state.clone()));
+    tokio::spawn(notify::webhook::run(app_state.clone()));
+    tokio::spawn(maintenance::scheduled(app_state.clone()));
+    tokio::spawn(manifest::watch(app_state.clone()));
+    let deployments = app_state.deployments.clone();
 
     let app = Router::new()
         .route("/", get(app))
-        .route("/status", get(status))
-        .route("/html/service_form", get(new_service_form))
-        .route("/html/service_form/{id}", get(edit_service_form))
         .route("/html/live_services", get(live_services))
-        .route("/api/service", post(add_new_service))
-        .route("/api/service/{id}", put(edit_existing_service))
-        .route("/api/service/{id}/deploy", get(deploy_service))
-        .route("/api/service/{id}/deactivate", get(deactivate_service))
-        .route("/api/service/{id}", delete(delete_service))
+        .route("/html/live_services/{id}", get(live_service))
+        .route("/ws/live_services", get(ws::live_services))
         .route("/api/all_status", get(all_status_request))
+        .route("/logout", post(auth::logout))
+        .route(
+            "/html/service_form",
+            require(Role::Admin, get(new_service_form)),
+        )
+        .route(
+            "/html/service_form/{id}",
+            require(Role::Admin, get(edit_service_form)),
+        )
+        .route(
+            "/html/service_form/{id}/duplicate",
+            require(Role::Admin, get(duplicate_service_form)),
+        )
+        .route(
+            "/html/service/{id}/env",
+            require(Role::Admin, get(service_env_editor)),
+        )
+        .route(
+            "/html/service/{id}/hook",
+            require(Role::Admin, get(service_hook)),
+        )
+        .route(
+            "/html/service/{id}/jobs",
+            require(Role::Admin, get(service_jobs)),
+        )
+        .route(
+            "/html/service/{id}/notifications",
+            require(Role::Admin, get(service_notifications)),
+        )
+        .route("/html/service/{id}/deployments", get(service_deployments))
+        .route(
+            "/html/service/{id}/review",
+            require(Role::Deployer, get(service_review)),
+        )
+        .route(
+            "/html/service/{id}/volumes",
+            require(Role::Deployer, get(service_volumes)),
+        )
+        .route(
+            "/html/service/{id}/files",
+            require(Role::Admin, get(service_files)),
+        )
+        .route(
+            "/html/service/{id}/compose",
+            require(Role::Admin, get(service_compose)),
+        )
+        .route(
+            "/html/service/{id}/terminal",
+            require(Role::Admin, get(service_terminal)),
+        )
+        .route(
+            "/ws/service/{id}/terminal",
+            require(Role::Admin, get(ws::terminal)),
+        )
+        .route("/html/deploy_keys", require(Role::Admin, get(deploy_keys)))
+        .route(
+            "/html/archived_services",
+            require(Role::Admin, get(archived_services)),
+        )
+        .route("/html/audit_log", require(Role::Admin, get(audit_log)))
+        .route("/html/activity", get(activity_feed))
+        .route("/api/activity", get(api::activity))
+        .route("/html/users", require(Role::Admin, get(users)))
+        .route("/api/service", require(Role::Admin, post(add_new_service)))
+        .route(
+            "/api/service/{id}",
+            require(
+                Role::Admin,
+                put(edit_existing_service).delete(delete_service),
+            ),
+        )
+        .route(
+            "/api/service/{id}/compose",
+            require(
+                Role::Admin,
+                get(api::service_compose).post(save_compose_override),
+            ),
+        )
+        .route(
+            "/api/service/{id}/deploy",
+            require(Role::Deployer, post(deploy_service)),
+        )
+        .route(
+            "/api/service/{id}/deploy/cancel",
+            require(Role::Deployer, post(cancel_deploy)),
+        )
+        .route(
+            "/api/service/{id}/promote",
+            require(Role::Deployer, post(promote_service)),
+        )
+        .route(
+            "/api/service/{id}/review/approve",
+            require(Role::Deployer, post(approve_review)),
+        )
+        .route(
+            "/api/service/{id}/review/decline",
+            require(Role::Deployer, post(decline_review)),
+        )
+        .route(
+            "/api/service/{id}/approval/approve",
+            require(Role::Deployer, post(approve_deploy)),
+        )
+        .route(
+            "/api/service/{id}/approval/decline",
+            require(Role::Deployer, post(decline_deploy)),
+        )
+        .route(
+            "/api/service/{id}/stop",
+            require(Role::Deployer, post(stop_service)),
+        )
+        .route(
+            "/api/service/{id}/start",
+            require(Role::Deployer, post(start_service)),
+        )
+        .route(
+            "/api/service/{id}/restart",
+            require(Role::Deployer, post(restart_service)),
+        )
+        .route(
+            "/api/service/{id}/teardown",
+            require(Role::Deployer, post(tear_down_service)),
+        )
+        .route(
+            "/api/service/{id}/volumes/{snapshot}/restore",
+            require(Role::Deployer, post(restore_volumes)),
+        )
+        .route(
+            "/api/service/{id}/deactivate",
+            require(Role::Deployer, post(deactivate_service)),
+        )
+        .route(
+            "/api/service/{id}/archive",
+            require(Role::Admin, post(archive_service)),
+        )
+        .route(
+            "/api/service/{id}/restore",
+            require(Role::Admin, post(restore_service)),
+        )
+        .route(
+            "/api/service/{id}/email",
+            require(Role::Admin, post(add_email_recipient)),
+        )
+        .route(
+            "/api/service/{id}/email/{recipient_id}",
+            require(Role::Admin, delete(delete_email_recipient)),
+        )
+        .route(
+            "/api/service/{id}/push",
+            require(Role::Admin, post(add_push_target)),
+        )
+        .route(
+            "/api/service/{id}/push/{target_id}",
+            require(Role::Admin, delete(delete_push_target)),
+        )
+        .route(
+            "/api/service/{id}/jobs",
+            require(Role::Admin, post(add_service_job)),
+        )
+        .route(
+            "/api/service/{id}/jobs/{job_id}",
+            require(Role::Admin, delete(delete_service_job)),
+        )
+        .route(
+            "/api/service/{id}/jobs/{job_id}/active",
+            require(Role::Admin, post(set_service_job_active)),
+        )
+        .route(
+            "/api/service/{id}/jobs/{job_id}/run",
+            require(Role::Admin, post(run_service_job)),
+        )
+        .route(
+            "/api/service/{id}/notification-rules",
+            require(Role::Admin, post(save_notification_rules)),
+        )
+        .route(
+            "/api/service/{id}/hook",
+            require(
+                Role::Admin,
+                post(rotate_service_hook).delete(delete_service_hook),
+            ),
+        )
+        .route(
+            "/api/service/{id}/env",
+            require(Role::Admin, post(set_service_env)),
+        )
+        .route(
+            "/api/service/{id}/env/{env_id}",
+            require(Role::Admin, delete(delete_service_env)),
+        )
+        .route(
+            "/api/deploy_key",
+            require(Role::Admin, post(generate_deploy_key)),
+        )
+        .route(
+            "/api/deploy_key/{id}",
+            require(Role::Admin, delete(delete_deploy_key)),
+        )
+        .route("/api/user", require(Role::Admin, post(create_user)))
+        .route("/api/user/{id}", require(Role::Admin, delete(delete_user)))
+        .route(
+            "/html/maintenance",
+            require(Role::Admin, get(maintenance_panel)),
+        )
+        .route("/html/adopt", require(Role::Admin, get(adopt_panel)))
+        .route(
+            "/html/adopt/{project}",
+            require(Role::Admin, get(adopt_form)),
+        )
+        .route("/api/maintenance/prune", require(Role::Admin, post(prune)))
+        .route(
+            "/api/maintenance/orphans",
+            require(Role::Admin, post(remove_orphans)),
+        )
+        .route("/html/nodes", require(Role::Admin, get(nodes)))
+        .route("/html/groups", require(Role::Admin, get(groups)))
+        .route("/api/group", require(Role::Admin, post(add_group)))
+        .route(
+            "/api/group/{id}",
+            require(Role::Admin, delete(delete_group)),
+        )
+        .route("/api/deploy_all", require(Role::Deployer, post(deploy_all)))
+        .route(
+            "/api/group/{id}/deploy",
+            require(Role::Deployer, post(deploy_group)),
+        )
+        .route("/api/node", require(Role::Admin, post(add_node)))
+        .route("/api/node/{id}", require(Role::Admin, delete(delete_node)))
+        // dashboard routes above require the page's CSRF token
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            csrf::verify,
+        ))
+        .route(
+            "/api/v1/services",
+            get(api::list_services).merge(require(Role::Admin, post(api::create_service))),
+        )
+        .route(
+            "/api/v1/services/{id}",
+            get(api::get_service).merge(require(Role::Admin, put(api::update_service))),
+        )
+        .route(
+            "/api/v1/services/{id}/duplicate",
+            require(Role::Admin, post(api::duplicate_service)),
+        )
+        .route(
+            "/api/v1/services/{id}/deploy",
+            require(Role::Deployer, post(api::deploy_service)),
+        )
+        .route(
+            "/api/v1/services/{id}/promote",
+            require(Role::Deployer, post(api::promote_service)),
+        )
+        .route("/api/v1/services/{id}/review", get(api::service_review))
+        .route(
+            "/api/v1/services/{id}/review/approve",
+            require(Role::Deployer, post(api::approve_review)),
+        )
+        .route(
+            "/api/v1/services/{id}/review/decline",
+            require(Role::Deployer, post(api::decline_review)),
+        )
+        .route("/api/v1/services/{id}/approval", get(api::service_approval))
+        .route(
+            "/api/v1/services/{id}/approval/approve",
+            require(Role::Deployer, post(api::approve_deploy)),
+        )
+        .route(
+            "/api/v1/services/{id}/approval/decline",
+            require(Role::Deployer, post(api::decline_deploy)),
+        )
+        .route(
+            "/api/v1/services/{id}/stop",
+            require(Role::Deployer, post(api::stop_service)),
+        )
+        .route(
+            "/api/v1/services/{id}/start",
+            require(Role::Deployer, post(api::start_service)),
+        )
+        .route(
+            "/api/v1/services/{id}/restart",
+            require(Role::Deployer, post(api::restart_service)),
+        )
+        .route(
+            "/api/v1/services/{id}/teardown",
+            require(Role::Deployer, post(api::tear_down_service)),
+        )
+        .route("/api/v1/services/{id}/status", get(api::service_status))
+        .route(
+            "/api/v1/services/{id}/deployments",
+            get(api::service_deployments),
+        )
+        .route("/api/v1/services/{id}/uptime", get(api::service_uptime))
+        .route(
+            "/api/deployments/{id}/log",
+            require(Role::Deployer, get(api::deployment_log)),
+        )
+        .route(
+            "/api/deployments/{id}/compose",
+            require(Role::Deployer, get(api::deployment_compose)),
+        )
+        .route(
+            "/api/v1/deploy_all",
+            require(Role::Deployer, post(api::deploy_all)),
+        )
+        .route("/api/v1/groups", get(api::list_groups))
+        .route("/api/v1/graphql", post(graphql::graphql))
+        .route(
+            "/api/v1/groups/{id}/deploy",
+            require(Role::Deployer, post(api::deploy_group)),
+        )
+        .route(
+            "/api/admin/backup",
+            require(Role::Admin, post(api::create_backup)),
+        )
+        .route(
+            "/api/admin/backups",
+            require(Role::Admin, get(api::list_backups)),
+        )
+        .route(
+            "/api/admin/restore/{name}",
+            require(Role::Admin, post(api::restore_backup)),
+        )
+        // everything above needs a signed-in user once any user exists
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::authenticate,
+        ))
+        .route("/login", get(auth::login_page).post(auth::login))
+        .route("/agent/connect", get(ws::agent_connect))
+        .route("/hooks/deploy/{token}", post(api::deploy_hook))
+        .route("/hooks/preview/{token}", post(api::preview_hook))
+        .route("/status", get(status))
+        .route("/healthz", get(api::healthz))
+        .route("/readyz", get(api::readyz))
+        .route("/statuspage", get(statuspage))
+        .route("/statuspage/live", get(live_statuspage))
+        .route("/badge/{file}", get(badge))
+        .route("/feed.atom", get(atom_feed))
+        .route("/static/{*file}", get(static_asset))
+        .layer(middleware::from_fn(csrf::same_origin))
+        .layer(middleware::from_fn(network::restrict))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            network::identify,
+        ))
+        .layer(middleware::from_fn(request_id::tag))
         .with_state(app_state);
 
     let listener =
@@ -109,7 +632,13 @@ async fn main() {
             }
         };
 
-    match axum::serve(listener, app.into_make_service()).await {
+    match axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(broadcaster))
+    .await
+    {
         Ok(_) => (),
         Err(e) => {
             event!(
@@ -120,4 +649,27 @@ async fn main() {
             panic!("Unexpected error in final app initialization step | {}", e);
         }
     }
+
+    // let in-flight deployments finish before exiting
+    deployments.close();
+    if !deployments.is_empty() {
+        event!(
+            Level::INFO,
+            "Waiting on {} in-flight deployment task(s)...",
+            deployments.len()
+        );
+    }
+    match tokio::time::timeout(
+        Duration::from_secs(config.shutdown_grace_secs),
+        deployments.wait(),
+    )
+    .await
+    {
+        Ok(_) => event!(Level::INFO, "Shutdown complete."),
+        Err(_) => event!(
+            Level::WARN,
+            "Shutdown grace period elapsed with {} deployment task(s) still running",
+            deployments.len()
+        ),
+    }
 }
@@ -0,0 +1,24 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::Activity;
+
+#[derive(Template)]
+#[template(path = "activity/feed.html")]
+struct FeedTemplate {
+    entries: Vec<Activity>,
+    error: Option<String>,
+}
+
+pub fn feed(entries: Result<Vec<Activity>, DBError>) -> Markup {
+    let (entries, error) = match entries {
+        Ok(es) => (es, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&FeedTemplate { entries, error })
+}
@@ -0,0 +1,49 @@
+pub mod html;
+
+use serde::Serialize;
+
+use crate::modules::service::short_commit;
+
+// entries shown when no limit is asked for, and the most that can be
+pub const DEFAULT_LIMIT: i64 = 100;
+pub const MAX_LIMIT: i64 = 500;
+
+// how many entries to show when `asked` for some
+pub fn limit(asked: Option<i64>) -> i64 {
+    asked.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+// one thing that happened to a service, gathered from its deployments, audit log and uptime
+// transitions; see `db::get_activity`
+#[derive(Clone, Debug, Serialize)]
+pub struct Activity {
+    // UTC, as stored
+    pub at: String,
+    // deployment, edit or health
+    pub kind: String,
+    pub service_id: Option<i64>,
+    // None once the service is deleted
+    pub service_name: Option<String>,
+    // the deployment's outcome, the audited action, or up or down
+    pub summary: String,
+    // who made an edit
+    pub actor: Option<String>,
+    pub deployment_id: Option<i64>,
+    pub commit: Option<String>,
+}
+
+impl Activity {
+    pub fn short_commit(&self) -> Option<&str> {
+        self.commit.as_deref().map(short_commit)
+    }
+
+    // the chip class the summary is shown with
+    pub fn class(&self) -> &'static str {
+        match (self.kind.as_str(), self.summary.as_str()) {
+            ("deployment", "succeeded") | ("health", "up") => "success-chip",
+            ("deployment", "failed") | ("health", "down") => "error-chip",
+            ("deployment", _) => "warning-chip",
+            _ => "unknown-chip",
+        }
+    }
+}
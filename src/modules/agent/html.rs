@@ -0,0 +1,36 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::Node;
+
+#[derive(Template)]
+#[template(path = "agent/manager.html")]
+struct ManagerTemplate {
+    nodes: Vec<Node>,
+    error: Option<String>,
+    message: Option<String>,
+    // shown once, right after the node is added
+    token: Option<String>,
+}
+
+pub fn manager(
+    nodes: Result<Vec<Node>, DBError>,
+    message: Option<String>,
+    token: Option<String>,
+) -> Markup {
+    let (nodes, error) = match nodes {
+        Ok(ns) => (ns, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&ManagerTemplate {
+        nodes,
+        error,
+        message,
+        token,
+    })
+}
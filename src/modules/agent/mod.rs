@@ -0,0 +1,431 @@
+pub mod html;
+pub mod worker;
+
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{
+    AppState,
+    db::{get_deploy_key, get_git_credential, get_service_env, new_deployment, set_last_commit},
+    request_id,
+    service::{
+        DeployFailure, DockerServiceEntry, Lifecycle, Service, ServiceError, ServiceEvent,
+        ServiceStatus,
+        credential::GitCredential,
+        env::{ServiceEnv, reveal},
+        timing::Timings,
+    },
+};
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub id: i64,
+    pub name: String,
+    // as reported by the agent when it connects
+    pub hostname: Option<String>,
+    pub last_seen: Option<String>,
+    pub connected: bool,
+}
+
+impl Node {
+    pub fn valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+// sent from the central instance to an agent; no Debug, it carries secrets
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Job {
+    Deploy {
+        id: u64,
+        service: Service,
+        env_vars: Vec<ServiceEnv>,
+        // private key contents when the service has a deploy key
+        deploy_key: Option<String>,
+        // revealed HTTPS credential, when the service has one
+        #[serde(default)]
+        git_credential: Option<GitCredential>,
+        // checked out instead of pulling the latest commit
+        #[serde(default)]
+        commit: Option<String>,
+    },
+    Stop {
+        id: u64,
+        service: Service,
+    },
+    Start {
+        id: u64,
+        service: Service,
+    },
+    Restart {
+        id: u64,
+        service: Service,
+    },
+    TearDown {
+        id: u64,
+        service: Service,
+        volumes: bool,
+    },
+    Remove {
+        id: u64,
+        service: Service,
+    },
+    // aborts the service's deploy, whether running or still queued
+    Cancel {
+        service_id: i64,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outcome {
+    pub commit: Option<String>,
+    pub images: Vec<String>,
+    // the status the pipeline failed with
+    pub error: Option<ServiceStatus>,
+    // how long each step of a deploy took on the node
+    #[serde(default)]
+    pub timings: Timings,
+}
+
+// sent from an agent to the central instance
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Report {
+    Hello {
+        hostname: String,
+    },
+    Status {
+        service_id: i64,
+        status: ServiceStatus,
+    },
+    Containers {
+        containers: Vec<DockerServiceEntry>,
+    },
+    Finished {
+        id: u64,
+        outcome: Outcome,
+    },
+}
+
+#[derive(Debug)]
+struct Link {
+    // tells this connection apart from a newer one from the same node
+    id: u64,
+    jobs: mpsc::UnboundedSender<Job>,
+    // job id to the service it runs for, the request it runs under and whoever awaits it
+    pending: HashMap<u64, (i64, Option<String>, oneshot::Sender<Outcome>)>,
+    containers: Vec<DockerServiceEntry>,
+}
+
+// connected agents by node id
+#[derive(Clone, Debug, Default)]
+pub struct Agents {
+    links: Arc<Mutex<HashMap<i64, Link>>>,
+    next_job: Arc<AtomicU64>,
+    next_link: Arc<AtomicU64>,
+}
+
+impl Agents {
+    fn links(&self) -> std::sync::MutexGuard<'_, HashMap<i64, Link>> {
+        self.links.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // a newer connection from the same node replaces the old one, whose jobs then end; the
+    // link holds the only sender
+    pub fn connect(&self, node_id: i64) -> (u64, mpsc::UnboundedReceiver<Job>) {
+        let (jobs, receiver) = mpsc::unbounded_channel();
+        let id = self.next_link.fetch_add(1, Ordering::Relaxed);
+        self.links().insert(
+            node_id,
+            Link {
+                id,
+                jobs,
+                pending: HashMap::new(),
+                containers: vec![],
+            },
+        );
+        (id, receiver)
+    }
+
+    pub fn disconnect(&self, node_id: i64, link_id: u64) {
+        let mut links = self.links();
+        if links.get(&node_id).is_some_and(|link| link.id == link_id) {
+            // dropping the pending senders fails whoever awaits those jobs
+            links.remove(&node_id);
+        }
+    }
+
+    pub fn mark(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let links = self.links();
+        nodes
+            .into_iter()
+            .map(|node| Node {
+                connected: links.contains_key(&node.id),
+                ..node
+            })
+            .collect()
+    }
+
+    pub fn is_connected(&self, node_id: i64) -> bool {
+        self.links().contains_key(&node_id)
+    }
+
+    // the latest containers every connected agent reported
+    pub fn containers(&self) -> Vec<DockerServiceEntry> {
+        self.links()
+            .values()
+            .flat_map(|link| link.containers.clone())
+            .collect()
+    }
+
+    pub fn set_containers(&self, node_id: i64, containers: Vec<DockerServiceEntry>) {
+        if let Some(link) = self.links().get_mut(&node_id) {
+            link.containers = containers;
+        }
+    }
+
+    // agents may only report on services they're running a job for; Some with the
+    // job's request id when the node is running one
+    pub fn running_job(&self, node_id: i64, service_id: i64) -> Option<Option<String>> {
+        self.links().get(&node_id).and_then(|link| {
+            link.pending
+                .values()
+                .find(|(service, ..)| *service == service_id)
+                .map(|(_, request_id, _)| request_id.clone())
+        })
+    }
+
+    pub fn finish(&self, node_id: i64, job_id: u64, outcome: Outcome) {
+        let done = self
+            .links()
+            .get_mut(&node_id)
+            .and_then(|link| link.pending.remove(&job_id));
+        match done {
+            Some((.., sender)) => {
+                let _ = sender.send(outcome);
+            }
+            None => event!(
+                Level::WARN,
+                "Node {} finished unknown job {}",
+                node_id,
+                job_id
+            ),
+        }
+    }
+
+    // hands the job to the node's agent and waits for it to finish; cancelling only asks
+    // the agent to stop, the outcome still comes from it
+    async fn run(
+        &self,
+        node_id: i64,
+        service_id: i64,
+        job: impl FnOnce(u64) -> Job,
+        cancel: &CancellationToken,
+    ) -> Result<Outcome, ServiceError> {
+        let job_id = self.next_job.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut links = self.links();
+            let link = links
+                .get_mut(&node_id)
+                .ok_or(ServiceError::Agent("Node is not connected".to_string()))?;
+            link.jobs
+                .send(job(job_id))
+                .map_err(|_| ServiceError::Agent("Node is not connected".to_string()))?;
+            link.pending
+                .insert(job_id, (service_id, request_id::current(), sender));
+        }
+
+        let mut receiver = receiver;
+        let outcome = tokio::select! {
+            outcome = &mut receiver => outcome,
+            _ = cancel.cancelled() => {
+                if let Some(link) = self.links().get(&node_id) {
+                    let _ = link.jobs.send(Job::Cancel { service_id });
+                }
+                receiver.await
+            }
+        };
+        outcome.map_err(|_| ServiceError::Agent("Lost connection to node".to_string()))
+    }
+}
+
+fn finished(outcome: &Outcome) -> Result<(), ServiceError> {
+    match &outcome.error {
+        Some(status) => Err(ServiceError::Remote(status.clone())),
+        None => Ok(()),
+    }
+}
+
+// the counterpart of `Service::deploy` for services assigned to a node
+pub async fn deploy(
+    app_state: &AppState,
+    serv: Service,
+    node_id: i64,
+    commit: Option<String>,
+    cancel: &CancellationToken,
+) -> Result<Option<i64>, DeployFailure> {
+    event!(
+        Level::INFO,
+        "Sending deployment of {} to node {}",
+        serv.name,
+        node_id
+    );
+    let pool = &app_state.pool;
+    let requested = Instant::now();
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::update(
+            serv.id,
+            ServiceStatus::DeploymentRequested,
+        ));
+
+    let service_id = serv.id;
+    let sent = async {
+        let env_vars = reveal(
+            get_service_env(pool, serv.id).await?,
+            app_state.config.secrets_key.as_ref(),
+        )?;
+        let deploy_key = match serv.deploy_key_id {
+            Some(key_id) => Some(std::fs::read_to_string(
+                get_deploy_key(pool, key_id).await?.key_path,
+            )?),
+            None => None,
+        };
+        let git_credential = match get_git_credential(pool, serv.id).await? {
+            Some(credential) => Some(credential.reveal(app_state.config.secrets_key.as_ref())?),
+            None => None,
+        };
+
+        app_state
+            .agents
+            .run(
+                node_id,
+                service_id,
+                |id| Job::Deploy {
+                    id,
+                    service: serv,
+                    env_vars,
+                    deploy_key,
+                    git_credential,
+                    commit,
+                },
+                cancel,
+            )
+            .await
+    };
+    // the node reports what it checked out only once the job finishes
+    let outcome = sent.await?;
+
+    if let Some(commit) = &outcome.commit {
+        set_last_commit(pool, service_id, commit.clone())
+            .await
+            .map_err(ServiceError::from)?;
+    }
+    finished(&outcome).map_err(|error| DeployFailure {
+        error,
+        commit: outcome.commit.clone(),
+    })?;
+
+    match new_deployment(
+        pool,
+        service_id,
+        outcome.commit,
+        outcome.images,
+        requested.elapsed(),
+        outcome.timings,
+    )
+    .await
+    {
+        Ok(id) => Ok(Some(id)),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to record deployment | {}", e);
+            Ok(None)
+        }
+    }
+}
+
+pub async fn stop(app_state: &AppState, serv: Service, node_id: i64) -> Result<(), ServiceError> {
+    let outcome = app_state
+        .agents
+        .run(
+            node_id,
+            serv.id,
+            |id| Job::Stop { id, service: serv },
+            &CancellationToken::new(),
+        )
+        .await?;
+    finished(&outcome)?;
+
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+    Ok(())
+}
+
+// the node's side of `Lifecycle::run`
+pub async fn lifecycle(
+    app_state: &AppState,
+    serv: Service,
+    node_id: i64,
+    action: Lifecycle,
+    cancel: &CancellationToken,
+) -> Result<(), ServiceError> {
+    let outcome = app_state
+        .agents
+        .run(
+            node_id,
+            serv.id,
+            |id| match action {
+                Lifecycle::Stop => Job::Stop { id, service: serv },
+                Lifecycle::Start => Job::Start { id, service: serv },
+                Lifecycle::Restart => Job::Restart { id, service: serv },
+                Lifecycle::TearDown { volumes } => Job::TearDown {
+                    id,
+                    service: serv,
+                    volumes,
+                },
+            },
+            cancel,
+        )
+        .await?;
+    finished(&outcome)
+}
+
+// removes the service from its node, then from the database
+pub async fn remove(app_state: &AppState, serv: Service, node_id: i64) -> Result<(), ServiceError> {
+    let service_id = serv.id;
+    let outcome = app_state
+        .agents
+        .run(
+            node_id,
+            service_id,
+            |id| Job::Remove { id, service: serv },
+            &CancellationToken::new(),
+        )
+        .await?;
+    finished(&outcome)?;
+
+    super::db::delete_service_entry(&app_state.pool, service_id).await?;
+
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::OpenOptions, io::Write, os::unix::fs::OpenOptionsExt, path::PathBuf, time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::tungstenite::{
+    self, Message,
+    client::IntoClientRequest,
+    http::{HeaderValue, header},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{Job, Outcome, Report};
+use crate::modules::{
+    Config,
+    service::{
+        Service, ServiceError, ServiceEvent, ServiceStatus,
+        credential::{GitAuth, GitCredential},
+        env::ServiceEnv,
+        process::InFlight,
+        timing::Timings,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("AGENT_SERVER and AGENT_TOKEN are required in agent mode")]
+    Config,
+    #[error("Invalid agent token")]
+    Token(#[from] header::InvalidHeaderValue),
+    #[error("Connection error | {0}")]
+    Socket(#[from] tungstenite::Error),
+    #[error("Unable to encode report | {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// how often the agent reports its containers to the dashboard
+const REPORT_TICK: Duration = Duration::from_secs(15);
+const MAX_RECONNECT_SECS: u64 = 60;
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or("unknown".to_string())
+}
+
+// keeps a connection to the central instance open, reconnecting with backoff
+pub async fn run(config: Config) {
+    let mut delay = 5;
+    loop {
+        match session(&config).await {
+            Ok(_) => {
+                event!(Level::WARN, "Connection to the central instance closed");
+                delay = 5;
+            }
+            Err(e) => event!(
+                Level::WARN,
+                "Unable to reach the central instance, retrying in {}s | {}",
+                delay,
+                e
+            ),
+        }
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+        delay = (delay * 2).min(MAX_RECONNECT_SECS);
+    }
+}
+
+async fn session(config: &Config) -> Result<(), AgentError> {
+    let (Some(server), Some(token)) = (&config.agent_server, &config.agent_token) else {
+        return Err(AgentError::Config);
+    };
+
+    let mut request =
+        format!("{}/agent/connect", server.trim_end_matches('/')).into_client_request()?;
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", token))?,
+    );
+    let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+    event!(Level::INFO, "Connected to {}", server);
+
+    let (mut sink, mut incoming) = socket.split();
+    let (reports, mut outgoing) = mpsc::unbounded_channel();
+    let _ = reports.send(Report::Hello {
+        hostname: hostname(),
+    });
+
+    // jobs run one at a time, in the order they arrive; cancels skip the queue
+    let (jobs, queue) = mpsc::unbounded_channel();
+    let in_flight = InFlight::default();
+    let worker = tokio::spawn(work(
+        config.clone(),
+        queue,
+        reports.clone(),
+        in_flight.clone(),
+    ));
+    let mut tick = tokio::time::interval(REPORT_TICK);
+
+    let result = loop {
+        tokio::select! {
+            Some(report) = outgoing.recv() => {
+                let text = serde_json::to_string(&report)?;
+                if let Err(e) = sink.send(Message::text(text)).await {
+                    break Err(e.into());
+                }
+            }
+            _ = tick.tick() => match Service::get_list(&[None]).await {
+                Ok(containers) => {
+                    let _ = reports.send(Report::Containers { containers });
+                }
+                Err(e) => event!(Level::WARN, "Unable to list containers | {}", e),
+            },
+            message = incoming.next() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Job>(&text) {
+                    Ok(Job::Cancel { service_id }) => {
+                        in_flight.cancel(service_id);
+                    }
+                    Ok(job) => {
+                        let cancel = match &job {
+                            Job::Deploy { service, .. } => in_flight.begin(service.id),
+                            _ => CancellationToken::new(),
+                        };
+                        let _ = jobs.send((job, cancel));
+                    }
+                    Err(e) => event!(Level::WARN, "Ignoring unreadable job | {}", e),
+                },
+                Some(Ok(Message::Close(_))) | None => break Ok(()),
+                Some(Err(e)) => break Err(e.into()),
+                _ => (),
+            },
+        }
+    };
+
+    worker.abort();
+    result
+}
+
+async fn work(
+    config: Config,
+    mut queue: mpsc::UnboundedReceiver<(Job, CancellationToken)>,
+    reports: mpsc::UnboundedSender<Report>,
+    in_flight: InFlight,
+) {
+    while let Some((job, cancel)) = queue.recv().await {
+        let (br, mut events) = broadcast::channel(config.broadcast_capacity);
+
+        // relay pipeline progress to the dashboard
+        let relay = reports.clone();
+        let forwarder = tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(ServiceEvent::ServiceUpdate { id, status, .. }) => {
+                        let _ = relay.send(Report::Status {
+                            service_id: id,
+                            status,
+                        });
+                    }
+                    Ok(_) => (),
+                    Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                        event!(
+                            Level::WARN,
+                            "Fell behind relaying progress; {} events dropped",
+                            dropped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let (id, outcome) = match job {
+            Job::Deploy {
+                id,
+                service,
+                env_vars,
+                deploy_key,
+                git_credential,
+                commit,
+            } => {
+                event!(Level::INFO, "Deploying {}...", service.name);
+                let service_id = service.id;
+                let outcome = match git_auth(&config, &service, deploy_key, git_credential) {
+                    Ok(auth) => {
+                        deploy(
+                            &config,
+                            service,
+                            env_vars,
+                            auth,
+                            commit.as_deref(),
+                            &cancel,
+                            &br,
+                        )
+                        .await
+                    }
+                    Err(e) => outcome(Err(ServiceError::Command(e))),
+                };
+                in_flight.end(service_id);
+                (id, outcome)
+            }
+            Job::Stop { id, service } => {
+                event!(Level::INFO, "Stopping {}...", service.name);
+                (id, outcome(service.stop(config.clone(), &cancel, &br)))
+            }
+            Job::Start { id, service } => {
+                event!(Level::INFO, "Starting {}...", service.name);
+                (id, outcome(service.start(config.clone(), &cancel, &br)))
+            }
+            Job::Restart { id, service } => {
+                event!(Level::INFO, "Restarting {}...", service.name);
+                (id, outcome(service.restart(config.clone(), &cancel, &br)))
+            }
+            Job::TearDown {
+                id,
+                service,
+                volumes,
+            } => {
+                event!(Level::INFO, "Tearing down {}...", service.name);
+                (
+                    id,
+                    outcome(service.down(config.clone(), volumes, &cancel, &br)),
+                )
+            }
+            Job::Remove { id, service } => {
+                event!(Level::INFO, "Removing {}...", service.name);
+                (id, outcome(service.teardown(config.clone(), &br).await))
+            }
+            Job::Cancel { .. } => continue,
+        };
+
+        // statuses sent after `Finished` would overwrite the final one
+        drop(br);
+        let _ = forwarder.await;
+        let _ = reports.send(Report::Finished { id, outcome });
+    }
+}
+
+fn outcome(result: Result<(), ServiceError>) -> Outcome {
+    Outcome {
+        error: result.err().map(ServiceStatus::from_error),
+        ..Outcome::default()
+    }
+}
+
+fn write_key(config: &Config, service_id: i64, key: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(&config.deploy_keys_dir)?;
+    let path = config
+        .deploy_keys_dir
+        .join(format!("wraut_service_{}", service_id));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.write_all(key.as_bytes())?;
+    Ok(path)
+}
+
+// the legacy global key is this host's own KEY_FILE
+fn git_auth(
+    config: &Config,
+    service: &Service,
+    deploy_key: Option<String>,
+    credential: Option<GitCredential>,
+) -> std::io::Result<GitAuth> {
+    let key_path = match (deploy_key, service.use_key) {
+        (Some(key), _) => Some(write_key(config, service.id, &key)?),
+        (None, true) => Some(config.key_file.clone()),
+        (None, false) => None,
+    };
+    Ok(GitAuth {
+        key_path,
+        credential,
+    })
+}
+
+async fn deploy(
+    config: &Config,
+    service: Service,
+    env_vars: Vec<ServiceEnv>,
+    auth: GitAuth,
+    commit: Option<&str>,
+    cancel: &CancellationToken,
+    br: &broadcast::Sender<ServiceEvent>,
+) -> Outcome {
+    let mut timings = Timings::default();
+    let commit = match timings.time("clone", || {
+        service.checkout(config, auth, commit, cancel, br)
+    }) {
+        Ok(commit) => commit,
+        Err(e) => return outcome(Err(e)),
+    };
+
+    match service
+        .release(
+            config.clone(),
+            &env_vars,
+            commit.as_deref(),
+            cancel,
+            br,
+            &mut timings,
+        )
+        .await
+    {
+        Ok(images) => Outcome {
+            commit,
+            images,
+            error: None,
+            timings,
+        },
+        Err(e) => Outcome {
+            commit,
+            ..outcome(Err(e))
+        },
+    }
+}
@@ -0,0 +1,30 @@
+use rust_embed::RustEmbed;
+
+// everything under `static/` at build time; see `static/fetch-assets.sh`
+#[derive(RustEmbed)]
+#[folder = "static/"]
+#[exclude = "*.sh"]
+pub struct Assets;
+
+pub const HTMX: &str = "htmx.min.js";
+pub const HTMX_CDN: &str = "https://cdn.jsdelivr.net/npm/htmx.org@2.0.8/dist/htmx.min.js";
+pub const HTMX_SSE: &str = "htmx-ext-sse.js";
+pub const HTMX_SSE_CDN: &str = "https://cdn.jsdelivr.net/npm/htmx-ext-sse@2.2.4";
+pub const FONT: &str = "ibm-plex-mono-latin-400-normal.woff2";
+pub const FONT_CSS: &str = "fonts.css";
+pub const FONT_CSS_CDN: &str = "https://fonts.googleapis.com/css2?family=IBM+Plex+Mono:ital,wght@0,100;0,200;0,300;0,400;0,500;0,600;0,700;1,100;1,200;1,300;1,400;1,500;1,600;1,700&display=swap";
+
+// prefers the embedded copy, falling back to the CDN when it wasn't vendored
+pub fn url(file: &str, cdn: &str) -> String {
+    match Assets::get(file) {
+        Some(_) => format!("/static/{}", file),
+        None => cdn.to_string(),
+    }
+}
+
+pub fn font_css_url() -> String {
+    match Assets::get(FONT) {
+        Some(_) => url(FONT_CSS, FONT_CSS_CDN),
+        None => FONT_CSS_CDN.to_string(),
+    }
+}
@@ -0,0 +1,24 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::AuditEntry;
+
+#[derive(Template)]
+#[template(path = "audit/log.html")]
+struct AuditLogTemplate {
+    entries: Vec<AuditEntry>,
+    error: Option<String>,
+}
+
+pub fn log(entries: Result<Vec<AuditEntry>, DBError>) -> Markup {
+    let (entries, error) = match entries {
+        Ok(es) => (es, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&AuditLogTemplate { entries, error })
+}
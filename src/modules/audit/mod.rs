@@ -0,0 +1,232 @@
+pub mod html;
+
+use std::convert::Infallible;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde_json::{Map, Value, json};
+use sqlx::SqlitePool;
+use tracing::{Level, event};
+
+use crate::modules::{
+    db,
+    network::ClientIp,
+    service::{Lifecycle, Service},
+    user::User,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum AuditAction {
+    Create,
+    Edit,
+    Delete,
+    Deploy,
+    CancelDeploy,
+    Deactivate,
+    Stop,
+    Start,
+    Restart,
+    TearDown,
+    Promote,
+    ApproveDeploy,
+    DeclineDeploy,
+    RotateHook,
+    DeleteHook,
+    Archive,
+    Restore,
+    SetEnv,
+    DeleteEnv,
+    Prune,
+    Backup,
+    RestoreBackup,
+    RestoreVolumes,
+    Terminal,
+    RemoveOrphans,
+    AddRecipient,
+    RemoveRecipient,
+    NotificationRules,
+    AddJob,
+    EditJob,
+    RemoveJob,
+    RunJob,
+    Duplicate,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Edit => "edit",
+            AuditAction::Delete => "delete",
+            AuditAction::Deploy => "deploy",
+            AuditAction::CancelDeploy => "cancel_deploy",
+            AuditAction::Deactivate => "deactivate",
+            AuditAction::Stop => "stop",
+            AuditAction::Start => "start",
+            AuditAction::Restart => "restart",
+            AuditAction::TearDown => "tear_down",
+            AuditAction::Promote => "promote",
+            AuditAction::ApproveDeploy => "approve_deploy",
+            AuditAction::DeclineDeploy => "decline_deploy",
+            AuditAction::RotateHook => "rotate_hook",
+            AuditAction::DeleteHook => "delete_hook",
+            AuditAction::Archive => "archive",
+            AuditAction::Restore => "restore",
+            AuditAction::SetEnv => "set_env",
+            AuditAction::DeleteEnv => "delete_env",
+            AuditAction::Prune => "prune",
+            AuditAction::Backup => "backup",
+            AuditAction::RestoreBackup => "restore_backup",
+            AuditAction::RestoreVolumes => "restore_volumes",
+            AuditAction::Terminal => "terminal",
+            AuditAction::RemoveOrphans => "remove_orphans",
+            AuditAction::AddRecipient => "add_recipient",
+            AuditAction::RemoveRecipient => "remove_recipient",
+            AuditAction::NotificationRules => "notification_rules",
+            AuditAction::AddJob => "add_job",
+            AuditAction::EditJob => "edit_job",
+            AuditAction::RemoveJob => "remove_job",
+            AuditAction::RunJob => "run_job",
+            AuditAction::Duplicate => "duplicate",
+        }
+    }
+}
+
+impl From<Lifecycle> for AuditAction {
+    fn from(action: Lifecycle) -> Self {
+        match action {
+            Lifecycle::Stop => AuditAction::Stop,
+            Lifecycle::Start => AuditAction::Start,
+            Lifecycle::Restart => AuditAction::Restart,
+            Lifecycle::TearDown { .. } => AuditAction::TearDown,
+        }
+    }
+}
+
+// who made a change and from where; falls back to the surface used when access control is off
+#[derive(Clone, Debug)]
+pub struct Actor {
+    pub name: String,
+    pub source_ip: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let path = parts.uri.path();
+        let name = match parts.extensions.get::<User>() {
+            Some(user) => user.username.clone(),
+            None if path.starts_with("/api/v1") => "api".to_string(),
+            None if path.starts_with("/hooks") => "deploy hook".to_string(),
+            None => "dashboard".to_string(),
+        };
+        let source_ip = parts
+            .extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| ip.to_string());
+
+        Ok(Actor { name, source_ip })
+    }
+}
+
+pub struct AuditEntry {
+    pub created_at: String,
+    pub service_id: Option<i64>,
+    pub service_name: Option<String>,
+    pub action: String,
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub changes: String,
+}
+
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl AuditEntry {
+    pub fn changed_fields(&self) -> Vec<FieldChange> {
+        let changes: Map<String, Value> = serde_json::from_str(&self.changes).unwrap_or_default();
+        changes
+            .into_iter()
+            .map(|(field, change)| FieldChange {
+                field,
+                before: display_value(&change["before"]),
+                after: display_value(&change["after"]),
+            })
+            .collect()
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn fields(service: Option<&Service>) -> Map<String, Value> {
+    match service.map(serde_json::to_value) {
+        Some(Ok(Value::Object(map))) => map,
+        _ => Map::new(),
+    }
+}
+
+// kept up to date by deploys rather than edited
+const RUNTIME_FIELDS: [&str; 5] = [
+    "id",
+    "last_commit",
+    "last_status",
+    "last_status_detail",
+    "last_status_at",
+];
+
+// field-level diff shaped as {"field": {"before": .., "after": ..}}; a missing side is null
+pub fn diff(before: Option<&Service>, after: Option<&Service>) -> Value {
+    let (before, after) = (fields(before), fields(after));
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if RUNTIME_FIELDS.contains(&key.as_str()) || changes.contains_key(key) {
+            continue;
+        }
+        let b = before.get(key).cloned().unwrap_or(Value::Null);
+        let a = after.get(key).cloned().unwrap_or(Value::Null);
+        if b != a {
+            changes.insert(key.clone(), json!({ "before": b, "after": a }));
+        }
+    }
+    Value::Object(changes)
+}
+
+pub fn env_change(key: &str, before: Option<String>, after: Option<String>) -> Value {
+    json!({ format!("env.{}", key): { "before": before, "after": after } })
+}
+
+// a failed audit write is logged but never blocks the change itself
+pub async fn record(
+    pool: &SqlitePool,
+    actor: &Actor,
+    service_id: Option<i64>,
+    action: AuditAction,
+    changes: Value,
+) {
+    if let Err(e) = db::new_audit_entry(
+        pool,
+        service_id,
+        action.as_str(),
+        &actor.name,
+        actor.source_ip.clone(),
+        changes.to_string(),
+    )
+    .await
+    {
+        event!(
+            Level::ERROR,
+            "Unable to write audit entry for {} | {}",
+            action.as_str(),
+            e
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{ConnectOptions, Connection, sqlite::SqliteConnectOptions};
+use thiserror::Error;
+use tracing::{Level, event};
+
+use super::{
+    AppState,
+    audit::{self, Actor, AuditAction},
+    db::{self, DBError},
+};
+
+const PREFIX: &str = "wraut-";
+const EXTENSION: &str = ".db";
+// a backup staged by `stage` sits beside the database under this suffix until the next start
+const RESTORE: &str = ".restore";
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Unable to use the backup directory | {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unable to back up the database | {0}")]
+    Db(#[from] DBError),
+    #[error("No backup is named '{0}'")]
+    NotFound(String),
+    #[error("Backup '{0}' can't be restored | {1}")]
+    Invalid(String, String),
+    #[error("Only a database kept in a file can be restored")]
+    InMemory,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Backup {
+    pub name: String,
+    pub bytes: u64,
+    pub created_at: String,
+}
+
+// where DB_URL keeps the database, None for an in-memory one
+pub fn database_path(db_url: &str) -> Option<PathBuf> {
+    let options = SqliteConnectOptions::from_str(db_url).ok()?;
+    let path = options.get_filename();
+    match path.as_os_str() == ":memory:" {
+        true => None,
+        false => Some(path.to_path_buf()),
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+// newest first
+pub fn list(dir: &Path) -> Result<Vec<Backup>, BackupError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || !name.starts_with(PREFIX) || !name.ends_with(EXTENSION) {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        backups.push((modified, name, metadata.len()));
+    }
+    backups.sort_by_key(|(modified, ..)| Reverse(*modified));
+
+    Ok(backups
+        .into_iter()
+        .map(|(modified, name, bytes)| Backup {
+            name,
+            bytes,
+            created_at: DateTime::<Utc>::from(modified).to_rfc3339(),
+        })
+        .collect())
+}
+
+// removes the oldest backups beyond BACKUP_KEEP, returning how many went
+fn trim(dir: &Path, keep: Option<usize>) -> Result<usize, BackupError> {
+    let Some(keep) = keep else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for backup in list(dir)?.into_iter().skip(keep) {
+        std::fs::remove_file(dir.join(&backup.name))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+fn changes(field: &str, name: &str) -> Value {
+    json!({ field: { "before": null, "after": name } })
+}
+
+// snapshots the live database into BACKUP_PATH, then trims old snapshots
+pub async fn create(app_state: &AppState, actor: &Actor) -> Result<Backup, BackupError> {
+    let dir = &app_state.config.backup_dir;
+    std::fs::create_dir_all(dir)?;
+    let name = format!(
+        "{}{}{}",
+        PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        EXTENSION
+    );
+    let path = dir.join(&name);
+    db::vacuum_into(&app_state.pool, &path.to_string_lossy()).await?;
+    event!(Level::INFO, "Backed up the database to {}", path.display());
+    audit::record(
+        &app_state.pool,
+        actor,
+        None,
+        AuditAction::Backup,
+        changes("backup", &name),
+    )
+    .await;
+
+    match trim(dir, app_state.config.backup_keep) {
+        Ok(0) => (),
+        Ok(removed) => event!(Level::INFO, "Removed {} old backups", removed),
+        Err(e) => event!(Level::WARN, "Unable to trim the backup directory | {}", e),
+    }
+    let bytes = std::fs::metadata(&path)?.len();
+    Ok(Backup {
+        name,
+        bytes,
+        created_at: Utc::now().to_rfc3339(),
+    })
+}
+
+// opens a backup read-only and makes sure it is an intact wraut database
+async fn check(name: &str, path: &Path) -> Result<(), BackupError> {
+    let invalid = |e: sqlx::Error| BackupError::Invalid(name.to_string(), e.to_string());
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(invalid)?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut connection)
+        .await
+        .map_err(invalid)?;
+    let migrations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
+        .fetch_one(&mut connection)
+        .await
+        .map_err(invalid)?;
+    let _ = connection.close().await;
+
+    match (integrity.as_str(), migrations) {
+        ("ok", 1..) => Ok(()),
+        ("ok", _) => Err(BackupError::Invalid(
+            name.to_string(),
+            "no migrations have been applied".to_string(),
+        )),
+        (problem, _) => Err(BackupError::Invalid(name.to_string(), problem.to_string())),
+    }
+}
+
+// checks a backup and stages it to replace the database at the next start; the running
+// instance keeps using the current database until then
+pub async fn stage(app_state: &AppState, actor: &Actor, name: &str) -> Result<(), BackupError> {
+    let database = database_path(&app_state.config.db_url).ok_or(BackupError::InMemory)?;
+    let dir = &app_state.config.backup_dir;
+    if !list(dir)?.iter().any(|b| b.name == name) {
+        return Err(BackupError::NotFound(name.to_string()));
+    }
+    let path = dir.join(name);
+    check(name, &path).await?;
+
+    // copied beside the database first so a partial copy is never applied
+    let partial = with_suffix(&database, ".restore.partial");
+    tokio::fs::copy(&path, &partial).await?;
+    tokio::fs::rename(&partial, with_suffix(&database, RESTORE)).await?;
+    event!(
+        Level::WARN,
+        "Staged backup {} to replace the database at the next start",
+        name
+    );
+    audit::record(
+        &app_state.pool,
+        actor,
+        None,
+        AuditAction::RestoreBackup,
+        changes("restore", name),
+    )
+    .await;
+    Ok(())
+}
+
+// swaps a staged backup in before the pool opens; the replaced database is kept beside it
+// as `<database>.pre-restore`
+pub fn apply_staged(db_url: &str) -> Result<(), BackupError> {
+    let Some(database) = database_path(db_url) else {
+        return Ok(());
+    };
+    let staged = with_suffix(&database, RESTORE);
+    if !staged.exists() {
+        return Ok(());
+    }
+
+    let replaced = with_suffix(&database, ".pre-restore");
+    if database.exists() {
+        std::fs::rename(&database, &replaced)?;
+    }
+    // the write-ahead log belongs to the replaced database and must not be applied to the
+    // restored one
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = with_suffix(&database, suffix);
+        if sidecar.exists() {
+            std::fs::rename(&sidecar, with_suffix(&replaced, suffix))?;
+        }
+    }
+    std::fs::rename(&staged, &database)?;
+    event!(
+        Level::WARN,
+        "Restored the database from a staged backup; the previous one is at {}",
+        replaced.display()
+    );
+    Ok(())
+}
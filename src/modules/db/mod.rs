@@ -1,4 +1,25 @@
-use crate::modules::service::Service;
+use crate::modules::{
+    activity::Activity,
+    agent::Node,
+    audit::AuditEntry,
+    deploy_key::DeployKey,
+    group::Group,
+    notify::{NotificationRule, email::EmailRecipient, push::PushTarget},
+    service::{
+        Deployment, DeploymentLog, Service,
+        approval::DeployApproval,
+        credential::GitCredential,
+        env::ServiceEnv,
+        job::{JobRun, Outcome, RUNS_KEPT, ServiceJob},
+        review::DeployReview,
+        timing::Timings,
+    },
+    statuspage::feed::Release,
+    uptime::{self, Transition},
+    user::{Role, User},
+};
+
+use std::time::Duration;
 
 use sqlx::{self, SqlitePool};
 use thiserror::Error;
@@ -7,13 +28,33 @@ use thiserror::Error;
 pub enum DBError {
     #[error("Unable to use database")]
     Sql(#[from] sqlx::Error),
+    #[error("A service named '{0}' already exists")]
+    DuplicateName(String),
+}
+
+// names are unique regardless of case since they name directories and compose projects
+fn name_conflict(e: sqlx::Error, name: &str) -> DBError {
+    match &e {
+        sqlx::Error::Database(d)
+            if d.is_unique_violation() && d.message().contains("service.name") =>
+        {
+            DBError::DuplicateName(name.to_string())
+        }
+        _ => DBError::Sql(e),
+    }
 }
 
-pub async fn get_services(pool: &SqlitePool) -> Result<Vec<Service>, DBError> {
+// `archived: None` returns every service
+pub async fn get_services(
+    pool: &SqlitePool,
+    archived: Option<bool>,
+) -> Result<Vec<Service>, DBError> {
     let rows = sqlx::query!(
         r#"
-            SELECT id, name, compose_name, repo_url, access_url, active, use_key FROM service
-        "#
+            SELECT id, name, compose_name, repo_url, access_url, active, use_key, pre_deploy_cmd, post_deploy_cmd, deploy_key_id, archived, schedule, poll_interval_secs, tag_pattern, clone_depth, compose_file, build_before_up, push_image, docker_host, node_id, group_id, environment, branch, proxy_port, compose_override, review_required, requires_approval, watch_paths, health_grace_secs, backup_volumes, post_start_exec, post_start_container, repo_dir, live_dir, smoke_url, smoke_status, smoke_regex, one_off, platform, secret_files, previews, preview_of, pipeline, scripts, last_commit, last_status, last_status_detail, last_status_at FROM service
+            WHERE $1 IS NULL OR archived = $1
+        "#,
+        archived,
     )
     .fetch_all(pool)
     .await?;
@@ -28,6 +69,47 @@ pub async fn get_services(pool: &SqlitePool) -> Result<Vec<Service>, DBError> {
             access_url: row.access_url,
             active: row.active,
             use_key: row.use_key,
+            pre_deploy_cmd: row.pre_deploy_cmd,
+            post_deploy_cmd: row.post_deploy_cmd,
+            deploy_key_id: row.deploy_key_id,
+            archived: row.archived,
+            schedule: row.schedule,
+            poll_interval_secs: row.poll_interval_secs,
+            tag_pattern: row.tag_pattern,
+            clone_depth: row.clone_depth,
+            compose_file: row.compose_file,
+            build_before_up: row.build_before_up,
+            push_image: row.push_image,
+            docker_host: row.docker_host,
+            node_id: row.node_id,
+            group_id: row.group_id,
+            environment: row.environment,
+            branch: row.branch,
+            proxy_port: row.proxy_port,
+            compose_override: row.compose_override,
+            review_required: row.review_required,
+            requires_approval: row.requires_approval,
+            watch_paths: row.watch_paths,
+            health_grace_secs: row.health_grace_secs,
+            backup_volumes: row.backup_volumes,
+            post_start_exec: row.post_start_exec,
+            post_start_container: row.post_start_container,
+            repo_dir: row.repo_dir,
+            live_dir: row.live_dir,
+            smoke_url: row.smoke_url,
+            smoke_status: row.smoke_status,
+            smoke_regex: row.smoke_regex,
+            one_off: row.one_off,
+            platform: row.platform,
+            secret_files: row.secret_files,
+            previews: row.previews,
+            preview_of: row.preview_of,
+            pipeline: row.pipeline,
+            scripts: row.scripts,
+            last_commit: row.last_commit,
+            last_status: row.last_status,
+            last_status_detail: row.last_status_detail,
+            last_status_at: row.last_status_at,
         })
         .collect();
 
@@ -38,7 +120,7 @@ pub async fn get_service(pool: &SqlitePool, service_id: i64) -> Result<Service,
     let result = sqlx::query_as!(
         Service,
         r#"
-            SELECT id, name, compose_name, repo_url, access_url, active, use_key FROM service WHERE id = $1
+            SELECT id, name, compose_name, repo_url, access_url, active, use_key, pre_deploy_cmd, post_deploy_cmd, deploy_key_id, archived, schedule, poll_interval_secs, tag_pattern, clone_depth, compose_file, build_before_up, push_image, docker_host, node_id, group_id, environment, branch, proxy_port, compose_override, review_required, requires_approval, watch_paths, health_grace_secs, backup_volumes, post_start_exec, post_start_container, repo_dir, live_dir, smoke_url, smoke_status, smoke_regex, one_off, platform, secret_files, previews, preview_of, pipeline, scripts, last_commit, last_status, last_status_detail, last_status_at FROM service WHERE id = $1
         "#,
         service_id,
     )
@@ -48,10 +130,10 @@ pub async fn get_service(pool: &SqlitePool, service_id: i64) -> Result<Service,
     Ok(result)
 }
 
-pub async fn new_service(pool: &SqlitePool, service: Service) -> Result<(), DBError> {
-    sqlx::query!(
-        "INSERT INTO service (name, compose_name, repo_url, access_url, active, use_key)
-        VALUES ($1, $2, $3, $4, $5, $6)
+pub async fn new_service(pool: &SqlitePool, service: Service) -> Result<i64, DBError> {
+    let row = sqlx::query!(
+        "INSERT INTO service (name, compose_name, repo_url, access_url, active, use_key, pre_deploy_cmd, post_deploy_cmd, deploy_key_id, schedule, poll_interval_secs, tag_pattern, clone_depth, compose_file, build_before_up, push_image, docker_host, node_id, health_grace_secs, group_id, environment, branch, proxy_port, compose_override, review_required, watch_paths, requires_approval, backup_volumes, post_start_exec, post_start_container, repo_dir, live_dir, smoke_url, smoke_status, smoke_regex, one_off, platform, secret_files, previews, preview_of, pipeline, scripts)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41, $42)
         RETURNING id",
         service.name,
         service.compose_name,
@@ -59,21 +141,125 @@ pub async fn new_service(pool: &SqlitePool, service: Service) -> Result<(), DBEr
         service.access_url,
         service.active,
         service.use_key,
+        service.pre_deploy_cmd,
+        service.post_deploy_cmd,
+        service.deploy_key_id,
+        service.schedule,
+        service.poll_interval_secs,
+        service.tag_pattern,
+        service.clone_depth,
+        service.compose_file,
+        service.build_before_up,
+        service.push_image,
+        service.docker_host,
+        service.node_id,
+        service.health_grace_secs,
+        service.group_id,
+        service.environment,
+        service.branch,
+        service.proxy_port,
+        service.compose_override,
+        service.review_required,
+        service.watch_paths,
+        service.requires_approval,
+        service.backup_volumes,
+        service.post_start_exec,
+        service.post_start_container,
+        service.repo_dir,
+        service.live_dir,
+        service.smoke_url,
+        service.smoke_status,
+        service.smoke_regex,
+        service.one_off,
+        service.platform,
+        service.secret_files,
+        service.previews,
+        service.preview_of,
+        service.pipeline,
+        service.scripts,
     )
     .fetch_one(pool)
-    .await?;
-    Ok(())
+    .await
+    .map_err(|e| name_conflict(e, &service.name))?;
+    Ok(row.id)
 }
 
 pub async fn update_service(pool: &SqlitePool, id: i64, service: Service) -> Result<(), DBError> {
     sqlx::query!(
-        "UPDATE service SET name = $1, compose_name = $2, repo_url = $3, access_url = $4, active = $5, use_key = $6  WHERE id = $7 RETURNING id",
+        "UPDATE service SET name = $1, compose_name = $2, repo_url = $3, access_url = $4, active = $5, use_key = $6, pre_deploy_cmd = $7, post_deploy_cmd = $8, deploy_key_id = $9, schedule = $10, poll_interval_secs = $11, tag_pattern = $12, clone_depth = $13, compose_file = $14, build_before_up = $15, push_image = $16, docker_host = $17, node_id = $18, health_grace_secs = $19, group_id = $20, environment = $21, branch = $22, proxy_port = $23, compose_override = $24, review_required = $25, watch_paths = $26, requires_approval = $27, backup_volumes = $28, post_start_exec = $29, post_start_container = $30, repo_dir = $31, live_dir = $32, smoke_url = $33, smoke_status = $34, smoke_regex = $35, one_off = $36, platform = $37, secret_files = $38, previews = $39, pipeline = $40, scripts = $41 WHERE id = $42 RETURNING id",
         service.name,
         service.compose_name,
         service.repo_url,
         service.access_url,
         service.active,
         service.use_key,
+        service.pre_deploy_cmd,
+        service.post_deploy_cmd,
+        service.deploy_key_id,
+        service.schedule,
+        service.poll_interval_secs,
+        service.tag_pattern,
+        service.clone_depth,
+        service.compose_file,
+        service.build_before_up,
+        service.push_image,
+        service.docker_host,
+        service.node_id,
+        service.health_grace_secs,
+        service.group_id,
+        service.environment,
+        service.branch,
+        service.proxy_port,
+        service.compose_override,
+        service.review_required,
+        service.watch_paths,
+        service.requires_approval,
+        service.backup_volumes,
+        service.post_start_exec,
+        service.post_start_container,
+        service.repo_dir,
+        service.live_dir,
+        service.smoke_url,
+        service.smoke_status,
+        service.smoke_regex,
+        service.one_off,
+        service.platform,
+        service.secret_files,
+        service.previews,
+        service.pipeline,
+        service.scripts,
+        id,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| name_conflict(e, &service.name))?;
+    Ok(())
+}
+
+// the service already registered for the repo in that environment, if any
+pub async fn service_in_environment(
+    pool: &SqlitePool,
+    repo_url: &str,
+    environment: &str,
+) -> Result<Option<i64>, DBError> {
+    let row = sqlx::query!(
+        r#"SELECT id AS "id!" FROM service WHERE repo_url = $1 AND environment = $2"#,
+        repo_url,
+        environment,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.id))
+}
+
+pub async fn set_service_archived(
+    pool: &SqlitePool,
+    id: i64,
+    archived: bool,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE service SET archived = $1 WHERE id = $2 RETURNING id",
+        archived,
         id,
     )
     .fetch_one(pool)
@@ -81,9 +267,1327 @@ pub async fn update_service(pool: &SqlitePool, id: i64, service: Service) -> Res
     Ok(())
 }
 
+pub async fn set_last_status(
+    pool: &SqlitePool,
+    id: i64,
+    status: String,
+    detail: Option<String>,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE service SET last_status = $1, last_status_detail = $2, last_status_at = datetime('now') WHERE id = $3",
+        status,
+        detail,
+        id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_last_commit(pool: &SqlitePool, id: i64, commit: String) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE service SET last_commit = $1 WHERE id = $2",
+        commit,
+        id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 pub async fn delete_service_entry(pool: &SqlitePool, id: i64) -> Result<(), DBError> {
     sqlx::query!("DELETE FROM service WHERE id = $1", id)
         .execute(pool)
         .await?;
     Ok(())
 }
+
+pub async fn get_service_env(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Vec<ServiceEnv>, DBError> {
+    let result = sqlx::query_as!(
+        ServiceEnv,
+        r#"
+            SELECT id AS "id!", key, value, secret FROM service_env WHERE service_id = $1 ORDER BY key
+        "#,
+        service_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn set_service_env(
+    pool: &SqlitePool,
+    service_id: i64,
+    key: String,
+    value: String,
+    secret: bool,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO service_env (service_id, key, value, secret) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (service_id, key) DO UPDATE SET value = excluded.value, secret = excluded.secret",
+        service_id,
+        key,
+        value,
+        secret,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// gives `to` the env vars of `from`, secrets only when asked; values are copied as stored,
+// so secrets stay encrypted
+pub async fn copy_service_env(
+    pool: &SqlitePool,
+    from: i64,
+    to: i64,
+    secrets: bool,
+) -> Result<u64, DBError> {
+    let result = sqlx::query!(
+        "INSERT INTO service_env (service_id, key, value, secret)
+        SELECT $1, key, value, secret FROM service_env WHERE service_id = $2 AND (secret = 0 OR $3)
+        ON CONFLICT (service_id, key) DO NOTHING",
+        to,
+        from,
+        secrets,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// every secret env var's encrypted value, for re-encrypting them under a new key
+pub async fn get_secret_env_values(pool: &SqlitePool) -> Result<Vec<(i64, String)>, DBError> {
+    let rows = sqlx::query!(r#"SELECT id AS "id!", value FROM service_env WHERE secret = 1"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| (row.id, row.value)).collect())
+}
+
+pub async fn set_secret_env_value(
+    pool: &SqlitePool,
+    id: i64,
+    value: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE service_env SET value = $1 WHERE id = $2 AND secret = 1",
+        value,
+        id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_service_env(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "DELETE FROM service_env WHERE id = $1 AND service_id = $2",
+        id,
+        service_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_email_recipients(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Vec<EmailRecipient>, DBError> {
+    let result = sqlx::query_as!(
+        EmailRecipient,
+        r#"
+            SELECT id AS "id!", address FROM email_recipient WHERE service_id = $1 ORDER BY address
+        "#,
+        service_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn add_email_recipient(
+    pool: &SqlitePool,
+    service_id: i64,
+    address: &str,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO email_recipient (service_id, address) VALUES ($1, $2)
+        ON CONFLICT (service_id, address) DO NOTHING",
+        service_id,
+        address,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_email_recipient(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "DELETE FROM email_recipient WHERE id = $1 AND service_id = $2",
+        id,
+        service_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_push_targets(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Vec<PushTarget>, DBError> {
+    let result = sqlx::query_as!(
+        PushTarget,
+        r#"
+            SELECT id AS "id!", channel, target FROM push_target WHERE service_id = $1
+            ORDER BY channel, target
+        "#,
+        service_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn add_push_target(
+    pool: &SqlitePool,
+    service_id: i64,
+    channel: &str,
+    target: &str,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO push_target (service_id, channel, target) VALUES ($1, $2, $3)
+        ON CONFLICT (service_id, channel, target) DO NOTHING",
+        service_id,
+        channel,
+        target,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_push_target(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "DELETE FROM push_target WHERE id = $1 AND service_id = $2",
+        id,
+        service_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_notification_rules(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Vec<NotificationRule>, DBError> {
+    let result = sqlx::query_as!(
+        NotificationRule,
+        r#"
+            SELECT channel, deployed AS "deployed: bool", deploy_failed AS "deploy_failed: bool",
+            health AS "health: bool", job_failed AS "job_failed: bool"
+            FROM notification_rule WHERE service_id = $1
+        "#,
+        service_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn set_notification_rule(
+    pool: &SqlitePool,
+    service_id: i64,
+    rule: &NotificationRule,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO notification_rule
+        (service_id, channel, deployed, deploy_failed, health, job_failed)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (service_id, channel) DO UPDATE SET deployed = excluded.deployed,
+        deploy_failed = excluded.deploy_failed, health = excluded.health,
+        job_failed = excluded.job_failed",
+        service_id,
+        rule.channel,
+        rule.deployed,
+        rule.deploy_failed,
+        rule.health,
+        rule.job_failed,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_service_jobs(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Vec<ServiceJob>, DBError> {
+    let result = sqlx::query_as!(
+        ServiceJob,
+        r#"
+            SELECT id AS "id!", service_id, name, schedule, mode, container, command,
+            active AS "active: bool" FROM service_job WHERE service_id = $1 ORDER BY name
+        "#,
+        service_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+// the jobs the scheduler runs
+pub async fn get_active_jobs(pool: &SqlitePool) -> Result<Vec<ServiceJob>, DBError> {
+    let result = sqlx::query_as!(
+        ServiceJob,
+        r#"
+            SELECT id AS "id!", service_id, name, schedule, mode, container, command,
+            active AS "active: bool" FROM service_job WHERE active = 1
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn get_service_job(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+) -> Result<ServiceJob, DBError> {
+    let result = sqlx::query_as!(
+        ServiceJob,
+        r#"
+            SELECT id AS "id!", service_id, name, schedule, mode, container, command,
+            active AS "active: bool" FROM service_job WHERE id = $1 AND service_id = $2
+        "#,
+        id,
+        service_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn new_service_job(pool: &SqlitePool, job: &ServiceJob) -> Result<i64, DBError> {
+    let row = sqlx::query!(
+        r#"
+            INSERT INTO service_job (service_id, name, schedule, mode, container, command, active)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id AS "id!"
+        "#,
+        job.service_id,
+        job.name,
+        job.schedule,
+        job.mode,
+        job.container,
+        job.command,
+        job.active,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.id)
+}
+
+pub async fn set_service_job_active(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+    active: bool,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE service_job SET active = $1 WHERE id = $2 AND service_id = $3",
+        active,
+        id,
+        service_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_service_job(
+    pool: &SqlitePool,
+    service_id: i64,
+    id: i64,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "DELETE FROM service_job WHERE id = $1 AND service_id = $2",
+        id,
+        service_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// records a finished run, dropping the job's runs beyond RUNS_KEPT
+pub async fn new_job_run(
+    pool: &SqlitePool,
+    job_id: i64,
+    trigger: &str,
+    outcome: &Outcome,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        r#"
+            INSERT INTO job_run (job_id, trigger, duration_ms, ok, exit_code, output)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        job_id,
+        trigger,
+        outcome.duration_ms,
+        outcome.ok,
+        outcome.exit_code,
+        outcome.output,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+            DELETE FROM job_run WHERE job_id = $1 AND id NOT IN
+            (SELECT id FROM job_run WHERE job_id = $1 ORDER BY id DESC LIMIT $2)
+        "#,
+        job_id,
+        RUNS_KEPT,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// the latest runs of all of a service's jobs, newest first
+pub async fn get_job_runs(
+    pool: &SqlitePool,
+    service_id: i64,
+    limit: i64,
+) -> Result<Vec<JobRun>, DBError> {
+    let result = sqlx::query_as!(
+        JobRun,
+        r#"
+            SELECT job_run.id AS "id!", service_job.name AS job, trigger, started_at,
+            duration_ms, ok AS "ok: bool", exit_code, output
+            FROM job_run JOIN service_job ON service_job.id = job_run.job_id
+            WHERE service_job.service_id = $1 ORDER BY job_run.id DESC LIMIT $2
+        "#,
+        service_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn get_deploy_keys(pool: &SqlitePool) -> Result<Vec<DeployKey>, DBError> {
+    let result = sqlx::query_as!(
+        DeployKey,
+        r#"
+            SELECT id AS "id!", name, public_key, key_path FROM deploy_key ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn get_deploy_key(pool: &SqlitePool, id: i64) -> Result<DeployKey, DBError> {
+    let result = sqlx::query_as!(
+        DeployKey,
+        r#"
+            SELECT id, name, public_key, key_path FROM deploy_key WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn new_deploy_key(
+    pool: &SqlitePool,
+    name: String,
+    public_key: String,
+    key_path: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO deploy_key (name, public_key, key_path) VALUES ($1, $2, $3)",
+        name,
+        public_key,
+        key_path,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_deploy_key(pool: &SqlitePool, id: i64) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM deploy_key WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_nodes(pool: &SqlitePool) -> Result<Vec<Node>, DBError> {
+    let rows =
+        sqlx::query!(r#"SELECT id AS "id!", name, hostname, last_seen FROM node ORDER BY name"#)
+            .fetch_all(pool)
+            .await?;
+
+    let result = rows
+        .into_iter()
+        .map(|row| Node {
+            id: row.id,
+            name: row.name,
+            hostname: row.hostname,
+            last_seen: row.last_seen,
+            connected: false,
+        })
+        .collect();
+
+    Ok(result)
+}
+
+pub async fn get_groups(pool: &SqlitePool) -> Result<Vec<Group>, DBError> {
+    let result = sqlx::query_as!(
+        Group,
+        r#"SELECT id AS "id!", name FROM service_group ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(result)
+}
+
+// environments in use by unarchived services, for the dashboard's filter
+pub async fn get_environments(pool: &SqlitePool) -> Result<Vec<String>, DBError> {
+    let rows = sqlx::query!(
+        "SELECT DISTINCT environment FROM service WHERE environment IS NOT NULL AND archived = 0 ORDER BY environment"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().filter_map(|r| r.environment).collect())
+}
+
+pub async fn new_group(pool: &SqlitePool, name: String) -> Result<(), DBError> {
+    sqlx::query!("INSERT INTO service_group (name) VALUES ($1)", name)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_group(pool: &SqlitePool, id: i64) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM service_group WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_node_by_token(pool: &SqlitePool, token_hash: &str) -> Result<Node, DBError> {
+    let row = sqlx::query!(
+        r#"SELECT id AS "id!", name, hostname, last_seen FROM node WHERE token_hash = $1"#,
+        token_hash,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Node {
+        id: row.id,
+        name: row.name,
+        hostname: row.hostname,
+        last_seen: row.last_seen,
+        connected: false,
+    })
+}
+
+pub async fn new_node(pool: &SqlitePool, name: String, token_hash: String) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO node (name, token_hash) VALUES ($1, $2)",
+        name,
+        token_hash,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_node(pool: &SqlitePool, id: i64) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM node WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// `hostname: None` keeps the last one reported
+pub async fn touch_node(
+    pool: &SqlitePool,
+    id: i64,
+    hostname: Option<String>,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE node SET hostname = COALESCE($1, hostname), last_seen = CURRENT_TIMESTAMP WHERE id = $2",
+        hostname,
+        id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn new_deployment(
+    pool: &SqlitePool,
+    service_id: i64,
+    commit_sha: Option<String>,
+    images: Vec<String>,
+    duration: Duration,
+    timings: Timings,
+) -> Result<i64, DBError> {
+    let images = serde_json::to_string(&images).unwrap_or("[]".to_string());
+    let duration_ms = duration.as_millis() as i64;
+    let steps = serde_json::to_string(&timings.steps()).unwrap_or("[]".to_string());
+    let row = sqlx::query!(
+        r#"
+            INSERT INTO deployment (service_id, commit_sha, images, duration_ms, steps)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id AS "id!"
+        "#,
+        service_id,
+        commit_sha,
+        images,
+        duration_ms,
+        steps,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.id)
+}
+
+// a deploy that failed or was cancelled, kept for its log
+pub async fn new_failed_deployment(
+    pool: &SqlitePool,
+    service_id: i64,
+    commit_sha: Option<String>,
+    outcome: &str,
+    duration: Duration,
+    log: String,
+    compose_file: Option<String>,
+) -> Result<i64, DBError> {
+    let duration_ms = duration.as_millis() as i64;
+    let row = sqlx::query!(
+        r#"
+            INSERT INTO deployment (service_id, commit_sha, duration_ms, outcome, log, compose_file)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id AS "id!"
+        "#,
+        service_id,
+        commit_sha,
+        duration_ms,
+        outcome,
+        log,
+        compose_file,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.id)
+}
+
+pub async fn set_deployment_log(
+    pool: &SqlitePool,
+    id: i64,
+    log: String,
+    compose_file: Option<String>,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE deployment SET log = $1, compose_file = $2 WHERE id = $3",
+        log,
+        compose_file,
+        id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_deployment_log(pool: &SqlitePool, id: i64) -> Result<DeploymentLog, DBError> {
+    let result = sqlx::query_as!(
+        DeploymentLog,
+        r#"SELECT id AS "id!", log, compose_file FROM deployment WHERE id = $1"#,
+        id,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(result)
+}
+
+// the commit of the service's latest successful deployment
+pub async fn last_deployed_commit(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<String>, DBError> {
+    let row = sqlx::query!(
+        r#"
+            SELECT commit_sha FROM deployment
+            WHERE service_id = $1 AND outcome = 'succeeded'
+            ORDER BY id DESC
+            LIMIT 1
+        "#,
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.and_then(|row| row.commit_sha))
+}
+
+// newest first
+pub async fn get_deployments(
+    pool: &SqlitePool,
+    service_id: i64,
+    limit: i64,
+) -> Result<Vec<Deployment>, DBError> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT id AS "id!", created_at, commit_sha, images, duration_ms, steps, outcome FROM deployment
+            WHERE service_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+        "#,
+        service_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let result = rows
+        .into_iter()
+        .map(|row| Deployment {
+            id: row.id,
+            created_at: row.created_at,
+            commit_sha: row.commit_sha,
+            images: serde_json::from_str(&row.images).unwrap_or_default(),
+            duration_ms: row.duration_ms,
+            steps: serde_json::from_str(&row.steps).unwrap_or_default(),
+            outcome: row.outcome,
+        })
+        .collect();
+
+    Ok(result)
+}
+
+// the latest deployments of unarchived services, newest first
+pub async fn get_releases(pool: &SqlitePool, limit: i64) -> Result<Vec<Release>, DBError> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT deployment.id AS "id!", deployment.created_at, deployment.commit_sha,
+                deployment.images, deployment.duration_ms, deployment.steps, deployment.outcome,
+                service.name, service.environment
+            FROM deployment JOIN service ON service.id = deployment.service_id
+            WHERE service.archived = 0
+            ORDER BY deployment.id DESC
+            LIMIT $1
+        "#,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let result = rows
+        .into_iter()
+        .map(|row| Release {
+            service_name: row.name,
+            environment: row.environment,
+            deployment: Deployment {
+                id: row.id,
+                created_at: row.created_at,
+                commit_sha: row.commit_sha,
+                images: serde_json::from_str(&row.images).unwrap_or_default(),
+                duration_ms: row.duration_ms,
+                steps: serde_json::from_str(&row.steps).unwrap_or_default(),
+                outcome: row.outcome,
+            },
+        })
+        .collect();
+
+    Ok(result)
+}
+
+// replaces the service's hook token, so the old URL stops working
+pub async fn set_deploy_hook(
+    pool: &SqlitePool,
+    service_id: i64,
+    token_hash: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO deploy_hook (service_id, token_hash) VALUES ($1, $2)",
+        service_id,
+        token_hash,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// when the service's hook was created, if it has one
+pub async fn get_deploy_hook_created(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<String>, DBError> {
+    let row = sqlx::query!(
+        "SELECT created_at FROM deploy_hook WHERE service_id = $1",
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| row.created_at))
+}
+
+pub async fn get_deploy_hook_service(
+    pool: &SqlitePool,
+    token_hash: &str,
+) -> Result<Option<i64>, DBError> {
+    let row = sqlx::query!(
+        r#"SELECT service_id AS "service_id!" FROM deploy_hook WHERE token_hash = $1"#,
+        token_hash,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| row.service_id))
+}
+
+// the preview opened for a pull request on `source_id`, if it's still up
+pub async fn get_preview(
+    pool: &SqlitePool,
+    source_id: i64,
+    pr_number: i64,
+) -> Result<Option<i64>, DBError> {
+    let row = sqlx::query!(
+        r#"
+            SELECT preview.service_id AS "service_id!" FROM preview
+            JOIN service ON service.id = preview.service_id
+            WHERE service.preview_of = $1 AND preview.pr_number = $2
+        "#,
+        source_id,
+        pr_number,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| row.service_id))
+}
+
+// records the preview's pull request, or pushes back its expiry when it's updated
+pub async fn set_preview(
+    pool: &SqlitePool,
+    service_id: i64,
+    pr_number: i64,
+    ttl_secs: i64,
+) -> Result<(), DBError> {
+    let ttl = format!("+{} seconds", ttl_secs);
+    sqlx::query!(
+        r#"
+            INSERT INTO preview (service_id, pr_number, expires_at) VALUES ($1, $2, datetime('now', $3))
+            ON CONFLICT (service_id) DO UPDATE SET expires_at = excluded.expires_at
+        "#,
+        service_id,
+        pr_number,
+        ttl,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// previews whose pull request went quiet for longer than PREVIEW_TTL_SECS
+pub async fn expired_previews(pool: &SqlitePool) -> Result<Vec<i64>, DBError> {
+    let rows = sqlx::query!(
+        r#"SELECT service_id AS "service_id!" FROM preview WHERE expires_at <= datetime('now')"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|row| row.service_id).collect())
+}
+
+pub async fn delete_deploy_hook(pool: &SqlitePool, service_id: i64) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM deploy_hook WHERE service_id = $1", service_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// the token stays encrypted; see `GitCredential::reveal`
+pub async fn get_git_credential(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<GitCredential>, DBError> {
+    let result = sqlx::query_as!(
+        GitCredential,
+        "SELECT username, token FROM git_credential WHERE service_id = $1",
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result)
+}
+
+// None removes the service's credential
+pub async fn set_git_credential(
+    pool: &SqlitePool,
+    service_id: i64,
+    credential: Option<GitCredential>,
+) -> Result<(), DBError> {
+    match credential {
+        Some(credential) => {
+            sqlx::query!(
+                "INSERT OR REPLACE INTO git_credential (service_id, username, token) VALUES ($1, $2, $3)",
+                service_id,
+                credential.username,
+                credential.token,
+            )
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM git_credential WHERE service_id = $1",
+                service_id
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+// every service's encrypted token, for re-encrypting them under a new key
+pub async fn get_git_credential_tokens(pool: &SqlitePool) -> Result<Vec<(i64, String)>, DBError> {
+    let rows = sqlx::query!(r#"SELECT service_id AS "service_id!", token FROM git_credential"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.service_id, row.token))
+        .collect())
+}
+
+pub async fn set_git_credential_token(
+    pool: &SqlitePool,
+    service_id: i64,
+    token: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE git_credential SET token = $1 WHERE service_id = $2",
+        token,
+        service_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// base64; None until the first secret key is derived, see `secrets::unlock`
+pub async fn get_secrets_salt(pool: &SqlitePool) -> Result<Option<String>, DBError> {
+    let row = sqlx::query!("SELECT salt FROM secrets_salt WHERE id = 1")
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|row| row.salt))
+}
+
+pub async fn set_secrets_salt(pool: &SqlitePool, salt: &str) -> Result<(), DBError> {
+    sqlx::query!("INSERT INTO secrets_salt (id, salt) VALUES (1, $1)", salt)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// replaces any review already waiting for the service
+pub async fn new_deploy_review(
+    pool: &SqlitePool,
+    service_id: i64,
+    commit_sha: Option<String>,
+    diff: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO deploy_review (service_id, commit_sha, diff) VALUES ($1, $2, $3)",
+        service_id,
+        commit_sha,
+        diff,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_deploy_review(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<DeployReview>, DBError> {
+    let result = sqlx::query_as!(
+        DeployReview,
+        r#"SELECT service_id AS "service_id!", commit_sha, diff, requested_at FROM deploy_review WHERE service_id = $1"#,
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result)
+}
+
+// ids of the services with a review waiting
+pub async fn get_deploy_review_ids(pool: &SqlitePool) -> Result<Vec<i64>, DBError> {
+    let rows = sqlx::query!(r#"SELECT service_id AS "service_id!" FROM deploy_review"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| row.service_id).collect())
+}
+
+// removes and returns the review, so only one approval or decline acts on it
+pub async fn take_deploy_review(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<DeployReview>, DBError> {
+    let result = sqlx::query_as!(
+        DeployReview,
+        r#"DELETE FROM deploy_review WHERE service_id = $1 RETURNING service_id AS "service_id!", commit_sha, diff, requested_at"#,
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result)
+}
+
+pub async fn new_deploy_approval(
+    pool: &SqlitePool,
+    service_id: i64,
+    trigger: &str,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT OR REPLACE INTO deploy_approval (service_id, trigger) VALUES ($1, $2)",
+        service_id,
+        trigger,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_deploy_approval(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<DeployApproval>, DBError> {
+    let result = sqlx::query_as!(
+        DeployApproval,
+        r#"SELECT service_id AS "service_id!", trigger, requested_at FROM deploy_approval WHERE service_id = $1"#,
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result)
+}
+
+// ids of the services with an automatic deploy waiting for approval
+pub async fn get_deploy_approval_ids(pool: &SqlitePool) -> Result<Vec<i64>, DBError> {
+    let rows = sqlx::query!(r#"SELECT service_id AS "service_id!" FROM deploy_approval"#)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| row.service_id).collect())
+}
+
+// removes and returns the approval, so only one approve or decline acts on it
+pub async fn take_deploy_approval(
+    pool: &SqlitePool,
+    service_id: i64,
+) -> Result<Option<DeployApproval>, DBError> {
+    let result = sqlx::query_as!(
+        DeployApproval,
+        r#"DELETE FROM deploy_approval WHERE service_id = $1 RETURNING service_id AS "service_id!", trigger, requested_at"#,
+        service_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(result)
+}
+
+pub async fn new_uptime_transition(
+    pool: &SqlitePool,
+    service_id: i64,
+    up: bool,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO uptime_transition (service_id, up) VALUES ($1, $2)",
+        service_id,
+        up,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// transitions since the given time, each service's led by the last one before it so the
+// state at the start of the window is known; ordered by service, then time
+pub async fn get_uptime_transitions(
+    pool: &SqlitePool,
+    service_id: Option<i64>,
+    since: chrono::NaiveDateTime,
+) -> Result<Vec<Transition>, DBError> {
+    let since = since.format(uptime::TIMESTAMP).to_string();
+    let rows = sqlx::query!(
+        r#"
+            SELECT service_id, up, at FROM uptime_transition t
+            WHERE ($1 IS NULL OR service_id = $1)
+            AND (at >= $2 OR id = (
+                SELECT MAX(id) FROM uptime_transition p
+                WHERE p.service_id = t.service_id AND p.at < $2
+            ))
+            ORDER BY service_id, at, id
+        "#,
+        service_id,
+        since,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let result = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(Transition {
+                service_id: row.service_id,
+                up: row.up,
+                at: chrono::NaiveDateTime::parse_from_str(&row.at, uptime::TIMESTAMP).ok()?,
+            })
+        })
+        .collect();
+
+    Ok(result)
+}
+
+pub async fn new_audit_entry(
+    pool: &SqlitePool,
+    service_id: Option<i64>,
+    action: &str,
+    actor: &str,
+    source_ip: Option<String>,
+    changes: String,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO audit_log (service_id, action, actor, source_ip, changes) VALUES ($1, $2, $3, $4, $5)",
+        service_id,
+        action,
+        actor,
+        source_ip,
+        changes,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// newest first; `service_id: None` returns entries for every service
+pub async fn get_audit_log(
+    pool: &SqlitePool,
+    service_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, DBError> {
+    let result = sqlx::query_as!(
+        AuditEntry,
+        r#"
+            SELECT audit_log.created_at, audit_log.service_id, service.name AS "service_name?",
+                audit_log.action, audit_log.actor, audit_log.source_ip, audit_log.changes
+            FROM audit_log LEFT JOIN service ON service.id = audit_log.service_id
+            WHERE $1 IS NULL OR audit_log.service_id = $1
+            ORDER BY audit_log.id DESC
+            LIMIT $2
+        "#,
+        service_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+// deployments, audited edits and uptime transitions, newest first; deploy requests are left
+// to the deployments they led to
+pub async fn get_activity(
+    pool: &SqlitePool,
+    service_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<Activity>, DBError> {
+    let result = sqlx::query_as!(
+        Activity,
+        r#"
+            SELECT activity.at AS "at!: String", activity.kind AS "kind!: String",
+                activity.service_id AS "service_id?: i64", service.name AS "service_name?",
+                activity.summary AS "summary!: String", activity.actor AS "actor?: String",
+                activity.deployment_id AS "deployment_id?: i64", activity.commit_sha AS "commit?: String"
+            FROM (
+                SELECT created_at AS at, 'deployment' AS kind, service_id, outcome AS summary,
+                    NULL AS actor, id AS deployment_id, commit_sha, id AS seq
+                FROM deployment
+                UNION ALL
+                SELECT created_at, 'edit', service_id, action, actor, NULL, NULL, id
+                FROM audit_log WHERE action != 'deploy'
+                UNION ALL
+                SELECT at, 'health', service_id, CASE WHEN up THEN 'up' ELSE 'down' END, NULL,
+                    NULL, NULL, id
+                FROM uptime_transition
+            ) activity LEFT JOIN service ON service.id = activity.service_id
+            WHERE $1 IS NULL OR activity.service_id = $1
+            ORDER BY activity.at DESC, activity.seq DESC
+            LIMIT $2
+        "#,
+        service_id,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(result)
+}
+
+pub async fn count_users(pool: &SqlitePool) -> Result<i64, DBError> {
+    let row = sqlx::query!(r#"SELECT COUNT(*) AS "count!: i64" FROM user"#)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.count)
+}
+
+pub async fn get_users(pool: &SqlitePool) -> Result<Vec<User>, DBError> {
+    let rows = sqlx::query!(r#"SELECT id AS "id!", username, role FROM user ORDER BY username"#)
+        .fetch_all(pool)
+        .await?;
+
+    let result = rows
+        .into_iter()
+        .map(|row| User {
+            id: row.id,
+            username: row.username,
+            role: row.role.parse().unwrap_or(Role::Viewer),
+        })
+        .collect();
+
+    Ok(result)
+}
+
+// on Result::Ok, returns the user with their stored password hash
+pub async fn get_user_login(pool: &SqlitePool, username: &str) -> Result<(User, String), DBError> {
+    let row = sqlx::query!(
+        r#"SELECT id AS "id!", username, role, password_hash FROM user WHERE username = $1"#,
+        username,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((
+        User {
+            id: row.id,
+            username: row.username,
+            role: row.role.parse().unwrap_or(Role::Viewer),
+        },
+        row.password_hash,
+    ))
+}
+
+pub async fn new_user(
+    pool: &SqlitePool,
+    username: String,
+    password_hash: String,
+    role: Role,
+) -> Result<(), DBError> {
+    let role = role.as_str();
+    sqlx::query!(
+        "INSERT INTO user (username, password_hash, role) VALUES ($1, $2, $3)",
+        username,
+        password_hash,
+        role,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_user(pool: &SqlitePool, id: i64) -> Result<(), DBError> {
+    sqlx::query!("DELETE FROM user WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn new_session(
+    pool: &SqlitePool,
+    token_hash: String,
+    user_id: i64,
+    ttl_secs: i64,
+) -> Result<(), DBError> {
+    let ttl = format!("+{} seconds", ttl_secs);
+    sqlx::query!(
+        "INSERT INTO session (token_hash, user_id, expires_at) VALUES ($1, $2, datetime('now', $3))",
+        token_hash,
+        user_id,
+        ttl,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_session_user(pool: &SqlitePool, token_hash: &str) -> Result<User, DBError> {
+    let row = sqlx::query!(
+        r#"
+            SELECT user.id AS "id!", user.username, user.role FROM session
+            JOIN user ON user.id = session.user_id
+            WHERE session.token_hash = $1 AND session.expires_at > datetime('now')
+        "#,
+        token_hash,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(User {
+        id: row.id,
+        username: row.username,
+        role: row.role.parse().unwrap_or(Role::Viewer),
+    })
+}
+
+pub async fn delete_session(pool: &SqlitePool, token_hash: &str) -> Result<(), DBError> {
+    sqlx::query!(
+        "DELETE FROM session WHERE token_hash = $1 OR expires_at <= datetime('now')",
+        token_hash
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn ping(pool: &SqlitePool) -> Result<(), DBError> {
+    sqlx::query!("SELECT 1 AS one").fetch_one(pool).await?;
+    Ok(())
+}
+
+// a consistent copy of the live database, taken without blocking writers
+pub async fn vacuum_into(pool: &SqlitePool, path: &str) -> Result<(), DBError> {
+    sqlx::query!("VACUUM INTO $1", path).execute(pool).await?;
+    Ok(())
+}
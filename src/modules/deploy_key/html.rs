@@ -0,0 +1,29 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::DeployKey;
+
+#[derive(Template)]
+#[template(path = "deploy_key/manager.html")]
+struct ManagerTemplate {
+    keys: Vec<DeployKey>,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+pub fn manager(keys: Result<Vec<DeployKey>, DBError>, message: Option<String>) -> Markup {
+    let (keys, error) = match keys {
+        Ok(ks) => (ks, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&ManagerTemplate {
+        keys,
+        error,
+        message,
+    })
+}
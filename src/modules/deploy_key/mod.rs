@@ -0,0 +1,79 @@
+pub mod html;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use thiserror::Error;
+use tracing::{Level, event};
+
+#[derive(Clone, Debug)]
+pub struct DeployKey {
+    pub id: i64,
+    pub name: String,
+    pub public_key: String,
+    pub key_path: String,
+}
+
+#[derive(Error, Debug)]
+pub enum DeployKeyError {
+    #[error("Key names may only contain letters, digits, '-' and '_'")]
+    Name,
+    #[error("A key file with that name already exists")]
+    Exists,
+    #[error("No response from ssh-keygen")]
+    Command(#[from] std::io::Error),
+    #[error("ssh-keygen resulted in failure")]
+    Status,
+}
+
+impl DeployKey {
+    pub fn valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    // on Result::Ok, returns the private key path and the public key contents
+    pub fn generate(keys_dir: &Path, name: &str) -> Result<(PathBuf, String), DeployKeyError> {
+        if !Self::valid_name(name) {
+            return Err(DeployKeyError::Name);
+        }
+
+        std::fs::create_dir_all(keys_dir)?;
+        let key_path = keys_dir.join(format!("wraut_{}_ed25519", name));
+        if key_path.exists() {
+            return Err(DeployKeyError::Exists);
+        }
+
+        let output = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", ""])
+            .arg("-C")
+            .arg(format!("wraut-{}", name))
+            .arg("-f")
+            .arg(&key_path)
+            .output()?;
+
+        match output.status.success() {
+            true => (),
+            false => {
+                event!(
+                    Level::ERROR,
+                    "KEYGEN FAIL | {}",
+                    std::str::from_utf8(&output.stderr).unwrap_or("NA")
+                );
+                return Err(DeployKeyError::Status);
+            }
+        }
+
+        let public_key = std::fs::read_to_string(key_path.with_extension("pub"))?;
+
+        Ok((key_path, public_key.trim().to_string()))
+    }
+
+    pub fn try_delete_files(&self) {
+        let key_path = PathBuf::from(&self.key_path);
+        let _ = std::fs::remove_file(key_path.with_extension("pub"));
+        let _ = std::fs::remove_file(key_path);
+    }
+}
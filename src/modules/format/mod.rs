@@ -0,0 +1,11 @@
+// a byte count as the dashboard shows it, e.g. 12 MB
+pub fn size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KB", "MB"] {
+        if size < 1024.0 {
+            return format!("{:.0} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} GB", size)
+}
@@ -0,0 +1,29 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::Group;
+
+#[derive(Template)]
+#[template(path = "group/manager.html")]
+struct ManagerTemplate {
+    groups: Vec<Group>,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+pub fn manager(groups: Result<Vec<Group>, DBError>, message: Option<String>) -> Markup {
+    let (groups, error) = match groups {
+        Ok(gs) => (gs, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&ManagerTemplate {
+        groups,
+        error,
+        message,
+    })
+}
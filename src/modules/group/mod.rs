@@ -0,0 +1,58 @@
+pub mod html;
+
+use std::fmt;
+
+use serde::Serialize;
+
+use super::service::{DockerServiceEntry, Service, ServiceStatus};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Group {
+    pub id: i64,
+    pub name: String,
+}
+
+// how a group's active services are doing, for its dashboard header
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub running: usize,
+    pub degraded: usize,
+    pub total: usize,
+}
+
+impl Summary {
+    pub fn of<'a>(
+        services: impl Iterator<Item = &'a Service>,
+        containers: &[DockerServiceEntry],
+    ) -> Self {
+        let mut summary = Summary::default();
+        // one-off jobs have no containers to count once they're done
+        for service in services.filter(|s| s.active && !s.is_one_off()) {
+            summary.total += 1;
+            match service.container_status(containers) {
+                ServiceStatus::Running => summary.running += 1,
+                ServiceStatus::Degraded(_) => summary.degraded += 1,
+                _ => (),
+            }
+        }
+        summary
+    }
+
+    pub fn class(&self) -> &'static str {
+        match self {
+            s if s.running == s.total => "success",
+            s if s.running + s.degraded > 0 => "warning",
+            _ => "error",
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} running", self.running, self.total)?;
+        if self.degraded > 0 {
+            write!(f, ", {} degraded", self.degraded)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use askama::Template;
+
+use crate::modules::{
+    markup::{Markup, render},
+    service::adopt::Unmanaged,
+};
+
+use super::{MaintenanceError, PruneReport, orphans::OrphanReport};
+
+#[derive(Template)]
+#[template(path = "maintenance/panel.html")]
+struct PanelTemplate {
+    schedule: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "maintenance/report.html")]
+struct ReportTemplate {
+    report: Option<PruneReport>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "maintenance/orphans.html")]
+struct OrphansTemplate {
+    report: Option<OrphanReport>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "maintenance/adopt.html")]
+struct AdoptTemplate {
+    projects: Option<Vec<Unmanaged>>,
+    error: Option<String>,
+}
+
+pub fn panel(schedule: Option<String>) -> Markup {
+    render(&PanelTemplate { schedule })
+}
+
+pub fn report(report: Result<PruneReport, MaintenanceError>) -> Markup {
+    let (report, error) = match report {
+        Ok(r) => (Some(r), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    render(&ReportTemplate { report, error })
+}
+
+pub fn orphans(report: Result<OrphanReport, MaintenanceError>) -> Markup {
+    let (report, error) = match report {
+        Ok(r) => (Some(r), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    render(&OrphansTemplate { report, error })
+}
+
+pub fn adopt(projects: Result<Vec<Unmanaged>, String>) -> Markup {
+    let (projects, error) = match projects {
+        Ok(p) => (Some(p), None),
+        Err(e) => (None, Some(e)),
+    };
+
+    render(&AdoptTemplate { projects, error })
+}
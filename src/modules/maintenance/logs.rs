@@ -0,0 +1,68 @@
+use std::cmp::Reverse;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use tracing::{Level, event};
+
+use crate::modules::Config;
+
+// the rolling files are named `wraut.log.<date>`, or just this when they never rotate
+pub const LOG_FILE: &str = "wraut.log";
+const TICK: Duration = Duration::from_secs(300);
+
+// how much of LOGS_PATH to keep; the file being written is always kept
+#[derive(Clone, Debug, Default)]
+pub struct LogRetention {
+    pub max_files: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+impl LogRetention {
+    fn unlimited(&self) -> bool {
+        self.max_files.is_none() && self.max_bytes.is_none()
+    }
+}
+
+// removes the oldest log files beyond the retention limits, returning how many went
+fn trim(dir: &Path, retention: &LogRetention) -> std::io::Result<usize> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && entry.file_name().to_string_lossy().starts_with(LOG_FILE) {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, metadata.len(), entry.path()));
+        }
+    }
+    // newest first
+    files.sort_by_key(|(modified, ..)| Reverse(*modified));
+
+    let mut kept_bytes = 0;
+    let mut removed = 0;
+    for (i, (_, len, path)) in files.into_iter().enumerate() {
+        kept_bytes += len;
+        let over = retention.max_files.is_some_and(|max| i >= max)
+            || retention.max_bytes.is_some_and(|max| kept_bytes > max);
+        if i > 0 && over {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// background task that keeps LOGS_PATH within LOG_MAX_FILES and LOG_MAX_MB, if set
+pub async fn retain(config: Config) {
+    if config.log_retention.unlimited() {
+        return;
+    }
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        match trim(&config.logs_dir, &config.log_retention) {
+            Ok(0) => (),
+            Ok(removed) => event!(Level::INFO, "Removed {} old log files", removed),
+            Err(e) => event!(Level::WARN, "Unable to trim the log directory | {}", e),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+pub mod html;
+pub mod logs;
+pub mod orphans;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{
+    AppState,
+    audit::{self, Actor, AuditAction},
+    db::DBError,
+    scheduler::cron::Schedule,
+    service::ServiceEvent,
+    tools,
+};
+
+const TICK: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum MaintenanceError {
+    #[error("No response from docker")]
+    Command(#[from] std::io::Error),
+    #[error("Docker command failed | {0}")]
+    Status(String),
+    #[error("Failed to parse docker output")]
+    Parse(#[from] std::str::Utf8Error),
+    #[error("Unable to read or remove directory | {0}")]
+    Dir(std::io::Error),
+    #[error("Unable to read services | {0}")]
+    Db(#[from] DBError),
+}
+
+struct PruneTarget {
+    name: &'static str,
+    args: &'static [&'static str],
+    // row in `docker system df` that estimates this target
+    df_type: &'static str,
+    // line of prune output carrying the amount freed
+    total_prefix: &'static str,
+}
+
+const TARGETS: [PruneTarget; 3] = [
+    PruneTarget {
+        name: "images",
+        args: &["image", "prune", "--all", "--force"],
+        df_type: "Images",
+        total_prefix: "Total reclaimed space:",
+    },
+    PruneTarget {
+        name: "volumes",
+        args: &["volume", "prune", "--force"],
+        df_type: "Local Volumes",
+        total_prefix: "Total reclaimed space:",
+    },
+    PruneTarget {
+        name: "build cache",
+        args: &["builder", "prune", "--force"],
+        df_type: "Build Cache",
+        total_prefix: "Total:",
+    },
+];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PruneStep {
+    pub target: &'static str,
+    pub reclaimed: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub steps: Vec<PruneStep>,
+}
+
+impl PruneReport {
+    // shaped like a service diff so the audit log can show it
+    fn changes(&self) -> Value {
+        let mut changes = Map::new();
+        for step in &self.steps {
+            changes.insert(
+                step.target.to_string(),
+                json!({ "before": null, "after": format!("{} reclaimed", step.reclaimed) }),
+            );
+        }
+        Value::Object(changes)
+    }
+}
+
+fn docker(args: &[&str]) -> Result<String, MaintenanceError> {
+    let output = tools::docker().args(args).output()?;
+    match output.status.success() {
+        true => Ok(std::str::from_utf8(&output.stdout)?.to_string()),
+        false => Err(MaintenanceError::Status(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+// docker can't dry-run a prune, so a dry run reports what `docker system df` calls reclaimable
+pub fn prune(dry_run: bool) -> Result<PruneReport, MaintenanceError> {
+    let steps = match dry_run {
+        true => {
+            let df = docker(&["system", "df", "--format", "json"])?;
+            let rows: Vec<Value> = df
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect();
+            TARGETS
+                .iter()
+                .map(|target| PruneStep {
+                    target: target.name,
+                    reclaimed: rows
+                        .iter()
+                        .find(|row| row["Type"] == target.df_type)
+                        .and_then(|row| row["Reclaimable"].as_str())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                })
+                .collect()
+        }
+        false => {
+            let mut steps = vec![];
+            for target in TARGETS.iter() {
+                let output = docker(target.args)?;
+                event!(Level::INFO, "Pruned {} | {}", target.name, output.trim());
+                steps.push(PruneStep {
+                    target: target.name,
+                    reclaimed: output
+                        .lines()
+                        .find_map(|line| line.trim().strip_prefix(target.total_prefix))
+                        .map(|total| total.trim().to_string())
+                        .unwrap_or("0B".to_string()),
+                });
+            }
+            steps
+        }
+    };
+
+    Ok(PruneReport { dry_run, steps })
+}
+
+pub async fn run_prune(
+    app_state: &AppState,
+    actor: &Actor,
+    dry_run: bool,
+) -> Result<PruneReport, MaintenanceError> {
+    let report = tokio::task::spawn_blocking(move || prune(dry_run))
+        .await
+        .map_err(|e| MaintenanceError::Status(e.to_string()))??;
+    if !dry_run {
+        audit::record(
+            &app_state.pool,
+            actor,
+            None,
+            AuditAction::Prune,
+            report.changes(),
+        )
+        .await;
+    }
+    Ok(report)
+}
+
+// background task that prunes on PRUNE_SCHEDULE, if one is set
+pub async fn scheduled(app_state: AppState) {
+    // validated when the config loaded
+    let Some(schedule) = app_state
+        .config
+        .prune_schedule
+        .as_deref()
+        .and_then(|s| s.parse::<Schedule>().ok())
+    else {
+        return;
+    };
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(TICK);
+    let mut last_tick = Utc::now();
+    let actor = Actor {
+        name: "scheduler".to_string(),
+        source_ip: None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let now = Utc::now();
+        if schedule.next_after(last_tick).is_some_and(|due| due <= now) {
+            match run_prune(&app_state, &actor, false).await {
+                Ok(report) => event!(Level::INFO, "Scheduled prune done | {:?}", report.steps),
+                Err(e) => event!(Level::ERROR, "Scheduled prune failed | {}", e),
+            }
+        }
+        last_tick = now;
+    }
+
+    event!(Level::INFO, "Prune scheduler stopped.");
+}
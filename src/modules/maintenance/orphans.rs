@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use tracing::{Level, event};
+
+use super::MaintenanceError;
+use crate::modules::{
+    AppState, Config,
+    audit::{self, Actor, AuditAction},
+    db, format,
+    service::{KEPT_KINDS, Service, valid_name, valid_slug},
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Orphan {
+    pub path: String,
+    // the service the directory was made for
+    pub service: String,
+    pub bytes: u64,
+}
+
+impl Orphan {
+    pub fn size(&self) -> String {
+        format::size(self.bytes)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrphanReport {
+    pub dry_run: bool,
+    pub orphans: Vec<Orphan>,
+}
+
+impl OrphanReport {
+    // shaped like a service diff so the audit log can show it
+    fn changes(&self) -> Value {
+        let mut changes = Map::new();
+        for orphan in &self.orphans {
+            changes.insert(
+                orphan.path.clone(),
+                json!({ "before": orphan.size(), "after": null }),
+            );
+        }
+        Value::Object(changes)
+    }
+}
+
+// the service a directory under the repo or live dir belongs to: its own name, or
+// `.{name}.{kind}` for the versions kept around a deploy. Anything else, like the deploy
+// keys, `lost+found` or an operator's own directories, isn't wraut's to collect
+fn owner(file_name: &str) -> Option<&str> {
+    let name = match file_name.strip_prefix('.') {
+        Some(kept) => kept
+            .rsplit_once('.')
+            .filter(|(_, kind)| KEPT_KINDS.contains(kind))
+            .map(|(name, _)| name)?,
+        None => file_name,
+    };
+    Some(name).filter(|name| valid_name(name) && valid_slug(name))
+}
+
+// links are counted, never followed
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+// directories wraut keeps its own state in, in case they were put under a services dir
+fn reserved(config: &Config) -> Vec<&PathBuf> {
+    vec![
+        &config.logs_dir,
+        &config.key_file,
+        &config.deploy_keys_dir,
+        &config.backup_dir,
+        &config.volume_backup_dir,
+    ]
+}
+
+// directories under SERVICE_REPO_PATH and SERVICE_LIVE_PATH with no service of that name
+// keeping its directories there, archived ones and those on nodes included. Services' own
+// repo_dir and live_dir may be shared with anything else, so they're never searched
+pub fn find(config: &Config, services: &[Service]) -> Result<Vec<Orphan>, MaintenanceError> {
+    let reserved = reserved(config);
+    let mut orphans = vec![];
+    for (root, repo) in [
+        (&config.services_repo_dir, true),
+        (&config.services_live_dir, false),
+    ] {
+        let services: HashSet<&str> = services
+            .iter()
+            .filter(|s| match repo {
+                true => s.repo_root(config) == *root,
+                false => s.live_root(config) == *root,
+            })
+            .map(|s| s.name.as_str())
+            .collect();
+        let entries = match std::fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(MaintenanceError::Dir(e)),
+        };
+        for entry in entries {
+            let entry = entry.map_err(MaintenanceError::Dir)?;
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            let Some(service) = owner(&file_name) else {
+                continue;
+            };
+            if !is_dir
+                || services.contains(service)
+                || reserved.iter().any(|r| r.starts_with(&path))
+            {
+                continue;
+            }
+            orphans.push(Orphan {
+                path: path.to_string_lossy().to_string(),
+                service: service.to_string(),
+                bytes: dir_size(&path),
+            });
+        }
+    }
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(orphans)
+}
+
+// lists the orphaned directories, removing them unless it's a dry run
+pub async fn collect(
+    app_state: &AppState,
+    actor: &Actor,
+    dry_run: bool,
+) -> Result<OrphanReport, MaintenanceError> {
+    let services = db::get_services(&app_state.pool, None).await?;
+    let config = app_state.config.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let orphans = find(&config, &services)?;
+        if !dry_run {
+            for orphan in &orphans {
+                std::fs::remove_dir_all(&orphan.path).map_err(MaintenanceError::Dir)?;
+                event!(Level::INFO, "Removed orphaned directory {}", orphan.path);
+            }
+        }
+        Ok::<_, MaintenanceError>(OrphanReport { dry_run, orphans })
+    })
+    .await
+    .map_err(|e| MaintenanceError::Status(e.to_string()))??;
+
+    if !dry_run && !report.orphans.is_empty() {
+        audit::record(
+            &app_state.pool,
+            actor,
+            None,
+            AuditAction::RemoveOrphans,
+            report.changes(),
+        )
+        .await;
+    }
+    Ok(report)
+}
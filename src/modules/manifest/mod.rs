@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{
+    AppState,
+    audit::{self, Actor, AuditAction},
+    db::{self, DBError},
+    service::{Service, ServiceError, ServiceEvent},
+};
+
+const TICK: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    #[error("Unable to read the services file | {0}")]
+    Read(#[from] std::io::Error),
+    #[error("Unable to parse the services file | {0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("Service '{0}' is declared more than once")]
+    Duplicate(String),
+    #[error("More than one service is named '{0}'; rename one to manage it from the file")]
+    Ambiguous(String),
+    #[error("Service '{name}' | no {kind} is named '{value}'")]
+    Unknown {
+        name: String,
+        kind: &'static str,
+        value: String,
+    },
+    #[error("Service '{0}' | {1}")]
+    Invalid(String, ServiceError),
+    #[error("Service '{0}' | this repo is already registered in {1} (service {2})")]
+    Environment(String, String, i64),
+    #[error("Unable to apply the services file | {0}")]
+    Db(#[from] DBError),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServicesFile {
+    #[serde(default)]
+    services: Vec<ServiceSpec>,
+}
+
+// a service as declared in the file; groups, nodes and deploy keys are named rather
+// than numbered, and anything left out is cleared
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServiceSpec {
+    name: String,
+    compose_name: String,
+    repo_url: String,
+    access_url: String,
+    #[serde(default = "active")]
+    active: bool,
+    #[serde(default)]
+    use_key: bool,
+    pre_deploy_cmd: Option<String>,
+    post_deploy_cmd: Option<String>,
+    deploy_key: Option<String>,
+    schedule: Option<String>,
+    poll_interval_secs: Option<i64>,
+    tag_pattern: Option<String>,
+    clone_depth: Option<i64>,
+    compose_file: Option<String>,
+    #[serde(default)]
+    build_before_up: bool,
+    #[serde(default)]
+    push_image: bool,
+    docker_host: Option<String>,
+    node: Option<String>,
+    group: Option<String>,
+    environment: Option<String>,
+    branch: Option<String>,
+    proxy_port: Option<i64>,
+    compose_override: Option<String>,
+    #[serde(default)]
+    review_required: bool,
+    #[serde(default)]
+    requires_approval: bool,
+    watch_paths: Option<String>,
+    health_grace_secs: Option<i64>,
+    backup_volumes: Option<String>,
+    post_start_exec: Option<String>,
+    post_start_container: Option<String>,
+    repo_dir: Option<String>,
+    live_dir: Option<String>,
+    smoke_url: Option<String>,
+    smoke_status: Option<i64>,
+    smoke_regex: Option<String>,
+    one_off: Option<String>,
+    platform: Option<String>,
+    #[serde(default)]
+    secret_files: bool,
+    #[serde(default)]
+    previews: bool,
+    pipeline: Option<String>,
+    scripts: Option<String>,
+}
+
+// declaring a service is asking for it to run
+fn active() -> bool {
+    true
+}
+
+// ids of the things a spec refers to by name
+struct Names {
+    groups: Vec<(String, i64)>,
+    nodes: Vec<(String, i64)>,
+    deploy_keys: Vec<(String, i64)>,
+}
+
+impl Names {
+    async fn load(app_state: &AppState) -> Result<Self, DBError> {
+        let pool = &app_state.pool;
+        Ok(Names {
+            groups: db::get_groups(pool)
+                .await?
+                .into_iter()
+                .map(|g| (g.name, g.id))
+                .collect(),
+            nodes: db::get_nodes(pool)
+                .await?
+                .into_iter()
+                .map(|n| (n.name, n.id))
+                .collect(),
+            deploy_keys: db::get_deploy_keys(pool)
+                .await?
+                .into_iter()
+                .map(|k| (k.name, k.id))
+                .collect(),
+        })
+    }
+}
+
+fn resolve(
+    service: &str,
+    kind: &'static str,
+    names: &[(String, i64)],
+    value: Option<String>,
+) -> Result<Option<i64>, ManifestError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    match names.iter().find(|(name, _)| *name == value) {
+        Some((_, id)) => Ok(Some(*id)),
+        None => Err(ManifestError::Unknown {
+            name: service.to_string(),
+            kind,
+            value,
+        }),
+    }
+}
+
+impl ServiceSpec {
+    fn into_service(self, names: &Names) -> Result<Service, ManifestError> {
+        let deploy_key_id = resolve(
+            &self.name,
+            "deploy key",
+            &names.deploy_keys,
+            self.deploy_key,
+        )?;
+        let node_id = resolve(&self.name, "node", &names.nodes, self.node)?;
+        let group_id = resolve(&self.name, "group", &names.groups, self.group)?;
+        let service = Service {
+            id: 0, // NOT USED
+            name: self.name,
+            compose_name: self.compose_name,
+            repo_url: self.repo_url,
+            access_url: self.access_url,
+            active: self.active,
+            use_key: self.use_key,
+            pre_deploy_cmd: self.pre_deploy_cmd,
+            post_deploy_cmd: self.post_deploy_cmd,
+            deploy_key_id,
+            archived: false,
+            schedule: self.schedule,
+            poll_interval_secs: self.poll_interval_secs,
+            tag_pattern: self.tag_pattern,
+            clone_depth: self.clone_depth,
+            compose_file: self.compose_file,
+            build_before_up: self.build_before_up,
+            push_image: self.push_image,
+            docker_host: self.docker_host,
+            node_id,
+            group_id,
+            environment: self.environment,
+            branch: self.branch,
+            proxy_port: self.proxy_port,
+            compose_override: self.compose_override.filter(|o| !o.trim().is_empty()),
+            review_required: self.review_required,
+            requires_approval: self.requires_approval,
+            watch_paths: self.watch_paths.filter(|p| !p.trim().is_empty()),
+            health_grace_secs: self.health_grace_secs,
+            backup_volumes: self.backup_volumes.filter(|v| !v.trim().is_empty()),
+            post_start_exec: self.post_start_exec.filter(|c| !c.trim().is_empty()),
+            post_start_container: self.post_start_container.filter(|c| !c.trim().is_empty()),
+            repo_dir: self.repo_dir.filter(|d| !d.trim().is_empty()),
+            live_dir: self.live_dir.filter(|d| !d.trim().is_empty()),
+            smoke_url: self.smoke_url.filter(|u| !u.trim().is_empty()),
+            smoke_status: self.smoke_status,
+            smoke_regex: self.smoke_regex.filter(|r| !r.is_empty()),
+            one_off: self.one_off.filter(|o| !o.trim().is_empty()),
+            platform: self.platform.filter(|p| !p.trim().is_empty()),
+            secret_files: self.secret_files,
+            previews: self.previews,
+            preview_of: None,
+            pipeline: self.pipeline.filter(|p| !p.trim().is_empty()),
+            scripts: self.scripts.filter(|s| !s.trim().is_empty()),
+            last_commit: None,
+            last_status: None,
+            last_status_detail: None,
+            last_status_at: None,
+        };
+        service
+            .validate()
+            .map_err(|e| ManifestError::Invalid(service.name.clone(), e))?;
+        Ok(service)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Applied {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+// creates or updates the services the file declares, matched by name; services it
+// doesn't mention are left alone. Nothing is written unless every entry is valid, but a
+// repo clash within an environment stops the rest from being applied
+pub async fn apply(app_state: &AppState, path: &Path) -> Result<Applied, ManifestError> {
+    let file: ServicesFile = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+    let mut declared = HashSet::new();
+    if let Some(spec) = file.services.iter().find(|s| !declared.insert(&s.name)) {
+        return Err(ManifestError::Duplicate(spec.name.clone()));
+    }
+
+    let pool = &app_state.pool;
+    let names = Names::load(app_state).await?;
+    let existing = db::get_services(pool, None).await?;
+    let mut changes = vec![];
+    for spec in file.services {
+        let mut matching = existing.iter().filter(|s| s.name == spec.name);
+        let before = matching.next();
+        if matching.next().is_some() {
+            return Err(ManifestError::Ambiguous(spec.name));
+        }
+        let mut service = spec.into_service(&names)?;
+        // an existing service's clone is where its saved settings put it
+        let repo_path = before.unwrap_or(&service).repo_path(&app_state.config);
+        service
+            .check_compose_names(&repo_path)
+            .map_err(|e| ManifestError::Invalid(service.name.clone(), e))?;
+        // archiving is done from the dashboard
+        service.archived = before.is_some_and(|b| b.archived);
+        changes.push((before, service));
+    }
+
+    let actor = Actor {
+        name: "services file".to_string(),
+        source_ip: None,
+    };
+    let mut applied = Applied::default();
+    for (before, service) in changes {
+        let id = before.map(|b| b.id);
+        if let Some(environment) = &service.environment
+            && let Some(other) =
+                db::service_in_environment(pool, &service.repo_url, environment).await?
+            && Some(other) != id
+        {
+            return Err(ManifestError::Environment(
+                service.name,
+                environment.clone(),
+                other,
+            ));
+        }
+
+        match before {
+            None => {
+                let id = db::new_service(pool, service).await?;
+                let after = db::get_service(pool, id).await?;
+                audit::record(
+                    pool,
+                    &actor,
+                    Some(id),
+                    AuditAction::Create,
+                    audit::diff(None, Some(&after)),
+                )
+                .await;
+                applied.created += 1;
+            }
+            Some(before) => {
+                let changes = audit::diff(Some(before), Some(&service));
+                if changes.as_object().is_some_and(|c| c.is_empty()) {
+                    applied.unchanged += 1;
+                    continue;
+                }
+                db::update_service(pool, before.id, service).await?;
+                audit::record(pool, &actor, Some(before.id), AuditAction::Edit, changes).await;
+                applied.updated += 1;
+            }
+        }
+    }
+    if applied.created + applied.updated > 0 {
+        let _ = app_state
+            .service_broadcast
+            .broadcaster
+            .send(ServiceEvent::AllStatus);
+    }
+    Ok(applied)
+}
+
+// background task that applies SERVICES_FILE, if one is set, at startup and whenever it
+// changes
+pub async fn watch(app_state: AppState) {
+    let Some(path) = app_state.config.services_file.clone() else {
+        return;
+    };
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(TICK);
+    // the modification time last seen, None while the file is missing
+    let mut seen: Option<Option<SystemTime>> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if seen == Some(modified) {
+            continue;
+        }
+        seen = Some(modified);
+        if modified.is_none() {
+            event!(
+                Level::WARN,
+                "Services file {} not found",
+                path.to_string_lossy()
+            );
+            continue;
+        }
+
+        match apply(&app_state, &path).await {
+            Ok(applied) => event!(
+                Level::INFO,
+                "Applied services file | {} created, {} updated, {} unchanged",
+                applied.created,
+                applied.updated,
+                applied.unchanged
+            ),
+            Err(e) => event!(Level::ERROR, "{}", e),
+        }
+    }
+
+    event!(Level::INFO, "Services file watcher stopped.");
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+
+use askama::{Template, filters};
+use axum::response::{Html, IntoResponse, Response};
+use tracing::{Level, event};
+
+// HTML that is safe to send to the browser as-is. The only ways to build one are
+// rendering an (auto-escaping) template, escaping plain text, or a static literal,
+// so user-provided values can't reach the page unescaped.
+#[derive(Clone, Debug, Default)]
+pub struct Markup(String);
+
+impl Markup {
+    pub fn text(text: impl fmt::Display) -> Self {
+        match filters::escape(text, filters::Html) {
+            Ok(escaped) => Markup(escaped.to_string()),
+            Err(never) => match never {},
+        }
+    }
+
+    // for hand-written markup that contains no dynamic values
+    pub fn trusted(html: &'static str) -> Self {
+        Markup(html.to_string())
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Markup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl filters::HtmlSafe for Markup {}
+
+impl IntoResponse for Markup {
+    fn into_response(self) -> Response {
+        Html(self.0).into_response()
+    }
+}
+
+// renders a template, logging and substituting a notice if rendering fails
+pub fn render<T: Template>(template: &T) -> Markup {
+    match template.render() {
+        Ok(html) => Markup(html),
+        Err(e) => {
+            event!(Level::ERROR, "Template rendering failed | {}", e);
+            Markup::trusted("<div class=\"error\">Rendering failed. See logs.</div>")
+        }
+    }
+}
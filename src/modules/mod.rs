@@ -1,19 +1,71 @@
+pub mod activity;
+pub mod agent;
+pub mod assets;
+pub mod audit;
+pub mod backup;
 pub mod db;
+pub mod deploy_key;
+pub mod format;
+pub mod group;
+pub mod maintenance;
+pub mod manifest;
+pub mod markup;
+pub mod network;
+pub mod notify;
+pub mod request_id;
+pub mod scheduler;
+pub mod secrets;
 pub mod service;
+pub mod statuspage;
+pub mod tools;
+pub mod uptime;
+pub mod user;
 
 use std::{
+    collections::VecDeque,
     env,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use agent::Agents;
+use askama::Template;
 use async_stream::stream;
+use audit::{Actor, AuditAction};
 use axum::response::sse::Event;
 use dotenv::dotenv;
 use futures::stream::Stream;
-use service::{Service, ServiceEvent};
+use ipnet::IpNet;
+use maintenance::logs::LogRetention;
+use markup::{Markup, render};
+use notify::{
+    email::{Smtp, SmtpTls},
+    github::{GitHub, GitHubAuth},
+    push::Push,
+    webhook::Webhooks,
+};
+use scheduler::cron::{CronError, Schedule};
+use secrets::{Passphrase, SecretsKey};
+use service::{
+    BulkProgress, Lifecycle, Service, ServiceError, ServiceEvent, ServiceFilter, ServiceStatus,
+    approval::{DeployApproval, Trigger},
+    cache::DockerCache,
+    deploy_log::DeployLog,
+    dry_run::DryRun,
+    job::{Outcome, RunningJobs, ServiceJob},
+    process::{InFlight, Retry, Timeouts},
+    proxy::Proxy,
+    review::DeployReview,
+};
 use sqlx::{Pool, Sqlite, SqlitePool};
 use thiserror::Error;
-use tokio::sync::broadcast;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, broadcast};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{Level, event};
+use tracing_appender::rolling::Rotation;
+use user::Role;
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -21,6 +73,196 @@ pub enum ConfigError {
     EnvVarError(#[from] env::VarError),
     #[error("Environment variable parse error")]
     ParseError(#[from] std::num::ParseIntError),
+    #[error("Invalid schedule | {0}")]
+    Schedule(#[from] CronError),
+    #[error("Unknown PROXY '{0}', expected traefik, caddy or none")]
+    Proxy(String),
+    #[error("Unknown LOG_FORMAT '{0}', expected pretty or json")]
+    LogFormat(String),
+    #[error("Unknown LOG_ROTATION '{0}', expected minutely, hourly, daily, weekly or never")]
+    LogRotation(String),
+    #[error("Invalid network '{1}' in {0}, expected an address or CIDR such as 10.8.0.0/24")]
+    Cidr(&'static str, String),
+    #[error("Unknown SMTP_TLS '{0}', expected starttls, tls or none")]
+    SmtpTls(String),
+    #[error("Invalid network name '{0}' in SHARED_NETWORKS")]
+    SharedNetwork(String),
+    #[error("Unable to read the GitHub App key in GITHUB_APP_KEY_FILE | {0}")]
+    GitHubKey(String),
+    #[error("Invalid URL '{0}' in WEBHOOK_URLS, expected http:// or https://")]
+    WebhookUrl(String),
+}
+
+// how the rolling files in LOGS_PATH are written; stdout is always pretty
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Pretty,
+    // one object per line, for log shippers
+    Json,
+}
+
+fn log_format_var() -> Result<LogFormat, ConfigError> {
+    match env::var("LOG_FORMAT") {
+        Ok(format) => match format.trim().to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(ConfigError::LogFormat(other.to_string())),
+        },
+        Err(_) => Ok(LogFormat::Pretty),
+    }
+}
+
+fn log_rotation_var() -> Result<Rotation, ConfigError> {
+    match env::var("LOG_ROTATION") {
+        Ok(rotation) => match rotation.trim().to_lowercase().as_str() {
+            "minutely" => Ok(Rotation::MINUTELY),
+            "hourly" => Ok(Rotation::HOURLY),
+            "daily" => Ok(Rotation::DAILY),
+            "weekly" => Ok(Rotation::WEEKLY),
+            "never" => Ok(Rotation::NEVER),
+            other => Err(ConfigError::LogRotation(other.to_string())),
+        },
+        Err(_) => Ok(Rotation::HOURLY),
+    }
+}
+
+// email notifications are off unless SMTP_HOST is set
+fn smtp_var() -> Result<Option<Smtp>, ConfigError> {
+    let Ok(host) = env::var("SMTP_HOST") else {
+        return Ok(None);
+    };
+    let tls = match env::var("SMTP_TLS") {
+        Ok(tls) => tls.parse().map_err(ConfigError::SmtpTls)?,
+        Err(_) => SmtpTls::StartTls,
+    };
+    let port = match env::var("SMTP_PORT") {
+        Ok(port) => Some(port.parse::<u16>()?),
+        Err(_) => None,
+    };
+    Ok(Some(Smtp {
+        host,
+        port,
+        username: env::var("SMTP_USERNAME").ok(),
+        password: env::var("SMTP_PASSWORD").ok(),
+        from: env::var("SMTP_FROM")?,
+        tls,
+    }))
+}
+
+// a token wins over an app when both are set
+fn github_var() -> Result<Option<GitHub>, ConfigError> {
+    let auth = match (env::var("GITHUB_TOKEN"), env::var("GITHUB_APP_ID")) {
+        (Ok(token), _) => GitHubAuth::Token(token),
+        (Err(_), Ok(id)) => {
+            let pem = std::fs::read(env::var("GITHUB_APP_KEY_FILE")?)
+                .map_err(|e| ConfigError::GitHubKey(e.to_string()))?;
+            let key = openssl::pkey::PKey::private_key_from_pem(&pem)
+                .map_err(|e| ConfigError::GitHubKey(e.to_string()))?;
+            GitHubAuth::App { id, key }
+        }
+        (Err(_), Err(_)) => return Ok(None),
+    };
+    Ok(Some(GitHub { auth }))
+}
+
+// comma-separated endpoint URLs; unset is none
+fn webhooks_var() -> Result<Option<Webhooks>, ConfigError> {
+    let Ok(value) = env::var("WEBHOOK_URLS") else {
+        return Ok(None);
+    };
+    let mut urls = vec![];
+    for url in value.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(ConfigError::WebhookUrl(url.to_string()));
+        }
+        urls.push(url.to_string());
+    }
+    if urls.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Webhooks {
+        urls,
+        secret: env::var("WEBHOOK_SECRET").ok(),
+    }))
+}
+
+fn push_var() -> Push {
+    Push {
+        ntfy_url: env::var("NTFY_URL").unwrap_or("https://ntfy.sh".to_string()),
+        ntfy_token: env::var("NTFY_TOKEN").ok(),
+        pushover_token: env::var("PUSHOVER_TOKEN").ok(),
+    }
+}
+
+// a path to the binary, or just its name to look it up on PATH
+fn bin_var(name: &str, default: &str) -> PathBuf {
+    PathBuf::from(env::var(name).unwrap_or(default.to_string()))
+}
+
+// unset is an empty list
+fn cidrs_var(name: &'static str) -> Result<Vec<IpNet>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => network::parse_cidrs(&value).map_err(|cidr| ConfigError::Cidr(name, cidr)),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+// comma-separated docker network names; unset is an empty list
+fn shared_networks_var() -> Result<Vec<String>, ConfigError> {
+    let Ok(value) = env::var("SHARED_NETWORKS") else {
+        return Ok(vec![]);
+    };
+    let mut networks: Vec<String> = vec![];
+    for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !service::network::valid_name(name) {
+            return Err(ConfigError::SharedNetwork(name.to_string()));
+        }
+        if !networks.iter().any(|n| n == name) {
+            networks.push(name.to_string());
+        }
+    }
+    Ok(networks)
+}
+
+// unset keeps seven, 0 keeps every backup
+fn backup_keep_var() -> Result<Option<usize>, ConfigError> {
+    let keep = match env::var("BACKUP_KEEP") {
+        Ok(keep) => keep.parse::<usize>()?,
+        Err(_) => 7,
+    };
+    Ok(Some(keep).filter(|k| *k > 0))
+}
+
+// unset keeps three per service, 0 keeps every release
+fn releases_keep_var() -> Result<Option<usize>, ConfigError> {
+    let keep = match env::var("RELEASES_KEEP") {
+        Ok(keep) => keep.parse::<usize>()?,
+        Err(_) => 3,
+    };
+    Ok(Some(keep).filter(|k| *k > 0))
+}
+
+// unset keeps five per service, 0 keeps every snapshot
+fn volume_backup_keep_var() -> Result<Option<usize>, ConfigError> {
+    let keep = match env::var("VOLUME_BACKUP_KEEP") {
+        Ok(keep) => keep.parse::<usize>()?,
+        Err(_) => 5,
+    };
+    Ok(Some(keep).filter(|k| *k > 0))
+}
+
+// unset or 0 keeps every log file
+fn log_retention_var() -> Result<LogRetention, ConfigError> {
+    let limit = |name: &str| -> Result<Option<u64>, ConfigError> {
+        match env::var(name) {
+            Ok(limit) => Ok(Some(limit.parse::<u64>()?).filter(|l| *l > 0)),
+            Err(_) => Ok(None),
+        }
+    };
+    Ok(LogRetention {
+        max_files: limit("LOG_MAX_FILES")?.map(|files| files as usize),
+        max_bytes: limit("LOG_MAX_MB")?.map(|mb| mb * 1024 * 1024),
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -29,9 +271,136 @@ pub struct Config {
     pub app_host: String,
     pub app_port: u16,
     pub logs_dir: PathBuf,
+    pub log_format: LogFormat,
+    pub log_rotation: Rotation,
+    pub log_retention: LogRetention,
     pub services_repo_dir: PathBuf,
     pub services_live_dir: PathBuf,
+    // releases kept in each service's live home, the current one included; see `service::live`
+    pub releases_keep: Option<usize>,
     pub key_file: PathBuf,
+    pub deploy_keys_dir: PathBuf,
+    pub shutdown_grace_secs: u64,
+    pub secrets_passphrase: Option<Passphrase>,
+    pub secrets_key: Option<SecretsKey>,
+    pub admin_username: String,
+    pub admin_password: Option<String>,
+    pub clone_depth: Option<u32>,
+    pub registry: Option<String>,
+    pub proxy: Proxy,
+    // external networks every service joins, created when missing
+    pub shared_networks: Vec<String>,
+    pub prune_schedule: Option<String>,
+    pub timeouts: Timeouts,
+    pub retry: Retry,
+    pub reconcile_interval: Duration,
+    // how long a pull request's preview outlives its last update; see `service::preview`
+    pub preview_ttl: Duration,
+    // previews are served as <name>.<domain> when set, otherwise under their source's host
+    pub preview_domain: Option<String>,
+    // redeploy or stop services that drifted instead of only reporting them
+    pub reconcile_autofix: bool,
+    // restarts of a crashed service allowed within the window; 0 turns the watchdog off
+    pub watchdog_max_restarts: usize,
+    pub watchdog_window: Duration,
+    // local deploys allowed to run at once; 0 means no limit
+    pub max_concurrent_deploys: usize,
+    // events a slow subscriber can fall behind by before it misses some
+    pub broadcast_capacity: usize,
+    // how long the dashboard reuses a `docker ps`; see `DockerCache`
+    pub docker_cache_ttl: Duration,
+    // services.yaml to create and update services from
+    pub services_file: Option<PathBuf>,
+    pub backup_dir: PathBuf,
+    pub backup_keep: Option<usize>,
+    // named volume snapshots taken before deploys, one directory per service
+    pub volume_backup_dir: PathBuf,
+    pub volume_backup_keep: Option<usize>,
+    // runs tar against the volumes; anything with sh, find and tar works
+    pub volume_backup_image: String,
+    pub git_bin: PathBuf,
+    pub docker_bin: PathBuf,
+    pub smtp: Option<Smtp>,
+    // networks state-changing and admin requests must come from; empty allows any
+    pub allow_cidrs: Vec<IpNet>,
+    // proxies whose X-Forwarded-For is believed
+    pub trusted_proxies: Vec<IpNet>,
+    pub push: Push,
+    pub github: Option<GitHub>,
+    // endpoints sent deploy and health events; see `notify::webhook`
+    pub webhooks: Option<Webhooks>,
+    // where users reach this instance, e.g. https://wraut.example.com, for links sent elsewhere
+    pub public_url: Option<String>,
+    // only set in agent mode
+    pub agent_server: Option<String>,
+    pub agent_token: Option<String>,
+}
+
+// unset or 0 clones full history
+fn clone_depth_var() -> Result<Option<u32>, ConfigError> {
+    match env::var("CLONE_DEPTH") {
+        Ok(depth) => Ok(Some(depth.parse::<u32>()?).filter(|d| *d > 0)),
+        Err(_) => Ok(None),
+    }
+}
+
+// a channel needs room for at least one event
+fn broadcast_capacity_var() -> Result<usize, ConfigError> {
+    match env::var("BROADCAST_CAPACITY") {
+        Ok(capacity) => Ok(capacity.parse::<usize>()?.max(1)),
+        Err(_) => Ok(100),
+    }
+}
+
+fn secs_var(name: &str, default: u64) -> Result<Duration, ConfigError> {
+    match env::var(name) {
+        Ok(secs) => Ok(Duration::from_secs(secs.parse::<u64>()?)),
+        Err(_) => Ok(Duration::from_secs(default)),
+    }
+}
+
+// traefik unless PROXY says otherwise; an empty TRAEFIK_CERT_RESOLVER turns off
+// certificate resolution for proxies that terminate TLS some other way
+fn proxy_var() -> Result<Proxy, ConfigError> {
+    let proxy = env::var("PROXY")
+        .unwrap_or("traefik".to_string())
+        .parse::<Proxy>()
+        .map_err(ConfigError::Proxy)?;
+    Ok(match proxy {
+        Proxy::Traefik {
+            entrypoint,
+            cert_resolver,
+        } => Proxy::Traefik {
+            entrypoint: env::var("TRAEFIK_ENTRYPOINT").unwrap_or(entrypoint),
+            cert_resolver: match env::var("TRAEFIK_CERT_RESOLVER") {
+                Ok(resolver) => Some(resolver).filter(|r| !r.is_empty()),
+                Err(_) => cert_resolver,
+            },
+        },
+        proxy => proxy,
+    })
+}
+
+// a command still running after its timeout is killed and fails the deploy
+fn timeouts_var() -> Result<Timeouts, ConfigError> {
+    Ok(Timeouts {
+        git: secs_var("GIT_TIMEOUT_SECS", 300)?,
+        copy: secs_var("COPY_TIMEOUT_SECS", 300)?,
+        compose: secs_var("COMPOSE_TIMEOUT_SECS", 1800)?,
+        smoke: secs_var("SMOKE_TIMEOUT_SECS", 60)?,
+    })
+}
+
+// 1 attempt turns retries off
+fn retry_var() -> Result<Retry, ConfigError> {
+    let attempts = match env::var("RETRY_ATTEMPTS") {
+        Ok(attempts) => attempts.parse::<u32>()?.max(1),
+        Err(_) => 3,
+    };
+    Ok(Retry {
+        attempts,
+        backoff: secs_var("RETRY_BACKOFF_SECS", 5)?,
+    })
 }
 
 impl Config {
@@ -48,14 +417,173 @@ impl Config {
         let services_live_dir = Path::new(services_live_dir_string.as_str());
         let key_file_string: String = env::var("KEY_FILE")?;
         let key_file = Path::new(key_file_string.as_str());
+        // generated deploy keys live beside KEY_FILE unless told otherwise
+        let deploy_keys_dir = match env::var("DEPLOY_KEYS_PATH") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => key_file.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let shutdown_grace_secs = match env::var("SHUTDOWN_GRACE_SECS") {
+            Ok(secs) => secs.parse::<u64>()?,
+            Err(_) => 30,
+        };
+        // derived into `secrets_key` once the database is open; see `secrets::unlock`
+        let secrets_passphrase = env::var("SECRETS_KEY").ok().map(Passphrase::new);
+        // only used to create the first admin when the user table is empty
+        let admin_username = env::var("ADMIN_USERNAME").unwrap_or("admin".to_string());
+        let admin_password = env::var("ADMIN_PASSWORD").ok();
+        // e.g. "registry.example.com/team"; pushes use the host's `docker login`
+        let registry = env::var("REGISTRY").ok();
+        let proxy = proxy_var()?;
+        let prune_schedule = env::var("PRUNE_SCHEDULE").ok();
+        if let Some(schedule) = &prune_schedule {
+            schedule.parse::<Schedule>()?;
+        }
+        let clone_depth = clone_depth_var()?;
+        let timeouts = timeouts_var()?;
+        let retry = retry_var()?;
+        let reconcile_interval = secs_var("RECONCILE_INTERVAL_SECS", 60)?;
+        let reconcile_autofix = env::var("RECONCILE_AUTOFIX").is_ok_and(|v| v == "true");
+        let watchdog_max_restarts = match env::var("WATCHDOG_MAX_RESTARTS") {
+            Ok(restarts) => restarts.parse::<usize>()?,
+            Err(_) => 3,
+        };
+        let watchdog_window = secs_var("WATCHDOG_WINDOW_SECS", 600)?;
+        let max_concurrent_deploys = match env::var("MAX_CONCURRENT_DEPLOYS") {
+            Ok(max) => max.parse::<usize>()?,
+            Err(_) => 2,
+        };
+        // snapshots sit beside the database unless told otherwise; point this at another disk
+        // for them to outlive it
+        let backup_dir = match env::var("BACKUP_PATH") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => backup::database_path(&db_url)
+                .and_then(|db| db.parent().map(|dir| dir.join("backups")))
+                .unwrap_or_else(|| PathBuf::from("backups")),
+        };
+        let volume_backup_dir = match env::var("VOLUME_BACKUP_PATH") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => backup_dir.join("volumes"),
+        };
         Ok(Config {
             db_url,
             app_host,
             app_port,
             logs_dir: logs_dir.to_path_buf(),
+            log_format: log_format_var()?,
+            log_rotation: log_rotation_var()?,
+            log_retention: log_retention_var()?,
             services_repo_dir: services_repo_dir.to_path_buf(),
             services_live_dir: services_live_dir.to_path_buf(),
+            releases_keep: releases_keep_var()?,
             key_file: key_file.to_path_buf(),
+            deploy_keys_dir,
+            shutdown_grace_secs,
+            secrets_passphrase,
+            secrets_key: None,
+            admin_username,
+            admin_password,
+            clone_depth,
+            registry,
+            proxy,
+            shared_networks: shared_networks_var()?,
+            prune_schedule,
+            timeouts,
+            retry,
+            reconcile_interval,
+            preview_ttl: secs_var("PREVIEW_TTL_SECS", 3 * 24 * 60 * 60)?,
+            preview_domain: env::var("PREVIEW_DOMAIN").ok(),
+            reconcile_autofix,
+            watchdog_max_restarts,
+            watchdog_window,
+            max_concurrent_deploys,
+            broadcast_capacity: broadcast_capacity_var()?,
+            docker_cache_ttl: secs_var("DOCKER_CACHE_SECS", 2)?,
+            services_file: env::var("SERVICES_FILE").ok().map(PathBuf::from),
+            backup_dir,
+            backup_keep: backup_keep_var()?,
+            volume_backup_dir,
+            volume_backup_keep: volume_backup_keep_var()?,
+            volume_backup_image: env::var("VOLUME_BACKUP_IMAGE").unwrap_or("alpine".to_string()),
+            git_bin: bin_var("GIT_BIN", "git"),
+            docker_bin: bin_var("DOCKER_BIN", "docker"),
+            smtp: smtp_var()?,
+            allow_cidrs: cidrs_var("ALLOW_CIDRS")?,
+            trusted_proxies: cidrs_var("TRUSTED_PROXIES")?,
+            push: push_var(),
+            github: github_var()?,
+            webhooks: webhooks_var()?,
+            public_url: env::var("PUBLIC_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            agent_server: None,
+            agent_token: None,
+        })
+    }
+
+    // agents only run pipelines handed to them by the central instance; they never open the
+    // database or serve the dashboard, so only paths and docker settings are read
+    pub fn agent() -> Result<Self, ConfigError> {
+        dotenv().ok();
+        let agent_server = env::var("AGENT_SERVER")?;
+        let agent_token = env::var("AGENT_TOKEN")?;
+        let services_repo_dir = PathBuf::from(env::var("SERVICE_REPO_PATH")?);
+        let key_file = env::var("KEY_FILE").map(PathBuf::from).unwrap_or_default();
+        // deploy keys sent by the central instance are written here
+        let deploy_keys_dir = match env::var("DEPLOY_KEYS_PATH") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => services_repo_dir.join(".wraut_keys"),
+        };
+        Ok(Config {
+            db_url: String::new(),
+            app_host: String::new(),
+            app_port: 0,
+            logs_dir: PathBuf::from(env::var("LOGS_PATH")?),
+            log_format: log_format_var()?,
+            log_rotation: log_rotation_var()?,
+            log_retention: log_retention_var()?,
+            services_live_dir: PathBuf::from(env::var("SERVICE_LIVE_PATH")?),
+            releases_keep: releases_keep_var()?,
+            services_repo_dir,
+            key_file,
+            deploy_keys_dir,
+            shutdown_grace_secs: 30,
+            secrets_passphrase: None,
+            secrets_key: None,
+            admin_username: String::new(),
+            admin_password: None,
+            clone_depth: clone_depth_var()?,
+            registry: env::var("REGISTRY").ok(),
+            proxy: proxy_var()?,
+            shared_networks: shared_networks_var()?,
+            prune_schedule: None,
+            timeouts: timeouts_var()?,
+            retry: retry_var()?,
+            reconcile_interval: Duration::from_secs(60),
+            preview_ttl: Duration::ZERO,
+            preview_domain: None,
+            reconcile_autofix: false,
+            watchdog_max_restarts: 0,
+            watchdog_window: Duration::from_secs(600),
+            max_concurrent_deploys: 0,
+            broadcast_capacity: broadcast_capacity_var()?,
+            docker_cache_ttl: Duration::ZERO,
+            services_file: None,
+            backup_dir: PathBuf::new(),
+            backup_keep: None,
+            volume_backup_dir: PathBuf::new(),
+            volume_backup_keep: None,
+            volume_backup_image: String::new(),
+            git_bin: bin_var("GIT_BIN", "git"),
+            docker_bin: bin_var("DOCKER_BIN", "docker"),
+            smtp: None,
+            allow_cidrs: vec![],
+            trusted_proxies: vec![],
+            push: push_var(),
+            github: None,
+            webhooks: None,
+            public_url: None,
+            agent_server: Some(agent_server),
+            agent_token: Some(agent_token),
         })
     }
 }
@@ -65,20 +593,654 @@ pub struct AppState {
     pub config: Config,
     pub pool: Pool<Sqlite>,
     pub service_broadcast: ServiceBroadcast,
+    pub deployments: TaskTracker,
+    pub csrf_token: String,
+    pub agents: Agents,
+    pub in_flight: InFlight,
+    // scheduled jobs with a run going
+    pub running_jobs: RunningJobs,
+    pub docker_cache: DockerCache,
+    // one permit per local deploy allowed to run at once
+    pub deploy_slots: Arc<Semaphore>,
 }
 
+pub fn deploy_slots(max_concurrent_deploys: usize) -> Arc<Semaphore> {
+    Arc::new(Semaphore::new(match max_concurrent_deploys {
+        0 => Semaphore::MAX_PERMITS,
+        max => max,
+    }))
+}
+
+impl AppState {
+    // runs the deploy pipeline in the background, reporting the outcome over the broadcast
+    pub fn spawn_deploy(&self, service_id: i64, service: Result<Service, db::DBError>) {
+        self.spawn_deploy_at(service_id, service, None);
+    }
+
+    // like `spawn_deploy`, but checks out `commit`, when given, instead of pulling.
+    // Services that require review wait for approval instead
+    pub fn spawn_deploy_at(
+        &self,
+        service_id: i64,
+        service: Result<Service, db::DBError>,
+        commit: Option<String>,
+    ) {
+        match service {
+            Ok(serv) if serv.review_required && !serv.archived => self.spawn_review(serv, commit),
+            service => self.spawn_approved(service_id, service, commit),
+        }
+    }
+
+    fn spawn_approved(
+        &self,
+        service_id: i64,
+        service: Result<Service, db::DBError>,
+        commit: Option<String>,
+    ) {
+        let app_state = self.clone();
+        let cancel = self.in_flight.begin(service_id);
+        self.deployments.spawn(request_id::carry(async move {
+            let slot = app_state.deploy_slot(service_id, &service, &cancel).await;
+            app_state
+                .deploy(service_id, service, commit, slot, cancel)
+                .await;
+        }));
+    }
+
+    // dry runs the deploy and holds its compose diff for review, pinned to the commit it
+    // pulled; a deploy that wouldn't change the compose files goes ahead
+    fn spawn_review(&self, service: Service, commit: Option<String>) {
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            let broadcaster = app_state.service_broadcast.broadcaster.clone();
+            let _ = broadcaster.send(ServiceEvent::update(
+                service.id,
+                ServiceStatus::DeploymentRequested,
+            ));
+            let status = match app_state.dry_run(service.clone(), commit.as_deref()).await {
+                Ok(dry_run) if dry_run.compose_diff.is_empty() => {
+                    app_state.spawn_approved(service.id, Ok(service), dry_run.commit);
+                    return;
+                }
+                Ok(dry_run) => match db::new_deploy_review(
+                    &app_state.pool,
+                    service.id,
+                    dry_run.commit,
+                    dry_run.compose_diff,
+                )
+                .await
+                {
+                    Ok(_) => ServiceStatus::AwaitingReview,
+                    Err(e) => ServiceStatus::from_error(e.into()),
+                },
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        "Unable to prepare review of {} | {}",
+                        service.name,
+                        e
+                    );
+                    ServiceStatus::from_error(e)
+                }
+            };
+            let _ = broadcaster.send(ServiceEvent::update(service.id, status));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }));
+    }
+
+    // deploys the reviewed commit; None when no review was waiting
+    pub async fn approve_review(
+        &self,
+        service: Service,
+    ) -> Result<Option<DeployReview>, db::DBError> {
+        let review = db::take_deploy_review(&self.pool, service.id).await?;
+        if let Some(review) = &review {
+            self.spawn_approved(service.id, Ok(service), review.commit_sha.clone());
+        }
+        Ok(review)
+    }
+
+    pub async fn decline_review(
+        &self,
+        service_id: i64,
+    ) -> Result<Option<DeployReview>, db::DBError> {
+        let review = db::take_deploy_review(&self.pool, service_id).await?;
+        if review.is_some() {
+            let broadcaster = &self.service_broadcast.broadcaster;
+            let _ = broadcaster.send(ServiceEvent::update(service_id, ServiceStatus::Declined));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }
+        Ok(review)
+    }
+
+    // deploys started by a hook, schedule or poller; services that require approval hold
+    // them until someone approves
+    pub fn spawn_triggered(&self, service: Service, trigger: Trigger) {
+        if !service.requires_approval || service.archived {
+            return self.spawn_deploy(service.id, Ok(service));
+        }
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            event!(
+                Level::INFO,
+                "Holding {} deploy of {} for approval",
+                trigger,
+                service.name
+            );
+            let status = match db::new_deploy_approval(
+                &app_state.pool,
+                service.id,
+                trigger.as_str(),
+            )
+            .await
+            {
+                Ok(_) => ServiceStatus::PendingApproval,
+                Err(e) => ServiceStatus::from_error(e.into()),
+            };
+            let broadcaster = &app_state.service_broadcast.broadcaster;
+            let _ = broadcaster.send(ServiceEvent::update(service.id, status));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }));
+    }
+
+    // deploys the held deploy; None when none was waiting
+    pub async fn approve_deploy(
+        &self,
+        service: Service,
+    ) -> Result<Option<DeployApproval>, db::DBError> {
+        let approval = db::take_deploy_approval(&self.pool, service.id).await?;
+        if approval.is_some() {
+            self.spawn_deploy(service.id, Ok(service));
+        }
+        Ok(approval)
+    }
+
+    pub async fn decline_deploy(
+        &self,
+        service_id: i64,
+    ) -> Result<Option<DeployApproval>, db::DBError> {
+        let approval = db::take_deploy_approval(&self.pool, service_id).await?;
+        if approval.is_some() {
+            let broadcaster = &self.service_broadcast.broadcaster;
+            let _ = broadcaster.send(ServiceEvent::update(service_id, ServiceStatus::Declined));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }
+        Ok(approval)
+    }
+
+    // waits for a free slot under MAX_CONCURRENT_DEPLOYS; deploys on nodes don't need one
+    async fn deploy_slot(
+        &self,
+        service_id: i64,
+        service: &Result<Service, db::DBError>,
+        cancel: &CancellationToken,
+    ) -> Result<Option<OwnedSemaphorePermit>, ServiceError> {
+        if let Ok(Service {
+            node_id: Some(_),
+            archived: false,
+            ..
+        }) = service
+        {
+            return Ok(None);
+        }
+        if let Ok(permit) = self.deploy_slots.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+
+        let _ = self
+            .service_broadcast
+            .broadcaster
+            .send(ServiceEvent::update(service_id, ServiceStatus::Queued));
+        tokio::select! {
+            permit = self.deploy_slots.clone().acquire_owned() => Ok(permit.ok()),
+            _ = cancel.cancelled() => Err(ServiceError::Cancelled),
+        }
+    }
+
+    // true when the service deployed
+    async fn deploy(
+        &self,
+        service_id: i64,
+        service: Result<Service, db::DBError>,
+        commit: Option<String>,
+        slot: Result<Option<OwnedSemaphorePermit>, ServiceError>,
+        cancel: CancellationToken,
+    ) -> bool {
+        let requested = std::time::Instant::now();
+        let deployed_status = service
+            .as_ref()
+            .map_or(ServiceStatus::Running, |s| s.deployed_status());
+        let log = DeployLog::start(&self.service_broadcast.broadcaster, service_id);
+        if slot.is_ok() {
+            let _ = self
+                .service_broadcast
+                .broadcaster
+                .send(ServiceEvent::DeployStarted { id: service_id });
+        }
+        // node services leave no compose file here
+        let local = service
+            .as_ref()
+            .ok()
+            .filter(|s| s.node_id.is_none())
+            .cloned();
+        let deployed = match (slot, service) {
+            (Err(e), _) => Err(e.into()),
+            (
+                Ok(_),
+                Ok(
+                    serv @ Service {
+                        node_id: Some(node_id),
+                        archived: false,
+                        ..
+                    },
+                ),
+            ) => agent::deploy(self, serv, node_id, commit, &cancel).await,
+            // the slot is held until the pipeline ends
+            (Ok(_slot), service) => {
+                Service::deploy(
+                    self.config.clone(),
+                    &self.pool,
+                    service,
+                    commit.as_deref(),
+                    &cancel,
+                    self.service_broadcast.broadcaster.clone(),
+                )
+                .await
+            }
+        };
+        self.in_flight.end(service_id);
+        let (status, ok, recorded, checked_out) = match deployed {
+            Ok(recorded) => (deployed_status, true, recorded, None),
+            Err(failure) => (
+                ServiceStatus::from_error(failure.error),
+                false,
+                None,
+                failure.commit,
+            ),
+        };
+
+        let broadcaster = &self.service_broadcast.broadcaster;
+        let _ = broadcaster.send(ServiceEvent::update(service_id, status.clone()));
+        let deployment = self
+            .keep_deploy_log(
+                service_id,
+                local,
+                &status,
+                (recorded, checked_out),
+                requested,
+                log,
+            )
+            .await;
+        let _ = broadcaster.send(ServiceEvent::Deployed {
+            id: service_id,
+            ok,
+            status,
+            deployment,
+        });
+        ok
+    }
+
+    // stores what the deploy logged and the compose file it left with its deployment; a deploy
+    // that didn't succeed is recorded here with its outcome and the commit it checked out. Returns
+    // the deployment's id, if it has a row
+    async fn keep_deploy_log(
+        &self,
+        service_id: i64,
+        local: Option<Service>,
+        status: &ServiceStatus,
+        (recorded, checked_out): (Option<i64>, Option<String>),
+        requested: std::time::Instant,
+        log: DeployLog,
+    ) -> Option<i64> {
+        let log = log.finish().await;
+        let compose_file = local.and_then(|service| {
+            let live_path = service.live_path(&self.config);
+            service
+                .compose_path(&live_path)
+                .ok()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+        });
+        let kept = match (recorded, status) {
+            (Some(id), _) => db::set_deployment_log(&self.pool, id, log, compose_file)
+                .await
+                .map(|_| Some(id)),
+            (None, ServiceStatus::Running | ServiceStatus::Completed) => Ok(None),
+            (None, status) => {
+                let outcome = match status {
+                    ServiceStatus::Cancelled => "cancelled",
+                    _ => "failed",
+                };
+                db::new_failed_deployment(
+                    &self.pool,
+                    service_id,
+                    checked_out,
+                    outcome,
+                    requested.elapsed(),
+                    log,
+                    compose_file,
+                )
+                .await
+                .map(Some)
+            }
+        };
+        match kept {
+            Ok(deployment) => deployment,
+            Err(e) => {
+                event!(Level::ERROR, "Unable to keep deploy log | {}", e);
+                recorded
+            }
+        }
+    }
+
+    // queues a deploy of every active service the filter matches, returning their ids;
+    // they start in order as slots free up
+    pub async fn deploy_all(
+        &self,
+        filter: &ServiceFilter,
+        actor: &Actor,
+    ) -> Result<Vec<i64>, db::DBError> {
+        let (reviewed, services): (Vec<Service>, Vec<Service>) =
+            db::get_services(&self.pool, Some(false))
+                .await?
+                .into_iter()
+                .filter(|s| s.active && filter.matches(s))
+                .partition(|s| s.review_required);
+        let ids: Vec<i64> = reviewed.iter().chain(&services).map(|s| s.id).collect();
+        for id in &ids {
+            audit::record(
+                &self.pool,
+                actor,
+                Some(*id),
+                AuditAction::Deploy,
+                serde_json::json!({
+                    "bulk": true,
+                    "group_id": filter.group,
+                    "environment": filter.environment,
+                    "search": filter.search,
+                }),
+            )
+            .await;
+        }
+        // services that require review wait for it outside the bulk deploy
+        for service in reviewed {
+            self.spawn_review(service, None);
+        }
+        if !services.is_empty() {
+            self.spawn_bulk_deploy(services);
+        }
+        Ok(ids)
+    }
+
+    fn spawn_bulk_deploy(&self, services: Vec<Service>) {
+        let broadcaster = self.service_broadcast.broadcaster.clone();
+        let queued: Vec<(CancellationToken, Service)> = services
+            .into_iter()
+            .map(|service| {
+                let _ = broadcaster.send(ServiceEvent::update(service.id, ServiceStatus::Queued));
+                (self.in_flight.begin(service.id), service)
+            })
+            .collect();
+        let progress = Arc::new(Mutex::new(BulkProgress {
+            total: queued.len(),
+            ..Default::default()
+        }));
+        let _ = broadcaster.send(ServiceEvent::BulkProgress {
+            progress: BulkProgress {
+                total: queued.len(),
+                ..Default::default()
+            },
+        });
+
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            for (cancel, service) in queued {
+                let service_id = service.id;
+                let service = Ok(service);
+                let slot = app_state.deploy_slot(service_id, &service, &cancel).await;
+
+                let app_state = app_state.clone();
+                let progress = progress.clone();
+                app_state
+                    .deployments
+                    .clone()
+                    .spawn(request_id::carry(async move {
+                        let deployed = app_state
+                            .deploy(service_id, service, None, slot, cancel)
+                            .await;
+                        let progress = {
+                            let mut progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+                            match deployed {
+                                true => progress.deployed += 1,
+                                false => progress.failed += 1,
+                            }
+                            progress.clone()
+                        };
+                        let _ = app_state
+                            .service_broadcast
+                            .broadcaster
+                            .send(ServiceEvent::BulkProgress { progress });
+                    }));
+            }
+        }));
+    }
+
+    // stops, starts, restarts or tears down the service's containers in the background, on
+    // its node if it has one, without redeploying
+    pub fn spawn_lifecycle(&self, service: Service, action: Lifecycle) {
+        let app_state = self.clone();
+        let cancel = self.in_flight.begin(service.id);
+        self.deployments.spawn(request_id::carry(async move {
+            let service_id = service.id;
+            let broadcaster = app_state.service_broadcast.broadcaster.clone();
+            let result = match service.node_id {
+                Some(node_id) => {
+                    agent::lifecycle(&app_state, service, node_id, action, &cancel).await
+                }
+                None => action.run(&service, app_state.config.clone(), &cancel, &broadcaster),
+            };
+            app_state.in_flight.end(service_id);
+            let status = match result {
+                Ok(_) => action.status(),
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        "Unable to {} service {} | {}",
+                        action,
+                        service_id,
+                        e
+                    );
+                    ServiceStatus::from_error(e)
+                }
+            };
+
+            let _ = broadcaster.send(ServiceEvent::update(service_id, status));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }));
+    }
+
+    // restores the service's volumes from one of its snapshots in the background; snapshots
+    // are only taken of local services
+    pub fn spawn_volume_restore(&self, service: Service, snapshot: String) {
+        let app_state = self.clone();
+        let cancel = self.in_flight.begin(service.id);
+        self.deployments.spawn(request_id::carry(async move {
+            let broadcaster = app_state.service_broadcast.broadcaster.clone();
+            let result =
+                service.restore_volumes(app_state.config.clone(), &snapshot, &cancel, &broadcaster);
+            app_state.in_flight.end(service.id);
+            let status = match result {
+                Ok(_) => ServiceStatus::Running,
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        "Unable to restore volumes of service {} | {}",
+                        service.id,
+                        e
+                    );
+                    ServiceStatus::from_error(e)
+                }
+            };
+
+            let _ = broadcaster.send(ServiceEvent::update(service.id, status));
+            let _ = broadcaster.send(ServiceEvent::AllStatus);
+        }));
+    }
+
+    // runs one of the service's jobs in the background and records the run; false when a
+    // run of it is still going. A job due while the service is deploying fails instead of
+    // exec'ing into containers that may be on their way down
+    pub fn spawn_job(&self, service: Service, job: ServiceJob, trigger: &'static str) -> bool {
+        if !self.running_jobs.begin(job.id) {
+            return false;
+        }
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            let outcome = match app_state.in_flight.contains(service.id) {
+                true => Outcome::skipped("Skipped, the service was deploying"),
+                false => service.run_job(&app_state.config, &job, &CancellationToken::new()),
+            };
+            app_state.running_jobs.end(job.id);
+            if let Err(e) = db::new_job_run(&app_state.pool, job.id, trigger, &outcome).await {
+                event!(
+                    Level::ERROR,
+                    "Unable to record run of job {} | {}",
+                    job.name,
+                    e
+                );
+            }
+
+            let detail = match outcome.exit_code {
+                Some(code) => format!("exited with {}", code),
+                None => outcome
+                    .output
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            let _ = app_state
+                .service_broadcast
+                .broadcaster
+                .send(ServiceEvent::JobRan {
+                    id: service.id,
+                    job: job.name,
+                    ok: outcome.ok,
+                    detail,
+                });
+        }));
+        true
+    }
+
+    // nodes deploy from their own clones, so only local services can be dry run
+    pub async fn dry_run(
+        &self,
+        service: Service,
+        commit: Option<&str>,
+    ) -> Result<DryRun, ServiceError> {
+        if service.node_id.is_some() {
+            return Err(ServiceError::Agent(
+                "Dry runs aren't supported for services on nodes".to_string(),
+            ));
+        }
+        let cancel = self.in_flight.begin(service.id);
+        let dry_run = service
+            .dry_run(self.config.clone(), &self.pool, commit, &cancel)
+            .await;
+        self.in_flight.end(service.id);
+        dry_run
+    }
+
+    // stops the service's containers in the background, on its node if it has one
+    pub fn spawn_deactivate(&self, service: Result<Service, db::DBError>) {
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            match service {
+                Ok(
+                    serv @ Service {
+                        node_id: Some(node_id),
+                        ..
+                    },
+                ) => agent::stop(&app_state, serv, node_id).await,
+                service => {
+                    Service::deactivate_service(
+                        app_state.config,
+                        service,
+                        app_state.service_broadcast.broadcaster,
+                    )
+                    .await
+                }
+            }
+        }));
+    }
+
+    // tears the service down and deletes it in the background, on its node if it has one
+    pub fn spawn_delete(&self, service: Result<Service, db::DBError>) {
+        let app_state = self.clone();
+        self.deployments.spawn(request_id::carry(async move {
+            match service {
+                Ok(
+                    serv @ Service {
+                        node_id: Some(node_id),
+                        ..
+                    },
+                ) => agent::remove(&app_state, serv, node_id).await,
+                service => {
+                    Service::delete_service(
+                        app_state.config,
+                        &app_state.pool,
+                        service,
+                        app_state.service_broadcast.broadcaster,
+                    )
+                    .await
+                }
+            }
+        }));
+    }
+}
+
+// an event and its place in the order they were sent
+type Numbered = (u64, ServiceEvent);
+
 #[derive(Clone, Debug)]
 pub struct ServiceBroadcast {
     pub broadcaster: broadcast::Sender<ServiceEvent>,
+    // the same events numbered by `sequence`, for the dashboard streams
+    sequenced: broadcast::Sender<Numbered>,
+    history: Arc<Mutex<History>>,
+}
+
+// the latest numbered events, replayed to a dashboard that reconnects with Last-Event-ID
+#[derive(Debug)]
+struct History {
+    next: u64,
+    capacity: usize,
+    events: VecDeque<Numbered>,
+}
+
+impl History {
+    // the events after `id`, or None when some of them are gone or `id` is from
+    // before a restart
+    fn since(&self, id: u64) -> Option<Vec<Numbered>> {
+        let first = self.events.front().map_or(self.next, |(first, _)| *first);
+        (first <= id.saturating_add(1) && id < self.next).then(|| {
+            self.events
+                .iter()
+                .filter(|(i, _)| *i > id)
+                .cloned()
+                .collect()
+        })
+    }
 }
 
 pub struct HTMLTarget {
     id: String,
     element: String,
     class: Option<String>,
-    html_content: String,
+    html_content: Markup,
 }
 
+#[derive(Template)]
+#[template(path = "service/event.html")]
 pub struct ServiceHTML {
     status_class: String,
     status_string: String,
@@ -86,85 +1248,264 @@ pub struct ServiceHTML {
 }
 
 impl ServiceHTML {
-    fn render(self) -> Event {
-        let target_snippets: String = self
-            .html_targets
-            .iter()
-            .map(|ht| {
-                format!(
-                    "
-                {}<{} id=\"{}\" hx-swap-oob=\"true\" {}>{}</{}>{}
-            ",
-                    match ht.element.as_str() {
-                        "td" => "<tr>".to_string(),
-                        _ => "".to_string(),
-                    },
-                    ht.element,
-                    ht.id,
-                    match &ht.class {
-                        Some(c) => format!("class=\"{}-chip\"", c),
-                        None => "".to_string(),
-                    },
-                    ht.html_content,
-                    ht.element,
-                    match ht.element.as_str() {
-                        "td" => "</tr>".to_string(),
-                        _ => "".to_string(),
-                    },
-                )
-            })
-            .collect();
-
-        Event::default().event("service_event").data(&format!(
-            "
-            <div id=\"link-status\" class=\"{}-chip\">{}</div>
-            {}
-            ",
-            self.status_class, self.status_string, target_snippets
-        ))
+    fn render(self, id: u64) -> Event {
+        Event::default()
+            .event("service_event")
+            .id(id.to_string())
+            .data(render(&self).into_string())
     }
 }
 
+const ALL_STATUS_DEBOUNCE: Duration = Duration::from_millis(250);
+
 impl ServiceBroadcast {
-    pub fn new() -> Self {
-        let (broadcaster, _) = broadcast::channel(100);
-        Self { broadcaster }
+    pub fn new(capacity: usize) -> Self {
+        let (broadcaster, _) = broadcast::channel(capacity);
+        let (sequenced, _) = broadcast::channel(capacity);
+        // counting from the clock keeps ids increasing across restarts
+        let next = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            broadcaster,
+            sequenced,
+            history: Arc::new(Mutex::new(History {
+                next,
+                capacity,
+                events: VecDeque::with_capacity(capacity),
+            })),
+        }
     }
 
-    fn subscribe(&self) -> broadcast::Receiver<ServiceEvent> {
+    pub fn subscribe(&self) -> broadcast::Receiver<ServiceEvent> {
         self.broadcaster.subscribe()
     }
 
-    pub async fn event_stream(
+    fn history(&self) -> std::sync::MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn latest(&self) -> u64 {
+        self.history().next.saturating_sub(1)
+    }
+
+    // numbers events in the order they were sent and keeps the latest for `resume`
+    pub async fn sequence(self) {
+        let mut receiver = self.subscribe();
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                    event!(
+                        Level::WARN,
+                        "Event sequencer fell behind by {} events, re-syncing dashboards",
+                        dropped
+                    );
+                    ServiceEvent::AllStatus
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            // under the lock, so `resume` sees each event either in the history or
+            // on the channel, never both
+            let mut history = self.history();
+            let id = history.next;
+            history.next += 1;
+            if history.events.len() == history.capacity {
+                history.events.pop_front();
+            }
+            history.events.push_back((id, event.clone()));
+            let _ = self.sequenced.send((id, event));
+        }
+    }
+
+    // subscribes to the numbered events along with those missed since `last_event_id`,
+    // or None when the viewer needs a full redraw; also returns the latest id
+    fn resume(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (broadcast::Receiver<Numbered>, Option<Vec<Numbered>>, u64) {
+        let history = self.history();
+        let missed = last_event_id.and_then(|id| history.since(id));
+        (
+            self.sequenced.subscribe(),
+            missed,
+            history.next.saturating_sub(1),
+        )
+    }
+
+    // keeps each service's latest pipeline status on its row so failures outlive a
+    // refresh or restart; streamed output and retries are too fleeting to keep
+    pub async fn record(self, pool: SqlitePool) {
+        let mut receiver = self.subscribe();
+        loop {
+            match receiver.recv().await {
+                Ok(ServiceEvent::ServiceUpdate {
+                    status:
+                        ServiceStatus::HookOutput(_)
+                        | ServiceStatus::Retrying(..)
+                        | ServiceStatus::CopyProgress(..),
+                    ..
+                }) => (),
+                Ok(ServiceEvent::ServiceUpdate { id, status, .. }) => {
+                    let Some((kind, detail)) = status.record() else {
+                        continue;
+                    };
+                    if let Err(e) = db::set_last_status(&pool, id, kind, detail).await {
+                        event!(
+                            Level::WARN,
+                            "Unable to record status of service {} | {}",
+                            id,
+                            e
+                        );
+                    }
+                }
+                Ok(_) => (),
+                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                    event!(
+                        Level::WARN,
+                        "Status recorder fell behind; {} events dropped, some statuses may be stale",
+                        dropped
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+impl AppState {
+    // the dashboard's events for `role`, picking up after `last_event_id` when a
+    // reconnecting browser sends one that's still in the history
+    pub fn event_stream(
         self,
-        pool: SqlitePool,
+        role: Role,
+        view: service::html::ListView,
+        last_event_id: Option<u64>,
     ) -> impl Stream<Item = Result<Event, axum::Error>> {
-        let mut receiver = self.subscribe();
+        let AppState {
+            service_broadcast,
+            pool,
+            agents,
+            in_flight,
+            docker_cache,
+            ..
+        } = self;
+        let filter = view.filter.clone();
+        let (mut receiver, missed, latest) = service_broadcast.resume(last_event_id);
 
         stream! {
-            // yield the list that triggers the AllStatus event
-            yield Ok(Event::default().event("service_event").data(
-                "
-                <div id=\"link-status\" class=\"success-chip\">Connected</div>
-                <table id=\"services-list\" hx-swap-oob=\"true\"><tr><td hx-get=\"/api/all_status\" hx-trigger=\"load\">Waiting query results...</td></tr></table>
-                <div id=\"app-message\"></div>
-                "
-            ));
+            // events missed while disconnected, then those that arrived while a full
+            // redraw was being debounced
+            let mut deferred: VecDeque<Numbered> = VecDeque::new();
+            match missed {
+                Some(missed) => deferred.extend(missed),
+                // yield the list that triggers the AllStatus event
+                None => yield Ok(service::html::connected().id(latest.to_string())),
+            }
 
-            while let Ok(event) = receiver.recv().await {
-                let docker_list = Service::get_list().await;
+            loop {
+                let received = match deferred.pop_front() {
+                    Some(event) => Ok(event),
+                    None => receiver.recv().await,
+                };
+                let (event_id, event) = match received {
+                    Ok(event) => event,
+                    // the missed updates are stale anyway; redraw everything instead
+                    Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                        event!(
+                            Level::WARN,
+                            "Dashboard stream fell behind by {} events, re-syncing",
+                            dropped
+                        );
+                        (service_broadcast.latest(), ServiceEvent::AllStatus)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
                 match event {
                     ServiceEvent::AllStatus => {
-                        let db_list = db::get_services(&pool).await;
-                        yield(Ok(service::html::list(db_list, docker_list).render()));
-                        yield(Ok(service::html::reset_button()));
+                        // a burst of requests, like a group deploy queueing up, redraws once
+                        tokio::time::sleep(ALL_STATUS_DEBOUNCE).await;
+                        loop {
+                            match receiver.try_recv() {
+                                Ok((_, ServiceEvent::AllStatus))
+                                | Err(broadcast::error::TryRecvError::Lagged(_)) => (),
+                                Ok(event) => {
+                                    if matches!(event.1, ServiceEvent::ServiceUpdate { .. }) {
+                                        docker_cache.invalidate();
+                                    }
+                                    deferred.push_back(event);
+                                }
+                                Err(_) => break,
+                            }
+                        }
+
+                        let db_list = db::get_services(&pool, Some(false)).await.map(|list| {
+                            list.into_iter()
+                                .filter(|s| filter.matches(s))
+                                .collect::<Vec<_>>()
+                        });
+                        let groups = db::get_groups(&pool).await.unwrap_or_default();
+                        let hosts = db_list.as_ref().map(Service::docker_hosts).unwrap_or_default();
+                        let docker_list = docker_cache.list(&hosts).await.map(|mut list| {
+                            list.extend(agents.containers());
+                            list
+                        });
+                        let nodes = agents.mark(db::get_nodes(&pool).await.unwrap_or_default());
+                        let reviews = db::get_deploy_review_ids(&pool).await.unwrap_or_default();
+                        let approvals = db::get_deploy_approval_ids(&pool).await.unwrap_or_default();
+                        let uptime = db::get_uptime_transitions(&pool, None, uptime::month_ago())
+                            .await
+                            .map(uptime::by_service)
+                            .unwrap_or_default();
+                        let activity = service::html::Activity {
+                            in_flight: &in_flight,
+                            reviews: &reviews,
+                            approvals: &approvals,
+                            uptime: &uptime,
+                        };
+                        yield(Ok(service::html::list(db_list, docker_list, &nodes, &groups, activity, role, &view).render(event_id)));
+                        yield(Ok(service::html::reset_button(role).id(event_id.to_string())));
                     },
-                    ServiceEvent::ServiceUpdate {id, status} => {
+                    ServiceEvent::ServiceUpdate {id, status, ..} => {
+                        // pipeline steps change containers
+                        docker_cache.invalidate();
+                        if !filter.includes(id) {
+                            continue;
+                        }
                         let service = db::get_service(&pool, id).await;
-                        yield(Ok(service::html::service(service, status).render()));
+                        let since = in_flight.since(id);
+                        yield(Ok(service::html::service(service, status, since).render(event_id)));
+                    },
+                    ServiceEvent::Usage { mut usage } => {
+                        usage.retain(|(id, _)| filter.includes(*id));
+                        if !usage.is_empty() {
+                            yield(Ok(service::html::usage(usage).render(event_id)));
+                        }
+                    },
+                    ServiceEvent::Drift { mut drift } => {
+                        drift.retain(|(id, _)| filter.includes(*id));
+                        if !drift.is_empty() {
+                            yield(Ok(service::html::drift(drift).render(event_id)));
+                        }
+                    },
+                    // the fleet's progress means nothing on one service's view
+                    ServiceEvent::BulkProgress { .. } if filter.service.is_some() => (),
+                    ServiceEvent::BulkProgress { progress } => {
+                        yield(Ok(service::html::bulk_progress(progress).render(event_id)));
                     },
                     ServiceEvent::UnknownEvent { msg } => {
-                        yield(Ok(service::html::unknown(msg).render()));
+                        yield(Ok(service::html::unknown(msg).render(event_id)));
+                    }
+                    // already shown through their status updates, or in the jobs panel
+                    ServiceEvent::DeployStarted { .. }
+                    | ServiceEvent::Deployed { .. }
+                    | ServiceEvent::Health { .. }
+                    | ServiceEvent::JobRan { .. } => (),
+                    ServiceEvent::Shutdown => {
+                        // end the stream so graceful shutdown isn't held open by SSE clients
+                        yield(Ok(service::html::shutdown().id(event_id.to_string())));
+                        break;
                     }
                 }
             }
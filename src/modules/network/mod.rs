@@ -0,0 +1,130 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use tracing::{Level, event};
+
+use super::AppState;
+
+pub const FORWARDED_FOR: &str = "X-Forwarded-For";
+
+// signing in and out stays open, so a session can be started before reaching the VPN
+const UNRESTRICTED: [&str; 2] = ["/login", "/logout"];
+
+// the address a request came from, after any trusted proxies
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+// whether the client is inside ALLOW_CIDRS, or there's no list
+#[derive(Clone, Copy, Debug)]
+pub struct Allowed(pub bool);
+
+// comma-separated networks; a bare address is a network of one
+pub fn parse_cidrs(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|cidr| !cidr.is_empty())
+        .map(|cidr| {
+            cidr.parse::<IpNet>()
+                .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| cidr.to_string())
+        })
+        .collect()
+}
+
+fn within(networks: &[IpNet], ip: IpAddr) -> bool {
+    // an IPv4 client on a dual-stack socket shows up mapped into IPv6
+    let ip = ip.to_canonical();
+    networks.iter().any(|network| network.contains(&ip))
+}
+
+// walks X-Forwarded-For back from the peer through trusted proxies; anything before the
+// first untrusted hop was written by the client and can't be believed
+pub fn client_ip(peer: IpAddr, forwarded_for: &[&str], trusted: &[IpNet]) -> IpAddr {
+    let mut client = peer.to_canonical();
+    let hops = forwarded_for
+        .iter()
+        .flat_map(|header| header.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        if !within(trusted, client) {
+            break;
+        }
+        match hop.parse::<IpAddr>() {
+            Ok(ip) => client = ip.to_canonical(),
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+// works out who's asking and whether they're inside ALLOW_CIDRS, for `restrict`, role
+// checks and the audit log
+pub async fn identify(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let config = &app_state.config;
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(peer) = peer {
+        let forwarded_for = request
+            .headers()
+            .get_all(FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+        let client = client_ip(peer, &forwarded_for, &config.trusted_proxies);
+        let allowed = config.allow_cidrs.is_empty() || within(&config.allow_cidrs, client);
+        request.extensions_mut().insert(ClientIp(client));
+        request.extensions_mut().insert(Allowed(allowed));
+    }
+
+    next.run(request).await
+}
+
+pub fn allowed(request: &Request) -> bool {
+    request
+        .extensions()
+        .get::<Allowed>()
+        .is_none_or(|Allowed(allowed)| *allowed)
+}
+
+pub fn refuse(request: &Request) -> Response {
+    let client = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_default();
+    event!(
+        Level::WARN,
+        "Refused {} {} from {} outside ALLOW_CIDRS",
+        request.method(),
+        request.uri().path(),
+        client
+    );
+    (StatusCode::FORBIDDEN, "Not allowed from this address").into_response()
+}
+
+// edits, deploys and anything else that changes state only come from inside ALLOW_CIDRS
+pub async fn restrict(request: Request, next: Next) -> Response {
+    let changes_state = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if changes_state && !UNRESTRICTED.contains(&request.uri().path()) && !allowed(&request) {
+        return refuse(&request);
+    }
+
+    next.run(request).await
+}
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use lettre::{
+    Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::Mailbox,
+    transport::smtp::{self, authentication::Credentials},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EmailError {
+    #[error("Invalid email address '{0}'")]
+    Address(String),
+    #[error("Unable to build the email | {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("Unable to send the email | {0}")]
+    Smtp(#[from] smtp::Error),
+}
+
+// how the connection to SMTP_HOST is secured
+#[derive(Clone, Copy, Debug)]
+pub enum SmtpTls {
+    StartTls,
+    Tls,
+    None,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            "none" => Ok(SmtpTls::None),
+            other => Err(other.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Smtp {
+    pub host: String,
+    // the usual port for the TLS mode when unset
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub tls: SmtpTls,
+}
+
+pub struct EmailRecipient {
+    pub id: i64,
+    pub address: String,
+}
+
+pub fn valid_address(address: &str) -> bool {
+    address.parse::<Address>().is_ok()
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, EmailError> {
+    address
+        .parse()
+        .map_err(|_| EmailError::Address(address.to_string()))
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(smtp: &Smtp) -> Result<Self, EmailError> {
+        let builder = match smtp.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+        };
+        let builder = match smtp.port {
+            Some(port) => builder.port(port),
+            None => builder,
+        };
+        let builder = match &smtp.username {
+            Some(username) => builder.credentials(Credentials::new(
+                username.clone(),
+                smtp.password.clone().unwrap_or_default(),
+            )),
+            None => builder,
+        };
+        Ok(Mailer {
+            transport: builder.build(),
+            from: mailbox(&smtp.from)?,
+        })
+    }
+
+    pub async fn send(&self, to: &[String], subject: &str, body: String) -> Result<(), EmailError> {
+        let mut message = Message::builder().from(self.from.clone()).subject(subject);
+        for address in to {
+            message = message.to(mailbox(address)?);
+        }
+        self.transport.send(message.body(body)?).await?;
+        Ok(())
+    }
+}
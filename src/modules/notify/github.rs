@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use openssl::{
+    base64,
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use crate::modules::{
+    AppState, db,
+    service::{Service, ServiceEvent, ServiceStatus},
+};
+
+const API_URL: &str = "https://api.github.com";
+const TIMEOUT: Duration = Duration::from_secs(10);
+// GitHub cuts a status's description off at this many characters
+const MAX_DESCRIPTION: usize = 140;
+
+#[derive(Error, Debug)]
+pub enum GitHubError {
+    #[error("Unable to sign the GitHub App token | {0}")]
+    Sign(#[from] openssl::error::ErrorStack),
+    #[error("Unable to reach GitHub | {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("GitHub answered {0} | {1}")]
+    Rejected(u16, String),
+}
+
+// how wraut signs in to post statuses: a token with the repo:status scope, or a GitHub App
+// installed on the repos with commit statuses write access
+#[derive(Clone, Debug)]
+pub enum GitHubAuth {
+    Token(String),
+    App { id: String, key: PKey<Private> },
+}
+
+#[derive(Clone, Debug)]
+pub struct GitHub {
+    pub auth: GitHubAuth,
+}
+
+#[derive(Deserialize)]
+struct Installation {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    token: String,
+}
+
+// "owner/repo" for a repo on github.com, from its HTTPS or SSH URL
+pub fn repo_slug(url: &str) -> Option<String> {
+    let path = match url.strip_prefix("git@github.com:") {
+        Some(path) => path,
+        None => {
+            let (_, rest) = url.split_once("://")?;
+            let host = rest.rsplit_once('@').map_or(rest, |(_, host)| host);
+            host.strip_prefix("github.com/")?
+        }
+    };
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, repo) = path.split_once('/')?;
+    (!owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+        .then(|| format!("{}/{}", owner, repo))
+}
+
+// JWT segments are unpadded URL-safe base64
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_block(bytes)
+        .replace('+', "-")
+        .replace('/', "_")
+        .trim_end_matches('=')
+        .to_string()
+}
+
+// the short-lived token an app signs its own requests with; backdated a minute for clock drift
+fn app_jwt(id: &str, key: &PKey<Private>) -> Result<String, GitHubError> {
+    let now = chrono::Utc::now().timestamp();
+    let header = encode(br#"{"alg":"RS256","typ":"JWT"}"#);
+    let claims = encode(
+        json!({ "iat": now - 60, "exp": now + 540, "iss": id })
+            .to_string()
+            .as_bytes(),
+    );
+    let signed = format!("{}.{}", header, claims);
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    signer.update(signed.as_bytes())?;
+    Ok(format!("{}.{}", signed, encode(&signer.sign_to_vec()?)))
+}
+
+#[derive(Clone)]
+pub struct Reporter {
+    client: Client,
+    github: GitHub,
+}
+
+impl Reporter {
+    pub fn new(github: &GitHub) -> Result<Self, GitHubError> {
+        Ok(Reporter {
+            client: Client::builder()
+                .timeout(TIMEOUT)
+                .user_agent("wraut")
+                .build()?,
+            github: github.clone(),
+        })
+    }
+
+    fn request(&self, request: RequestBuilder, token: &str) -> RequestBuilder {
+        request
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+
+    async fn send<T: for<'de> Deserialize<'de>>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, GitHubError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(GitHubError::Rejected(
+                status.as_u16(),
+                text.trim().to_string(),
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    // the token for `slug`: the configured one, or an app installation's, asked for each time
+    // since they expire within the hour
+    async fn token(&self, slug: &str) -> Result<String, GitHubError> {
+        let (id, key) = match &self.github.auth {
+            GitHubAuth::Token(token) => return Ok(token.clone()),
+            GitHubAuth::App { id, key } => (id, key),
+        };
+        let jwt = app_jwt(id, key)?;
+        let installation: Installation = self
+            .send(
+                self.request(
+                    self.client
+                        .get(format!("{}/repos/{}/installation", API_URL, slug)),
+                    &jwt,
+                ),
+            )
+            .await?;
+        let access: AccessToken = self
+            .send(self.request(
+                self.client.post(format!(
+                    "{}/app/installations/{}/access_tokens",
+                    API_URL, installation.id
+                )),
+                &jwt,
+            ))
+            .await?;
+        Ok(access.token)
+    }
+
+    // sets the service's status on the deployed commit, linking to the deployment's log when
+    // PUBLIC_URL is set
+    pub async fn report(
+        &self,
+        service: &Service,
+        slug: &str,
+        commit: &str,
+        ok: bool,
+        status: &ServiceStatus,
+        target_url: Option<String>,
+    ) -> Result<(), GitHubError> {
+        let token = self.token(slug).await?;
+        let description: String = status.to_string().chars().take(MAX_DESCRIPTION).collect();
+        let body = json!({
+            "state": if ok { "success" } else { "failure" },
+            "target_url": target_url,
+            "description": description,
+            "context": format!("wraut/{}", service.name),
+        });
+        let _: serde_json::Value = self
+            .send(
+                self.request(
+                    self.client
+                        .post(format!("{}/repos/{}/statuses/{}", API_URL, slug, commit))
+                        .json(&body),
+                    &token,
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+// the deploy a Deployed event was about, if it can be reported: only on the commit that deploy
+// checked out, so one that failed before checking one out isn't reported at all
+async fn deployed(
+    app_state: &AppState,
+    id: i64,
+    deployment: Option<i64>,
+) -> Option<(Service, String, String, i64)> {
+    let deployment_id = deployment?;
+    let service = db::get_service(&app_state.pool, id).await.ok()?;
+    let slug = repo_slug(&service.repo_url)?;
+    let deployment = db::get_deployments(&app_state.pool, id, 1)
+        .await
+        .ok()?
+        .pop()
+        .filter(|d| d.id == deployment_id)?;
+    let commit = deployment.commit_sha?;
+    Some((service, slug, commit, deployment.id))
+}
+
+// background task that sets a commit status on GitHub for each deploy of a repo hosted there
+pub async fn run(app_state: AppState) {
+    let Some(github) = &app_state.config.github else {
+        return;
+    };
+    let reporter = match Reporter::new(github) {
+        Ok(reporter) => reporter,
+        Err(e) => {
+            event!(Level::ERROR, "GitHub statuses disabled | {}", e);
+            return;
+        }
+    };
+    let mut receiver = app_state.service_broadcast.subscribe();
+
+    loop {
+        let (id, ok, status, deployment) = match receiver.recv().await {
+            Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+            Ok(ServiceEvent::Deployed {
+                id,
+                ok,
+                status,
+                deployment,
+            }) => (id, ok, status, deployment),
+            Ok(_) => continue,
+            Err(RecvError::Lagged(dropped)) => {
+                event!(
+                    Level::WARN,
+                    "GitHub reporter fell behind; {} events dropped, some statuses may be missing",
+                    dropped
+                );
+                continue;
+            }
+        };
+        let Some((service, slug, commit, deployment_id)) =
+            deployed(&app_state, id, deployment).await
+        else {
+            continue;
+        };
+        let target_url = app_state
+            .config
+            .public_url
+            .as_ref()
+            .map(|url| format!("{}/api/deployments/{}/log", url, deployment_id));
+        let reporter = reporter.clone();
+        tokio::spawn(async move {
+            let reported = reporter
+                .report(&service, &slug, &commit, ok, &status, target_url)
+                .await;
+            match reported {
+                Ok(_) => event!(
+                    Level::INFO,
+                    "Set GitHub status of {} on {}",
+                    service.name,
+                    slug
+                ),
+                Err(e) => event!(
+                    Level::WARN,
+                    "Unable to set GitHub status of {} | {}",
+                    service.name,
+                    e
+                ),
+            }
+        });
+    }
+
+    event!(Level::INFO, "GitHub reporter stopped.");
+}
@@ -0,0 +1,262 @@
+pub mod email;
+pub mod github;
+pub mod push;
+pub mod webhook;
+
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{
+    AppState, db,
+    service::{Service, ServiceEvent, ServiceStatus, short_commit},
+};
+use email::Mailer;
+use push::Pusher;
+
+// what a notification is about
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Deployed,
+    DeployFailed,
+    Down,
+    Up,
+    JobFailed,
+}
+
+// every channel a rule can be set for
+pub const CHANNELS: [&str; 3] = ["email", "ntfy", "pushover"];
+
+// which notifications a channel sends for a service; one without a stored rule sends them all
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NotificationRule {
+    pub channel: String,
+    pub deployed: bool,
+    pub deploy_failed: bool,
+    pub health: bool,
+    pub job_failed: bool,
+}
+
+impl NotificationRule {
+    pub fn all(channel: &str) -> Self {
+        NotificationRule {
+            channel: channel.to_string(),
+            deployed: true,
+            deploy_failed: true,
+            health: true,
+            job_failed: true,
+        }
+    }
+
+    pub fn allows(&self, kind: Kind) -> bool {
+        match kind {
+            Kind::Deployed => self.deployed,
+            Kind::DeployFailed => self.deploy_failed,
+            Kind::Down | Kind::Up => self.health,
+            Kind::JobFailed => self.job_failed,
+        }
+    }
+}
+
+// a rule for each of CHANNELS, filling in the ones never stored
+pub fn rules(stored: Vec<NotificationRule>) -> Vec<NotificationRule> {
+    CHANNELS
+        .iter()
+        .map(|channel| {
+            stored
+                .iter()
+                .find(|r| r.channel == *channel)
+                .cloned()
+                .unwrap_or_else(|| NotificationRule::all(channel))
+        })
+        .collect()
+}
+
+pub struct Notification {
+    pub service: Service,
+    pub kind: Kind,
+    pub detail: String,
+}
+
+impl Notification {
+    // worth waking someone for
+    pub fn urgent(&self) -> bool {
+        matches!(self.kind, Kind::DeployFailed | Kind::Down | Kind::JobFailed)
+    }
+
+    pub fn subject(&self) -> String {
+        let name = &self.service.name;
+        match self.kind {
+            Kind::Deployed => format!("{} deployed", name),
+            Kind::DeployFailed => format!("{} failed to deploy", name),
+            Kind::Down => format!("{} is down", name),
+            Kind::Up => format!("{} is back up", name),
+            Kind::JobFailed => format!("A scheduled job of {} failed", name),
+        }
+    }
+
+    pub fn body(&self) -> String {
+        let service = &self.service;
+        let mut lines = vec![self.subject(), String::new(), self.detail.clone()];
+        if let Some(environment) = &service.environment {
+            lines.push(format!("Environment: {}", environment));
+        }
+        if let Some(commit) = &service.last_commit {
+            lines.push(format!("Commit: {}", short_commit(commit)));
+        }
+        lines.push(format!("URL: {}", service.access_url));
+        lines.join("\n")
+    }
+}
+
+// turns an event into a notification, if it's one worth sending
+async fn notification(app_state: &AppState, event: ServiceEvent) -> Option<Notification> {
+    let (id, kind, detail) = match event {
+        ServiceEvent::Deployed {
+            id,
+            ok: true,
+            status,
+            ..
+        } => (id, Kind::Deployed, status.to_string()),
+        ServiceEvent::Deployed { id, status, .. } => (id, Kind::DeployFailed, status.to_string()),
+        ServiceEvent::JobRan {
+            id,
+            job,
+            ok: false,
+            detail,
+        } => (id, Kind::JobFailed, format!("Job '{}' | {}", job, detail)),
+        ServiceEvent::Health { id, up } => {
+            // a deploy, or a stop asked for, takes the containers down on purpose
+            if app_state.in_flight.contains(id) {
+                return None;
+            }
+            match up {
+                true => (id, Kind::Up, ServiceStatus::Running.to_string()),
+                false => (id, Kind::Down, ServiceStatus::Inactive.to_string()),
+            }
+        }
+        _ => return None,
+    };
+    let service = db::get_service(&app_state.pool, id).await.ok()?;
+    let stopped = matches!(
+        service.recorded_status(),
+        Some(ServiceStatus::Stopped | ServiceStatus::TornDown)
+    );
+    if matches!(kind, Kind::Down | Kind::Up) && (stopped || !service.active) {
+        return None;
+    }
+    Some(Notification {
+        service,
+        kind,
+        detail,
+    })
+}
+
+// the channels configured for this instance
+#[derive(Clone)]
+struct Channels {
+    mailer: Option<Mailer>,
+    pusher: Pusher,
+}
+
+// emails and pushes to the service's subscribers without holding up the next event
+async fn send(app_state: &AppState, channels: &Channels, notification: Notification) {
+    let id = notification.service.id;
+    let rules = match db::get_notification_rules(&app_state.pool, id).await {
+        Ok(stored) => rules(stored),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get notification rules | {}", e);
+            return;
+        }
+    };
+    let allows = |channel: &str| {
+        rules
+            .iter()
+            .any(|r| r.channel == channel && r.allows(notification.kind))
+    };
+
+    let recipients: Vec<String> = match &channels.mailer {
+        _ if !allows("email") => vec![],
+        None => vec![],
+        Some(_) => match db::get_email_recipients(&app_state.pool, id).await {
+            Ok(recipients) => recipients.into_iter().map(|r| r.address).collect(),
+            Err(e) => {
+                event!(Level::ERROR, "Unable to get email recipients | {}", e);
+                vec![]
+            }
+        },
+    };
+    let targets = match db::get_push_targets(&app_state.pool, id).await {
+        Ok(targets) => targets.into_iter().filter(|t| allows(&t.channel)).collect(),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get push targets | {}", e);
+            vec![]
+        }
+    };
+    if recipients.is_empty() && targets.is_empty() {
+        return;
+    }
+    let channels = channels.clone();
+    tokio::spawn(async move {
+        let subject = notification.subject();
+        let body = notification.body();
+        if let Some(mailer) = channels.mailer.filter(|_| !recipients.is_empty()) {
+            match mailer.send(&recipients, &subject, body.clone()).await {
+                Ok(_) => event!(Level::INFO, "Emailed '{}'", subject),
+                Err(e) => event!(Level::WARN, "Unable to email '{}' | {}", subject, e),
+            }
+        }
+        for target in targets {
+            let sent = channels
+                .pusher
+                .send(&target, &subject, &body, notification.urgent())
+                .await;
+            match sent {
+                Ok(_) => event!(Level::INFO, "Pushed '{}' to {}", subject, target.channel),
+                Err(e) => event!(Level::WARN, "Unable to push '{}' | {}", subject, e),
+            }
+        }
+    });
+}
+
+// background task that tells people about deploys, services going up or down, and failed
+// scheduled jobs
+pub async fn run(app_state: AppState) {
+    let mailer = match app_state.config.smtp.as_ref().map(Mailer::new) {
+        Some(Ok(mailer)) => Some(mailer),
+        Some(Err(e)) => {
+            event!(Level::ERROR, "Email notifications disabled | {}", e);
+            None
+        }
+        None => None,
+    };
+    let pusher = match Pusher::new(&app_state.config.push) {
+        Ok(pusher) => pusher,
+        Err(e) => {
+            event!(Level::ERROR, "Notifications disabled | {}", e);
+            return;
+        }
+    };
+    let channels = Channels { mailer, pusher };
+    let mut receiver = app_state.service_broadcast.subscribe();
+
+    loop {
+        let received = match receiver.recv().await {
+            Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+            Ok(received) => received,
+            Err(RecvError::Lagged(dropped)) => {
+                event!(
+                    Level::WARN,
+                    "Notifier fell behind; {} events dropped, some notifications may be missing",
+                    dropped
+                );
+                continue;
+            }
+        };
+        if let Some(notification) = notification(&app_state, received).await {
+            send(&app_state, &channels, notification).await;
+        }
+    }
+
+    event!(Level::INFO, "Notifier stopped.");
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::json;
+use thiserror::Error;
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum PushError {
+    #[error("'{0}' isn't an ntfy topic or topic URL")]
+    Topic(String),
+    #[error("'{0}' isn't a Pushover user or group key")]
+    UserKey(String),
+    #[error("Set PUSHOVER_TOKEN to send to Pushover")]
+    NoPushoverToken,
+    #[error("Unknown push channel '{0}'")]
+    Channel(String),
+    #[error("Unable to reach {0} | {1}")]
+    Http(Channel, reqwest::Error),
+    #[error("{0} answered {1} | {2}")]
+    Rejected(Channel, u16, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Ntfy,
+    Pushover,
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Ntfy => "ntfy",
+            Channel::Pushover => "pushover",
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Channel {
+    type Err = PushError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ntfy" => Ok(Channel::Ntfy),
+            "pushover" => Ok(Channel::Pushover),
+            other => Err(PushError::Channel(other.to_string())),
+        }
+    }
+}
+
+// where pushes go; topics without a server are published to `ntfy_url`
+#[derive(Clone, Debug)]
+pub struct Push {
+    pub ntfy_url: String,
+    pub ntfy_token: Option<String>,
+    pub pushover_token: Option<String>,
+}
+
+// an ntfy topic or a Pushover user key subscribed to a service
+pub struct PushTarget {
+    pub id: i64,
+    pub channel: String,
+    pub target: String,
+}
+
+fn valid_topic(topic: &str) -> bool {
+    (1..=64).contains(&topic.len())
+        && topic
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// checks a target before it's saved, returning it as it should be stored
+pub fn validate(push: &Push, channel: Channel, target: &str) -> Result<String, PushError> {
+    let target = target.trim();
+    match channel {
+        Channel::Ntfy => {
+            let topic = match target.split_once("://") {
+                Some(("http" | "https", rest)) => rest.rsplit_once('/').map(|(_, t)| t),
+                Some(_) => None,
+                None => Some(target),
+            };
+            match topic.is_some_and(valid_topic) {
+                true => Ok(target.trim_end_matches('/').to_string()),
+                false => Err(PushError::Topic(target.to_string())),
+            }
+        }
+        Channel::Pushover => {
+            if push.pushover_token.is_none() {
+                return Err(PushError::NoPushoverToken);
+            }
+            match target.len() == 30 && target.chars().all(|c| c.is_ascii_alphanumeric()) {
+                true => Ok(target.to_string()),
+                false => Err(PushError::UserKey(target.to_string())),
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Pusher {
+    client: Client,
+    push: Push,
+}
+
+impl Pusher {
+    pub fn new(push: &Push) -> Result<Self, reqwest::Error> {
+        Ok(Pusher {
+            client: Client::builder().timeout(TIMEOUT).build()?,
+            push: push.clone(),
+        })
+    }
+
+    // urgent pushes ring through quiet settings on the phone
+    pub async fn send(
+        &self,
+        target: &PushTarget,
+        title: &str,
+        body: &str,
+        urgent: bool,
+    ) -> Result<(), PushError> {
+        let channel: Channel = target.channel.parse()?;
+        let request = match channel {
+            Channel::Ntfy => {
+                let url = match target.target.contains("://") {
+                    true => target.target.clone(),
+                    false => format!(
+                        "{}/{}",
+                        self.push.ntfy_url.trim_end_matches('/'),
+                        target.target
+                    ),
+                };
+                let (priority, tags) = match urgent {
+                    true => ("high", "rotating_light"),
+                    false => ("default", "white_check_mark"),
+                };
+                let request = self
+                    .client
+                    .post(url)
+                    .header("Title", title)
+                    .header("Priority", priority)
+                    .header("Tags", tags)
+                    .body(body.to_string());
+                match &self.push.ntfy_token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            Channel::Pushover => {
+                let token = self
+                    .push
+                    .pushover_token
+                    .as_ref()
+                    .ok_or(PushError::NoPushoverToken)?;
+                self.client.post(PUSHOVER_URL).json(&json!({
+                    "token": token,
+                    "user": target.target,
+                    "title": title,
+                    "message": body,
+                    "priority": if urgent { 1 } else { 0 },
+                }))
+            }
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| PushError::Http(channel, e))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        Err(PushError::Rejected(
+            channel,
+            status.as_u16(),
+            text.trim().to_string(),
+        ))
+    }
+}
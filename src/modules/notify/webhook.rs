@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+use reqwest::Client;
+use serde_json::{Value, json};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use crate::modules::{
+    AppState, db,
+    service::{Service, ServiceEvent},
+    user::hex,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Unable to sign the payload | {0}")]
+    Sign(#[from] openssl::error::ErrorStack),
+    #[error("Unable to reach {0} | {1}")]
+    Http(String, reqwest::Error),
+    #[error("{0} answered {1}")]
+    Rejected(String, u16),
+}
+
+// endpoints every deploy and health event is posted to, as JSON signed with `secret` when
+// it's set
+#[derive(Clone, Debug)]
+pub struct Webhooks {
+    pub urls: Vec<String>,
+    pub secret: Option<String>,
+}
+
+// "sha256=<hex>", the HMAC-SHA256 of the body under the secret
+pub fn signature(secret: &str, body: &[u8]) -> Result<String, WebhookError> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    Ok(format!("sha256={}", hex(&signer.sign_to_vec()?)))
+}
+
+#[derive(Clone)]
+pub struct Sender {
+    client: Client,
+    webhooks: Webhooks,
+}
+
+impl Sender {
+    pub fn new(webhooks: &Webhooks) -> Result<Self, reqwest::Error> {
+        Ok(Sender {
+            client: Client::builder()
+                .timeout(TIMEOUT)
+                .user_agent("wraut")
+                .build()?,
+            webhooks: webhooks.clone(),
+        })
+    }
+
+    pub async fn send(&self, url: &str, name: &str, payload: &Value) -> Result<(), WebhookError> {
+        let body = payload.to_string();
+        let mut request = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-Wraut-Event", name);
+        if let Some(secret) = &self.webhooks.secret {
+            request = request.header("X-Wraut-Signature", signature(secret, body.as_bytes())?);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| WebhookError::Http(url.to_string(), e))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(WebhookError::Rejected(url.to_string(), status.as_u16())),
+        }
+    }
+}
+
+fn service_json(service: &Service) -> Value {
+    json!({
+        "id": service.id,
+        "name": service.name,
+        "environment": service.environment,
+        "access_url": service.access_url,
+    })
+}
+
+// the event's name and payload, if it's one that's posted
+async fn payload(app_state: &AppState, received: ServiceEvent) -> Option<(&'static str, Value)> {
+    let id = match &received {
+        ServiceEvent::DeployStarted { id }
+        | ServiceEvent::Deployed { id, .. }
+        | ServiceEvent::Health { id, .. } => *id,
+        _ => return None,
+    };
+    let service = db::get_service(&app_state.pool, id).await.ok()?;
+    let (name, detail) = match received {
+        ServiceEvent::Deployed {
+            ok,
+            status,
+            deployment,
+            ..
+        } => {
+            let deployment = db::get_deployments(&app_state.pool, id, 1)
+                .await
+                .ok()
+                .and_then(|mut deployments| deployments.pop())
+                .filter(|d| Some(d.id) == deployment);
+            let name = match ok {
+                true => "deployment.succeeded",
+                false => "deployment.failed",
+            };
+            // the commit the deploy checked out, which a failed one may not have
+            let detail = json!({
+                "status": status.to_string(),
+                "commit": deployment.as_ref().and_then(|d| d.commit_sha.clone()),
+                "deployment_id": deployment.map(|d| d.id),
+            });
+            (name, detail)
+        }
+        ServiceEvent::Health { up, .. } => ("service.health_changed", json!({ "up": up })),
+        _ => ("deployment.started", json!({})),
+    };
+    let mut payload = json!({
+        "event": name,
+        "sent_at": chrono::Utc::now().to_rfc3339(),
+        "service": service_json(&service),
+    });
+    if let (Value::Object(payload), Value::Object(detail)) = (&mut payload, detail) {
+        payload.extend(detail);
+    }
+    Some((name, payload))
+}
+
+// background task that posts deploys starting and finishing, and services going up or down,
+// to WEBHOOK_URLS
+pub async fn run(app_state: AppState) {
+    let Some(webhooks) = &app_state.config.webhooks else {
+        return;
+    };
+    let sender = match Sender::new(webhooks) {
+        Ok(sender) => sender,
+        Err(e) => {
+            event!(Level::ERROR, "Webhooks disabled | {}", e);
+            return;
+        }
+    };
+    let mut receiver = app_state.service_broadcast.subscribe();
+
+    loop {
+        let received = match receiver.recv().await {
+            Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+            Ok(received) => received,
+            Err(RecvError::Lagged(dropped)) => {
+                event!(
+                    Level::WARN,
+                    "Webhook sender fell behind; {} events dropped, some webhooks may be missing",
+                    dropped
+                );
+                continue;
+            }
+        };
+        let Some((name, payload)) = payload(&app_state, received).await else {
+            continue;
+        };
+        for url in &webhooks.urls {
+            let (sender, url, payload) = (sender.clone(), url.clone(), payload.clone());
+            tokio::spawn(async move {
+                match sender.send(&url, name, &payload).await {
+                    Ok(_) => event!(Level::INFO, "Posted {} to {}", name, url),
+                    Err(e) => event!(Level::WARN, "Unable to post {} | {}", name, e),
+                }
+            });
+        }
+    }
+
+    event!(Level::INFO, "Webhook sender stopped.");
+}
@@ -0,0 +1,69 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use openssl::rand::rand_bytes;
+use tracing::{Instrument, info_span};
+
+// a request's correlation id, also carried by the deploys and other background work it
+// starts, so their logs and status events can be traced back to it
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+pub const HEADER: &str = "X-Request-ID";
+
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+pub fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    match rand_bytes(&mut bytes) {
+        Ok(_) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        // unique enough to grep for
+        Err(_) => format!(
+            "{:x}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default()
+        ),
+    }
+}
+
+// an id a client or proxy sent along, if it's safe to log and echo back
+pub fn accept(id: &str) -> Option<String> {
+    (!id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    .then(|| id.to_string())
+}
+
+// runs `future` as `id`, inside a span that puts the id on every line it logs
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    let span = info_span!("request", id = %id);
+    REQUEST_ID.scope(id, future.instrument(span)).await
+}
+
+// for spawning background work under the current id; work the scheduler starts has
+// none, so it gets its own
+pub fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    scope(current().unwrap_or_else(new_id), future)
+}
+
+// tags each request with the id it came with, or a new one, and returns it as a header
+pub async fn tag(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(accept)
+        .unwrap_or_else(new_id);
+    let mut response = scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,157 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, NaiveTime, Timelike, Utc};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CronError {
+    #[error("Schedules need 5 fields: minute hour day-of-month month day-of-week")]
+    FieldCount,
+    #[error("Invalid schedule field '{0}'")]
+    Field(String),
+}
+
+// standard five-field crontab schedule, evaluated in UTC
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // crontab matches either day field when both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn value(text: &str, names: &[&str], offset: u32) -> Option<u32> {
+    match text.parse::<u32>() {
+        Ok(v) => Some(v),
+        Err(_) => names
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(text))
+            .map(|i| i as u32 + offset),
+    }
+}
+
+// parses one field into a bitset of allowed values within min..=max
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, CronError> {
+    let error = || CronError::Field(text.to_string());
+    let mut bits = 0u64;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().map_err(|_| error())?),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (
+                    value(a, names, min).ok_or_else(error)?,
+                    value(b, names, min).ok_or_else(error)?,
+                ),
+                // "5/15" runs from 5 to the end of the range
+                None => {
+                    let v = value(r, names, min).ok_or_else(error)?;
+                    (v, if item.contains('/') { max } else { v })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(error());
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Schedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expanded = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError::FieldCount);
+        };
+
+        let mut weekdays = field(weekday, 0, 7, &WEEKDAYS)?;
+        // both 0 and 7 mean Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Schedule {
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+impl Schedule {
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // first matching minute strictly after `after`; None if the schedule can never fire
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        let limit = after + Duration::days(5 * 366);
+
+        while time <= limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    m => (time.year(), m + 1),
+                };
+                time = time
+                    .with_day(1)?
+                    .with_year(year)?
+                    .with_month(month)?
+                    .with_time(NaiveTime::MIN)
+                    .single()?;
+                continue;
+            }
+            if !self.day_matches(&time) {
+                time = (time + Duration::days(1))
+                    .with_time(NaiveTime::MIN)
+                    .single()?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += Duration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
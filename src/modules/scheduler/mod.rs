@@ -0,0 +1,605 @@
+pub mod cron;
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use crate::modules::{
+    AppState,
+    audit::{self, Actor, AuditAction},
+    db,
+    service::{Drift, Lifecycle, Service, ServiceEvent, ServiceStatus, approval::Trigger},
+};
+
+const TICK: Duration = Duration::from_secs(30);
+const POLL_TICK: Duration = Duration::from_secs(10);
+const USAGE_TICK: Duration = Duration::from_secs(15);
+const WATCHDOG_TICK: Duration = Duration::from_secs(15);
+const JOBS_TICK: Duration = Duration::from_secs(30);
+const PREVIEWS_TICK: Duration = Duration::from_secs(300);
+
+pub fn next_run(service: &Service) -> Option<DateTime<Utc>> {
+    service
+        .parsed_schedule()
+        .ok()
+        .flatten()
+        .and_then(|s| s.next_after(Utc::now()))
+}
+
+// background task that deploys active services whose schedule fired since the last tick
+pub async fn run(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(TICK);
+    let mut last_tick = Utc::now();
+    let actor = Actor {
+        name: "scheduler".to_string(),
+        source_ip: None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let now = Utc::now();
+        let services = match db::get_services(&app_state.pool, Some(false)).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(Level::ERROR, "Scheduler unable to get services | {}", e);
+                continue;
+            }
+        };
+
+        for service in services.into_iter().filter(|s| s.active) {
+            let schedule = match service.parsed_schedule() {
+                Ok(Some(s)) => s,
+                Ok(None) => continue,
+                Err(e) => {
+                    event!(
+                        Level::WARN,
+                        "Skipping bad schedule for {} | {}",
+                        service.name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            if schedule
+                .next_after(last_tick)
+                .is_some_and(|next| next <= now)
+            {
+                event!(Level::INFO, "Scheduled deploy of {}", service.name);
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service.id),
+                    AuditAction::Deploy,
+                    serde_json::json!({}),
+                )
+                .await;
+                app_state.spawn_triggered(service, Trigger::Schedule);
+            }
+        }
+
+        last_tick = now;
+    }
+
+    event!(Level::INFO, "Scheduler stopped.");
+}
+
+// background task that deploys active services when their remote head moves
+pub async fn poll(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(POLL_TICK);
+    let mut next_poll: HashMap<i64, Instant> = HashMap::new();
+    let actor = Actor {
+        name: "git poll".to_string(),
+        source_ip: None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let services = match db::get_services(&app_state.pool, Some(false)).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(Level::ERROR, "Poller unable to get services | {}", e);
+                continue;
+            }
+        };
+
+        let now = Instant::now();
+        for service in services.into_iter().filter(|s| s.active) {
+            let Some(secs) = service.poll_interval_secs else {
+                continue;
+            };
+            if next_poll.get(&service.id).is_some_and(|due| *due > now) {
+                continue;
+            }
+            next_poll.insert(service.id, now + Duration::from_secs(secs.max(0) as u64));
+
+            let auth = match service.git_auth(&app_state.config, &app_state.pool).await {
+                Ok(a) => a,
+                Err(e) => {
+                    event!(
+                        Level::WARN,
+                        "Poller unable to find credentials for {} | {}",
+                        service.name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let remote = service.clone();
+            let remote_auth = auth.clone();
+            let head =
+                match tokio::task::spawn_blocking(move || remote.remote_head(remote_auth)).await {
+                    Ok(Ok(head)) => head,
+                    Ok(Err(e)) => {
+                        event!(Level::WARN, "Poll of {} failed | {}", service.name, e);
+                        continue;
+                    }
+                    Err(e) => {
+                        event!(Level::ERROR, "Poll task failed | {}", e);
+                        continue;
+                    }
+                };
+
+            if service.last_commit.as_deref() == Some(head.as_str()) {
+                continue;
+            }
+            // record the head first so a slow deploy isn't enqueued again on the next poll
+            if let Err(e) = db::set_last_commit(&app_state.pool, service.id, head.clone()).await {
+                event!(Level::ERROR, "Unable to record remote head | {}", e);
+                continue;
+            }
+            let Some(previous) = service.last_commit.clone() else {
+                // first sighting of the repo; nothing to compare against yet
+                continue;
+            };
+
+            if service.watch_paths.is_some() {
+                let (watched, config) = (service.clone(), app_state.config.clone());
+                let (from, to) = (previous.clone(), head.clone());
+                let touched = tokio::task::spawn_blocking(move || {
+                    watched.touches_watched_paths(&config, auth, &from, &to)
+                })
+                .await;
+                match touched {
+                    Ok(Ok(true)) => (),
+                    Ok(Ok(false)) => {
+                        event!(
+                            Level::INFO,
+                            "New commit on {} ({} -> {}) leaves its watched paths alone, skipping",
+                            service.name,
+                            previous,
+                            head
+                        );
+                        continue;
+                    }
+                    // deploying needlessly beats missing a change
+                    Ok(Err(e)) => event!(
+                        Level::WARN,
+                        "Unable to check watched paths of {}, deploying | {}",
+                        service.name,
+                        e
+                    ),
+                    Err(e) => event!(Level::ERROR, "Path check task failed | {}", e),
+                }
+            }
+
+            event!(
+                Level::INFO,
+                "New commit on {} ({} -> {}), deploying",
+                service.name,
+                previous,
+                head
+            );
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service.id),
+                AuditAction::Deploy,
+                serde_json::json!({ "commit": { "before": previous, "after": head } }),
+            )
+            .await;
+            app_state.spawn_triggered(service, Trigger::Poll);
+        }
+    }
+
+    event!(Level::INFO, "Git poller stopped.");
+}
+
+// background task that samples container resource usage for the dashboard
+pub async fn usage(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(USAGE_TICK);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let services = match db::get_services(&app_state.pool, Some(false)).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(Level::ERROR, "Usage sampler unable to get services | {}", e);
+                continue;
+            }
+        };
+        let hosts = Service::docker_hosts(&services);
+        let (containers, stats) = match (
+            Service::get_list(&hosts).await,
+            Service::get_stats(&hosts).await,
+        ) {
+            (Ok(c), Ok(s)) => (c, s),
+            (Err(e), _) | (_, Err(e)) => {
+                event!(Level::WARN, "Unable to sample container usage | {}", e);
+                continue;
+            }
+        };
+
+        let usage = services
+            .iter()
+            .map(|service| (service.id, service.usage(&containers, &stats)))
+            .collect();
+        let _ = app_state
+            .service_broadcast
+            .broadcaster
+            .send(ServiceEvent::Usage { usage });
+    }
+
+    event!(Level::INFO, "Usage sampler stopped.");
+}
+
+// background task that compares services with what docker runs, reporting drift and,
+// with RECONCILE_AUTOFIX, correcting it
+pub async fn reconcile(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(app_state.config.reconcile_interval);
+    let actor = Actor {
+        name: "reconciler".to_string(),
+        source_ip: None,
+    };
+    // services corrected once already; one that drifts again right away is only reported
+    let mut corrected = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let services = match db::get_services(&app_state.pool, None).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(Level::ERROR, "Reconciler unable to get services | {}", e);
+                continue;
+            }
+        };
+        let containers = match Service::get_list(&Service::docker_hosts(&services)).await {
+            Ok(mut list) => {
+                list.extend(app_state.agents.containers());
+                list
+            }
+            Err(e) => {
+                event!(Level::WARN, "Reconciler unable to list containers | {}", e);
+                continue;
+            }
+        };
+        let nodes = app_state
+            .agents
+            .mark(db::get_nodes(&app_state.pool).await.unwrap_or_default());
+
+        let mut drift = vec![];
+        for service in services {
+            // a deploy under way, or a node that can't be seen, isn't drift
+            let hidden = service
+                .node_id
+                .is_some_and(|id| !nodes.iter().any(|n| n.id == id && n.connected));
+            if hidden || app_state.in_flight.contains(service.id) {
+                drift.push((service.id, None));
+                continue;
+            }
+
+            let found = service.drift(&containers);
+            match &found {
+                Some(d) => {
+                    event!(Level::WARN, "Drift | {} | {}", service.name, d);
+                    if app_state.config.reconcile_autofix && corrected.insert(service.id) {
+                        correct(&app_state, &actor, service.clone(), d).await;
+                    }
+                }
+                None => {
+                    corrected.remove(&service.id);
+                }
+            }
+            drift.push((service.id, found));
+        }
+
+        let _ = app_state
+            .service_broadcast
+            .broadcaster
+            .send(ServiceEvent::Drift { drift });
+    }
+
+    event!(Level::INFO, "Reconciler stopped.");
+}
+
+async fn correct(app_state: &AppState, actor: &Actor, service: Service, drift: &Drift) {
+    match drift {
+        Drift::NotRunning => {
+            // a failed deploy needs someone to look at it, not another attempt every tick
+            if service.recorded_status().is_some_and(|s| s.is_failure()) {
+                return;
+            }
+            event!(Level::INFO, "Redeploying {} to correct drift", service.name);
+            audit::record(
+                &app_state.pool,
+                actor,
+                Some(service.id),
+                AuditAction::Deploy,
+                serde_json::json!({ "drift": drift.to_string() }),
+            )
+            .await;
+            app_state.spawn_deploy(service.id, Ok(service));
+        }
+        Drift::ArchivedRunning => {
+            event!(
+                Level::INFO,
+                "Stopping archived {} to correct drift",
+                service.name
+            );
+            audit::record(
+                &app_state.pool,
+                actor,
+                Some(service.id),
+                AuditAction::Deactivate,
+                serde_json::json!({ "drift": drift.to_string() }),
+            )
+            .await;
+            app_state.spawn_deactivate(Ok(service));
+        }
+    }
+}
+
+// background task that brings back active services whose containers exited on their own,
+// giving up on a service that keeps crashing until it's deployed again
+pub async fn watchdog(app_state: AppState) {
+    let max_restarts = app_state.config.watchdog_max_restarts;
+    if max_restarts == 0 {
+        return;
+    }
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(WATCHDOG_TICK);
+    let mut restarts: HashMap<i64, Vec<Instant>> = HashMap::new();
+    let actor = Actor {
+        name: "watchdog".to_string(),
+        source_ip: None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let services = match db::get_services(&app_state.pool, Some(false)).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(Level::ERROR, "Watchdog unable to get services | {}", e);
+                continue;
+            }
+        };
+        // agents restart nothing on their own; node services are left to their deploys
+        let services: Vec<Service> = services
+            .into_iter()
+            .filter(|s| s.active && s.node_id.is_none())
+            .collect();
+        let containers = match Service::get_list(&Service::docker_hosts(&services)).await {
+            Ok(list) => list,
+            Err(e) => {
+                event!(Level::WARN, "Watchdog unable to list containers | {}", e);
+                continue;
+            }
+        };
+
+        for service in services {
+            // anything but a finished deploy means wraut stopped it, or is working on it
+            let deployed = matches!(service.recorded_status(), Some(ServiceStatus::Running));
+            if !deployed
+                || app_state.in_flight.contains(service.id)
+                || !service.has_crashed(&containers)
+            {
+                continue;
+            }
+
+            let recent = restarts.entry(service.id).or_default();
+            recent.retain(|at| at.elapsed() < app_state.config.watchdog_window);
+            if recent.len() >= max_restarts {
+                event!(
+                    Level::ERROR,
+                    "{} crashed {} times in {}s, no longer restarting it",
+                    service.name,
+                    recent.len() + 1,
+                    app_state.config.watchdog_window.as_secs()
+                );
+                recent.clear();
+                let _ = app_state
+                    .service_broadcast
+                    .broadcaster
+                    .send(ServiceEvent::update(
+                        service.id,
+                        ServiceStatus::CrashLooping,
+                    ));
+                continue;
+            }
+            recent.push(Instant::now());
+
+            event!(Level::WARN, "{} crashed, restarting it", service.name);
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service.id),
+                AuditAction::Restart,
+                serde_json::json!({}),
+            )
+            .await;
+            // `compose up -d` on the live dir as it is, without pulling or rebuilding
+            app_state.spawn_lifecycle(service, Lifecycle::Start);
+        }
+    }
+
+    event!(Level::INFO, "Watchdog stopped.");
+}
+
+// background task that runs the scheduled jobs that fired since the last tick, for active
+// services on this host
+pub async fn jobs(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(JOBS_TICK);
+    let mut last_tick = Utc::now();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let now = Utc::now();
+        let jobs = match db::get_active_jobs(&app_state.pool).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                event!(Level::ERROR, "Job scheduler unable to get jobs | {}", e);
+                continue;
+            }
+        };
+        let services: HashMap<i64, Service> =
+            match db::get_services(&app_state.pool, Some(false)).await {
+                Ok(services) => services
+                    .into_iter()
+                    .filter(|s| s.active && s.node_id.is_none())
+                    .map(|s| (s.id, s))
+                    .collect(),
+                Err(e) => {
+                    event!(Level::ERROR, "Job scheduler unable to get services | {}", e);
+                    continue;
+                }
+            };
+
+        for job in jobs {
+            let Some(service) = services.get(&job.service_id) else {
+                continue;
+            };
+            let schedule = match job.parsed_schedule() {
+                Ok(s) => s,
+                Err(e) => {
+                    event!(
+                        Level::WARN,
+                        "Skipping bad schedule of job {} | {}",
+                        job.name,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let due = schedule
+                .next_after(last_tick)
+                .is_some_and(|next| next <= now);
+            if !due {
+                continue;
+            }
+            event!(Level::INFO, "Running job {} of {}", job.name, service.name);
+            let name = job.name.clone();
+            if !app_state.spawn_job(service.clone(), job, "schedule") {
+                event!(
+                    Level::WARN,
+                    "Job {} of {} is still running, skipping this run",
+                    name,
+                    service.name
+                );
+            }
+        }
+
+        last_tick = now;
+    }
+
+    event!(Level::INFO, "Job scheduler stopped.");
+}
+
+// background task that deletes pull request previews left without an update for
+// PREVIEW_TTL_SECS, for pull requests that were never closed or whose close was missed
+pub async fn previews(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(PREVIEWS_TICK);
+    let actor = Actor {
+        name: "previews".to_string(),
+        source_ip: None,
+    };
+    // deleted already; their rows go once the teardown finishes
+    let mut deleting = HashSet::new();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let expired = match db::expired_previews(&app_state.pool).await {
+            Ok(expired) => expired,
+            Err(e) => {
+                event!(Level::ERROR, "Unable to get expired previews | {}", e);
+                continue;
+            }
+        };
+        for id in expired {
+            if app_state.in_flight.contains(id) || !deleting.insert(id) {
+                continue;
+            }
+            let service = db::get_service(&app_state.pool, id).await;
+            event!(Level::INFO, "Deleting expired preview {}", id);
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(id),
+                AuditAction::Delete,
+                audit::diff(service.as_ref().ok(), None),
+            )
+            .await;
+            app_state.spawn_delete(service);
+        }
+    }
+
+    event!(Level::INFO, "Preview expiry stopped.");
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+
+use openssl::{
+    base64,
+    hash::MessageDigest,
+    pkcs5::pbkdf2_hmac,
+    rand::rand_bytes,
+    sha::sha256,
+    symm::{Cipher, decrypt_aead, encrypt_aead},
+};
+use sqlx::SqlitePool;
+use thiserror::Error;
+use tracing::{Level, event};
+
+use crate::modules::db::{self, DBError};
+
+// marks values encrypted by this module so the format can change later; v1 values were
+// encrypted under the unsalted key of earlier versions and are rewritten by `upgrade`
+const PREFIX: &str = "enc:v2:";
+const LEGACY_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_ITERATIONS: usize = 100_000;
+const SALT_LEN: usize = 16;
+pub const MASK: &str = "••••••••";
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("No SECRETS_KEY configured")]
+    NoKey,
+    #[error("Encryption failure")]
+    Crypto(#[from] openssl::error::ErrorStack),
+    #[error("Stored secret is malformed")]
+    Malformed,
+    #[error("Unable to read or store the secrets salt | {0}")]
+    Db(#[from] DBError),
+}
+
+// SECRETS_KEY as configured, until `unlock` derives the key from it; never printed
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Self {
+        Self(passphrase)
+    }
+}
+
+// 256-bit key derived from the configured passphrase, and the unsalted one v1 values were
+// encrypted under; never printed
+#[derive(Clone)]
+pub struct SecretsKey {
+    key: [u8; 32],
+    legacy: [u8; 32],
+}
+
+impl SecretsKey {
+    // PBKDF2-SHA256 under the database's salt, so a copy of the database can't be tried
+    // against passphrases at hash speed
+    pub fn derive(passphrase: &Passphrase, salt: &[u8]) -> Result<Self, SecretError> {
+        let mut key = [0u8; 32];
+        pbkdf2_hmac(
+            passphrase.0.as_bytes(),
+            salt,
+            KEY_ITERATIONS,
+            MessageDigest::sha256(),
+            &mut key,
+        )?;
+        Ok(Self {
+            key,
+            legacy: sha256(passphrase.0.as_bytes()),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, SecretError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand_bytes(&mut nonce)?;
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &[],
+            plaintext.as_bytes(),
+            &mut tag,
+        )?;
+
+        let mut blob = nonce.to_vec();
+        blob.extend(ciphertext);
+        blob.extend(tag);
+        Ok(format!("{}{}", PREFIX, base64::encode_block(&blob)))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> Result<String, SecretError> {
+        let (key, encoded) = match stored.strip_prefix(PREFIX) {
+            Some(encoded) => (&self.key, encoded),
+            None => (
+                &self.legacy,
+                stored
+                    .strip_prefix(LEGACY_PREFIX)
+                    .ok_or(SecretError::Malformed)?,
+            ),
+        };
+        let blob = base64::decode_block(encoded)?;
+        if blob.len() < NONCE_LEN + TAG_LEN {
+            return Err(SecretError::Malformed);
+        }
+        let (nonce, rest) = blob.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            &[],
+            ciphertext,
+            tag,
+        )?;
+        String::from_utf8(plaintext).map_err(|_| SecretError::Malformed)
+    }
+}
+
+impl fmt::Debug for SecretsKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretsKey({})", MASK)
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Passphrase({})", MASK)
+    }
+}
+
+// the key for this database; its salt is made on first use and kept in the database, so a
+// restored backup comes with the salt its secrets were encrypted under
+pub async fn unlock(pool: &SqlitePool, passphrase: &Passphrase) -> Result<SecretsKey, SecretError> {
+    let salt = match db::get_secrets_salt(pool).await? {
+        Some(salt) => base64::decode_block(&salt)?,
+        None => {
+            let mut salt = [0u8; SALT_LEN];
+            rand_bytes(&mut salt)?;
+            db::set_secrets_salt(pool, &base64::encode_block(&salt)).await?;
+            salt.to_vec()
+        }
+    };
+    SecretsKey::derive(passphrase, &salt)
+}
+
+// re-encrypts the secret env vars and git tokens still under the unsalted key; returns how
+// many were rewritten
+pub async fn upgrade(pool: &SqlitePool, key: &SecretsKey) -> Result<usize, SecretError> {
+    let mut upgraded = 0;
+    for (id, value) in db::get_secret_env_values(pool).await? {
+        if value.starts_with(LEGACY_PREFIX) {
+            db::set_secret_env_value(pool, id, key.encrypt(&key.decrypt(&value)?)?).await?;
+            upgraded += 1;
+        }
+    }
+    for (service_id, token) in db::get_git_credential_tokens(pool).await? {
+        if token.starts_with(LEGACY_PREFIX) {
+            db::set_git_credential_token(pool, service_id, key.encrypt(&key.decrypt(&token)?)?)
+                .await?;
+            upgraded += 1;
+        }
+    }
+    if upgraded > 0 {
+        event!(
+            Level::INFO,
+            "Re-encrypted {} secrets under the salted key",
+            upgraded
+        );
+    }
+    Ok(upgraded)
+}
@@ -0,0 +1,35 @@
+use std::fmt;
+
+use serde::Serialize;
+
+// what started a deploy without anyone asking for it on the dashboard or API
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    Hook,
+    Schedule,
+    Poll,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::Hook => "hook",
+            Trigger::Schedule => "schedule",
+            Trigger::Poll => "poll",
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// an automatic deploy held until someone approves it
+#[derive(Clone, Debug, Serialize)]
+pub struct DeployApproval {
+    pub service_id: i64,
+    pub trigger: String,
+    pub requested_at: String,
+}
@@ -0,0 +1,152 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use super::{Service, ServiceError, env::SECRETS_DIR, inside_repo};
+use crate::modules::Config;
+
+// larger files are cut off in the viewer
+const MAX_VIEW_BYTES: u64 = 256 * 1024;
+// holds the decrypted secrets; the env editor shows its values instead
+const ENV_FILE: &str = ".env";
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    // relative to the live dir
+    pub path: String,
+    pub is_dir: bool,
+    // where a link points; links are shown, never followed
+    pub link: Option<String>,
+    pub bytes: u64,
+    pub modified: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Contents {
+    Dir(Vec<Entry>),
+    Text { text: String, truncated: bool },
+    Binary(u64),
+    Hidden,
+}
+
+#[derive(Clone, Debug)]
+pub struct Browse {
+    // relative to the live dir, "" for the live dir itself
+    pub path: String,
+    pub contents: Contents,
+    // the compose file docker compose runs, relative to the live dir
+    pub compose_file: Option<String>,
+}
+
+impl Browse {
+    // each ancestor of `path` with its own path, for the panel's breadcrumbs
+    pub fn crumbs(&self) -> Vec<(String, String)> {
+        let mut crumbs = vec![];
+        let mut so_far = PathBuf::new();
+        for part in Path::new(&self.path).iter() {
+            so_far.push(part);
+            crumbs.push((
+                part.to_string_lossy().to_string(),
+                so_far.to_string_lossy().to_string(),
+            ));
+        }
+        crumbs
+    }
+}
+
+fn relative(live_path: &Path, path: &Path) -> String {
+    path.strip_prefix(live_path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn entry(live_path: &Path, entry: std::fs::DirEntry) -> Result<Entry, ServiceError> {
+    let metadata = entry.metadata()?;
+    let link = match metadata.is_symlink() {
+        true => Some(
+            std::fs::read_link(entry.path())?
+                .to_string_lossy()
+                .to_string(),
+        ),
+        false => None,
+    };
+    Ok(Entry {
+        name: entry.file_name().to_string_lossy().to_string(),
+        path: relative(live_path, &entry.path()),
+        is_dir: metadata.is_dir(),
+        link,
+        bytes: metadata.len(),
+        modified: metadata
+            .modified()
+            .map(|m| {
+                DateTime::<Utc>::from(m)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+fn read(path: &Path) -> Result<Contents, ServiceError> {
+    let file = std::fs::File::open(path)?;
+    let bytes = file.metadata()?.len();
+    let mut buf = vec![];
+    file.take(MAX_VIEW_BYTES).read_to_end(&mut buf)?;
+    if buf.contains(&0) {
+        return Ok(Contents::Binary(bytes));
+    }
+    Ok(Contents::Text {
+        text: String::from_utf8_lossy(&buf).to_string(),
+        truncated: bytes > MAX_VIEW_BYTES,
+    })
+}
+
+impl Service {
+    // what's at `path` under the live dir, read-only; paths can't climb out of it, through
+    // `..` or a link
+    pub fn browse(&self, config: &Config, path: &str) -> Result<Browse, ServiceError> {
+        if self.node_id.is_some() {
+            return Err(ServiceError::BrowseOnNode);
+        }
+        let path = path.trim_matches('/');
+        if !path.is_empty() && !inside_repo(path) {
+            return Err(ServiceError::BrowsePath(path.to_string()));
+        }
+        let live_path = self.live_path(config);
+        let live_path = live_path
+            .canonicalize()
+            .map_err(|_| ServiceError::BrowsePath(live_path.to_string_lossy().to_string()))?;
+        let target = live_path
+            .join(path)
+            .canonicalize()
+            .ok()
+            .filter(|resolved| resolved.starts_with(&live_path))
+            .ok_or_else(|| ServiceError::BrowsePath(path.to_string()))?;
+
+        let contents = if target.is_dir() {
+            let mut entries = std::fs::read_dir(&target)?
+                .map(|e| entry(&live_path, e?))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+            Contents::Dir(entries)
+        } else if target == live_path.join(ENV_FILE)
+            || target.starts_with(live_path.join(SECRETS_DIR))
+        {
+            Contents::Hidden
+        } else {
+            read(&target)?
+        };
+
+        Ok(Browse {
+            path: path.to_string(),
+            contents,
+            compose_file: self
+                .compose_path(&live_path)
+                .ok()
+                .map(|compose| relative(&live_path, &compose)),
+        })
+    }
+}
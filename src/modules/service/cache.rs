@@ -0,0 +1,71 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use super::{DockerServiceEntry, Service, ServiceError};
+
+struct Snapshot {
+    hosts: Vec<Option<String>>,
+    generation: u64,
+    taken: Instant,
+    entries: Vec<DockerServiceEntry>,
+}
+
+// the last `docker ps` of a set of hosts, shared by everything that only displays state,
+// so a burst of dashboard redraws runs it once. Pipelines query Docker themselves
+#[derive(Clone)]
+pub struct DockerCache {
+    ttl: Duration,
+    generation: Arc<AtomicU64>,
+    // held while refreshing, so concurrent readers wait for one query instead of each
+    // running their own
+    snapshot: Arc<Mutex<Option<Snapshot>>>,
+}
+
+impl DockerCache {
+    // a zero TTL queries Docker every time
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            generation: Arc::default(),
+            snapshot: Arc::default(),
+        }
+    }
+
+    // makes the next read query Docker; for when a pipeline has changed containers
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub async fn list(
+        &self,
+        hosts: &[Option<String>],
+    ) -> Result<Vec<DockerServiceEntry>, ServiceError> {
+        let mut snapshot = self.snapshot.lock().await;
+        let generation = self.generation.load(Ordering::Relaxed);
+        if let Some(s) = snapshot.as_ref().filter(|s| {
+            s.hosts == hosts && s.generation == generation && s.taken.elapsed() < self.ttl
+        }) {
+            return Ok(s.entries.clone());
+        }
+
+        let entries = Service::get_list(hosts).await?;
+        *snapshot = Some(Snapshot {
+            hosts: hosts.to_vec(),
+            generation,
+            taken: Instant::now(),
+            entries: entries.clone(),
+        });
+        Ok(entries)
+    }
+}
+
+impl std::fmt::Debug for DockerCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DockerCache({:?})", self.ttl)
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use super::{Service, ServiceError};
+
+impl Service {
+    // the compose services defined by the compose file in the clone at `repo_path`, plus any
+    // the override adds; None until the repo's been cloned there, as for a new service or one
+    // deployed by a node
+    pub fn repo_compose_names(&self, repo_path: &Path) -> Option<Vec<String>> {
+        let compose_path = self.compose_path(repo_path).ok()?;
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(compose_path).ok()?).ok()?;
+        let overridden = self
+            .compose_override
+            .as_deref()
+            .and_then(|text| serde_yaml::from_str::<serde_yaml::Value>(text).ok());
+
+        let mut names: Vec<String> = [Some(compose), overridden]
+            .iter()
+            .flatten()
+            .filter_map(|file| file.get("services")?.as_mapping())
+            .flat_map(|services| services.keys().filter_map(|k| k.as_str()))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        Some(names)
+    }
+
+    // fails with the compose_name entries the clone's compose file doesn't define, which a
+    // deploy would otherwise only find once it rewrites the file
+    pub fn check_compose_names(&self, repo_path: &Path) -> Result<(), ServiceError> {
+        let (Some(targets), Some(known)) =
+            (self.compose_targets(), self.repo_compose_names(repo_path))
+        else {
+            return Ok(());
+        };
+        let unknown: Vec<String> = targets
+            .into_iter()
+            .filter(|target| !known.contains(target))
+            .collect();
+        match unknown.is_empty() {
+            true => Ok(()),
+            false => Err(ServiceError::UnknownComposeService(
+                unknown.join(", "),
+                known.join(", "),
+            )),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::{Service, ServiceError};
+use crate::modules::Config;
+
+// a service's compose file as the repo has it and as its last deploy wrote it, beside the
+// override that's merged over it on the next deploy
+#[derive(Clone, Debug, Serialize)]
+pub struct ComposeView {
+    // the compose file in the clone; None before the first clone, or on a node
+    pub repo: Option<String>,
+    // the compose file docker compose runs, as rewritten by the last deploy
+    pub live: Option<String>,
+    // the override as the last deploy wrote it, template variables filled in
+    pub live_override: Option<String>,
+    // the override saved in wraut, applied on the next deploy
+    pub compose_override: Option<String>,
+}
+
+impl Service {
+    pub fn compose_view(&self, config: &Config) -> ComposeView {
+        // a node keeps its clone and live dir to itself
+        let local = self.node_id.is_none();
+        let read = |path: Result<PathBuf, ServiceError>| {
+            path.ok()
+                .filter(|_| local)
+                .and_then(|path| std::fs::read_to_string(path).ok())
+        };
+        let live_path = self.live_path(config);
+        ComposeView {
+            repo: read(self.compose_path(&self.repo_path(config))),
+            live: read(self.compose_path(&live_path)),
+            live_override: read(self.override_path(&live_path)),
+            compose_override: self.compose_override.clone(),
+        }
+    }
+
+    // checks an override before it's saved, as the service form does
+    pub fn check_compose_override(&self) -> Result<(), ServiceError> {
+        match self
+            .field_errors()
+            .into_iter()
+            .find(|(field, _)| *field == "compose_override")
+        {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::secrets::{MASK, SecretError, SecretsKey};
+use crate::modules::tools;
+
+// answers git's credential requests from the environment, so the token never shows up
+// in the process list or on disk; the empty helper first clears any the host configures
+const HELPER: &str = "!f() { test \"$1\" = get && printf 'username=%s\\npassword=%s\\n' \"$WRAUT_GIT_USERNAME\" \"$WRAUT_GIT_TOKEN\"; }; f";
+
+// username and personal access token for repos cloned over HTTPS; the token is
+// encrypted with SECRETS_KEY in the database and revealed only for git
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GitCredential {
+    pub username: String,
+    pub token: String,
+}
+
+impl GitCredential {
+    pub fn reveal(self, key: Option<&SecretsKey>) -> Result<Self, SecretError> {
+        let token = key.ok_or(SecretError::NoKey)?.decrypt(&self.token)?;
+        Ok(Self { token, ..self })
+    }
+}
+
+impl fmt::Debug for GitCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GitCredential({}, {})", self.username, MASK)
+    }
+}
+
+// how git authenticates to a service's remote: an SSH identity, an HTTPS credential,
+// or neither
+#[derive(Clone, Debug, Default)]
+pub struct GitAuth {
+    pub key_path: Option<PathBuf>,
+    pub credential: Option<GitCredential>,
+}
+
+impl GitAuth {
+    pub fn command(&self) -> Command {
+        let mut git = tools::git();
+        if let Some(k) = &self.key_path {
+            git.env(
+                "GIT_SSH_COMMAND",
+                format!(
+                    "ssh -i '{}' -o IdentitiesOnly=yes -o StrictHostKeyChecking=accept-new",
+                    k.to_string_lossy()
+                ),
+            );
+        }
+        if let Some(credential) = &self.credential {
+            git.args(["-c", "credential.helper=", "-c"])
+                .arg(format!("credential.helper={}", HELPER))
+                .env("WRAUT_GIT_USERNAME", &credential.username)
+                .env("WRAUT_GIT_TOKEN", &credential.token)
+                // a rejected token fails the command instead of waiting on a prompt
+                .env("GIT_TERMINAL_PROMPT", "0");
+        }
+        git
+    }
+}
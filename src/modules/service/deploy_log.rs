@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use chrono::Utc;
+use tokio::sync::{
+    broadcast::{
+        self,
+        error::{RecvError, TryRecvError},
+    },
+    oneshot,
+};
+use tokio::task::JoinHandle;
+
+use super::ServiceEvent;
+
+// longer logs keep their end, where a failure is
+const MAX_LOG_BYTES: usize = 1024 * 1024;
+
+// collects a deploy's status updates and streamed command output while it runs, to be kept
+// with its deployment
+pub struct DeployLog {
+    done: oneshot::Sender<()>,
+    lines: JoinHandle<String>,
+}
+
+fn write(log: &mut String, service_id: i64, event: ServiceEvent) {
+    if let ServiceEvent::ServiceUpdate { id, status, .. } = event
+        && id == service_id
+    {
+        let _ = writeln!(log, "{} {}", Utc::now().format("%H:%M:%S"), status);
+    }
+}
+
+fn missed(log: &mut String, missed: u64) {
+    let _ = writeln!(log, "... {} events missed", missed);
+}
+
+fn trim(log: String) -> String {
+    if log.len() <= MAX_LOG_BYTES {
+        return log;
+    }
+    let mut start = log.len() - MAX_LOG_BYTES;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = log[start..].find('\n').map_or(start, |n| start + n + 1);
+    format!("... earlier output dropped\n{}", &log[start..])
+}
+
+impl DeployLog {
+    pub fn start(br: &broadcast::Sender<ServiceEvent>, service_id: i64) -> Self {
+        let mut receiver = br.subscribe();
+        let (done, mut finished) = oneshot::channel::<()>();
+        let lines = tokio::spawn(async move {
+            let mut log = String::new();
+            loop {
+                tokio::select! {
+                    biased;
+                    received = receiver.recv() => match received {
+                        Ok(event) => write(&mut log, service_id, event),
+                        Err(RecvError::Lagged(n)) => missed(&mut log, n),
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = &mut finished => {
+                        // whatever the deploy sent before it ended is already queued
+                        loop {
+                            match receiver.try_recv() {
+                                Ok(event) => write(&mut log, service_id, event),
+                                Err(TryRecvError::Lagged(n)) => missed(&mut log, n),
+                                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+            trim(log)
+        });
+        DeployLog { done, lines }
+    }
+
+    pub async fn finish(self) -> String {
+        let _ = self.done.send(());
+        self.lines.await.unwrap_or_default()
+    }
+}
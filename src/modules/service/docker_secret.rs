@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use super::{Service, ServiceError, env::ServiceEnv};
+
+impl Service {
+    // the secret vars written as files instead of into `.env`; none unless the service asks
+    pub fn secret_files<'a>(&self, env_vars: &'a [ServiceEnv]) -> Vec<&'a ServiceEnv> {
+        match self.secret_files {
+            true => env_vars.iter().filter(|var| var.secret).collect(),
+            false => vec![],
+        }
+    }
+}
+
+// grants a compose service each secret, in whichever of the list forms it uses; a secret it
+// already lists is left as it is
+pub fn grant(
+    compose_service: &mut Mapping,
+    target: &str,
+    secrets: &[String],
+) -> Result<(), ServiceError> {
+    let existing = compose_service
+        .entry(Value::String("secrets".into()))
+        .or_insert_with(|| Value::Sequence(vec![]));
+    let Some(list) = existing.as_sequence_mut() else {
+        return Err(ServiceError::Key(format!("{} secrets (as list)", target)));
+    };
+    for secret in secrets {
+        // short entries are names, long ones name theirs under `source`
+        let listed = list
+            .iter()
+            .any(|s| s.as_str().or_else(|| s.get("source")?.as_str()) == Some(secret.as_str()));
+        if !listed {
+            list.push(Value::String(secret.clone()));
+        }
+    }
+    Ok(())
+}
+
+// declares each secret at the top of the compose file as a file under `dir`, replacing any
+// declaration of the same name so the repo can't point it elsewhere
+pub fn declare(compose: &mut Mapping, secrets: &[String], dir: &Path) -> Result<(), ServiceError> {
+    let declared = compose
+        .entry(Value::String("secrets".into()))
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if declared.is_null() {
+        *declared = Value::Mapping(Mapping::new());
+    }
+    let Some(declared) = declared.as_mapping_mut() else {
+        return Err(ServiceError::Key("secrets (as map)".into()));
+    };
+    for secret in secrets {
+        let mut file = Mapping::new();
+        file.insert(
+            Value::String("file".into()),
+            Value::String(dir.join(secret).to_string_lossy().to_string()),
+        );
+        declared.insert(Value::String(secret.clone()), Value::Mapping(file));
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    Config, OVERRIDE_FILE, Service, ServiceError, ServiceEvent, env::reveal, process, template,
+};
+use crate::modules::db::get_service_env;
+
+#[derive(Debug, Serialize)]
+pub struct DryRun {
+    pub commit: Option<String>,
+    // true when the service has no live dir yet
+    pub first_deploy: bool,
+    // in the order the deploy would run them
+    pub commands: Vec<String>,
+    // unified diff of the live compose files against the ones the deploy would write
+    pub compose_diff: String,
+}
+
+fn describe(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            match arg.contains(char::is_whitespace) {
+                true => format!("'{}'", arg),
+                false => arg.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Service {
+    // pulls and rewrites the config like a deploy, but into a scratch live dir that's
+    // removed afterwards; no container is touched and no status is broadcast
+    pub async fn dry_run(
+        &self,
+        config: Config,
+        pool: &SqlitePool,
+        commit: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<DryRun, ServiceError> {
+        if self.archived {
+            return Err(ServiceError::Archived);
+        }
+        let (br, _) = broadcast::channel::<ServiceEvent>(16);
+        // only reads Docker; a stop is planned when something is running
+        let services = Self::get_list(std::slice::from_ref(&self.docker_host)).await?;
+        let running = self.is_running(&services);
+
+        let auth = self.git_auth(&config, pool).await?;
+        let commit = self.checkout(&config, auth, commit, cancel, &br)?;
+        let env_vars = reveal(
+            get_service_env(pool, self.id).await?,
+            config.secrets_key.as_ref(),
+        )?;
+
+        // beside the live dir, like the kept versions; the scratch copy of the service keeps
+        // its live dir there whatever its own live_dir says
+        let scratch = Config {
+            services_live_dir: self.kept_path(&self.live_root(&config), "dry-run"),
+            ..config.clone()
+        };
+        let scratch_service = Service {
+            live_dir: None,
+            ..self.clone()
+        };
+        let _ = std::fs::remove_dir_all(&scratch.services_live_dir);
+        // `copy_to_live` only creates the last level
+        std::fs::create_dir_all(&scratch.services_live_dir)?;
+        let rendered = scratch_service
+            .copy_to_live(scratch.clone(), &env_vars, cancel, &br)
+            .and_then(|_| {
+                let vars = template::variables(self, commit.as_deref(), &env_vars);
+                let secrets: Vec<String> = self
+                    .secret_files(&env_vars)
+                    .into_iter()
+                    .map(|var| var.key.clone())
+                    .collect();
+                scratch_service.apply_tags(scratch.clone(), &vars, &secrets, &br)
+            })
+            .and_then(|_| {
+                let live_path = self.live_path(&config);
+                let scratch_path = scratch_service.live_path(&scratch);
+                Ok(DryRun {
+                    commit: commit.clone(),
+                    first_deploy: !live_path.is_dir(),
+                    commands: self.planned_commands(
+                        &config,
+                        commit.as_deref(),
+                        running,
+                        &live_path,
+                        &scratch_path,
+                    )?,
+                    compose_diff: self.compose_diff(&config, &live_path, &scratch_path, cancel)?,
+                })
+            });
+        let _ = std::fs::remove_dir_all(&scratch.services_live_dir);
+        rendered
+    }
+
+    // what `release` would run, described with the scratch dir's paths as the live dir's
+    fn planned_commands(
+        &self,
+        config: &Config,
+        commit: Option<&str>,
+        running: bool,
+        live_path: &Path,
+        scratch_path: &Path,
+    ) -> Result<Vec<String>, ServiceError> {
+        let live = live_path.to_string_lossy();
+        let scratch = scratch_path.to_string_lossy();
+        let compose = |args: &[&str]| -> Result<String, ServiceError> {
+            let mut command = self.compose(scratch_path)?;
+            command.args(args);
+            Ok(describe(&command).replace(scratch.as_ref(), live.as_ref()))
+        };
+        let hook = |cmd: &str| describe(Command::new("sh").arg("-c").arg(cmd));
+
+        let mut commands = vec![];
+        if let Some(cmd) = &self.pre_deploy_cmd {
+            commands.push(hook(cmd));
+        }
+        if self.build_before_up {
+            commands.push(compose(&["build", "--pull"])?);
+            if self.push_image {
+                let registry = config
+                    .registry
+                    .as_deref()
+                    .ok_or(ServiceError::Registry)?
+                    .trim_end_matches('/');
+                let commit = commit.ok_or(ServiceError::Push)?;
+                for (local, remote) in self.built_images(scratch_path, registry, commit)? {
+                    let mut docker = self.docker();
+                    docker.args(["tag", &local, &remote]);
+                    commands.push(describe(&docker));
+                    let mut docker = self.docker();
+                    docker.args(["push", &remote]);
+                    commands.push(describe(&docker));
+                }
+            }
+        }
+        if running {
+            commands.push(compose(&["stop"])?);
+        }
+        commands.push(compose(&["up", "-d"])?);
+        if let Some(cmd) = &self.post_deploy_cmd {
+            commands.push(hook(cmd));
+        }
+        Ok(commands)
+    }
+
+    // empty when the deploy wouldn't change the compose file or its override
+    fn compose_diff(
+        &self,
+        config: &Config,
+        live_path: &Path,
+        scratch_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<String, ServiceError> {
+        let compose_path = self.compose_path(scratch_path)?;
+        let relative = compose_path
+            .strip_prefix(scratch_path)
+            .map_err(|_| ServiceError::ComposePath)?;
+        let files = [
+            relative.to_path_buf(),
+            relative.with_file_name(OVERRIDE_FILE),
+        ];
+
+        let mut diff = String::new();
+        for file in files {
+            let (live, deploy) = (live_path.join(&file), scratch_path.join(&file));
+            if !live.is_file() && !deploy.is_file() {
+                continue;
+            }
+            let name = file.to_string_lossy();
+            let mut command = Command::new("diff");
+            command
+                .arg("-uN")
+                .args(["--label", &format!("live/{}", name)])
+                .args(["--label", &format!("deploy/{}", name)])
+                .arg(live)
+                .arg(deploy);
+            let output = process::output(
+                command,
+                process::Limit {
+                    step: "diff",
+                    timeout: config.timeouts.copy,
+                    cancel,
+                },
+            )?;
+            // diff exits 1 when the files differ and 2 on trouble
+            if output.status.code() == Some(2) {
+                return Err(ServiceError::Unexpected);
+            }
+            diff.push_str(&String::from_utf8_lossy(&output.stdout));
+        }
+        Ok(diff)
+    }
+}
@@ -0,0 +1,36 @@
+use super::Service;
+
+const SUFFIX: &str = "-copy";
+
+// a new service configured like `source`, under a name none of `taken` has; runtime state
+// isn't copied, and neither is the environment, since a repo is registered once per
+// environment and a twin usually goes in another one
+pub fn duplicate(source: &Service, taken: &[String]) -> Service {
+    Service {
+        id: 0,
+        name: copy_name(&source.name, taken),
+        environment: None,
+        archived: false,
+        preview_of: None,
+        last_commit: None,
+        last_status: None,
+        last_status_detail: None,
+        last_status_at: None,
+        ..source.clone()
+    }
+}
+
+// "web-copy", then "web-copy-2" and so on, shortening the original to stay a valid name
+fn copy_name(name: &str, taken: &[String]) -> String {
+    (1..)
+        .map(|n| {
+            let suffix = match n {
+                1 => SUFFIX.to_string(),
+                n => format!("{}-{}", SUFFIX, n),
+            };
+            let keep = name.len().min(64 - suffix.len());
+            format!("{}{}", &name[..keep], suffix)
+        })
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules::secrets::{MASK, SecretError, SecretsKey};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServiceEnv {
+    pub id: i64,
+    pub key: String,
+    pub value: String,
+    pub secret: bool,
+}
+
+impl ServiceEnv {
+    // compose interpolation only understands shell-style variable names
+    pub fn valid_key(key: &str) -> bool {
+        let mut chars = key.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        }
+    }
+
+    // what the UI is allowed to show
+    pub fn display_value(&self) -> &str {
+        match self.secret {
+            true => MASK,
+            false => &self.value,
+        }
+    }
+
+    // single quotes keep the value literal; a value holding one is double-quoted instead, with
+    // `$` doubled so compose doesn't interpolate it
+    fn to_line(&self) -> String {
+        match self.value.contains('\'') {
+            false => format!("{}='{}'\n", self.key, self.value),
+            true => format!(
+                "{}=\"{}\"\n",
+                self.key,
+                self.value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('$', "$$")
+            ),
+        }
+    }
+}
+
+// decrypts secret values for writing to disk at deploy time
+pub fn reveal(
+    vars: Vec<ServiceEnv>,
+    key: Option<&SecretsKey>,
+) -> Result<Vec<ServiceEnv>, SecretError> {
+    vars.into_iter()
+        .map(|var| match var.secret {
+            false => Ok(var),
+            true => {
+                let value = key.ok_or(SecretError::NoKey)?.decrypt(&var.value)?;
+                Ok(ServiceEnv { value, ..var })
+            }
+        })
+        .collect()
+}
+
+// under the live dir, holding a file per secret for compose to mount as a docker secret
+pub const SECRETS_DIR: &str = ".secrets";
+
+// overwrites `.env` in the given directory; readable only by the wraut user
+pub fn write_env_file(dir: &Path, vars: &[ServiceEnv]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(dir.join(".env"))?;
+
+    for var in vars {
+        file.write_all(var.to_line().as_bytes())?;
+    }
+
+    Ok(())
+}
+
+// writes each var to a file named for its key under SECRETS_DIR in the given directory. Only
+// the wraut user can enter the directory; the files themselves are left readable so that a
+// container running as any user can read what compose mounts at /run/secrets/<key>
+pub fn write_secret_files(dir: &Path, vars: &[&ServiceEnv]) -> std::io::Result<()> {
+    if vars.is_empty() {
+        return Ok(());
+    }
+    let secrets = dir.join(SECRETS_DIR);
+    std::fs::create_dir_all(&secrets)?;
+    std::fs::set_permissions(&secrets, std::fs::Permissions::from_mode(0o700))?;
+
+    for var in vars {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o444)
+            .open(secrets.join(&var.key))?;
+        file.write_all(var.value.as_bytes())?;
+    }
+
+    Ok(())
+}
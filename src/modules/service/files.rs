@@ -0,0 +1,77 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use walkdir::WalkDir;
+
+use super::{ServiceError, process::Limit};
+
+// how often a copy reports how far it has got
+const PROGRESS_EVERY: Duration = Duration::from_millis(250);
+
+fn remove_error(path: &Path, e: impl std::fmt::Display) -> ServiceError {
+    ServiceError::Remove(format!("{} | {}", path.display(), e))
+}
+
+fn copy_error(path: &Path, e: impl std::fmt::Display) -> ServiceError {
+    ServiceError::Copy(format!("{} | {}", path.display(), e))
+}
+
+// removes a directory and everything in it; one that's already gone is fine
+pub fn remove(dir: &Path) -> Result<(), ServiceError> {
+    match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(remove_error(dir, e)),
+        _ => Ok(()),
+    }
+}
+
+// copies what's inside `from` into `to` like `cp -a`, keeping links as links and
+// permissions as they were; `progress` hears files copied out of the total now and then
+pub fn copy(
+    from: &Path,
+    to: &Path,
+    limit: Limit,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<u64, ServiceError> {
+    let started = Instant::now();
+    let total = WalkDir::new(from)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .count() as u64;
+
+    let mut copied = 0;
+    let mut reported = Instant::now();
+    for entry in WalkDir::new(from).min_depth(1) {
+        limit.check(started)?;
+        let entry = entry.map_err(|e| copy_error(from, e))?;
+        let source = entry.path();
+        let relative = source
+            .strip_prefix(from)
+            .map_err(|e| copy_error(source, e))?;
+        let target = to.join(relative);
+        let file_type = entry.file_type();
+
+        let result = if file_type.is_dir() {
+            std::fs::create_dir_all(&target).and_then(|_| {
+                let permissions = entry.metadata()?.permissions();
+                std::fs::set_permissions(&target, permissions)
+            })
+        } else if file_type.is_symlink() {
+            std::fs::read_link(source).and_then(|link| std::os::unix::fs::symlink(link, &target))
+        } else {
+            std::fs::copy(source, &target).map(|_| ())
+        };
+        result.map_err(|e| copy_error(source, e))?;
+
+        if !file_type.is_dir() {
+            copied += 1;
+        }
+        if reported.elapsed() >= PROGRESS_EVERY {
+            progress(copied, total);
+            reported = Instant::now();
+        }
+    }
+    Ok(copied)
+}
@@ -1,221 +1,973 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use askama::Template;
 use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+
+use crate::modules::{
+    Config, HTMLTarget, ServiceHTML,
+    agent::Node,
+    assets,
+    db::DBError,
+    deploy_key::DeployKey,
+    group::{Group, Summary},
+    markup::{Markup, render},
+    notify::{NotificationRule, email::EmailRecipient, push::PushTarget},
+    scheduler,
+    uptime::Uptime,
+    user::Role,
+};
+
+use super::{
+    BulkProgress, ContainerState, ContainerUsage, Deployment, DockerServiceEntry, Drift, Service,
+    ServiceError, ServiceFilter, ServiceStatus,
+    browse::{Browse, Contents},
+    compose_view::ComposeView,
+    env::ServiceEnv,
+    job::{JOB_MODES, JobRun, RunningJobs, ServiceJob},
+    preset::{PRESETS, Preset},
+    process::InFlight,
+    review::DeployReview,
+    volume::{self, Snapshot},
+};
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexTemplate {
+    htmx_src: String,
+    htmx_sse_src: String,
+    font_css_href: String,
+    csrf_token: String,
+    role: Role,
+    username: Option<String>,
+    groups: Vec<Group>,
+    environments: Vec<String>,
+    // only the matching services are listed
+    view: ListView,
+    statuses: [StatusFilter; 4],
+}
+
+// validation messages keyed by form field, shown beside the inputs they're about
+#[derive(Default)]
+pub struct FieldErrors(Vec<(&'static str, String)>);
+
+impl FieldErrors {
+    pub fn push(&mut self, field: &'static str, message: impl ToString) {
+        self.0.push((field, message.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, message)| message.as_str())
+    }
+}
+
+impl From<Vec<(&'static str, ServiceError)>> for FieldErrors {
+    fn from(errors: Vec<(&'static str, ServiceError)>) -> Self {
+        FieldErrors(
+            errors
+                .into_iter()
+                .map(|(field, e)| (field, e.to_string()))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Template)]
+#[template(path = "service/form.html")]
+struct NewFormTemplate {
+    // what was submitted, when the form comes back with errors
+    service: Service,
+    git_username: Option<String>,
+    errors: FieldErrors,
+    keys: Vec<DeployKey>,
+    selected: Option<i64>,
+    nodes: Vec<Node>,
+    node_selected: Option<i64>,
+    groups: Vec<Group>,
+    group_selected: Option<i64>,
+    presets: &'static [Preset],
+    // the preset the form was last filled from, and whether with its starter override
+    preset_selected: Option<&'static str>,
+    starter: bool,
+    // the service being duplicated, and whether its secrets are copied too
+    duplicate_of: Option<i64>,
+    copy_secrets: bool,
+}
+
+#[derive(Template)]
+#[template(path = "service/edit_form.html")]
+struct EditFormTemplate {
+    service: Service,
+    // the token itself is never sent back to the browser
+    git_username: Option<String>,
+    errors: FieldErrors,
+    keys: Vec<DeployKey>,
+    selected: Option<i64>,
+    nodes: Vec<Node>,
+    node_selected: Option<i64>,
+    groups: Vec<Group>,
+    group_selected: Option<i64>,
+    compose_names: Vec<String>,
+}
+
+struct ServiceRow {
+    service: Service,
+    status_class: &'static str,
+    status: ServiceStatus,
+    next_run: Option<String>,
+    containers: Vec<ContainerState>,
+    node: Option<Node>,
+    // when the status shown was recorded by a deploy rather than read from docker
+    status_at: Option<String>,
+    uptime: Uptime,
+    // a deploy is waiting for someone to review it
+    reviewing: bool,
+    // an automatic deploy is waiting for someone to approve it
+    approving: bool,
+    // when the deploy under way began
+    since: Option<DateTime<Utc>>,
+}
+
+impl ServiceRow {
+    fn new(service: Service, status_class: &'static str, status: ServiceStatus) -> Self {
+        let next_run = match service.active {
+            true => {
+                scheduler::next_run(&service).map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+            }
+            false => None,
+        };
+        ServiceRow {
+            service,
+            status_class,
+            status,
+            next_run,
+            containers: vec![],
+            node: None,
+            status_at: None,
+            uptime: Uptime::default(),
+            reviewing: false,
+            approving: false,
+            since: None,
+        }
+    }
+
+    // a failed deploy stays visible until the next one; a deploy's progress only while
+    // it's still running, since a restart abandons it
+    fn recorded(self, deploying: bool) -> Self {
+        let Some(status) = self.service.recorded_status() else {
+            return self;
+        };
+        let status_class = match status {
+            ref s if s.is_failure() => "error",
+            ServiceStatus::Stopped | ServiceStatus::TornDown => "unknown",
+            // a one-off job has no containers left to show its last run by
+            ServiceStatus::Completed => "success",
+            ServiceStatus::Running | ServiceStatus::Inactive | ServiceStatus::Degraded(_) => {
+                return self;
+            }
+            _ if deploying => "warning",
+            _ => return self,
+        };
+        ServiceRow {
+            status_class,
+            status_at: self.service.last_status_at.clone(),
+            status,
+            ..self
+        }
+    }
+
+    // shown as waiting until the review is approved or declined, whatever else happens
+    fn reviewing(self, reviewing: bool) -> Self {
+        match reviewing {
+            true => ServiceRow {
+                status_class: "warning",
+                status: ServiceStatus::AwaitingReview,
+                reviewing,
+                ..self
+            },
+            false => self,
+        }
+    }
+
+    fn approving(self, approving: bool) -> Self {
+        match approving {
+            true => ServiceRow {
+                status_class: "warning",
+                status: ServiceStatus::PendingApproval,
+                approving,
+                ..self
+            },
+            false => self,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "service/table.html")]
+struct TableTemplate {
+    sections: Vec<Section>,
+    role: Role,
+    error: Option<String>,
+    pager: Pager,
+    view: ListView,
+}
+
+const PAGE_SIZE: usize = 25;
+
+// the colour a row's status is shown in, most urgent first; the dashboard filters and
+// groups by it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusFilter {
+    Failed,
+    Warning,
+    Running,
+    Down,
+}
+
+impl StatusFilter {
+    pub const ALL: [StatusFilter; 4] = [
+        StatusFilter::Failed,
+        StatusFilter::Warning,
+        StatusFilter::Running,
+        StatusFilter::Down,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusFilter::Failed => "failed",
+            StatusFilter::Warning => "warning",
+            StatusFilter::Running => "running",
+            StatusFilter::Down => "down",
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match self {
+            StatusFilter::Failed => "Failing",
+            StatusFilter::Warning => "Pending",
+            StatusFilter::Running => "Healthy",
+            StatusFilter::Down => "Stopped or unknown",
+        }
+    }
+
+    fn of(row: &ServiceRow) -> Self {
+        match row.status_class {
+            "error" => StatusFilter::Failed,
+            "warning" => StatusFilter::Warning,
+            "success" => StatusFilter::Running,
+            _ => StatusFilter::Down,
+        }
+    }
+}
+
+impl fmt::Display for StatusFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StatusFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StatusFilter::ALL
+            .into_iter()
+            .find(|f| f.as_str() == s)
+            .ok_or_else(|| format!("Unknown status filter '{}'", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    Id,
+    Name,
+    Repo,
+    Url,
+    Status,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 5] = [
+        SortKey::Id,
+        SortKey::Name,
+        SortKey::Repo,
+        SortKey::Url,
+        SortKey::Status,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Id => "id",
+            SortKey::Name => "name",
+            SortKey::Repo => "repo",
+            SortKey::Url => "url",
+            SortKey::Status => "status",
+        }
+    }
+}
+
+// a column to sort each section's rows by; written `name`, or `-name` for descending
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    fn compare(&self, a: &ServiceRow, b: &ServiceRow) -> Ordering {
+        let lower = |s: &str| s.to_lowercase();
+        let ordering = match self.key {
+            SortKey::Id => a.service.id.cmp(&b.service.id),
+            SortKey::Name => lower(&a.service.name).cmp(&lower(&b.service.name)),
+            SortKey::Repo => lower(&a.service.repo_url).cmp(&lower(&b.service.repo_url)),
+            SortKey::Url => lower(&a.service.access_url).cmp(&lower(&b.service.access_url)),
+            SortKey::Status => urgency(a)
+                .cmp(&urgency(b))
+                .then_with(|| lower(&a.service.name).cmp(&lower(&b.service.name))),
+        };
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.descending {
+            f.write_str("-")?;
+        }
+        f.write_str(self.key.as_str())
+    }
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (descending, key) = match s.strip_prefix('-') {
+            Some(key) => (true, key),
+            None => (false, s),
+        };
+        SortKey::ALL
+            .into_iter()
+            .find(|k| k.as_str() == key)
+            .map(|key| Sort { key, descending })
+            .ok_or_else(|| format!("Unknown sort '{}'", s))
+    }
+}
+
+fn urgency(row: &ServiceRow) -> usize {
+    let status = StatusFilter::of(row);
+    StatusFilter::ALL
+        .iter()
+        .position(|s| *s == status)
+        .unwrap_or_default()
+}
+
+// the services a dashboard lists, narrowed further by what docker reports, in sections
+// by group or by status and shown a page at a time; pages count from 1
+#[derive(Clone, Debug, Default)]
+pub struct ListView {
+    pub filter: ServiceFilter,
+    pub status: Option<StatusFilter>,
+    pub sort: Option<Sort>,
+    pub by_status: bool,
+    pub page: usize,
+}
+
+impl ListView {
+    // the query string that shows this view again, on the dashboard or its event stream
+    pub fn query(&self) -> String {
+        let filter = &self.filter;
+        let pairs: Vec<(&str, String)> = [
+            ("group", filter.group.map(|g| g.to_string())),
+            ("environment", filter.environment.clone()),
+            ("search", filter.search.clone()),
+            ("status", self.status.map(|s| s.to_string())),
+            ("sort", self.sort.map(|s| s.to_string())),
+            ("by_status", self.by_status.then(|| "true".to_string())),
+            ("page", (self.page > 1).then(|| self.page.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| (name, v)))
+        .collect();
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+
+    fn page_href(&self, page: usize) -> String {
+        let view = ListView {
+            page,
+            ..self.clone()
+        };
+        format!("/?{}", view.query())
+    }
+
+    // a column's header sorts by it, or reverses the sort when it's already ascending
+    fn sort_href(&self, key: SortKey) -> String {
+        let descending = self.sort
+            == Some(Sort {
+                key,
+                descending: false,
+            });
+        let view = ListView {
+            sort: Some(Sort { key, descending }),
+            page: 1,
+            ..self.clone()
+        };
+        format!("/?{}", view.query())
+    }
+
+    fn sort_arrow(&self, key: SortKey) -> &'static str {
+        match self.sort {
+            Some(sort) if sort.key == key && sort.descending => " ▼",
+            Some(sort) if sort.key == key => " ▲",
+            _ => "",
+        }
+    }
+}
+
+// the page shown out of how many
+struct Pager {
+    page: usize,
+    pages: usize,
+    total: usize,
+}
+
+// keeps the rows of the requested page, in the order the sections show them
+fn paginate(sections: Vec<Section>, requested: usize) -> (Vec<Section>, Pager) {
+    let total: usize = sections.iter().map(|s| s.rows.len()).sum();
+    let pages = total.div_ceil(PAGE_SIZE).max(1);
+    let page = requested.clamp(1, pages);
+    let mut skip = (page - 1) * PAGE_SIZE;
+    let mut take = PAGE_SIZE;
+    let sections = sections
+        .into_iter()
+        .filter_map(|section| {
+            let start = skip.min(section.rows.len());
+            skip -= start;
+            let rows: Vec<ServiceRow> = section.rows.into_iter().skip(start).take(take).collect();
+            take -= rows.len();
+            (!rows.is_empty()).then_some(Section { rows, ..section })
+        })
+        .collect();
+    (sections, Pager { page, pages, total })
+}
+
+// a group's or status's rows under its header; ungrouped services come last
+struct Section {
+    group: Option<Group>,
+    status: Option<StatusFilter>,
+    // None when docker couldn't be queried
+    summary: Option<Summary>,
+    rows: Vec<ServiceRow>,
+}
+
+impl Section {
+    fn new(group: Option<Group>, status: Option<StatusFilter>) -> Self {
+        Section {
+            group,
+            status,
+            summary: None,
+            rows: vec![],
+        }
+    }
+}
+
+fn sections(
+    rows: Vec<ServiceRow>,
+    groups: &[Group],
+    containers: Option<&[DockerServiceEntry]>,
+    view: &ListView,
+) -> Vec<Section> {
+    let mut sections: Vec<Section> = match view.by_status {
+        true => StatusFilter::ALL
+            .into_iter()
+            .map(|status| Section::new(None, Some(status)))
+            .collect(),
+        false => groups
+            .iter()
+            .map(|group| Section::new(Some(group.clone()), None))
+            .chain([Section::new(None, None)])
+            .collect(),
+    };
+    for row in rows {
+        let index = match view.by_status {
+            true => urgency(&row),
+            // services whose group is unknown fall through to the ungrouped section
+            false => sections
+                .iter()
+                .position(|s| s.group.as_ref().map(|g| g.id) == row.service.group_id)
+                .unwrap_or(sections.len() - 1),
+        };
+        sections[index].rows.push(row);
+    }
+
+    sections.retain(|s| !s.rows.is_empty());
+    for section in sections.iter_mut() {
+        match view.sort {
+            Some(sort) => section.rows.sort_by(|a, b| sort.compare(a, b)),
+            // production first, then staging, other environments and services without one
+            None => section
+                .rows
+                .sort_by(|a, b| environment_rank(&a.service).cmp(&environment_rank(&b.service))),
+        }
+    }
+    for section in sections.iter_mut() {
+        section.summary =
+            containers.map(|c| Summary::of(section.rows.iter().map(|r| &r.service), c));
+    }
+    sections
+}
+
+fn environment_rank(service: &Service) -> (u8, Option<&str>) {
+    match service.environment.as_deref() {
+        Some("production") => (0, None),
+        Some("staging") => (1, None),
+        Some(other) => (2, Some(other)),
+        None => (3, None),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "service/status.html")]
+struct StatusTemplate {
+    status: ServiceStatus,
+    since: Option<DateTime<Utc>>,
+}
+
+#[derive(Template)]
+#[template(path = "service/usage.html")]
+struct UsageTemplate {
+    lines: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "service/connected.html")]
+struct ConnectedTemplate;
+
+#[derive(Template)]
+#[template(path = "service/reset_button.html")]
+struct ResetButtonTemplate {
+    role: Role,
+}
+
+#[derive(Template)]
+#[template(path = "service/shutdown.html")]
+struct ShutdownTemplate;
+
+#[derive(Template)]
+#[template(path = "service/env_editor.html")]
+struct EnvEditorTemplate {
+    service: Service,
+    env_vars: Vec<ServiceEnv>,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "service/review.html")]
+struct ReviewTemplate {
+    service: Service,
+    review: Option<DeployReview>,
+}
+
+#[derive(Template)]
+#[template(path = "service/notifications.html")]
+struct NotificationsTemplate {
+    service: Service,
+    recipients: Vec<EmailRecipient>,
+    targets: Vec<PushTarget>,
+    rules: Vec<NotificationRule>,
+    // whether SMTP and Pushover are configured at all
+    smtp: bool,
+    pushover: bool,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "service/hook.html")]
+struct HookTemplate {
+    service: Service,
+    created_at: Option<String>,
+    // only right after it's generated; the database keeps its hash
+    token: Option<String>,
+    message: Option<String>,
+}
+
+// a job with what the panel shows beside it
+struct JobRow {
+    job: ServiceJob,
+    next_run: Option<String>,
+    running: bool,
+}
+
+#[derive(Template)]
+#[template(path = "service/jobs.html")]
+struct JobsTemplate {
+    service: Service,
+    jobs: Vec<JobRow>,
+    runs: Vec<JobRun>,
+    modes: [&'static str; 2],
+    error: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "service/deployments.html")]
+struct DeploymentsTemplate {
+    service: Service,
+    deployments: Vec<Deployment>,
+}
+
+#[derive(Template)]
+#[template(path = "service/volumes.html")]
+struct VolumesTemplate {
+    service: Service,
+    snapshots: Vec<Snapshot>,
+}
+
+#[derive(Template)]
+#[template(path = "service/terminal.html")]
+struct TerminalTemplate {
+    service: Service,
+    containers: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "service/compose.html")]
+struct ComposeTemplate {
+    service: Service,
+    compose: ComposeView,
+    message: Option<String>,
+    saved: bool,
+}
+
+#[derive(Template)]
+#[template(path = "service/files.html")]
+struct FilesTemplate {
+    service: Service,
+    browse: Browse,
+}
+
+#[derive(Template)]
+#[template(path = "service/message.html")]
+struct MessageTemplate {
+    class: &'static str,
+    text: String,
+}
+
+#[derive(Template)]
+#[template(path = "service/archived_list.html")]
+struct ArchivedListTemplate {
+    services: Vec<Service>,
+    error: Option<String>,
+}
+
+pub fn page(
+    csrf_token: &str,
+    role: Role,
+    username: Option<String>,
+    groups: Vec<Group>,
+    environments: Vec<String>,
+    view: ListView,
+) -> Markup {
+    render(&IndexTemplate {
+        htmx_src: assets::url(assets::HTMX, assets::HTMX_CDN),
+        htmx_sse_src: assets::url(assets::HTMX_SSE, assets::HTMX_SSE_CDN),
+        font_css_href: assets::font_css_url(),
+        csrf_token: csrf_token.to_string(),
+        role,
+        username,
+        groups,
+        environments,
+        view,
+        statuses: StatusFilter::ALL,
+    })
+}
+
+// what a service form needs to offer besides the service itself
+pub struct FormChoices {
+    pub keys: Vec<DeployKey>,
+    pub nodes: Vec<Node>,
+    pub groups: Vec<Group>,
+    // defined by the compose file of the service's clone, when it has one
+    pub compose_names: Vec<String>,
+}
+
+// what a new-service form was filled from
+#[derive(Clone, Copy)]
+pub enum Prefill {
+    Blank,
+    // with or without its starter override
+    Preset(&'static Preset, bool),
+    // another service's configuration, with or without its secret env vars
+    Duplicate(i64, bool),
+}
+
+pub fn new_form(
+    service: Service,
+    git_username: Option<String>,
+    errors: FieldErrors,
+    choices: FormChoices,
+    prefill: Prefill,
+) -> Markup {
+    let FormChoices {
+        keys,
+        nodes,
+        groups,
+        ..
+    } = choices;
+    render(&NewFormTemplate {
+        selected: service.deploy_key_id,
+        node_selected: service.node_id,
+        group_selected: service.group_id,
+        service,
+        git_username,
+        errors,
+        keys,
+        nodes,
+        groups,
+        presets: &PRESETS,
+        preset_selected: match prefill {
+            Prefill::Preset(preset, _) => Some(preset.slug),
+            _ => None,
+        },
+        starter: !matches!(prefill, Prefill::Preset(_, false)),
+        duplicate_of: match prefill {
+            Prefill::Duplicate(id, _) => Some(id),
+            _ => None,
+        },
+        copy_secrets: matches!(prefill, Prefill::Duplicate(_, true)),
+    })
+}
 
-use crate::modules::{HTMLTarget, ServiceHTML, db::DBError};
+pub fn edit_form(
+    service: Service,
+    git_username: Option<String>,
+    errors: FieldErrors,
+    choices: FormChoices,
+) -> Markup {
+    let FormChoices {
+        keys,
+        nodes,
+        groups,
+        compose_names,
+    } = choices;
+    let selected = service.deploy_key_id;
+    let node_selected = service.node_id;
+    let group_selected = service.group_id;
+    render(&EditFormTemplate {
+        service,
+        git_username,
+        errors,
+        keys,
+        selected,
+        nodes,
+        node_selected,
+        groups,
+        group_selected,
+        compose_names,
+    })
+}
 
-use super::{DockerServiceEntry, Service, ServiceError, ServiceStatus};
+fn services_table(status_class: &str, status_string: &str, table: TableTemplate) -> ServiceHTML {
+    ServiceHTML {
+        status_class: status_class.to_string(),
+        status_string: status_string.to_string(),
+        html_targets: vec![HTMLTarget {
+            id: "services-list".to_string(),
+            element: "table".to_string(),
+            class: None,
+            html_content: render(&table),
+        }],
+    }
+}
+
+// what's under way for each service besides its containers, and how it's been up
+pub struct Activity<'a> {
+    pub in_flight: &'a InFlight,
+    // services with a deploy waiting for review
+    pub reviews: &'a [i64],
+    // services with an automatic deploy waiting for approval
+    pub approvals: &'a [i64],
+    pub uptime: &'a HashMap<i64, Uptime>,
+}
 
 pub fn list(
     db_list: Result<Vec<Service>, DBError>,
     docker_list: Result<Vec<DockerServiceEntry>, ServiceError>,
+    nodes: &[Node],
+    groups: &[Group],
+    activity: Activity,
+    role: Role,
+    view: &ListView,
 ) -> ServiceHTML {
+    let node = |service: &Service| {
+        service
+            .node_id
+            .and_then(|id| nodes.iter().find(|n| n.id == id).cloned())
+    };
+    let with_uptime = |row: ServiceRow| ServiceRow {
+        uptime: activity
+            .uptime
+            .get(&row.service.id)
+            .cloned()
+            .unwrap_or_default(),
+        ..row
+    };
+    let table = |rows: Vec<ServiceRow>, containers: Option<&[DockerServiceEntry]>, error| {
+        let rows = rows
+            .into_iter()
+            .filter(|row| view.status.is_none_or(|s| s == StatusFilter::of(row)))
+            .map(with_uptime)
+            .collect();
+        let (sections, pager) = paginate(sections(rows, groups, containers, view), view.page);
+        TableTemplate {
+            sections,
+            role,
+            error,
+            pager,
+            view: view.clone(),
+        }
+    };
     match db_list {
         Ok(dbl) => match docker_list {
-            Ok(dkl) => ServiceHTML {
-                status_class: "success".to_string(),
-                status_string: "Services found".to_string(),
-                html_targets: vec![HTMLTarget {
-                    id: "services-list".to_string(),
-                    element: "table".to_string(),
-                    class: None,
-                    html_content: format!(
-                        "
-                        <tr>
-                            <th>ID</th>
-                            <th>Name</th>
-                            <th>Repo</th>
-                            <th>URL</th>
-                            <th>Active</th>
-                            <th>Status</th>
-                            <th style=\"display:flex; justify-content:center;\">Actions</th>
-                        </tr>
-                        {}",
-                        dbl.iter()
-                            .map(|dbe| {
-                                format!(
-                                    "
-                            <tr>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td><div id=\"service-{}-status\" class=\"{}-chip\">{}</div></td>
-                                <td style=\"display:flex; justify-content: center;\">
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-get=\"/api/service/{}/deploy\"
-                                    >
-                                        &#127744;
-                                    </span>
-                                    &nbsp;
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-get=\"/api/service/{}/deactivate\"
-                                        hx-confirm=\"Are you sure you want to deactivate {}?\"
-                                    >
-                                        &#8631;
-                                    </span>
-                                    &nbsp;
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-delete=\"/api/service/{}\"
-                                        hx-confirm=\"Are you sure you want to delete {}?\"
-                                    >
-                                        &#128163;
-                                    </span>
-                                </td>
-                            </tr>
-                        ",
-                                    dbe.id,
-                                    dbe.name,
-                                    dbe.repo_url,
-                                    dbe.access_url,
-                                    dbe.active,
-                                    dbe.id,
-                                    match dbe.is_running(&dkl) {
-                                        true => "success",
-                                        false => "unknown",
-                                    },
-                                    match dbe.is_running(&dkl) {
-                                        true => ServiceStatus::Running.to_string(),
-                                        false => ServiceStatus::Inactive.to_string(),
-                                    },
-                                    dbe.id,
-                                    dbe.id,
-                                    dbe.name,
-                                    dbe.id,
-                                    dbe.name,
-                                )
-                            })
-                            .collect::<String>(),
-                    ),
-                }],
-            },
-            Err(e) => ServiceHTML {
-                status_class: "warning".to_string(),
-                status_string: "Services status unknown".to_string(),
-                html_targets: vec![HTMLTarget {
-                    id: "services-list".to_string(),
-                    element: "table".to_string(),
-                    class: None,
-                    html_content: format!(
-                        "
-                        <tr>
-                            <th>ID</th>
-                            <th>Name</th>
-                            <th>Repo</th>
-                            <th>URL</th>
-                            <th>Active</th>
-                            <th>Status</th>
-                            <th>Actions</th>
-                        </tr>
-                        {}
-                        <tr><td colspan=\"6\" class=\"error-chip\">{}</td></tr>",
-                        dbl.iter()
-                            .map(|dbe| {
-                                format!(
-                                    "
-                            <tr>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td>{}</td>
-                                <td><div id=\"service-{}-status\" class=\"unknown-chip\">{}</div></td>
-                                <td style=\"display:flex; justify-content: center;\">
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-get=\"/api/service/{}/deploy\"
-                                    >
-                                        &#127744;
-                                    </span>
-                                    &nbsp;
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-get=\"/api/service/{}/deactivate\"
-                                        hx-confirm=\"Are you sure you want to deactivate {}?\"
-                                    >
-                                        &#8631;
-                                    </span>
-                                    &nbsp;
-                                    <span
-                                        style=\"cursor:pointer;\"
-                                        hx-delete=\"/api/service/{}\"
-                                        hx-confirm=\"Are you sure you want to delete {}?\"
-                                    >
-                                        &#128163;
-                                    </span>
-                                </td>
-                            </tr>
-                        ",
-                                    dbe.id,
-                                    dbe.name,
-                                    dbe.repo_url,
-                                    dbe.access_url,
-                                    dbe.active,
-                                    dbe.id,
-                                    ServiceStatus::Unknown.to_string(),
-                                    dbe.id,
-                                    dbe.id,
-                                    dbe.name,
-                                    dbe.id,
-                                    dbe.name,
-                                )
-                            })
-                            .collect::<String>(),
-                        e,
-                    ),
-                }],
-            },
+            Ok(dkl) => {
+                let rows = dbl
+                    .into_iter()
+                    .map(|service| {
+                        let node = node(&service);
+                        // nothing is known about containers on a disconnected node
+                        if node.as_ref().is_some_and(|n| !n.connected) {
+                            return ServiceRow {
+                                node,
+                                ..ServiceRow::new(service, "unknown", ServiceStatus::Unknown)
+                            };
+                        }
+                        let status = service.container_status(&dkl);
+                        let containers = service.containers(&dkl);
+                        let class = match status {
+                            ServiceStatus::Running => "success",
+                            ServiceStatus::Degraded(_) => "warning",
+                            _ => "unknown",
+                        };
+                        let deploying = activity.in_flight.contains(service.id);
+                        let reviewing = activity.reviews.contains(&service.id);
+                        let approving = activity.approvals.contains(&service.id);
+                        let since = activity.in_flight.since(service.id);
+                        ServiceRow {
+                            containers,
+                            node,
+                            since,
+                            ..ServiceRow::new(service, class, status)
+                        }
+                        .recorded(deploying)
+                        .reviewing(reviewing)
+                        .approving(approving)
+                    })
+                    .collect();
+                services_table("success", "Services found", table(rows, Some(&dkl), None))
+            }
+            Err(e) => {
+                let rows = dbl
+                    .into_iter()
+                    .map(|service| ServiceRow::new(service, "unknown", ServiceStatus::Unknown))
+                    .collect();
+                services_table(
+                    "warning",
+                    "Services status unknown",
+                    table(rows, None, Some(e.to_string())),
+                )
+            }
         },
-        Err(e) => ServiceHTML {
-            status_class: "error".to_string(),
-            status_string: "Database error".to_string(),
-            html_targets: vec![HTMLTarget {
-                id: "services-list".to_string(),
-                element: "table".to_string(),
-                class: None,
-                html_content: format!(
-                    "
-                    <tr><td class=\"error-chip\">Unable to retrieve services from database. | {} </td></tr>
-                ",
+        Err(e) => services_table(
+            "error",
+            "Database error",
+            table(
+                vec![],
+                None,
+                Some(format!(
+                    "Unable to retrieve services from database. | {}",
                     e
-                ),
-            }],
-        },
+                )),
+            ),
+        ),
     }
 }
 
-pub fn reset_button() -> Event {
-    Event::default().event("service_event").data(
-        "
-        <div id=\"link-status\" class=\"success-chip\">Connected</div>
-        <div
-            id=\"add-service-btn\"
-            style=\"margin:12px;border-radius:4px;cursor:pointer;\"
-            class=\"success-chip\"
-            hx-get=\"/html/service_form\"
-            hx-swap=\"outerHTML\"
-            hx-swap-oob=\"true\"
-        >
-            + Add service
-        </div>",
-    )
+pub fn connected() -> Event {
+    Event::default()
+        .event("service_event")
+        .data(render(&ConnectedTemplate).into_string())
+}
+
+pub fn reset_button(role: Role) -> Event {
+    Event::default()
+        .event("service_event")
+        .data(render(&ResetButtonTemplate { role }).into_string())
+}
+
+pub fn shutdown() -> Event {
+    Event::default()
+        .event("service_event")
+        .data(render(&ShutdownTemplate).into_string())
 }
 
 fn app_status_class(status: &ServiceStatus) -> String {
     match status {
         ServiceStatus::Unknown => "unknown".to_string(),
-        ServiceStatus::Running | ServiceStatus::Inactive => "success".to_string(),
+        ServiceStatus::Running
+        | ServiceStatus::Completed
+        | ServiceStatus::Inactive
+        | ServiceStatus::Stopped
+        | ServiceStatus::TornDown
+        | ServiceStatus::Declined => "success".to_string(),
+        ServiceStatus::Degraded(_) => "warning".to_string(),
         ServiceStatus::DiscoveryFailed
         | ServiceStatus::CommandFailed(_)
-        | ServiceStatus::CloneOrPullFailed => "error".to_string(),
+        | ServiceStatus::CloneOrPullFailed
+        | ServiceStatus::RolledBack
+        | ServiceStatus::Cancelled
+        | ServiceStatus::CrashLooping => "error".to_string(),
         ServiceStatus::Cloning
         | ServiceStatus::Pulling
+        | ServiceStatus::CheckingOut(_)
         | ServiceStatus::Stopping
         | ServiceStatus::Starting
+        | ServiceStatus::Restarting
+        | ServiceStatus::TearingDown
         | ServiceStatus::Copying
+        | ServiceStatus::CopyProgress(..)
         | ServiceStatus::RewritingConfig
+        | ServiceStatus::RunningPreDeploy
+        | ServiceStatus::RunningScript(_)
+        | ServiceStatus::BackingUpVolumes
+        | ServiceStatus::RestoringVolumes
+        | ServiceStatus::Building
+        | ServiceStatus::Pushing
+        | ServiceStatus::CheckingHealth
+        | ServiceStatus::RollingBack
+        | ServiceStatus::RunningPostStartExec
+        | ServiceStatus::RunningOneOff
+        | ServiceStatus::SmokeTesting
+        | ServiceStatus::RunningPostDeploy
+        | ServiceStatus::HookOutput(_)
+        | ServiceStatus::Retrying(..)
+        | ServiceStatus::Queued
+        | ServiceStatus::AwaitingReview
+        | ServiceStatus::PendingApproval
         | ServiceStatus::DeploymentRequested => "warning".to_string(),
     }
 }
@@ -223,14 +975,39 @@ fn app_status_class(status: &ServiceStatus) -> String {
 fn app_status_name(status: &ServiceStatus) -> String {
     match status {
         ServiceStatus::Unknown => "Service unknown".to_string(),
+        ServiceStatus::Degraded(_) => "Service degraded".to_string(),
         ServiceStatus::DiscoveryFailed
         | ServiceStatus::CommandFailed(_)
-        | ServiceStatus::CloneOrPullFailed => "Service failure".to_string(),
+        | ServiceStatus::CloneOrPullFailed
+        | ServiceStatus::RolledBack
+        | ServiceStatus::Cancelled
+        | ServiceStatus::CrashLooping => "Service failure".to_string(),
         ServiceStatus::Cloning
         | ServiceStatus::Pulling
+        | ServiceStatus::CheckingOut(_)
         | ServiceStatus::Stopping
         | ServiceStatus::Starting
+        | ServiceStatus::Restarting
+        | ServiceStatus::TearingDown
         | ServiceStatus::Copying
+        | ServiceStatus::CopyProgress(..)
+        | ServiceStatus::RunningPreDeploy
+        | ServiceStatus::RunningScript(_)
+        | ServiceStatus::BackingUpVolumes
+        | ServiceStatus::RestoringVolumes
+        | ServiceStatus::Building
+        | ServiceStatus::Pushing
+        | ServiceStatus::CheckingHealth
+        | ServiceStatus::RollingBack
+        | ServiceStatus::RunningPostStartExec
+        | ServiceStatus::RunningOneOff
+        | ServiceStatus::SmokeTesting
+        | ServiceStatus::RunningPostDeploy
+        | ServiceStatus::HookOutput(_)
+        | ServiceStatus::Retrying(..)
+        | ServiceStatus::Queued
+        | ServiceStatus::AwaitingReview
+        | ServiceStatus::PendingApproval
         | ServiceStatus::DeploymentRequested => "Service pending...".to_string(),
         _ => "Connected".to_string(),
     }
@@ -238,26 +1015,56 @@ fn app_status_name(status: &ServiceStatus) -> String {
 
 fn service_class_name(status: &ServiceStatus) -> String {
     match status {
-        ServiceStatus::Unknown | ServiceStatus::Inactive => "unknown".to_string(),
-        ServiceStatus::Running => "success".to_string(),
+        ServiceStatus::Unknown
+        | ServiceStatus::Inactive
+        | ServiceStatus::Stopped
+        | ServiceStatus::TornDown
+        | ServiceStatus::Declined => "unknown".to_string(),
+        ServiceStatus::Running | ServiceStatus::Completed => "success".to_string(),
+        ServiceStatus::Degraded(_) => "warning".to_string(),
         ServiceStatus::DiscoveryFailed
         | ServiceStatus::CommandFailed(_)
-        | ServiceStatus::CloneOrPullFailed => "error".to_string(),
+        | ServiceStatus::CloneOrPullFailed
+        | ServiceStatus::RolledBack
+        | ServiceStatus::Cancelled
+        | ServiceStatus::CrashLooping => "error".to_string(),
         ServiceStatus::Cloning
         | ServiceStatus::Pulling
+        | ServiceStatus::CheckingOut(_)
         | ServiceStatus::Stopping
         | ServiceStatus::Starting
+        | ServiceStatus::Restarting
+        | ServiceStatus::TearingDown
         | ServiceStatus::Copying
+        | ServiceStatus::CopyProgress(..)
         | ServiceStatus::RewritingConfig
+        | ServiceStatus::RunningPreDeploy
+        | ServiceStatus::RunningScript(_)
+        | ServiceStatus::BackingUpVolumes
+        | ServiceStatus::RestoringVolumes
+        | ServiceStatus::Building
+        | ServiceStatus::Pushing
+        | ServiceStatus::CheckingHealth
+        | ServiceStatus::RollingBack
+        | ServiceStatus::RunningPostStartExec
+        | ServiceStatus::RunningOneOff
+        | ServiceStatus::SmokeTesting
+        | ServiceStatus::RunningPostDeploy
+        | ServiceStatus::HookOutput(_)
+        | ServiceStatus::Retrying(..)
+        | ServiceStatus::Queued
+        | ServiceStatus::AwaitingReview
+        | ServiceStatus::PendingApproval
         | ServiceStatus::DeploymentRequested => "warning".to_string(),
     }
 }
 
-fn service_status_name(status: &ServiceStatus) -> String {
-    status.clone().to_string()
-}
-
-pub fn service(service: Result<Service, DBError>, status: ServiceStatus) -> ServiceHTML {
+// `since` is when the deploy under way began, if one is
+pub fn service(
+    service: Result<Service, DBError>,
+    status: ServiceStatus,
+    since: Option<DateTime<Utc>>,
+) -> ServiceHTML {
     match service {
         Ok(serv) => ServiceHTML {
             status_class: app_status_class(&status),
@@ -266,7 +1073,7 @@ pub fn service(service: Result<Service, DBError>, status: ServiceStatus) -> Serv
                 id: format!("service-{}-status", serv.id),
                 element: "div".to_string(),
                 class: Some(service_class_name(&status)),
-                html_content: service_status_name(&status),
+                html_content: render(&StatusTemplate { status, since }),
             }],
         },
         Err(e) => ServiceHTML {
@@ -276,12 +1083,90 @@ pub fn service(service: Result<Service, DBError>, status: ServiceStatus) -> Serv
                 id: "app-message".to_string(),
                 element: "div".to_string(),
                 class: Some("error".to_string()),
-                html_content: format!("Unable to access service from the database | {}", e),
+                html_content: Markup::text(format!(
+                    "Unable to access service from the database | {}",
+                    e
+                )),
             }],
         },
     }
 }
 
+// fills the CPU and memory cells; containers are named when a service has several
+pub fn usage(usage: Vec<(i64, Vec<ContainerUsage>)>) -> ServiceHTML {
+    let mut html_targets = vec![];
+    for (id, containers) in usage {
+        let lines = |value: fn(&ContainerUsage) -> &String| -> Vec<String> {
+            match containers.len() {
+                1 => vec![value(&containers[0]).clone()],
+                _ => containers
+                    .iter()
+                    .map(|c| format!("{}: {}", c.service, value(c)))
+                    .collect(),
+            }
+        };
+        html_targets.push(HTMLTarget {
+            id: format!("service-{}-cpu", id),
+            element: "div".to_string(),
+            class: None,
+            html_content: render(&UsageTemplate {
+                lines: lines(|c| &c.cpu),
+            }),
+        });
+        html_targets.push(HTMLTarget {
+            id: format!("service-{}-memory", id),
+            element: "div".to_string(),
+            class: None,
+            html_content: render(&UsageTemplate {
+                lines: lines(|c| &c.memory),
+            }),
+        });
+    }
+
+    ServiceHTML {
+        status_class: "success".to_string(),
+        status_string: "Connected".to_string(),
+        html_targets,
+    }
+}
+
+// flags services whose containers don't match their row, and clears the rest
+pub fn drift(drift: Vec<(i64, Option<Drift>)>) -> ServiceHTML {
+    let html_targets = drift
+        .into_iter()
+        .map(|(id, drift)| HTMLTarget {
+            id: format!("service-{}-drift", id),
+            element: "div".to_string(),
+            class: drift.as_ref().map(|_| "warning".to_string()),
+            html_content: Markup::text(drift.map(|d| d.to_string()).unwrap_or_default()),
+        })
+        .collect();
+
+    ServiceHTML {
+        status_class: "success".to_string(),
+        status_string: "Connected".to_string(),
+        html_targets,
+    }
+}
+
+pub fn bulk_progress(progress: BulkProgress) -> ServiceHTML {
+    let class = match &progress {
+        p if !p.finished() => "warning",
+        p if p.failed > 0 => "error",
+        _ => "success",
+    };
+    ServiceHTML {
+        status_class: "success".to_string(),
+        status_string: "Connected".to_string(),
+        html_targets: vec![HTMLTarget {
+            id: "bulk-progress".to_string(),
+            element: "div".to_string(),
+            class: Some(class.to_string()),
+            html_content: Markup::text(progress),
+        }],
+    }
+}
+
 pub fn unknown(msg: String) -> ServiceHTML {
     ServiceHTML {
         status_class: "error".to_string(),
@@ -290,7 +1175,254 @@ pub fn unknown(msg: String) -> ServiceHTML {
             id: "app-message".to_string(),
             element: "div".to_string(),
             class: Some("error".to_string()),
-            html_content: msg,
+            html_content: Markup::text(msg),
         }],
     }
 }
+
+pub fn error_message(text: String) -> Markup {
+    render(&MessageTemplate {
+        class: "error",
+        text,
+    })
+}
+
+pub fn env_editor(
+    service: Result<Service, DBError>,
+    env_vars: Result<Vec<ServiceEnv>, DBError>,
+    message: Option<String>,
+) -> Markup {
+    let service = match service {
+        Ok(s) => s,
+        Err(e) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access service from the database | {}", e),
+            });
+        }
+    };
+
+    let (env_vars, error) = match env_vars {
+        Ok(vars) => (vars, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&EnvEditorTemplate {
+        service,
+        env_vars,
+        error,
+        message,
+    })
+}
+
+pub fn notifications(
+    service: Result<Service, DBError>,
+    recipients: Result<Vec<EmailRecipient>, DBError>,
+    targets: Result<Vec<PushTarget>, DBError>,
+    rules: Result<Vec<NotificationRule>, DBError>,
+    smtp: bool,
+    pushover: bool,
+    message: Option<String>,
+) -> Markup {
+    let service = match service {
+        Ok(s) => s,
+        Err(e) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access service from the database | {}", e),
+            });
+        }
+    };
+
+    let (recipients, targets, rules, error) = match (recipients, targets, rules) {
+        (Ok(recipients), Ok(targets), Ok(rules)) => (recipients, targets, rules, None),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            (vec![], vec![], vec![], Some(e.to_string()))
+        }
+    };
+
+    render(&NotificationsTemplate {
+        service,
+        recipients,
+        targets,
+        rules,
+        smtp,
+        pushover,
+        error,
+        message,
+    })
+}
+
+pub fn hook(
+    service: Result<Service, DBError>,
+    created_at: Result<Option<String>, DBError>,
+    token: Option<String>,
+    message: Option<String>,
+) -> Markup {
+    let (service, created_at) = match (service, created_at) {
+        (Ok(service), Ok(created_at)) => (service, created_at),
+        (Err(e), _) | (_, Err(e)) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access the deploy hook from the database | {}", e),
+            });
+        }
+    };
+
+    render(&HookTemplate {
+        service,
+        created_at,
+        token,
+        message,
+    })
+}
+
+pub fn jobs(
+    service: Result<Service, DBError>,
+    jobs: Result<Vec<ServiceJob>, DBError>,
+    runs: Result<Vec<JobRun>, DBError>,
+    running: &RunningJobs,
+    message: Option<String>,
+) -> Markup {
+    let service = match service {
+        Ok(s) => s,
+        Err(e) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access service from the database | {}", e),
+            });
+        }
+    };
+
+    let (jobs, runs, error) = match (jobs, runs) {
+        (Ok(jobs), Ok(runs)) => (jobs, runs, None),
+        (Err(e), _) | (_, Err(e)) => (vec![], vec![], Some(e.to_string())),
+    };
+    let jobs = jobs
+        .into_iter()
+        .map(|job| JobRow {
+            next_run: job
+                .next_run()
+                .filter(|_| job.active && service.active)
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string()),
+            running: running.contains(job.id),
+            job,
+        })
+        .collect();
+
+    render(&JobsTemplate {
+        service,
+        jobs,
+        runs,
+        modes: JOB_MODES,
+        error,
+        message,
+    })
+}
+
+pub fn deployments(
+    service: Result<Service, DBError>,
+    deployments: Result<Vec<Deployment>, DBError>,
+) -> Markup {
+    let (service, deployments) = match (service, deployments) {
+        (Ok(service), Ok(deployments)) => (service, deployments),
+        (Err(e), _) | (_, Err(e)) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access deployments from the database | {}", e),
+            });
+        }
+    };
+
+    render(&DeploymentsTemplate {
+        service,
+        deployments,
+    })
+}
+
+pub fn volumes(service: Result<Service, DBError>, config: &Config) -> Markup {
+    let snapshots = service
+        .map_err(ServiceError::from)
+        .and_then(|service| Ok((volume::list(config, &service.name)?, service)));
+    match snapshots {
+        Ok((snapshots, service)) => render(&VolumesTemplate { service, snapshots }),
+        Err(e) => render(&MessageTemplate {
+            class: "block error",
+            text: format!("Unable to list volume snapshots | {}", e),
+        }),
+    }
+}
+
+pub fn files(service: Result<Service, DBError>, config: &Config, path: &str) -> Markup {
+    let browse = service
+        .map_err(ServiceError::from)
+        .and_then(|service| Ok((service.browse(config, path)?, service)));
+    match browse {
+        Ok((browse, service)) => render(&FilesTemplate { service, browse }),
+        Err(e) => error_message(e.to_string()),
+    }
+}
+
+// `message` is why an override wasn't saved; `saved` that one just was
+pub fn compose(
+    service: Result<Service, DBError>,
+    config: &Config,
+    message: Option<String>,
+    saved: bool,
+) -> Markup {
+    match service {
+        Ok(service) => render(&ComposeTemplate {
+            compose: service.compose_view(config),
+            service,
+            message,
+            saved,
+        }),
+        Err(e) => error_message(format!(
+            "Unable to access service from the database | {}",
+            e
+        )),
+    }
+}
+
+// `containers` are the compose services with a running container
+pub fn terminal(service: Result<Service, DBError>, containers: Vec<String>) -> Markup {
+    match service {
+        Ok(service) if service.node_id.is_some() => {
+            error_message(ServiceError::TerminalOnNode.to_string())
+        }
+        Ok(service) => render(&TerminalTemplate {
+            service,
+            containers,
+        }),
+        Err(e) => error_message(format!(
+            "Unable to access service from the database | {}",
+            e
+        )),
+    }
+}
+
+pub fn review(
+    service: Result<Service, DBError>,
+    review: Result<Option<DeployReview>, DBError>,
+) -> Markup {
+    let (service, review) = match (service, review) {
+        (Ok(service), Ok(review)) => (service, review),
+        (Err(e), _) | (_, Err(e)) => {
+            return render(&MessageTemplate {
+                class: "block error",
+                text: format!("Unable to access the review from the database | {}", e),
+            });
+        }
+    };
+
+    render(&ReviewTemplate { service, review })
+}
+
+pub fn archived_list(archived: Result<Vec<Service>, DBError>) -> Markup {
+    let (services, error) = match archived {
+        Ok(services) => (services, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&ArchivedListTemplate { services, error })
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{
+    Service, ServiceError, network,
+    process::{self, Limit},
+    timing, valid_slug,
+};
+use crate::modules::{
+    Config,
+    scheduler::cron::{CronError, Schedule},
+};
+
+// `exec` runs the command in the service's running container, `run` in a fresh one that's
+// removed afterwards
+pub const JOB_MODES: [&str; 2] = ["exec", "run"];
+// runs kept per job; older ones are dropped as new ones are recorded
+pub const RUNS_KEPT: i64 = 50;
+// output beyond this keeps only its last lines
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServiceJob {
+    pub id: i64,
+    pub service_id: i64,
+    pub name: String,
+    pub schedule: String,
+    pub mode: String,
+    // the compose service to run in; the post-start exec's container when unset
+    pub container: Option<String>,
+    pub command: String,
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobRun {
+    pub id: i64,
+    pub job: String,
+    // schedule or manual
+    pub trigger: String,
+    pub started_at: String,
+    pub duration_ms: i64,
+    pub ok: bool,
+    pub exit_code: Option<i64>,
+    pub output: String,
+}
+
+impl JobRun {
+    pub fn duration(&self) -> String {
+        timing::format_millis(self.duration_ms.max(0) as u64)
+    }
+}
+
+// how a run ended, before it's recorded
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub ok: bool,
+    pub exit_code: Option<i64>,
+    pub output: String,
+    pub duration_ms: i64,
+}
+
+impl Outcome {
+    // a run that never started its command
+    pub fn skipped(reason: impl ToString) -> Self {
+        Outcome {
+            ok: false,
+            exit_code: None,
+            output: reason.to_string(),
+            duration_ms: 0,
+        }
+    }
+}
+
+impl ServiceJob {
+    pub fn parsed_schedule(&self) -> Result<Schedule, CronError> {
+        self.schedule.parse()
+    }
+
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        self.parsed_schedule().ok()?.next_after(Utc::now())
+    }
+
+    // the compose service the command runs in
+    pub fn target(&self, service: &Service) -> Option<String> {
+        self.container.clone().or_else(|| service.exec_container())
+    }
+
+    // every problem with the job, keyed by the form field to show it against
+    pub fn field_errors(&self, service: &Service) -> Vec<(&'static str, ServiceError)> {
+        let mut errors = vec![];
+        if !valid_slug(&self.name) {
+            errors.push(("name", ServiceError::JobName));
+        }
+        if let Err(e) = self.parsed_schedule() {
+            errors.push(("schedule", e.into()));
+        }
+        if !JOB_MODES.contains(&self.mode.as_str()) {
+            errors.push(("mode", ServiceError::JobMode));
+        }
+        match &self.container {
+            // compose service names follow the same rule as network names
+            Some(container) if !network::valid_name(container) => {
+                errors.push(("container", ServiceError::Container(container.clone())))
+            }
+            Some(_) => (),
+            None if service.exec_container().is_none() => {
+                errors.push(("container", ServiceError::ExecContainer))
+            }
+            None => (),
+        }
+        if self.command.trim().is_empty() {
+            errors.push(("command", ServiceError::JobCommand));
+        }
+        if service.node_id.is_some() {
+            errors.push(("name", ServiceError::JobOnNode));
+        }
+        errors
+    }
+}
+
+// jobs with a run going, so a slow run isn't overlapped by the next one
+#[derive(Clone, Debug, Default)]
+pub struct RunningJobs {
+    jobs: Arc<Mutex<HashSet<i64>>>,
+}
+
+impl RunningJobs {
+    fn jobs(&self) -> std::sync::MutexGuard<'_, HashSet<i64>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // false when the job is already running
+    pub fn begin(&self, job_id: i64) -> bool {
+        self.jobs().insert(job_id)
+    }
+
+    pub fn end(&self, job_id: i64) {
+        self.jobs().remove(&job_id);
+    }
+
+    pub fn contains(&self, job_id: i64) -> bool {
+        self.jobs().contains(&job_id)
+    }
+}
+
+// the last lines of a run's output, up to MAX_OUTPUT_BYTES
+#[derive(Default)]
+struct Tail {
+    lines: VecDeque<String>,
+    bytes: usize,
+    dropped: bool,
+}
+
+impl Tail {
+    fn push(&mut self, line: String) {
+        self.bytes += line.len() + 1;
+        self.lines.push_back(line);
+        while self.bytes > MAX_OUTPUT_BYTES
+            && let Some(old) = self.lines.pop_front()
+        {
+            self.bytes -= old.len() + 1;
+            self.dropped = true;
+        }
+    }
+
+    fn text(self) -> String {
+        let mut lines = Vec::from(self.lines);
+        if self.dropped {
+            lines.insert(0, "...".to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+impl Service {
+    // runs the job's command with `compose exec` or `compose run --rm`, under the compose
+    // timeout; failing to start it is a failed run like any other
+    pub fn run_job(
+        &self,
+        config: &Config,
+        job: &ServiceJob,
+        cancel: &CancellationToken,
+    ) -> Outcome {
+        let started = Instant::now();
+        let mut tail = Tail::default();
+        let result = self.job_command(config, job).and_then(|command| {
+            let limit = Limit {
+                step: "job",
+                timeout: config.timeouts.compose,
+                cancel,
+            };
+            process::stream(command, limit, |line| {
+                event!(Level::INFO, "JOB | {} | {} | {}", self.name, job.name, line);
+                tail.push(line);
+            })
+        });
+
+        let (ok, exit_code) = match result {
+            Ok(status) => (status.success(), status.code().map(i64::from)),
+            Err(e) => {
+                tail.push(e.to_string());
+                (false, None)
+            }
+        };
+        match ok {
+            true => event!(Level::INFO, "Job {} of {} succeeded", job.name, self.name),
+            false => event!(Level::WARN, "JOB FAIL | {} | {}", self.name, job.name),
+        }
+        Outcome {
+            ok,
+            exit_code,
+            output: tail.text(),
+            duration_ms: started.elapsed().as_millis() as i64,
+        }
+    }
+
+    fn job_command(
+        &self,
+        config: &Config,
+        job: &ServiceJob,
+    ) -> Result<std::process::Command, ServiceError> {
+        if self.node_id.is_some() {
+            return Err(ServiceError::JobOnNode);
+        }
+        let container = job.target(self).ok_or(ServiceError::ExecContainer)?;
+        if !network::valid_name(&container) {
+            return Err(ServiceError::Container(container));
+        }
+        let mut compose = self.compose(&self.live_path(config))?;
+        match job.mode.as_str() {
+            "run" => compose.args(["run", "--rm", "-T"]),
+            _ => compose.args(["exec", "-T"]),
+        };
+        compose.arg(&container).args(["sh", "-c"]).arg(&job.command);
+        Ok(compose)
+    }
+}
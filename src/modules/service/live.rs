@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{
+    Service, ServiceError, ServiceEvent, ServiceStatus, env::write_secret_files, files,
+    process::Limit, valid_name, write_env_file,
+};
+use crate::modules::{Config, service::env::ServiceEnv};
+
+// under the service's live home: one directory per deploy, and a link to the one in use
+const RELEASES: &str = "releases";
+const CURRENT: &str = "current";
+// a release is copied under this suffix and renamed once it's complete
+const PARTIAL: &str = ".partial";
+// a release a failed health check rolled back from, kept for inspection
+const FAILED: &str = ".failed";
+// the live dir of a deploy from before releases; sorts before every timestamp
+const LEGACY: &str = "00000000T000000.000Z";
+
+fn release_name() -> String {
+    Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+// points `current` at a release in one rename, so it's never missing or half-written
+fn swap(home: &Path, release: &str) -> Result<(), ServiceError> {
+    let current = home.join(CURRENT);
+    // `start` on a service that was never deployed creates a plain directory here
+    if std::fs::symlink_metadata(&current).is_ok_and(|m| m.is_dir()) {
+        files::remove(&current)?;
+    }
+    let next = home.join(format!("{}.next", CURRENT));
+    let _ = std::fs::remove_file(&next);
+    std::os::unix::fs::symlink(Path::new(RELEASES).join(release), &next)?;
+    std::fs::rename(&next, &current)?;
+    Ok(())
+}
+
+// the release `current` points at
+fn current(home: &Path) -> Option<String> {
+    std::fs::read_link(home.join(CURRENT))
+        .ok()?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+// finished releases, newest first; failed ones included
+fn releases(home: &Path) -> Result<Vec<String>, ServiceError> {
+    let mut releases = vec![];
+    for entry in std::fs::read_dir(home.join(RELEASES))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.ends_with(PARTIAL) {
+            releases.push(name);
+        }
+    }
+    releases.sort_by(|a, b| b.cmp(a));
+    Ok(releases)
+}
+
+impl Service {
+    // holds the service's releases and the `current` link
+    pub fn live_home(&self, config: &Config) -> PathBuf {
+        self.live_root(config).join(&self.name)
+    }
+
+    // the live dir docker compose runs from: the current release. A live dir from before
+    // releases is used as it is until the next deploy moves it into them
+    pub fn live_path(&self, config: &Config) -> PathBuf {
+        let home = self.live_home(config);
+        match home.is_dir() && !home.join(RELEASES).is_dir() {
+            true => home,
+            false => home.join(CURRENT),
+        }
+    }
+
+    // sets up the releases layout, moving a live dir from before releases into it
+    fn ensure_releases(&self, config: &Config) -> Result<PathBuf, ServiceError> {
+        let home = self.live_home(config);
+        if home.join(RELEASES).is_dir() {
+            return Ok(home);
+        }
+        if !home.is_dir() {
+            std::fs::create_dir_all(home.join(RELEASES))?;
+            return Ok(home);
+        }
+
+        event!(
+            Level::INFO,
+            "Moving the live dir of {} into releases",
+            self.name
+        );
+        let root = self.live_root(config);
+        let legacy = self.kept_path(&root, "legacy");
+        files::remove(&legacy)?;
+        std::fs::rename(&home, &legacy)?;
+        std::fs::create_dir_all(home.join(RELEASES))?;
+        std::fs::rename(&legacy, home.join(RELEASES).join(LEGACY))?;
+        swap(&home, LEGACY)?;
+        // rollbacks now re-point `current` instead
+        for kind in ["previous", "failed"] {
+            files::remove(&self.kept_path(&root, kind))?;
+        }
+        Ok(home)
+    }
+
+    // copies the repo into a new release and points `current` at it; the release in use is
+    // left as it was until the copy is complete
+    pub fn copy_to_live(
+        &self,
+        config: Config,
+        env_vars: &[ServiceEnv],
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Copying));
+        let limit = |step| Limit {
+            step,
+            timeout: config.timeouts.copy,
+            cancel,
+        };
+
+        // an override can land on the other global directory
+        if self.repo_root(&config) == self.live_root(&config) {
+            return Err(ServiceError::SameDirs);
+        }
+        self.check_dir_mounted(&self.live_dir)?;
+        // checked again here as the name picks the directory releases are written to
+        if !valid_name(&self.name) {
+            return Err(ServiceError::Name);
+        }
+        let home = self.ensure_releases(&config)?;
+
+        let name = release_name();
+        let partial = home.join(RELEASES).join(format!("{}{}", name, PARTIAL));
+        std::fs::create_dir(&partial)?;
+        let repo_path = self.repo_path(&config);
+        let copied = files::copy(&repo_path, &partial, limit("copy"), |copied, total| {
+            let _ = br.send(ServiceEvent::update(
+                self.id,
+                ServiceStatus::CopyProgress(copied, total),
+            ));
+        })
+        .and_then(|copied| {
+            // leave any repo-provided .env alone unless wraut manages variables for this service
+            let secrets = self.secret_files(env_vars);
+            let env_file: Vec<ServiceEnv> = env_vars
+                .iter()
+                .filter(|var| !(self.secret_files && var.secret))
+                .cloned()
+                .collect();
+            if !env_vars.is_empty()
+                && let Err(e) = write_env_file(&partial, &env_file)
+                    .and_then(|_| write_secret_files(&partial, &secrets))
+            {
+                event!(Level::ERROR, "ENV FILE FAIL | {} | {}", self.name, e);
+                return Err(ServiceError::EnvFile);
+            }
+            Ok(copied)
+        })
+        .inspect_err(|e| {
+            event!(Level::ERROR, "{} | {}", self.name, e);
+            let _ = files::remove(&partial);
+        })?;
+
+        std::fs::rename(&partial, home.join(RELEASES).join(&name))?;
+        swap(&home, &name)?;
+        event!(
+            Level::INFO,
+            "Copied {} files for {} into release {}",
+            copied,
+            self.name,
+            name
+        );
+        Ok(())
+    }
+
+    // points `current` back at the newest release before it that wasn't rolled back, and
+    // marks the one it leaves as failed
+    pub fn restore_previous_release(&self, config: &Config) -> Result<(), ServiceError> {
+        let home = self.live_home(config);
+        let Some(rejected) = current(&home) else {
+            return Err(ServiceError::Unhealthy);
+        };
+        let previous = releases(&home)?
+            .into_iter()
+            .find(|r| *r < rejected && !r.ends_with(FAILED))
+            .ok_or(ServiceError::Unhealthy)?;
+        swap(&home, &previous)?;
+        let releases_dir = home.join(RELEASES);
+        std::fs::rename(
+            releases_dir.join(&rejected),
+            releases_dir.join(format!("{}{}", rejected, FAILED)),
+        )?;
+        event!(
+            Level::WARN,
+            "Rolled {} back from release {} to {}",
+            self.name,
+            rejected,
+            previous
+        );
+        Ok(())
+    }
+
+    // removes the oldest releases beyond RELEASES_KEEP, never the current one, along with
+    // any copy a crash left unfinished
+    pub fn trim_releases(&self, config: &Config) -> Result<usize, ServiceError> {
+        let home = self.live_home(config);
+        let releases_dir = home.join(RELEASES);
+        if !releases_dir.is_dir() {
+            return Ok(0);
+        }
+        for entry in std::fs::read_dir(&releases_dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().ends_with(PARTIAL) {
+                files::remove(&entry.path())?;
+            }
+        }
+        let Some(keep) = config.releases_keep else {
+            return Ok(0);
+        };
+        let current = current(&home);
+        let mut removed = 0;
+        for release in releases(&home)?
+            .into_iter()
+            .filter(|r| Some(r) != current.as_ref())
+            .skip(keep.saturating_sub(1))
+        {
+            files::remove(&releases_dir.join(release))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
@@ -1,38 +1,156 @@
+pub mod adopt;
+pub mod approval;
+pub mod browse;
+pub mod cache;
+pub mod compose_names;
+pub mod compose_view;
+pub mod credential;
+pub mod deploy_log;
+pub mod docker_secret;
+pub mod dry_run;
+pub mod duplicate;
+pub mod env;
+pub mod files;
 pub mod html;
-
-use std::path::PathBuf;
-
-use serde::Deserialize;
+pub mod job;
+pub mod live;
+pub mod network;
+pub mod one_off;
+pub mod pipeline;
+pub mod preset;
+pub mod preview;
+pub mod process;
+pub mod promote;
+pub mod proxy;
+pub mod release;
+pub mod review;
+pub mod script;
+pub mod smoke;
+pub mod template;
+pub mod terminal;
+pub mod timing;
+pub mod volume;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_yaml::Error as SerdeError;
 use sqlx::SqlitePool;
 use std::process::{Command, Output};
 use thiserror::Error;
 use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 use tracing::{Level, event};
 
 use super::{
     Config,
-    db::{DBError, delete_service_entry},
+    db::{
+        DBError, delete_service_entry, get_deploy_key, get_git_credential, get_service_env,
+        new_deployment, set_last_commit,
+    },
+    request_id,
+    scheduler::cron::{CronError, Schedule},
+    secrets::SecretError,
+    tools,
 };
+use credential::GitAuth;
+use env::{ServiceEnv, reveal, write_env_file};
+use process::Limit;
+use timing::{StepTiming, Timings};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail")]
 pub enum ServiceStatus {
     Inactive,
     Running,
+    Degraded(String),
     DiscoveryFailed,
     CommandFailed(String),
     CloneOrPullFailed,
     DeploymentRequested,
+    // held until someone approves its compose diff
+    AwaitingReview,
+    // an automatic deploy held until someone approves it
+    PendingApproval,
+    Declined,
+    // waiting for a free slot under MAX_CONCURRENT_DEPLOYS
+    Queued,
     Cloning,
     Pulling,
+    // fetching and checking out a pinned commit instead of pulling
+    CheckingOut(String),
     Stopping,
+    // stopped from the dashboard or API, and left that way until started again
+    Stopped,
     Starting,
+    Restarting,
+    TearingDown,
+    // containers and networks removed with `compose down`, and volumes too if asked
+    TornDown,
     Copying,
+    // files copied so far, out of how many
+    CopyProgress(u64, u64),
     RewritingConfig,
+    RunningPreDeploy,
+    // one of the service's own scripts, by name
+    RunningScript(String),
+    // snapshotting the service's named volumes before the new version starts
+    BackingUpVolumes,
+    RestoringVolumes,
+    Building,
+    Pushing,
+    CheckingHealth,
+    RollingBack,
+    RolledBack,
+    Cancelled,
+    CrashLooping,
+    // attempt about to run, out of how many
+    Retrying(u32, u32),
+    RunningPostStartExec,
+    // a one-off job's container running to completion
+    RunningOneOff,
+    // a one-off job's last deploy exited 0
+    Completed,
+    SmokeTesting,
+    RunningPostDeploy,
+    HookOutput(String),
     Unknown,
 }
 
 impl ServiceStatus {
+    // the variant name and its text, if it carries any, as stored on the service row
+    pub fn record(&self) -> Option<(String, Option<String>)> {
+        let value = serde_json::to_value(self).ok()?;
+        let kind = value.get("kind")?.as_str()?.to_string();
+        let detail = value
+            .get("detail")
+            .and_then(|d| d.as_str())
+            .map(str::to_string);
+        Some((kind, detail))
+    }
+
+    pub fn from_record(kind: &str, detail: Option<&str>) -> Option<Self> {
+        let value = match detail {
+            Some(detail) => serde_json::json!({ "kind": kind, "detail": detail }),
+            None => serde_json::json!({ "kind": kind }),
+        };
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::DiscoveryFailed
+                | Self::CommandFailed(_)
+                | Self::CloneOrPullFailed
+                | Self::RolledBack
+                | Self::Cancelled
+                | Self::CrashLooping
+        )
+    }
+
     pub fn from_error(se: ServiceError) -> Self {
         match se {
             ServiceError::Command(e) => Self::CommandFailed(e.to_string()),
@@ -49,10 +167,25 @@ impl ServiceStatus {
                 Self::CommandFailed("Failed to start Docker service".to_string())
             }
             ServiceError::Stop => Self::CommandFailed("Failed to stop Docker service".to_string()),
-            ServiceError::Remove => {
-                Self::CommandFailed("Failed to remove live directory contents".to_string())
+            ServiceError::Restart => {
+                Self::CommandFailed("Failed to restart Docker service".to_string())
+            }
+            ServiceError::Down => {
+                Self::CommandFailed("Failed to tear down Docker service".to_string())
+            }
+            ServiceError::Build => Self::CommandFailed("Failed to build Docker images".to_string()),
+            ServiceError::Push => {
+                Self::CommandFailed("Failed to push images to the registry".to_string())
+            }
+            ServiceError::Remove(context) => {
+                Self::CommandFailed(format!("Failed to remove {}", context))
+            }
+            ServiceError::Copy(context) => {
+                Self::CommandFailed(format!("Failed to copy {}", context))
+            }
+            ServiceError::NotADirectory(path) => {
+                Self::CommandFailed(format!("{} is not a directory", path))
             }
-            ServiceError::Copy => Self::CommandFailed("Failed to copy repo contents".to_string()),
             ServiceError::Yaml(_) => Self::CommandFailed("Failed to parse YAML file".to_string()),
             ServiceError::Key(k) => Self::CommandFailed(format!("Failed to find key '{}'", k)),
             ServiceError::Unknown => Self::Unknown,
@@ -62,54 +195,455 @@ impl ServiceStatus {
                 Self::CommandFailed("Failed to remove entire directory".to_string())
             }
             ServiceError::Db(_) => Self::CommandFailed("Failed to run database action".to_string()),
+            ServiceError::Secret(e) => {
+                Self::CommandFailed(format!("Failed to decrypt secret | {}", e))
+            }
+            ServiceError::Archived => Self::CommandFailed("Service is archived".to_string()),
+            ServiceError::EnvFile => Self::CommandFailed("Failed to write .env file".to_string()),
+            ServiceError::Hook(cmd) => Self::CommandFailed(format!("Deploy hook failed '{}'", cmd)),
+            ServiceError::Script(name) => Self::CommandFailed(format!("Script '{}' failed", name)),
+            ServiceError::Exec(cmd) => {
+                Self::CommandFailed(format!("Post-start exec failed '{}'", cmd))
+            }
+            ServiceError::Smoke(reason) => {
+                Self::CommandFailed(format!("Smoke test failed | {}", reason))
+            }
+            ServiceError::OneOffExit(code) => {
+                Self::CommandFailed(format!("One-off job exited with {}", code))
+            }
+            ServiceError::RolledBack => Self::RolledBack,
+            ServiceError::Cancelled => Self::Cancelled,
+            ServiceError::Agent(msg) => Self::CommandFailed(msg),
+            ServiceError::Network(network) => {
+                Self::CommandFailed(format!("Failed to create network '{}'", network))
+            }
+            ServiceError::VolumeBackup(volume) => {
+                Self::CommandFailed(format!("Failed to back up volume '{}'", volume))
+            }
+            ServiceError::VolumeRestore(volume) => {
+                Self::CommandFailed(format!("Failed to restore volume '{}'", volume))
+            }
+            ServiceError::Remote(status) => status,
+            e @ (ServiceError::Schedule(_)
+            | ServiceError::PollInterval
+            | ServiceError::CloneDepth
+            | ServiceError::ComposeName
+            | ServiceError::UnknownComposeService(..)
+            | ServiceError::Name
+            | ServiceError::RepoUrl
+            | ServiceError::AccessUrl
+            | ServiceError::Registry
+            | ServiceError::PushWithoutBuild
+            | ServiceError::HealthGrace
+            | ServiceError::Environment
+            | ServiceError::Branch
+            | ServiceError::ProxyPort
+            | ServiceError::ComposeOverride(_)
+            | ServiceError::ReviewOnNode
+            | ServiceError::BackupVolumes
+            | ServiceError::BackupOnNode
+            | ServiceError::ExecContainer
+            | ServiceError::ServiceDir
+            | ServiceError::SameDirs
+            | ServiceError::SmokeUrl
+            | ServiceError::SmokeStatus
+            | ServiceError::SmokeRegex(_)
+            | ServiceError::JobName
+            | ServiceError::JobMode
+            | ServiceError::JobCommand
+            | ServiceError::JobOnNode
+            | ServiceError::OneOff
+            | ServiceError::OneOffOptions
+            | ServiceError::Platform
+            | ServiceError::Pipeline(_)
+            | ServiceError::Scripts(_)
+            | ServiceError::NoSnapshot(_)
+            | ServiceError::TerminalOnNode
+            | ServiceError::Container(_)
+            | ServiceError::BrowseOnNode
+            | ServiceError::BrowsePath(_)
+            | ServiceError::WatchPaths
+            | ServiceError::BranchWithTags
+            | ServiceError::Unhealthy
+            | ServiceError::Timeout(_)
+            | ServiceError::ComposeFile(_)
+            | ServiceError::ComposePath
+            | ServiceError::RemoteHead
+            | ServiceError::NoMatchingTag(_)) => Self::CommandFailed(e.to_string()),
         }
     }
+}
 
-    pub fn to_string(self) -> String {
+impl fmt::Display for ServiceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Inactive => "Inactive".into(),
-            Self::Running => "Running".into(),
-            Self::DiscoveryFailed => "Failed to discover service".into(),
-            Self::CommandFailed(s) => format!("Failed command | {}", s),
-            Self::CloneOrPullFailed => "Failed to clone or pull".into(),
-            Self::DeploymentRequested => "Deployment requested...".into(),
-            Self::Cloning => "Cloning repo...".into(),
-            Self::Pulling => "Pulling repo...".into(),
-            Self::Stopping => "Stopping service...".into(),
-            Self::Starting => "Starting service...".into(),
-            Self::Copying => "Copying repo...".into(),
-            Self::RewritingConfig => "Rewriting docker-compose.yml...".into(),
-            Self::Unknown => "Unknown status".into(),
+            Self::Inactive => write!(f, "Inactive"),
+            Self::Running => write!(f, "Running"),
+            Self::Degraded(s) => write!(f, "Degraded | {}", s),
+            Self::DiscoveryFailed => write!(f, "Failed to discover service"),
+            Self::CommandFailed(s) => write!(f, "Failed command | {}", s),
+            Self::CloneOrPullFailed => write!(f, "Failed to clone or pull"),
+            Self::DeploymentRequested => write!(f, "Deployment requested..."),
+            Self::AwaitingReview => write!(f, "Awaiting review of compose changes"),
+            Self::PendingApproval => write!(f, "Pending approval"),
+            Self::Declined => write!(f, "Deployment declined"),
+            Self::Queued => write!(f, "Queued..."),
+            Self::Cloning => write!(f, "Cloning repo..."),
+            Self::Pulling => write!(f, "Pulling repo..."),
+            Self::CheckingOut(commit) => write!(f, "Checking out {}...", short_commit(commit)),
+            Self::Stopping => write!(f, "Stopping service..."),
+            Self::Stopped => write!(f, "Stopped"),
+            Self::Starting => write!(f, "Starting service..."),
+            Self::Restarting => write!(f, "Restarting service..."),
+            Self::TearingDown => write!(f, "Tearing down service..."),
+            Self::TornDown => write!(f, "Torn down"),
+            Self::Copying => write!(f, "Copying repo..."),
+            Self::CopyProgress(copied, total) => {
+                write!(f, "Copying repo ({}/{} files)...", copied, total)
+            }
+            Self::RewritingConfig => write!(f, "Rewriting compose file..."),
+            Self::RunningPreDeploy => write!(f, "Running pre-deploy hook..."),
+            Self::RunningScript(name) => write!(f, "Running script '{}'...", name),
+            Self::BackingUpVolumes => write!(f, "Backing up volumes..."),
+            Self::RestoringVolumes => write!(f, "Restoring volumes..."),
+            Self::Building => write!(f, "Building images..."),
+            Self::Pushing => write!(f, "Pushing images..."),
+            Self::CheckingHealth => write!(f, "Waiting for healthy containers..."),
+            Self::RollingBack => write!(f, "Unhealthy, rolling back..."),
+            Self::RolledBack => write!(f, "Unhealthy, rolled back to the previous version"),
+            Self::Cancelled => write!(f, "Deployment cancelled"),
+            Self::CrashLooping => write!(f, "Crashing repeatedly, no longer restarted"),
+            Self::Retrying(attempt, of) => write!(f, "Retrying ({}/{})...", attempt, of),
+            Self::RunningPostStartExec => write!(f, "Running post-start exec..."),
+            Self::RunningOneOff => write!(f, "Running one-off job..."),
+            Self::Completed => write!(f, "Completed"),
+            Self::SmokeTesting => write!(f, "Running smoke test..."),
+            Self::RunningPostDeploy => write!(f, "Running post-deploy hook..."),
+            Self::HookOutput(s) => write!(f, "{}", s),
+            Self::Unknown => write!(f, "Unknown status"),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
 pub enum ServiceEvent {
     AllStatus,
-    ServiceUpdate { id: i64, status: ServiceStatus },
-    UnknownEvent { msg: String },
+    ServiceUpdate {
+        id: i64,
+        status: ServiceStatus,
+        // the request or deploy the update belongs to; see `request_id`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<String>,
+    },
+    // latest resource usage per service id
+    Usage {
+        usage: Vec<(i64, Vec<ContainerUsage>)>,
+    },
+    // what each checked service's containers differ from, if anything
+    Drift {
+        drift: Vec<(i64, Option<Drift>)>,
+    },
+    // how far the running bulk deploy has got
+    BulkProgress {
+        progress: BulkProgress,
+    },
+    // a deploy got its slot and is under way
+    DeployStarted {
+        id: i64,
+    },
+    // a deploy finished, with the status it ended on
+    Deployed {
+        id: i64,
+        ok: bool,
+        status: ServiceStatus,
+        // the deployment row it was recorded as, if it could be
+        deployment: Option<i64>,
+    },
+    // a service's containers went up or down since last checked
+    Health {
+        id: i64,
+        up: bool,
+    },
+    // one of a service's scheduled jobs finished a run
+    JobRan {
+        id: i64,
+        job: String,
+        ok: bool,
+        detail: String,
+    },
+    UnknownEvent {
+        msg: String,
+    },
+    Shutdown,
+}
+
+impl ServiceEvent {
+    pub fn update(id: i64, status: ServiceStatus) -> Self {
+        ServiceEvent::ServiceUpdate {
+            id,
+            status,
+            request_id: request_id::current(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BulkProgress {
+    pub deployed: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl BulkProgress {
+    pub fn finished(&self) -> bool {
+        self.deployed + self.failed == self.total
+    }
+}
+
+impl fmt::Display for BulkProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Deployed {}/{}", self.deployed, self.total)?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        if !self.finished() {
+            write!(f, "...")?;
+        }
+        Ok(())
+    }
+}
+
+// run against the live dir as it is, without pulling or rebuilding
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Lifecycle {
+    Stop,
+    Start,
+    Restart,
+    // `compose down`, also removing the project's volumes when set
+    TearDown { volumes: bool },
+}
+
+impl Lifecycle {
+    pub fn run(
+        self,
+        service: &Service,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        match self {
+            Lifecycle::Stop => service.stop(config, cancel, br),
+            Lifecycle::Start => service.start(config, cancel, br),
+            Lifecycle::Restart => service.restart(config, cancel, br),
+            Lifecycle::TearDown { volumes } => service.down(config, volumes, cancel, br),
+        }
+    }
+
+    // what the service is left as once the action succeeds
+    pub fn status(self) -> ServiceStatus {
+        match self {
+            Lifecycle::Stop => ServiceStatus::Stopped,
+            Lifecycle::Start | Lifecycle::Restart => ServiceStatus::Running,
+            Lifecycle::TearDown { .. } => ServiceStatus::TornDown,
+        }
+    }
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stop => write!(f, "stop"),
+            Self::Start => write!(f, "start"),
+            Self::Restart => write!(f, "restart"),
+            Self::TearDown { .. } => write!(f, "tear down"),
+        }
+    }
+}
+
+// where docker disagrees with the service's row
+#[derive(Clone, Debug, Serialize)]
+pub enum Drift {
+    NotRunning,
+    ArchivedRunning,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotRunning => write!(f, "Active but not running"),
+            Self::ArchivedRunning => write!(f, "Running but archived"),
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Service {
     pub id: i64,
     pub name: String,
+    // comma-separated compose services, or "*" for all; the first gets the Traefik route
     pub compose_name: String,
     pub repo_url: String,
     pub access_url: String,
     pub active: bool,
     pub use_key: bool,
+    pub pre_deploy_cmd: Option<String>,
+    pub post_deploy_cmd: Option<String>,
+    pub deploy_key_id: Option<i64>,
+    pub archived: bool,
+    pub schedule: Option<String>,
+    pub poll_interval_secs: Option<i64>,
+    // glob such as "v*"; when set, only the highest matching tag is deployed
+    pub tag_pattern: Option<String>,
+    pub clone_depth: Option<i64>,
+    // path within the repo; found among COMPOSE_FILES when unset
+    pub compose_file: Option<String>,
+    // for compose files that build from a local Dockerfile
+    pub build_before_up: bool,
+    // tag built images with the commit and push them to REGISTRY
+    pub push_image: bool,
+    // remote daemon (ssh://user@host) or docker context name; local when unset
+    pub docker_host: Option<String>,
+    // deployed by this node's agent instead of locally
+    pub node_id: Option<i64>,
+    // dashboard section and target of group-wide deploys
+    pub group_id: Option<i64>,
+    // tier such as "production" or "staging"; a repo is registered once per environment
+    pub environment: Option<String>,
+    // deployed instead of the remote's default branch
+    pub branch: Option<String>,
+    // the container port the reverse proxy forwards to, for images exposing several
+    pub proxy_port: Option<i64>,
+    // YAML merged over the repo's compose file as OVERRIDE_FILE, for host-specific tweaks
+    pub compose_override: Option<String>,
+    // deploys wait on the dashboard for someone to approve their compose diff
+    pub review_required: bool,
+    // deploys started by its hook, schedule or poller wait for someone to approve them
+    pub requires_approval: bool,
+    // comma-separated repo paths; when set, the poller only deploys commits touching them
+    pub watch_paths: Option<String>,
+    // when set, an unhealthy deploy rolls back after this many seconds
+    pub health_grace_secs: Option<i64>,
+    // comma-separated named volumes snapshotted before each deploy
+    pub backup_volumes: Option<String>,
+    // run with `compose exec` once the service is up, in `post_start_container` or the
+    // first compose service
+    pub post_start_exec: Option<String>,
+    pub post_start_container: Option<String>,
+    // absolute directories holding the clone and the live dir instead of SERVICE_REPO_PATH
+    // and SERVICE_LIVE_PATH; see `repo_root` and `live_root`
+    pub repo_dir: Option<String>,
+    pub live_dir: Option<String>,
+    // requested once the service is up; see `smoke_test`
+    pub smoke_url: Option<String>,
+    pub smoke_status: Option<i64>,
+    pub smoke_regex: Option<String>,
+    // the compose service a one-off job's deploy runs to completion instead of `up -d`;
+    // see `run_one_off`
+    pub one_off: Option<String>,
+    // e.g. "linux/arm64"; what compose pulls and builds for, as DOCKER_DEFAULT_PLATFORM
+    pub platform: Option<String>,
+    // secret env vars become files compose mounts as docker secrets instead of `.env` lines;
+    // see `write_secret_files`
+    pub secret_files: bool,
+    // pull requests sent to its hook get a preview service of their own; see `preview`
+    pub previews: bool,
+    // the service a pull request preview was copied from; set only when it's created
+    pub preview_of: Option<i64>,
+    // the steps its deploys run, comma-separated and in order; None runs them all, see
+    // `pipeline::STEPS`
+    pub pipeline: Option<String>,
+    // commands of its own run as pipeline steps, as a YAML list; see `script::Script`
+    pub scripts: Option<String>,
+    // commit checked out by the last pull, or the remote head last seen by the poller
+    pub last_commit: Option<String>,
+    // the latest pipeline status and when it was reached (UTC); see `recorded_status`
+    pub last_status: Option<String>,
+    pub last_status_detail: Option<String>,
+    pub last_status_at: Option<String>,
 }
 
 #[allow(non_snake_case, dead_code)]
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DockerServiceEntry {
     ID: String,
     Image: String,
     Names: String,
     Labels: String,
     State: String,
+    // e.g. "Up 2 minutes (healthy)"
+    #[serde(default)]
+    Status: String,
+}
+
+impl DockerServiceEntry {
+    fn label(&self, key: &str) -> Option<&str> {
+        self.Labels
+            .split(',')
+            .filter_map(|label| label.split_once('='))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+}
+
+#[allow(non_snake_case, dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct DockerStatsEntry {
+    ID: String,
+    CPUPerc: String,
+    MemUsage: String,
+    MemPerc: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ContainerUsage {
+    pub service: String,
+    pub cpu: String,
+    pub memory: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Deployment {
+    pub id: i64,
+    pub created_at: String,
+    pub commit_sha: Option<String>,
+    // registry references pushed for this commit, for other hosts to pull
+    pub images: Vec<String>,
+    // from the request to the containers being up; None for deployments from before
+    // they were timed
+    pub duration_ms: Option<i64>,
+    pub steps: Vec<StepTiming>,
+    // succeeded, failed or cancelled
+    pub outcome: String,
+}
+
+// what a deploy logged and the compose file it left in the live dir, for download
+#[derive(Clone, Debug)]
+pub struct DeploymentLog {
+    pub id: i64,
+    pub log: String,
+    pub compose_file: Option<String>,
+}
+
+impl Deployment {
+    pub fn succeeded(&self) -> bool {
+        self.outcome == "succeeded"
+    }
+
+    pub fn short_commit(&self) -> Option<&str> {
+        self.commit_sha.as_deref().map(short_commit)
+    }
+
+    pub fn duration(&self) -> Option<String> {
+        self.duration_ms
+            .map(|millis| timing::format_millis(millis.max(0) as u64))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ContainerState {
+    pub service: String,
+    pub state: String,
 }
 
 #[allow(dead_code)]
@@ -133,10 +667,34 @@ pub enum ServiceError {
     Start,
     #[error("Error stopping the Docker service")]
     Stop,
-    #[error("Error removing the contents of a directory")]
-    Remove,
-    #[error("Error copying the contents of a directory")]
-    Copy,
+    #[error("Error restarting the Docker service")]
+    Restart,
+    #[error("Error tearing down the Docker service")]
+    Down,
+    #[error("Error building the Docker images")]
+    Build,
+    #[error("Error pushing images to the registry")]
+    Push,
+    #[error("Set REGISTRY to push images")]
+    Registry,
+    #[error("Pushing images requires building before up")]
+    PushWithoutBuild,
+    #[error("Error removing {0}")]
+    Remove(String),
+    #[error("Error copying {0}")]
+    Copy(String),
+    #[error(
+        "Service name must be 1-64 letters, digits, '-', '_' or '.', starting with a letter or digit"
+    )]
+    Name,
+    #[error(
+        "Repo URL must be an https://, ssh:// or git:// URL, user@host:path, or an absolute path"
+    )]
+    RepoUrl,
+    #[error("Access URL must be a host name, optionally followed by a path, without a scheme")]
+    AccessUrl,
+    #[error("{0} exists but is not a directory")]
+    NotADirectory(String),
     #[error("Error parsing YAML file")]
     Yaml(#[from] SerdeError),
     #[error("Error parsing expected key")]
@@ -145,293 +703,1198 @@ pub enum ServiceError {
     Delete,
     #[error("Error running database action")]
     Db(#[from] DBError),
+    #[error("Error running deploy hook")]
+    Hook(String),
+    #[error("Error running post-start exec")]
+    Exec(String),
+    #[error("Error running script")]
+    Script(String),
+    #[error("Name the compose service to exec in when all of them are managed")]
+    ExecContainer,
+    #[error("Error writing the service .env file")]
+    EnvFile,
+    #[error("Error decrypting a secret value")]
+    Secret(#[from] SecretError),
+    #[error("Archived services cannot be deployed")]
+    Archived,
+    #[error("Invalid schedule | {0}")]
+    Schedule(#[from] CronError),
+    #[error("Poll interval must be at least {MIN_POLL_SECS} seconds")]
+    PollInterval,
+    #[error("Error reading the remote head")]
+    RemoteHead,
+    #[error("Compose services must be a comma-separated list of names, or *")]
+    ComposeName,
+    #[error("The repo's compose file has no service named {0}; it has {1}")]
+    UnknownComposeService(String, String),
+    #[error("No compose file found; searched {}", .0.join(", "))]
+    ComposeFile(Vec<String>),
+    #[error("Compose file must be a relative path inside the repo")]
+    ComposePath,
+    #[error("Clone depth must be 0 (full history) or more")]
+    CloneDepth,
+    #[error("No tag matches '{0}'")]
+    NoMatchingTag(String),
+    #[error("{0}")]
+    Agent(String),
+    #[error("Node reported {0}")]
+    Remote(ServiceStatus),
+    #[error("Health grace period must be at least 1 second")]
+    HealthGrace,
+    #[error("Environment may only contain letters, numbers, - and _")]
+    Environment,
+    #[error("Invalid branch name")]
+    Branch,
+    #[error("Proxy port must be between 1 and 65535")]
+    ProxyPort,
+    #[error("Compose override must be a YAML mapping | {0}")]
+    ComposeOverride(String),
+    #[error("Watched paths must be relative paths inside the repo")]
+    WatchPaths,
+    #[error("Deploy review isn't supported for services on nodes")]
+    ReviewOnNode,
+    #[error("Set a branch or a release tag pattern, not both")]
+    BranchWithTags,
+    #[error("Service never became healthy and there is no previous version to restore")]
+    Unhealthy,
+    #[error("Service never became healthy and was rolled back")]
+    RolledBack,
+    #[error("{0} timed out")]
+    Timeout(String),
+    #[error("Deployment cancelled")]
+    Cancelled,
+    #[error("Error creating the Docker network '{0}'")]
+    Network(String),
+    #[error("Backed up volumes must be a comma-separated list of volume names")]
+    BackupVolumes,
+    #[error("Volume backups aren't supported for services on nodes")]
+    BackupOnNode,
+    #[error("Error backing up volume '{0}'")]
+    VolumeBackup(String),
+    #[error("Error restoring volume '{0}'")]
+    VolumeRestore(String),
+    #[error("No volume snapshot is named '{0}'")]
+    NoSnapshot(String),
+    #[error("Terminals aren't supported for services on nodes")]
+    TerminalOnNode,
+    #[error("Invalid compose service '{0}'")]
+    Container(String),
+    #[error("Browsing files isn't supported for services on nodes")]
+    BrowseOnNode,
+    #[error("Nothing to show at '{0}' in the live dir")]
+    BrowsePath(String),
+    #[error("Repo and live directories must be absolute paths, without '..'")]
+    ServiceDir,
+    #[error("The repo and live directories can't be the same")]
+    SameDirs,
+    #[error("Smoke test URL must be an http or https URL")]
+    SmokeUrl,
+    #[error("Expected smoke test status must be an HTTP status, and needs a URL")]
+    SmokeStatus,
+    #[error("Invalid smoke test regex | {0}")]
+    SmokeRegex(String),
+    #[error("Smoke test failed | {0}")]
+    Smoke(String),
+    #[error(
+        "Job name must be 1-64 letters, digits, '-', '_' or '.', starting with a letter or digit"
+    )]
+    JobName,
+    #[error("Job mode must be exec or run")]
+    JobMode,
+    #[error("Job command can't be empty")]
+    JobCommand,
+    #[error("Scheduled jobs aren't supported for services on nodes")]
+    JobOnNode,
+    #[error("One-off job must name a compose service")]
+    OneOff,
+    #[error("One-off jobs can't have a health grace period, post-start exec or smoke test")]
+    OneOffOptions,
+    #[error("Platform must be os/arch, optionally with a variant, like linux/arm64")]
+    Platform,
+    #[error("Pipeline can't run: {0}")]
+    Pipeline(String),
+    #[error("Scripts can't run: {0}")]
+    Scripts(String),
+    #[error("One-off job exited with {0}")]
+    OneOffExit(String),
 }
 
-impl Service {
-    pub fn label_name(&self) -> String {
-        format!("|||{}|||", self.name)
+// why a deploy failed and the commit it had checked out by then; None when it failed before
+// checking one out, so the failure isn't pinned on whatever deployed last
+#[derive(Debug)]
+pub struct DeployFailure {
+    pub error: ServiceError,
+    pub commit: Option<String>,
+}
+
+impl From<ServiceError> for DeployFailure {
+    fn from(error: ServiceError) -> Self {
+        DeployFailure {
+            error,
+            commit: None,
+        }
     }
+}
 
-    pub async fn get_list() -> Result<Vec<DockerServiceEntry>, ServiceError> {
-        let output = match Command::new("docker")
-            .args(vec!["ps", "--format", "json"])
-            .output()
-        {
-            Ok(json) => json,
-            Err(e) => {
-                event!(Level::ERROR, "{}", e);
-                return Err(ServiceError::Command(e));
-            }
-        };
+pub const MIN_POLL_SECS: i64 = 30;
 
-        match output.status.success() {
-            true => (),
-            false => {
-                return Err(ServiceError::Status);
-            }
-        }
+// a relative path that can't climb out of the repo
+fn inside_repo(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
 
-        let output_string = std::str::from_utf8(&output.stdout)?;
+// the name doubles as the service's directory under the repo and live directories; kept
+// paths like `.{name}.previous` sit beside those, so a leading dot is refused too
+pub fn valid_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.starts_with('.')
+        && matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(n)), None) if n == name
+        )
+}
 
-        let containers: Vec<DockerServiceEntry> = output_string
-            .lines()
-            .filter(|line| !line.is_empty())
-            .filter_map(|line| serde_json::from_str(line).ok())
-            .collect();
+// what new and edited services are named: letters, digits, '-', '_' and '.', starting with a
+// letter or digit, so the name is safe as a directory, compose project and label
+pub fn valid_slug(name: &str) -> bool {
+    name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
 
-        Ok(containers)
-    }
+// os/arch or os/arch/variant, as docker names platforms
+fn valid_platform(platform: &str) -> bool {
+    let parts: Vec<&str> = platform.split('/').collect();
+    (2..=3).contains(&parts.len())
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        })
+}
 
-    pub fn is_running(&self, services: &Vec<DockerServiceEntry>) -> bool {
-        match services.len() {
-            0 => {
-                // no services running
-                false
-            }
-            _ => {
-                match services
-                    .iter()
-                    .find(|service| service.Labels.contains(&self.label_name()))
-                {
-                    Some(service) => service.State == "running".to_string(),
-                    None => false,
-                }
+// anything git clones: a URL, scp-like user@host:path, or a path on this host
+pub fn valid_repo_url(url: &str) -> bool {
+    if url.is_empty()
+        || url.starts_with('-')
+        || url.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return false;
+    }
+    if let Some((scheme, rest)) = url.split_once("://") {
+        return match scheme {
+            "https" | "http" | "ssh" | "git" => {
+                rest.split('/').next().is_some_and(|host| !host.is_empty())
             }
+            "file" => rest.starts_with('/'),
+            _ => false,
+        };
+    }
+    if url.starts_with('/') {
+        return true;
+    }
+    match url.split_once(':') {
+        Some((host, path)) => {
+            let host = host.rsplit('@').next().unwrap_or(host);
+            !host.is_empty() && !host.contains('/') && !path.is_empty()
         }
+        None => false,
     }
+}
 
-    pub fn try_delete(&self, mut parent_path: PathBuf) -> () {
-        parent_path.push(self.name.clone());
-        let path = parent_path;
-        let _ = Command::new("rm")
-            .args(vec!["-rf", &path.to_string_lossy().to_string()])
-            .output();
-        ()
+// routed by the proxy as a host and an optional path prefix
+pub fn valid_access_url(url: &str) -> bool {
+    let host = url.split('/').next().unwrap_or_default();
+    !host.is_empty()
+        && !url.contains("://")
+        && !url
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '`')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':' | '*'))
+}
+
+pub fn short_commit(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+// an absolute path that doesn't climb with `..`
+fn valid_service_dir(dir: &str) -> bool {
+    let path = Path::new(dir);
+    path.is_absolute()
+        && path
+            .components()
+            .all(|c| !matches!(c, std::path::Component::ParentDir))
+}
+
+pub fn valid_environment(env: &str) -> bool {
+    !env.is_empty()
+        && env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// the services a dashboard view, bulk deploy or API listing is narrowed to
+#[derive(Clone, Debug, Default)]
+pub struct ServiceFilter {
+    pub group: Option<i64>,
+    pub environment: Option<String>,
+    // part of the name or repo URL, in any case
+    pub search: Option<String>,
+    // a single service, for views of just that one
+    pub service: Option<i64>,
+}
+
+impl ServiceFilter {
+    pub fn matches(&self, service: &Service) -> bool {
+        self.includes(service.id)
+            && self.group.is_none_or(|g| service.group_id == Some(g))
+            && self
+                .environment
+                .as_ref()
+                .is_none_or(|e| service.environment.as_ref() == Some(e))
+            && self.search.as_ref().is_none_or(|s| {
+                let s = s.to_lowercase();
+                service.name.to_lowercase().contains(&s)
+                    || service.repo_url.to_lowercase().contains(&s)
+            })
     }
 
-    pub fn delete(&self, mut parent_path: PathBuf) -> Result<(), ServiceError> {
-        parent_path.push(self.name.clone());
-        let path = parent_path;
-        Command::new("rm")
-            .args(vec!["-rf", &path.to_string_lossy().to_string()])
-            .output()?;
-        Ok(())
+    // only the id is known for events, so a group, environment or search doesn't narrow them
+    pub fn includes(&self, id: i64) -> bool {
+        self.service.is_none_or(|s| s == id)
     }
+}
+const HEALTH_POLL: std::time::Duration = std::time::Duration::from_secs(2);
+// in the order docker compose itself prefers them
+pub const COMPOSE_FILES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+// written next to the compose file when the service has an override
+pub const OVERRIDE_FILE: &str = "docker-compose.wraut.yml";
+// the `kept_path`s a service can have next to its live home; "previous" and "failed" are
+// left by health-gated deploys from before releases
+pub const KEPT_KINDS: [&str; 4] = ["previous", "failed", "dry-run", "legacy"];
 
-    pub fn try_remove_from_docker(&self, mut parent_path: PathBuf) -> () {
-        parent_path.push(self.name.clone());
-        let path = parent_path;
-        let _ = Command::new("docker")
-            .args(vec!["compose", "rm", "-f"])
-            .current_dir(path)
-            .output();
+impl Service {
+    pub fn recorded_status(&self) -> Option<ServiceStatus> {
+        ServiceStatus::from_record(
+            self.last_status.as_deref()?,
+            self.last_status_detail.as_deref(),
+        )
+    }
+
+    pub fn label_name(&self) -> String {
+        format!("|||{}|||", self.name)
+    }
 
-        ()
+    pub fn parsed_schedule(&self) -> Result<Option<Schedule>, CronError> {
+        self.schedule.as_deref().map(str::parse).transpose()
     }
 
-    fn make_labels(&self) -> Vec<String> {
-        // TODO: swap websecure and certresolver out for config values.
-        vec![
-            self.label_name(),
-            "traefik.enable=true".into(),
-            format!(
-                "traefik.http.routers.{}.entrypoints=websecure",
-                self.name.clone()
-            ),
-            format!(
-                "traefik.http.routers.{}.rule=Host(`{}`)",
-                self.name.clone(),
-                self.access_url
-            ),
-            format!("traefik.http.routers.{}.tls=true", self.name.clone()),
-            format!(
-                "traefik.http.routers.{}.tls.certresolver=letsencrypt",
-                self.name.clone()
+    // None means every service in the compose file
+    pub fn compose_targets(&self) -> Option<Vec<String>> {
+        match self.compose_name.trim() {
+            "*" => None,
+            names => Some(
+                names
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_string)
+                    .collect(),
             ),
-        ]
+        }
     }
 
-    // on Result::Ok, returns path, and a boolean: true = created; false = got existing
-    fn get_or_create_directory(path: PathBuf) -> Result<(PathBuf, bool), ServiceError> {
-        let chkdir_output = Command::new("sh")
-            .arg("-c")
-            .arg(format!(
-                "[ -d {} ] && echo \"Y\" || echo \"N\"",
-                path.to_string_lossy()
-            ))
-            .output()?;
+    // checks user-provided settings before they're saved
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        match self.field_errors().into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
 
-        match chkdir_output.status.success() {
-            true => (),
-            false => {
-                return Err(ServiceError::Status);
+    // every problem with the service, keyed by the form field to show it against
+    pub fn field_errors(&self) -> Vec<(&'static str, ServiceError)> {
+        let mut errors = vec![];
+        if !valid_slug(&self.name) {
+            errors.push(("name", ServiceError::Name));
+        }
+        if self.compose_targets().is_some_and(|names| names.is_empty()) {
+            errors.push(("compose_name", ServiceError::ComposeName));
+        }
+        if !valid_repo_url(&self.repo_url) {
+            errors.push(("repo_url", ServiceError::RepoUrl));
+        }
+        if !valid_access_url(&self.access_url) {
+            errors.push(("access_url", ServiceError::AccessUrl));
+        }
+        if let Err(e) = self.parsed_schedule() {
+            errors.push(("schedule", e.into()));
+        }
+        if self.push_image && !self.build_before_up {
+            errors.push(("push_image", ServiceError::PushWithoutBuild));
+        }
+        if self
+            .poll_interval_secs
+            .is_some_and(|secs| secs < MIN_POLL_SECS)
+        {
+            errors.push(("poll_interval_secs", ServiceError::PollInterval));
+        }
+        if self.clone_depth.is_some_and(|d| d < 0) {
+            errors.push(("clone_depth", ServiceError::CloneDepth));
+        }
+        if self.health_grace_secs.is_some_and(|secs| secs < 1) {
+            errors.push(("health_grace_secs", ServiceError::HealthGrace));
+        }
+        if self
+            .proxy_port
+            .is_some_and(|port| !(1..=65535).contains(&port))
+        {
+            errors.push(("proxy_port", ServiceError::ProxyPort));
+        }
+        if self
+            .environment
+            .as_deref()
+            .is_some_and(|env| !valid_environment(env))
+        {
+            errors.push(("environment", ServiceError::Environment));
+        }
+        if let Some(branch) = &self.branch {
+            if branch.starts_with('-')
+                || branch.contains("..")
+                || branch.chars().any(|c| c.is_whitespace() || c.is_control())
+            {
+                errors.push(("branch", ServiceError::Branch));
+            }
+            if self.tag_pattern.is_some() {
+                errors.push(("tag_pattern", ServiceError::BranchWithTags));
             }
         }
-
-        let chkdir_output_string = std::str::from_utf8(&chkdir_output.stdout)?;
-
-        match chkdir_output_string {
-            "Y\n" => Ok((path, false)),
-            "N\n" => {
-                let mkdir_output = Command::new("mkdir")
-                    .arg(format!("{}", path.to_string_lossy()))
-                    .output()?;
-
-                match mkdir_output.status.success() {
-                    true => Ok((path, true)),
-                    false => Err(ServiceError::Status),
-                }
+        if self.review_required && self.node_id.is_some() {
+            errors.push(("review_required", ServiceError::ReviewOnNode));
+        }
+        if let Some(text) = &self.compose_override {
+            match serde_yaml::from_str::<serde_yaml::Value>(text) {
+                Ok(serde_yaml::Value::Mapping(_)) => (),
+                Ok(_) => errors.push((
+                    "compose_override",
+                    ServiceError::ComposeOverride("not a mapping".to_string()),
+                )),
+                Err(e) => errors.push((
+                    "compose_override",
+                    ServiceError::ComposeOverride(e.to_string()),
+                )),
+            }
+        }
+        if self
+            .compose_file
+            .as_deref()
+            .is_some_and(|f| !inside_repo(f))
+        {
+            errors.push(("compose_file", ServiceError::ComposePath));
+        }
+        if self
+            .watched_paths()
+            .is_some_and(|paths| paths.is_empty() || !paths.iter().all(|p| inside_repo(p)))
+        {
+            errors.push(("watch_paths", ServiceError::WatchPaths));
+        }
+        if !self.valid_backup_volumes() {
+            errors.push(("backup_volumes", ServiceError::BackupVolumes));
+        }
+        if self.post_start_exec.is_some() && self.exec_container().is_none() {
+            errors.push(("post_start_container", ServiceError::ExecContainer));
+        }
+        if self.backup_volumes.is_some() && self.node_id.is_some() {
+            errors.push(("backup_volumes", ServiceError::BackupOnNode));
+        }
+        for (field, dir) in [("repo_dir", &self.repo_dir), ("live_dir", &self.live_dir)] {
+            if dir.as_deref().is_some_and(|d| !valid_service_dir(d)) {
+                errors.push((field, ServiceError::ServiceDir));
+            }
+        }
+        if self.repo_dir.is_some() && self.repo_dir == self.live_dir {
+            errors.push(("live_dir", ServiceError::SameDirs));
+        }
+        if !self.valid_smoke_url() {
+            errors.push(("smoke_url", ServiceError::SmokeUrl));
+        }
+        if self
+            .smoke_status
+            .is_some_and(|s| !(100..=599).contains(&s) || self.smoke_url.is_none())
+        {
+            errors.push(("smoke_status", ServiceError::SmokeStatus));
+        }
+        if let Err(e) = self.parsed_smoke_regex() {
+            errors.push(("smoke_regex", e));
+        }
+        if let Some(one_off) = &self.one_off {
+            if !network::valid_name(one_off) {
+                errors.push(("one_off", ServiceError::OneOff));
+            }
+            if self.health_grace_secs.is_some()
+                || self.post_start_exec.is_some()
+                || self.smoke_url.is_some()
+            {
+                errors.push(("one_off", ServiceError::OneOffOptions));
+            }
+        }
+        if self.platform.as_deref().is_some_and(|p| !valid_platform(p)) {
+            errors.push(("platform", ServiceError::Platform));
+        }
+        if let Some(Err(e)) = self.pipeline.as_deref().map(pipeline::check) {
+            errors.push(("pipeline", ServiceError::Pipeline(e)));
+        }
+        if let Some(text) = &self.scripts {
+            let steps = pipeline::step_names(self.pipeline.as_deref());
+            if let Err(e) = script::parse(text).and_then(|scripts| script::check(&scripts, &steps))
+            {
+                errors.push(("scripts", ServiceError::Scripts(e)));
             }
-            _ => Err(ServiceError::Unexpected),
         }
+        errors
     }
 
-    pub fn clone_or_pull(
+    pub fn watched_paths(&self) -> Option<Vec<&str>> {
+        self.watch_paths.as_deref().map(|paths| {
+            paths
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .collect()
+        })
+    }
+
+    // an associated deploy key wins over the legacy global KEY_FILE
+    pub async fn git_auth(
         &self,
-        config: Config,
-        br: &broadcast::Sender<ServiceEvent>,
-    ) -> Result<(), ServiceError> {
-        let cf_string_opt = match self.use_key {
-            true => Some(format!(
-                " -c \"core.sshCommand=ssh -i {}\" ",
-                config.key_file.to_string_lossy().to_string()
-            )),
-            false => None,
+        config: &Config,
+        pool: &SqlitePool,
+    ) -> Result<GitAuth, ServiceError> {
+        let key_path = match (self.deploy_key_id, self.use_key) {
+            (Some(key_id), _) => Some(PathBuf::from(get_deploy_key(pool, key_id).await?.key_path)),
+            (None, true) => Some(config.key_file.clone()),
+            (None, false) => None,
         };
+        let credential = match get_git_credential(pool, self.id).await? {
+            Some(credential) => Some(credential.reveal(config.secrets_key.as_ref())?),
+            None => None,
+        };
+        Ok(GitAuth {
+            key_path,
+            credential,
+        })
+    }
 
-        let mut path = config.services_repo_dir;
-        path.push(&self.name);
-
-        let (path, created) = Service::get_or_create_directory(path)?;
+    // commit the remote's default branch (or the service's branch, or newest matching tag)
+    // points at, without touching the local clone
+    pub fn remote_head(&self, auth: GitAuth) -> Result<String, ServiceError> {
+        let mut git = auth.command();
+        match (&self.tag_pattern, &self.branch) {
+            (Some(_), _) => git.args(["ls-remote", "--tags", &self.repo_url]),
+            (None, Some(branch)) => git.args([
+                "ls-remote",
+                &self.repo_url,
+                &format!("refs/heads/{}", branch),
+            ]),
+            (None, None) => git.args(["ls-remote", &self.repo_url, "HEAD"]),
+        };
+        let output = git.output()?;
+        if !output.status.success() {
+            event!(
+                Level::WARN,
+                "ls-remote failed for {} | {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Err(ServiceError::RemoteHead);
+        }
+        let stdout = std::str::from_utf8(&output.stdout)?;
+
+        let Some(pattern) = &self.tag_pattern else {
+            return stdout
+                .split_whitespace()
+                .next()
+                .map(str::to_string)
+                .ok_or(ServiceError::RemoteHead);
+        };
 
-        let output: Output = match created {
-            true => {
-                let _ = br.send(ServiceEvent::ServiceUpdate {
-                    id: self.id,
-                    status: ServiceStatus::Cloning,
-                });
-
-                match cf_string_opt {
-                    Some(cf_string) => Command::new("git")
-                        .arg("clone")
-                        .arg(cf_string)
-                        .arg(self.repo_url.clone())
-                        .arg(path.to_string_lossy().to_string())
-                        .output()?,
-                    None => Command::new("git")
-                        .arg("clone")
-                        .arg(self.repo_url.clone())
-                        .arg(path.to_string_lossy().to_string())
-                        .output()?,
-                }
+        // annotated tags are listed twice; the peeled "^{}" line holds the commit
+        let mut tags: Vec<(&str, &str)> = vec![];
+        for (commit, reference) in stdout.lines().filter_map(|l| l.split_once('\t')) {
+            let Some(tag) = reference.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            match tag.strip_suffix("^{}") {
+                Some(tag) => match tags.iter_mut().find(|(t, _)| *t == tag) {
+                    Some(entry) => entry.1 = commit,
+                    None => tags.push((tag, commit)),
+                },
+                None if !tags.iter().any(|(t, _)| *t == tag) => tags.push((tag, commit)),
+                None => (),
             }
-            false => {
-                let _ = br.send(ServiceEvent::ServiceUpdate {
-                    id: self.id,
-                    status: ServiceStatus::Pulling,
-                });
-
-                match cf_string_opt {
-                    Some(cf_string) => Command::new("git")
-                        .arg("pull")
-                        .arg(cf_string)
-                        .current_dir(path)
-                        .output()?,
-                    None => Command::new("git").arg("pull").current_dir(path).output()?,
-                }
-            }
-        };
+        }
+        let tag = release::pick_tag(pattern, tags.iter().map(|(t, _)| *t))
+            .ok_or_else(|| ServiceError::NoMatchingTag(pattern.clone()))?;
+        tags.iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, commit)| commit.to_string())
+            .ok_or(ServiceError::RemoteHead)
+    }
 
+    // detaches the clone at the highest local tag matching the pattern
+    fn checkout_release(&self, path: &Path, pattern: &str) -> Result<(), ServiceError> {
+        let output = tools::git()
+            .args(["tag", "--list"])
+            .current_dir(path)
+            .output()?;
+        if !output.status.success() {
+            return Err(ServiceError::Status);
+        }
+        let tags = std::str::from_utf8(&output.stdout)?;
+        let tag = release::pick_tag(pattern, tags.lines())
+            .ok_or_else(|| ServiceError::NoMatchingTag(pattern.to_string()))?;
+
+        event!(
+            Level::INFO,
+            "Checking out release {} for {}",
+            tag,
+            self.name
+        );
+        let output = tools::git()
+            .args([
+                "checkout",
+                "--quiet",
+                "--detach",
+                &format!("refs/tags/{}", tag),
+            ])
+            .current_dir(path)
+            .output()?;
         match output.status.success() {
             true => Ok(()),
             false => {
                 event!(
                     Level::ERROR,
-                    "CLONE FAIL | {}",
-                    std::str::from_utf8(&output.stderr)?
+                    "CHECKOUT FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
                 );
                 Err(ServiceError::CloneOrPull)
             }
         }
     }
 
-    pub fn copy_to_live(
-        &self,
-        config: Config,
-        br: &broadcast::Sender<ServiceEvent>,
-    ) -> Result<(), ServiceError> {
-        let _ = br.send(ServiceEvent::ServiceUpdate {
-            id: self.id,
-            status: ServiceStatus::Copying,
-        });
-
-        let mut live_path = config.services_live_dir;
-        live_path.push(self.name.clone());
-
-        let (live_path, created) = Service::get_or_create_directory(live_path)?;
-
-        let mut live_path_contents = live_path.clone();
-        live_path_contents.push("*");
-
-        if !created {
-            let rm_outp = Command::new("rm")
-                .arg("-rf")
-                .arg(live_path_contents.to_string_lossy().to_string())
-                .output()?;
-
-            match rm_outp.status.success() {
-                true => (),
-                false => {
-                    event!(
-                        Level::ERROR,
-                        "{}",
-                        std::str::from_utf8(&rm_outp.stderr).unwrap_or("NA")
-                    );
-                    return Err(ServiceError::Remove);
-                }
-            }
-        }
-
-        let mut repo_path_contents = config.services_repo_dir;
-        repo_path_contents.push(self.name.clone());
-        repo_path_contents.push(".");
-
-        let cp_outp = Command::new("cp")
-            .arg("-af")
-            .arg(repo_path_contents.to_string_lossy().to_string())
-            .arg(".")
-            .current_dir(live_path.to_string_lossy().to_string())
+    // checks out the branch `update` just fetched, discarding whatever the clone had
+    fn checkout_fetched(path: &Path, branch: &str) -> Result<(), ServiceError> {
+        let output = tools::git()
+            .args(["checkout", "--quiet", "--force", "-B", branch, "FETCH_HEAD"])
+            .current_dir(path)
             .output()?;
-
-        match cp_outp.status.success() {
+        match output.status.success() {
             true => Ok(()),
             false => {
                 event!(
                     Level::ERROR,
-                    "{}",
-                    std::str::from_utf8(&cp_outp.stderr).unwrap_or("NA")
+                    "CHECKOUT FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
                 );
-                Err(ServiceError::Copy)
+                Err(ServiceError::CloneOrPull)
             }
         }
     }
 
-    pub fn apply_tags(
-        &self,
-        config: Config,
+    // a clone left detached by tag releases, or on a branch that's no longer set, goes
+    // back to the remote's default branch
+    fn reattach_branch(path: &Path) -> Result<(), ServiceError> {
+        let output = tools::git()
+            .args(["rev-parse", "--abbrev-ref", "origin/HEAD"])
+            .current_dir(path)
+            .output()?;
+        let default = std::str::from_utf8(&output.stdout)?.trim();
+        let branch = default
+            .strip_prefix("origin/")
+            .ok_or(ServiceError::CloneOrPull)?;
+
+        let current = tools::git()
+            .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .current_dir(path)
+            .output()?;
+        if current.status.success() && std::str::from_utf8(&current.stdout)?.trim() == branch {
+            return Ok(());
+        }
+
+        match tools::git()
+            .args(["checkout", "--quiet", branch])
+            .current_dir(path)
+            .status()?
+            .success()
+        {
+            true => Ok(()),
+            false => Err(ServiceError::CloneOrPull),
+        }
+    }
+
+    pub fn local_head(&self, config: &Config) -> Result<String, ServiceError> {
+        let output = tools::git()
+            .args(["rev-parse", "HEAD"])
+            .current_dir(self.repo_path(config))
+            .output()?;
+        match output.status.success() {
+            true => Ok(std::str::from_utf8(&output.stdout)?.trim().to_string()),
+            false => Err(ServiceError::Status),
+        }
+    }
+
+    // a URL such as ssh://deploy@host goes in DOCKER_HOST; anything else names a docker context
+    fn docker_command(host: Option<&str>) -> Command {
+        let mut docker = tools::docker();
+        match host {
+            Some(url) if url.contains("://") => {
+                docker.env("DOCKER_HOST", url);
+            }
+            Some(context) => {
+                docker.args(["--context", context]);
+            }
+            None => (),
+        }
+        docker
+    }
+
+    fn docker(&self) -> Command {
+        Self::docker_command(self.docker_host.as_deref())
+    }
+
+    // each distinct docker host the services run on, None being the local daemon
+    pub fn docker_hosts<'a>(
+        services: impl IntoIterator<Item = &'a Service>,
+    ) -> Vec<Option<String>> {
+        let mut hosts = vec![];
+        for service in services {
+            if !hosts.contains(&service.docker_host) {
+                hosts.push(service.docker_host.clone());
+            }
+        }
+        hosts
+    }
+
+    // one JSON object per output line from every host; unreachable hosts are skipped
+    // unless none of them answer
+    fn docker_json<T: DeserializeOwned>(
+        hosts: &[Option<String>],
+        args: &[&str],
+    ) -> Result<Vec<T>, ServiceError> {
+        let mut entries = vec![];
+        let mut failure = None;
+        let mut reached = false;
+        for host in hosts {
+            let output = match Self::docker_command(host.as_deref()).args(args).output() {
+                Ok(o) if o.status.success() => o,
+                Ok(o) => {
+                    event!(
+                        Level::WARN,
+                        "docker {} failed on {} | {}",
+                        args[0],
+                        host.as_deref().unwrap_or("local"),
+                        String::from_utf8_lossy(&o.stderr).trim()
+                    );
+                    failure = Some(ServiceError::Status);
+                    continue;
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "{}", e);
+                    failure = Some(ServiceError::Command(e));
+                    continue;
+                }
+            };
+            reached = true;
+            entries.extend(
+                std::str::from_utf8(&output.stdout)?
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .filter_map(|line| serde_json::from_str(line).ok()),
+            );
+        }
+
+        match (reached, failure) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(entries),
+        }
+    }
+
+    pub async fn get_list(
+        hosts: &[Option<String>],
+    ) -> Result<Vec<DockerServiceEntry>, ServiceError> {
+        // include stopped containers so partial failures show up
+        Self::docker_json(hosts, &["ps", "--all", "--format", "json"])
+    }
+
+    pub fn containers(&self, services: &[DockerServiceEntry]) -> Vec<ContainerState> {
+        services
+            .iter()
+            .filter(|service| service.Labels.contains(&self.label_name()))
+            .map(|service| ContainerState {
+                service: service
+                    .label("com.docker.compose.service")
+                    .unwrap_or(&service.Names)
+                    .to_string(),
+                state: service.State.clone(),
+            })
+            .collect()
+    }
+
+    pub async fn get_stats(
+        hosts: &[Option<String>],
+    ) -> Result<Vec<DockerStatsEntry>, ServiceError> {
+        Self::docker_json(hosts, &["stats", "--no-stream", "--format", "json"])
+    }
+
+    // the local daemon's version; one that doesn't answer within `timeout` counts as down
+    pub fn docker_version(timeout: std::time::Duration) -> Result<String, ServiceError> {
+        let mut command = Self::docker_command(None);
+        command.args(["version", "--format", "{{.Server.Version}}"]);
+        let output = process::output(
+            command,
+            Limit {
+                step: "docker version",
+                timeout,
+                cancel: &CancellationToken::new(),
+            },
+        )?;
+        if !output.status.success() {
+            event!(
+                Level::WARN,
+                "docker version failed | {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(ServiceError::Status);
+        }
+        Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
+    }
+
+    // usage of running containers, matched to the service through `docker ps` labels
+    pub fn usage(
+        &self,
+        services: &[DockerServiceEntry],
+        stats: &[DockerStatsEntry],
+    ) -> Vec<ContainerUsage> {
+        services
+            .iter()
+            .filter(|service| service.Labels.contains(&self.label_name()))
+            .filter_map(|service| {
+                let stat = stats.iter().find(|s| s.ID == service.ID)?;
+                let used = stat.MemUsage.split(" / ").next().unwrap_or_default();
+                Some(ContainerUsage {
+                    service: service
+                        .label("com.docker.compose.service")
+                        .unwrap_or(&service.Names)
+                        .to_string(),
+                    cpu: stat.CPUPerc.clone(),
+                    memory: format!("{} ({})", used, stat.MemPerc),
+                })
+            })
+            .collect()
+    }
+
+    // true when any of the service's containers is up
+    pub fn is_running(&self, services: &[DockerServiceEntry]) -> bool {
+        self.containers(services)
+            .iter()
+            .any(|container| container.state == "running")
+    }
+
+    // a container stopped without wraut stopping it; only meaningful while the service's
+    // recorded status is still Running
+    pub fn has_crashed(&self, services: &[DockerServiceEntry]) -> bool {
+        self.containers(services)
+            .iter()
+            .any(|container| container.state == "exited" || container.state == "dead")
+    }
+
+    pub fn drift(&self, services: &[DockerServiceEntry]) -> Option<Drift> {
+        // a service someone stopped or tore down is meant to be down
+        let stopped = matches!(
+            self.recorded_status(),
+            Some(ServiceStatus::Stopped | ServiceStatus::TornDown)
+        );
+        match (self.archived, self.active, self.is_running(services)) {
+            (true, _, true) => Some(Drift::ArchivedRunning),
+            // a one-off job's container is gone once it's done
+            (false, true, false) if !stopped && !self.is_one_off() => Some(Drift::NotRunning),
+            _ => None,
+        }
+    }
+
+    // every container is up and none is failing (or still starting) its healthcheck;
+    // containers without a healthcheck only need to be running
+    fn is_healthy(&self, services: &[DockerServiceEntry]) -> bool {
+        let mut containers = services
+            .iter()
+            .filter(|service| service.Labels.contains(&self.label_name()))
+            .peekable();
+        containers.peek().is_some()
+            && containers.all(|c| {
+                c.State == "running"
+                    && !c.Status.contains("(unhealthy)")
+                    && !c.Status.contains("(health: starting)")
+            })
+    }
+
+    async fn wait_healthy(
+        &self,
+        grace_secs: i64,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<bool, ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::CheckingHealth));
+
+        let deadline =
+            tokio::time::Instant::now() + std::time::Duration::from_secs(grace_secs as u64);
+        loop {
+            if let Ok(services) = Self::get_list(std::slice::from_ref(&self.docker_host)).await
+                && self.is_healthy(&services)
+            {
+                return Ok(true);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                event!(
+                    Level::WARN,
+                    "{} not healthy after {}s",
+                    self.name,
+                    grace_secs
+                );
+                return Ok(false);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(HEALTH_POLL) => (),
+                _ = cancel.cancelled() => return Err(ServiceError::Cancelled),
+            }
+        }
+    }
+
+    // the directory holding the service's clone: its own repo_dir, or SERVICE_REPO_PATH
+    pub fn repo_root(&self, config: &Config) -> PathBuf {
+        match &self.repo_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => config.services_repo_dir.clone(),
+        }
+    }
+
+    // the directory holding the service's live dir: its own live_dir, or SERVICE_LIVE_PATH
+    pub fn live_root(&self, config: &Config) -> PathBuf {
+        match &self.live_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => config.services_live_dir.clone(),
+        }
+    }
+
+    // an overridden directory is usually a mount; it's never created, so a missing mount
+    // fails the deploy instead of filling the disk beneath it
+    fn check_dir_mounted(&self, dir: &Option<String>) -> Result<(), ServiceError> {
+        match dir {
+            Some(dir) if !Path::new(dir).is_dir() => Err(ServiceError::NotADirectory(dir.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn repo_path(&self, config: &Config) -> PathBuf {
+        self.repo_root(config).join(&self.name)
+    }
+
+    // siblings of the live home: ".<name>.dry-run" is a dry run's scratch, ".<name>.legacy"
+    // a live dir from before releases on its way into them
+    fn kept_path(&self, live_dir: &Path, kind: &str) -> PathBuf {
+        live_dir.join(format!(".{}.{}", self.name, kind))
+    }
+
+    // stops the new version and brings back the release before it
+    fn rollback(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
         br: &broadcast::Sender<ServiceEvent>,
     ) -> Result<(), ServiceError> {
-        let _ = br.send(ServiceEvent::ServiceUpdate {
-            id: self.id,
-            status: ServiceStatus::RewritingConfig,
-        });
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::RollingBack));
+        event!(Level::WARN, "Rolling back {}", self.name);
+
+        if let Ok(mut compose) = self.compose(&self.live_path(&config)) {
+            compose.arg("stop");
+            let _ = process::output(
+                compose,
+                Limit {
+                    step: "compose stop",
+                    timeout: config.timeouts.compose,
+                    cancel,
+                },
+            );
+        }
+
+        self.restore_previous_release(&config)?;
+
+        // the new build replaced the previous images under the same names
+        if self.build_before_up {
+            self.build(config.clone(), cancel, br)?;
+        }
+        self.start(config, cancel, br)
+    }
+
+    pub fn container_status(&self, services: &[DockerServiceEntry]) -> ServiceStatus {
+        let containers = self.containers(services);
+        let running = containers.iter().filter(|c| c.state == "running").count();
+        match running {
+            0 => ServiceStatus::Inactive,
+            r if r == containers.len() => ServiceStatus::Running,
+            r => ServiceStatus::Degraded(format!("{}/{} containers running", r, containers.len())),
+        }
+    }
+
+    pub fn try_delete(&self, parent_path: PathBuf) {
+        let _ = self.delete(parent_path);
+    }
+
+    pub fn delete(&self, parent_path: PathBuf) -> Result<(), ServiceError> {
+        if !valid_name(&self.name) {
+            return Err(ServiceError::Name);
+        }
+        files::remove(&parent_path.join(&self.name))
+    }
+
+    // the configured compose file, or the first standard name present in the live dir
+    pub fn compose_path(&self, live_path: &Path) -> Result<PathBuf, ServiceError> {
+        let candidates: Vec<&str> = match &self.compose_file {
+            Some(file) => vec![file.as_str()],
+            None => COMPOSE_FILES.to_vec(),
+        };
+        candidates
+            .iter()
+            .map(|file| live_path.join(file))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                ServiceError::ComposeFile(candidates.iter().map(|c| c.to_string()).collect())
+            })
+    }
+
+    // beside the compose file, so its relative paths resolve the same way
+    pub fn override_path(&self, live_path: &Path) -> Result<PathBuf, ServiceError> {
+        let compose_path = self.compose_path(live_path)?;
+        Ok(compose_path
+            .parent()
+            .unwrap_or(live_path)
+            .join(OVERRIDE_FILE))
+    }
+
+    // the name docker compose derives from the live dir
+    fn project_name(&self) -> String {
+        self.name
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect()
+    }
+
+    // pins the project name to the service so a compose file in a subdirectory
+    // doesn't take that directory's name; compose merges the override over the file
+    fn compose(&self, live_path: &Path) -> Result<Command, ServiceError> {
+        let compose_path = self.compose_path(live_path)?;
+        let override_path = self.override_path(live_path)?;
+        let project = self.project_name();
+        let mut docker = self.docker();
+        docker.arg("compose").arg("-f").arg(compose_path);
+        if override_path.is_file() {
+            docker.arg("-f").arg(override_path);
+        }
+        docker.args(["-p", &project]).current_dir(live_path);
+        if let Some(platform) = &self.platform {
+            docker.env("DOCKER_DEFAULT_PLATFORM", platform);
+        }
+        Ok(docker)
+    }
+
+    pub fn try_remove_from_docker(&self, mut parent_path: PathBuf) {
+        parent_path.push(self.name.clone());
+        let path = parent_path;
+        if let Ok(mut docker) = self.compose(&path) {
+            let _ = docker.args(vec!["rm", "-f"]).output();
+        }
+    }
+
+    // on Result::Ok, returns path, and a boolean: true = created; false = got existing
+    fn get_or_create_directory(path: PathBuf) -> Result<(PathBuf, bool), ServiceError> {
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => Ok((path, false)),
+            Ok(_) => Err(ServiceError::NotADirectory(
+                path.to_string_lossy().to_string(),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::create_dir_all(&path)?;
+                Ok((path, true))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // a service's own depth wins over CLONE_DEPTH; 0 asks for full history
+    pub fn clone_depth(&self, config: &Config) -> Option<u32> {
+        match self.clone_depth {
+            Some(d) => u32::try_from(d).ok().filter(|d| *d > 0),
+            None => config.clone_depth,
+        }
+    }
+
+    fn is_shallow(path: &Path) -> bool {
+        tools::git()
+            .args(["rev-parse", "--is-shallow-repository"])
+            .current_dir(path)
+            .output()
+            .is_ok_and(|o| o.stdout.starts_with(b"true"))
+    }
+
+    // tag releases only need the tags, and a set branch only itself; the default branch
+    // pulls, which keeps any shallow boundary
+    fn update(
+        &self,
+        path: &Path,
+        auth: GitAuth,
+        depth: Option<u32>,
+        limit: Limit,
+    ) -> Result<Output, ServiceError> {
+        let mut git = auth.command();
+        match (&self.tag_pattern, &self.branch) {
+            (Some(_), _) => {
+                git.args(["fetch", "--tags", "--force", "--prune"]);
+                if let Some(d) = depth {
+                    git.arg(format!("--depth={}", d));
+                }
+            }
+            (None, Some(branch)) => {
+                git.args(["fetch", "--force", "origin", branch]);
+                if let Some(d) = depth {
+                    git.arg(format!("--depth={}", d));
+                }
+            }
+            (None, None) => {
+                git.arg("pull");
+            }
+        }
+        git.current_dir(path);
+        process::output(git, limit)
+    }
+
+    pub fn clone_or_pull(
+        &self,
+        config: Config,
+        auth: GitAuth,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let depth = self.clone_depth(&config);
+        let limit = |step| Limit {
+            step,
+            timeout: config.timeouts.git,
+            cancel,
+        };
+        self.check_dir_mounted(&self.repo_dir)?;
+        let path = self.repo_path(&config);
+
+        let (path, created) = Service::get_or_create_directory(path)?;
+
+        let output: Output = match created {
+            true => {
+                let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Cloning));
+
+                let mut git = auth.command();
+                git.arg("clone");
+                if let Some(d) = depth {
+                    git.arg(format!("--depth={}", d));
+                }
+                git.arg(self.repo_url.clone())
+                    .arg(path.to_string_lossy().to_string());
+                let output = process::output(git, limit("git clone"))?;
+                match output.status.success() {
+                    true if self.tag_pattern.is_some() || self.branch.is_some() => {
+                        self.update(&path, auth, depth, limit("git fetch"))?
+                    }
+                    true => output,
+                    // so the next attempt clones again instead of pulling an empty dir
+                    false => {
+                        let _ = std::fs::remove_dir_all(&path);
+                        output
+                    }
+                }
+            }
+            false => {
+                let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Pulling));
+
+                if self.tag_pattern.is_none() && self.branch.is_none() {
+                    Self::reattach_branch(&path)?;
+                }
+                let output = self.update(&path, auth.clone(), depth, limit("git pull"))?;
+                // a truncated history can lack the commits a fetch or merge needs
+                if !output.status.success() && Self::is_shallow(&path) {
+                    event!(
+                        Level::WARN,
+                        "Shallow update of {} failed, fetching full history | {}",
+                        self.name,
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    let mut git = auth.command();
+                    git.args(["fetch", "--unshallow"]).current_dir(&path);
+                    let unshallow = process::output(git, limit("git fetch"))?;
+                    match unshallow.status.success() {
+                        true => self.update(&path, auth, None, limit("git pull"))?,
+                        false => unshallow,
+                    }
+                } else {
+                    output
+                }
+            }
+        };
+
+        match output.status.success() {
+            true => match (&self.tag_pattern, &self.branch) {
+                (Some(pattern), _) => self.checkout_release(&path, pattern),
+                (None, Some(branch)) => Self::checkout_fetched(&path, branch),
+                (None, None) => Ok(()),
+            },
+            false => {
+                event!(
+                    Level::ERROR,
+                    "CLONE FAIL | {}",
+                    std::str::from_utf8(&output.stderr)?
+                );
+                Err(ServiceError::CloneOrPull)
+            }
+        }
+    }
+
+    // `vars` are substituted into the compose file's values; `secrets` are the keys of the
+    // secret files its compose services are granted
+    pub fn apply_tags(
+        &self,
+        config: Config,
+        vars: &[(String, String)],
+        secrets: &[String],
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let _ = br.send(ServiceEvent::update(
+            self.id,
+            ServiceStatus::RewritingConfig,
+        ));
 
         // Read docker-compose file
-        let mut compose_path = config.services_live_dir;
-        compose_path.push(self.name.clone());
-        compose_path.push("docker-compose.yml");
+        let compose_path = self.compose_path(&self.live_path(&config))?;
         let compose_content = std::fs::read_to_string(compose_path.clone())?;
         let mut compose: serde_yaml::Value = serde_yaml::from_str(&compose_content)?;
+        template::render_value(&mut compose, vars);
 
         // Get or create labels
         let services = match compose.get_mut("services") {
@@ -441,45 +1904,82 @@ impl Service {
             }
         };
 
-        let service = match services.get_mut(self.compose_name.clone()) {
-            Some(svc) => svc,
-            None => {
-                return Err(ServiceError::Key(self.compose_name.clone()));
-            }
+        let targets = match self.compose_targets() {
+            Some(names) => names,
+            None => services
+                .as_mapping()
+                .map(|m| {
+                    m.keys()
+                        .filter_map(|k| k.as_str())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
         };
+        if targets.is_empty() {
+            return Err(ServiceError::Key("services".into()));
+        }
 
-        let service_map = match service.as_mapping_mut() {
-            Some(sm) => sm,
-            None => {
-                return Err(ServiceError::Key(format!(
-                    "{} (as map)",
-                    self.compose_name.clone()
-                )));
-            }
-        };
+        for (i, target) in targets.iter().enumerate() {
+            let service = match services.get_mut(target) {
+                Some(svc) => svc,
+                None => {
+                    return Err(ServiceError::Key(target.clone()));
+                }
+            };
 
-        let labels = service_map
-            .entry(serde_yaml::Value::String("labels".into()))
-            .or_insert_with(|| serde_yaml::Value::Sequence(vec![]));
+            let service_map = match service.as_mapping_mut() {
+                Some(sm) => sm,
+                None => {
+                    return Err(ServiceError::Key(format!("{} (as map)", target)));
+                }
+            };
 
-        let label_array = match labels.as_sequence_mut() {
-            Some(la) => la,
-            None => {
-                return Err(ServiceError::Key(format!(
-                    "{} labels (as sequence)",
-                    self.compose_name.clone()
-                )));
+            // only the first service is routed; the rest are just tracked
+            let mut labels = vec![(self.label_name(), String::new())];
+            if i == 0 {
+                labels.extend(config.proxy.labels(self));
             }
-        };
+            proxy::inject(service_map, target, &labels)?;
+            if !config.shared_networks.is_empty() {
+                network::join(service_map, target, &config.shared_networks)?;
+            }
+            if !secrets.is_empty() {
+                docker_secret::grant(service_map, target, secrets)?;
+            }
+            if self.preview_of.is_some() {
+                preview::detach(service_map);
+            }
+        }
 
-        for label in self.make_labels() {
-            label_array.push(serde_yaml::Value::String(label))
+        if !secrets.is_empty() {
+            let dir = self.live_path(&config).join(env::SECRETS_DIR);
+            match compose.as_mapping_mut() {
+                Some(root) => docker_secret::declare(root, secrets, &dir)?,
+                None => return Err(ServiceError::Key("compose file (as map)".into())),
+            }
+        }
+
+        if !config.shared_networks.is_empty() {
+            match compose.as_mapping_mut() {
+                Some(root) => network::declare(root, &config.shared_networks)?,
+                None => return Err(ServiceError::Key("compose file (as map)".into())),
+            }
         }
 
         let yaml_string: String = serde_yaml::to_string(&compose)?;
 
         std::fs::write(compose_path, yaml_string)?;
 
+        if let Some(text) = &self.compose_override {
+            let mut compose_override: serde_yaml::Value = serde_yaml::from_str(text)?;
+            template::render_value(&mut compose_override, vars);
+            std::fs::write(
+                self.override_path(&self.live_path(&config))?,
+                serde_yaml::to_string(&compose_override)?,
+            )?;
+        }
+
         // Write back to file
         Ok(())
     }
@@ -487,23 +1987,26 @@ impl Service {
     pub fn stop(
         &self,
         config: Config,
+        cancel: &CancellationToken,
         br: &broadcast::Sender<ServiceEvent>,
     ) -> Result<(), ServiceError> {
-        let _ = br.send(ServiceEvent::ServiceUpdate {
-            id: self.id,
-            status: ServiceStatus::Stopping,
-        });
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Stopping));
 
-        let mut path = config.services_live_dir;
-        path.push(&self.name);
+        let timeout = config.timeouts.compose;
+        let path = self.live_path(&config);
 
         let (path, _) = Service::get_or_create_directory(path)?;
 
-        let outp = Command::new("docker")
-            .arg("compose")
-            .arg("stop")
-            .current_dir(path.to_string_lossy().to_string())
-            .output()?;
+        let mut compose = self.compose(&path)?;
+        compose.arg("stop");
+        let outp = process::output(
+            compose,
+            Limit {
+                step: "compose stop",
+                timeout,
+                cancel,
+            },
+        )?;
 
         match outp.status.success() {
             true => Ok(()),
@@ -514,15 +2017,13 @@ impl Service {
     pub fn start(
         &self,
         config: Config,
+        cancel: &CancellationToken,
         br: &broadcast::Sender<ServiceEvent>,
     ) -> Result<(), ServiceError> {
-        let _ = br.send(ServiceEvent::ServiceUpdate {
-            id: self.id,
-            status: ServiceStatus::Starting,
-        });
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Starting));
 
-        let mut path = config.services_live_dir;
-        path.push(&self.name);
+        let timeout = config.timeouts.compose;
+        let path = self.live_path(&config);
 
         let (path, created) = match Service::get_or_create_directory(path) {
             Ok(outp) => outp,
@@ -543,17 +2044,18 @@ impl Service {
 
         event!(Level::INFO, "{}", path.to_string_lossy().to_string());
 
-        let output = match Command::new("docker")
-            .arg("compose")
-            .arg("up")
-            .arg("-d")
-            .current_dir(path.to_string_lossy().to_string())
-            .output()
-        {
+        let mut compose = self.compose(&path)?;
+        compose.arg("up").arg("-d");
+        let limit = Limit {
+            step: "compose up",
+            timeout,
+            cancel,
+        };
+        let output = match process::output(compose, limit) {
             Ok(outp) => outp,
             Err(e) => {
                 event!(Level::ERROR, "DCE | {}", e);
-                return Err(ServiceError::Command(e));
+                return Err(e);
             }
         };
 
@@ -570,45 +2072,648 @@ impl Service {
         }
     }
 
-    pub async fn deploy(
+    // `compose restart` on the live dir as it is; containers keep their config
+    pub fn restart(
+        &self,
         config: Config,
-        service: Result<Service, DBError>,
-        br: broadcast::Sender<ServiceEvent>,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
     ) -> Result<(), ServiceError> {
-        // emit `ServiceEvent`s instead of returning a value
-        event!(Level::INFO, "Initiating deployment...");
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Restarting));
 
-        match service {
-            Ok(serv) => {
-                let _ = br.send(ServiceEvent::ServiceUpdate {
-                    id: serv.id,
-                    status: ServiceStatus::DeploymentRequested,
-                });
-
-                let services = match Self::get_list().await {
-                    Ok(lst) => lst,
-                    Err(_e) => {
-                        let _ = br.send(ServiceEvent::ServiceUpdate {
-                            id: serv.id,
-                            status: ServiceStatus::DiscoveryFailed,
-                        });
-                        return Err(ServiceError::Discovery);
-                    }
+        let path = self.live_path(&config);
+        if !path.is_dir() {
+            event!(
+                Level::ERROR,
+                "Service {} has no live dir to restart",
+                self.name
+            );
+            return Err(ServiceError::Restart);
+        }
+
+        let mut compose = self.compose(&path)?;
+        compose.arg("restart");
+        let output = process::output(
+            compose,
+            Limit {
+                step: "compose restart",
+                timeout: config.timeouts.compose,
+                cancel,
+            },
+        )?;
+
+        match output.status.success() {
+            true => Ok(()),
+            false => {
+                event!(
+                    Level::ERROR,
+                    "RESTART FAIL | {}",
+                    std::str::from_utf8(&output.stderr)?
+                );
+                Err(ServiceError::Restart)
+            }
+        }
+    }
+
+    // `compose down` on the live dir, which stays in place so the service can be started
+    // or deployed again; images are kept
+    pub fn down(
+        &self,
+        config: Config,
+        volumes: bool,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::TearingDown));
+
+        let path = self.live_path(&config);
+        if !path.is_dir() {
+            event!(
+                Level::ERROR,
+                "Service {} has no live dir to tear down",
+                self.name
+            );
+            return Err(ServiceError::Down);
+        }
+
+        let mut compose = self.compose(&path)?;
+        compose.arg("down").arg("--remove-orphans");
+        if volumes {
+            compose.arg("--volumes");
+        }
+        let output = process::output(
+            compose,
+            Limit {
+                step: "compose down",
+                timeout: config.timeouts.compose,
+                cancel,
+            },
+        )?;
+
+        match output.status.success() {
+            true => Ok(()),
+            false => {
+                event!(
+                    Level::ERROR,
+                    "DOWN FAIL | {}",
+                    std::str::from_utf8(&output.stderr)?
+                );
+                Err(ServiceError::Down)
+            }
+        }
+    }
+
+    // runs a command with stdout and stderr interleaved, streaming each line as an event
+    fn stream_output(
+        &self,
+        command: Command,
+        prefix: &str,
+        limit: Limit,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<bool, ServiceError> {
+        let status = process::stream(command, limit, |line| {
+            event!(Level::INFO, "{} | {} | {}", prefix, self.name, line);
+            let _ = br.send(ServiceEvent::update(
+                self.id,
+                ServiceStatus::HookOutput(line),
+            ));
+        })?;
+        Ok(status.success())
+    }
+
+    pub fn build(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Building));
+
+        let path = self.live_path(&config);
+        let mut docker = self.compose(&path)?;
+        docker.args(["build", "--pull"]);
+        let limit = Limit {
+            step: "compose build",
+            timeout: config.timeouts.compose,
+            cancel,
+        };
+
+        match self.stream_output(docker, "BUILD", limit, br)? {
+            true => Ok(()),
+            false => {
+                event!(Level::ERROR, "BUILD FAIL | {}", self.name);
+                Err(ServiceError::Build)
+            }
+        }
+    }
+
+    // tags each image the compose file builds with the commit and pushes it; returns the pushed references
+    pub fn push_images(
+        &self,
+        config: &Config,
+        commit: &str,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<Vec<String>, ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::Pushing));
+
+        let registry = config
+            .registry
+            .as_deref()
+            .ok_or(ServiceError::Registry)?
+            .trim_end_matches('/');
+        let live_path = self.live_path(config);
+
+        let limit = |step| Limit {
+            step,
+            timeout: config.timeouts.compose,
+            cancel,
+        };
+        let mut pushed = vec![];
+        for (local, remote) in self.built_images(&live_path, registry, commit)? {
+            let mut docker = self.docker();
+            docker.args(["tag", &local, &remote]);
+            let tagged = process::output(docker, limit("docker tag"))?;
+            if !tagged.status.success() {
+                event!(
+                    Level::ERROR,
+                    "TAG FAIL | {} | {}",
+                    local,
+                    String::from_utf8_lossy(&tagged.stderr)
+                );
+                return Err(ServiceError::Push);
+            }
+
+            let mut docker = self.docker();
+            docker.args(["push", &remote]);
+            if !self.stream_output(docker, "PUSH", limit("docker push"), br)? {
+                event!(Level::ERROR, "PUSH FAIL | {}", remote);
+                return Err(ServiceError::Push);
+            }
+            pushed.push(remote);
+        }
+
+        Ok(pushed)
+    }
+
+    // the local and registry names of each image the compose file builds
+    fn built_images(
+        &self,
+        live_path: &Path,
+        registry: &str,
+        commit: &str,
+    ) -> Result<Vec<(String, String)>, ServiceError> {
+        let compose_path = self.compose_path(live_path)?;
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(compose_path)?)?;
+        let services = compose
+            .get("services")
+            .and_then(|s| s.as_mapping())
+            .ok_or(ServiceError::Key("services".into()))?;
+
+        let project = self.project_name();
+        Ok(services
+            .iter()
+            .filter(|(_, definition)| definition.get("build").is_some())
+            .filter_map(|(name, definition)| {
+                let name = name.as_str()?;
+                // compose names built images <project>-<service> unless `image` is set
+                let local = match definition.get("image").and_then(|i| i.as_str()) {
+                    Some(image) => image.to_string(),
+                    None => format!("{}-{}", project, name),
                 };
+                let remote = format!("{}/{}-{}:{}", registry, project, name, commit);
+                Some((local, remote))
+            })
+            .collect())
+    }
+
+    // runs a hook command in the live dir, streaming each output line as an event
+    fn run_hook(
+        &self,
+        cmd: &str,
+        path: &Path,
+        status: ServiceStatus,
+        limit: Limit,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let _ = br.send(ServiceEvent::update(self.id, status));
+
+        let mut sh = Command::new("sh");
+        sh.arg("-c").arg(cmd).current_dir(path);
 
-                serv.clone_or_pull(config.clone(), &br)?;
+        match self.stream_output(sh, "HOOK", limit, br)? {
+            true => Ok(()),
+            false => {
+                event!(Level::ERROR, "HOOK FAIL | {} | {}", self.name, cmd);
+                Err(ServiceError::Hook(cmd.to_string()))
+            }
+        }
+    }
 
-                serv.copy_to_live(config.clone(), &br)?;
+    pub fn pre_deploy(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(cmd) = &self.pre_deploy_cmd else {
+            return Ok(());
+        };
+
+        let limit = Limit {
+            step: "pre-deploy hook",
+            timeout: config.timeouts.compose,
+            cancel,
+        };
+        let path = self.live_path(&config);
+
+        self.run_hook(cmd, &path, ServiceStatus::RunningPreDeploy, limit, br)
+    }
+
+    pub async fn post_deploy(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(cmd) = &self.post_deploy_cmd else {
+            return Ok(());
+        };
+
+        // only run once the service is confirmed running
+        let services = Self::get_list(std::slice::from_ref(&self.docker_host)).await?;
+        if !self.is_running(&services) {
+            event!(
+                Level::ERROR,
+                "Service {} not running, skipping post-deploy hook",
+                self.name
+            );
+            return Err(ServiceError::Start);
+        }
+
+        let limit = Limit {
+            step: "post-deploy hook",
+            timeout: config.timeouts.compose,
+            cancel,
+        };
+        let path = self.live_path(&config);
+
+        self.run_hook(cmd, &path, ServiceStatus::RunningPostDeploy, limit, br)
+    }
+
+    // the compose service the post-start exec runs in
+    pub fn exec_container(&self) -> Option<String> {
+        self.post_start_container
+            .clone()
+            .or_else(|| self.compose_targets()?.into_iter().next())
+    }
+
+    // `compose exec` in a running container, e.g. migrations that need the new image
+    pub fn post_start_exec(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(cmd) = &self.post_start_exec else {
+            return Ok(());
+        };
+        let container = self.exec_container().ok_or(ServiceError::ExecContainer)?;
+        let _ = br.send(ServiceEvent::update(
+            self.id,
+            ServiceStatus::RunningPostStartExec,
+        ));
+
+        let path = self.live_path(&config);
+        let mut compose = self.compose(&path)?;
+        compose
+            .args(["exec", "-T"])
+            .arg(&container)
+            .args(["sh", "-c"])
+            .arg(cmd);
+        let limit = Limit {
+            step: "post-start exec",
+            timeout: config.timeouts.compose,
+            cancel,
+        };
 
-                serv.apply_tags(config.clone(), &br)?;
+        match self.stream_output(compose, "EXEC", limit, br)? {
+            true => Ok(()),
+            false => {
+                event!(Level::ERROR, "EXEC FAIL | {} | {}", self.name, cmd);
+                Err(ServiceError::Exec(cmd.to_string()))
+            }
+        }
+    }
 
-                if serv.is_running(&services) {
-                    serv.stop(config.clone(), &br)?;
+    // runs a network-bound step again when it fails in a way that may be transient
+    fn retry<T>(
+        &self,
+        config: &Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+        mut step: impl FnMut() -> Result<T, ServiceError>,
+    ) -> Result<T, ServiceError> {
+        let attempts = config.retry.attempts;
+        let mut delay = config.retry.backoff;
+        for attempt in 2..=attempts {
+            match step() {
+                Err(
+                    e @ (ServiceError::CloneOrPull
+                    | ServiceError::Build
+                    | ServiceError::Push
+                    | ServiceError::Timeout(_)),
+                ) => {
+                    event!(
+                        Level::WARN,
+                        "{} | {} | retrying in {}s",
+                        self.name,
+                        e,
+                        delay.as_secs()
+                    );
+                    let _ = br.send(ServiceEvent::update(
+                        self.id,
+                        ServiceStatus::Retrying(attempt, attempts),
+                    ));
+                    process::pause(delay, cancel)?;
+                    delay *= 2;
                 }
+                result => return result,
+            }
+        }
+        step()
+    }
 
-                serv.start(config, &br)?;
+    fn has_commit(path: &Path, commit: &str) -> bool {
+        tools::git()
+            .args(["cat-file", "-e", &format!("{}^{{commit}}", commit)])
+            .current_dir(path)
+            .status()
+            .is_ok_and(|s| s.success())
+    }
 
-                Ok(())
+    // fetches the commit into the clone unless it's already there
+    fn fetch_commit(
+        &self,
+        path: &Path,
+        auth: GitAuth,
+        commit: &str,
+        limit: Limit,
+    ) -> Result<(), ServiceError> {
+        if Self::has_commit(path, commit) {
+            return Ok(());
+        }
+        let mut git = auth.command();
+        git.args(["fetch", "origin", commit]).current_dir(path);
+        let output = process::output(git, limit)?;
+        // not every server hands out commits by id; fall back to everything it has
+        if !output.status.success() || !Self::has_commit(path, commit) {
+            event!(
+                Level::WARN,
+                "Fetching {} of {} by id failed, fetching all branches | {}",
+                short_commit(commit),
+                self.name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let mut git = auth.command();
+            git.args(["fetch", "--force"]);
+            if Self::is_shallow(path) {
+                git.arg("--unshallow");
+            }
+            git.args(["origin", "+refs/heads/*:refs/remotes/origin/*"])
+                .current_dir(path);
+            process::output(git, limit)?;
+        }
+        Ok(())
+    }
+
+    // whether anything under the watched paths changed between the two commits. Without a
+    // clone, or when the earlier commit can't be found, the answer is yes, so the deploy
+    // goes ahead
+    pub fn touches_watched_paths(
+        &self,
+        config: &Config,
+        auth: GitAuth,
+        from: &str,
+        to: &str,
+    ) -> Result<bool, ServiceError> {
+        let Some(paths) = self.watched_paths() else {
+            return Ok(true);
+        };
+        let path = self.repo_path(config);
+        if !path.join(".git").is_dir() {
+            return Ok(true);
+        }
+        let cancel = CancellationToken::new();
+        let limit = Limit {
+            step: "git fetch",
+            timeout: config.timeouts.git,
+            cancel: &cancel,
+        };
+        self.fetch_commit(&path, auth, to, limit)?;
+        if !Self::has_commit(&path, from) {
+            return Ok(true);
+        }
+
+        let status = tools::git()
+            .args(["diff", "--quiet", from, to, "--"])
+            .args(paths)
+            .current_dir(&path)
+            .status()?;
+        // exits 1 when there are differences
+        match status.code() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(ServiceError::Status),
+        }
+    }
+
+    // detaches the clone at the commit, fetching it if the clone lacks it; cloning first
+    // if there is no clone yet
+    fn checkout_commit(
+        &self,
+        config: Config,
+        auth: GitAuth,
+        commit: &str,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let path = self.repo_path(&config);
+        if !path.join(".git").is_dir() {
+            self.clone_or_pull(config.clone(), auth.clone(), cancel, br)?;
+        }
+        let _ = br.send(ServiceEvent::update(
+            self.id,
+            ServiceStatus::CheckingOut(commit.to_string()),
+        ));
+        let limit = Limit {
+            step: "git fetch",
+            timeout: config.timeouts.git,
+            cancel,
+        };
+        self.fetch_commit(&path, auth, commit, limit)?;
+
+        let output = tools::git()
+            .args(["checkout", "--quiet", "--force", "--detach", commit])
+            .current_dir(&path)
+            .output()?;
+        match output.status.success() {
+            true => Ok(()),
+            false => {
+                event!(
+                    Level::ERROR,
+                    "CHECKOUT FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                Err(ServiceError::CloneOrPull)
+            }
+        }
+    }
+
+    // pulls the repo, or checks out the given commit without pulling; on Result::Ok,
+    // returns the commit now checked out if it could be read
+    pub fn checkout(
+        &self,
+        config: &Config,
+        auth: GitAuth,
+        commit: Option<&str>,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<Option<String>, ServiceError> {
+        self.retry(config, cancel, br, || match commit {
+            Some(commit) => self.checkout_commit(config.clone(), auth.clone(), commit, cancel, br),
+            None => self.clone_or_pull(config.clone(), auth.clone(), cancel, br),
+        })?;
+
+        match self.local_head(config) {
+            Ok(commit) => Ok(Some(commit)),
+            Err(e) => {
+                event!(Level::WARN, "Unable to read pulled commit | {}", e);
+                Ok(None)
+            }
+        }
+    }
+
+    // everything after the pull, run as the steps of the service's pipeline, returning the
+    // images pushed; needs no database, so agents run it too. Each step's duration goes in
+    // `timings`
+    pub async fn release(
+        &self,
+        config: Config,
+        env_vars: &[ServiceEnv],
+        commit: Option<&str>,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+        timings: &mut Timings,
+    ) -> Result<Vec<String>, ServiceError> {
+        let services = match Self::get_list(std::slice::from_ref(&self.docker_host)).await {
+            Ok(lst) => lst,
+            Err(_e) => {
+                let _ = br.send(ServiceEvent::update(
+                    self.id,
+                    ServiceStatus::DiscoveryFailed,
+                ));
+                return Err(ServiceError::Discovery);
+            }
+        };
+
+        let mut run = pipeline::Run {
+            service: self,
+            config: &config,
+            env_vars,
+            commit,
+            cancel,
+            br,
+            timings,
+            containers: services,
+            images: vec![],
+        };
+        let scripts = self.parsed_scripts()?;
+        for step in self.pipeline_steps(&scripts)? {
+            if step.applies(self) {
+                step.run(&mut run).await?;
+            }
+        }
+        let images = run.images;
+
+        match self.trim_releases(&config) {
+            Ok(0) => (),
+            Ok(removed) => event!(Level::INFO, "Removed {} old releases", removed),
+            Err(e) => event!(Level::WARN, "Unable to trim releases | {}", e),
+        }
+        Ok(images)
+    }
+
+    // `commit` pins the deploy to that commit instead of the latest one
+    pub async fn deploy(
+        config: Config,
+        pool: &SqlitePool,
+        service: Result<Service, DBError>,
+        commit: Option<&str>,
+        cancel: &CancellationToken,
+        br: broadcast::Sender<ServiceEvent>,
+    ) -> Result<Option<i64>, DeployFailure> {
+        // emit `ServiceEvent`s instead of returning a value; on Result::Ok, returns the id of
+        // the recorded deployment
+        event!(Level::INFO, "Initiating deployment...");
+
+        match service {
+            Ok(serv) if serv.archived => {
+                event!(
+                    Level::WARN,
+                    "Refusing to deploy archived service {}",
+                    serv.name
+                );
+                Err(ServiceError::Archived.into())
+            }
+            Ok(serv) => {
+                let requested = Instant::now();
+                let _ = br.send(ServiceEvent::update(
+                    serv.id,
+                    ServiceStatus::DeploymentRequested,
+                ));
+
+                let auth = serv.git_auth(&config, pool).await?;
+
+                let mut timings = Timings::default();
+                let commit = timings.time("clone", || {
+                    serv.checkout(&config, auth, commit, cancel, &br)
+                })?;
+
+                let released = async {
+                    if let Some(commit) = &commit {
+                        set_last_commit(pool, serv.id, commit.clone()).await?;
+                    }
+                    let env_vars = reveal(
+                        get_service_env(pool, serv.id).await?,
+                        config.secrets_key.as_ref(),
+                    )?;
+                    serv.release(
+                        config,
+                        &env_vars,
+                        commit.as_deref(),
+                        cancel,
+                        &br,
+                        &mut timings,
+                    )
+                    .await
+                }
+                .await;
+                let images = released.map_err(|error| DeployFailure {
+                    error,
+                    commit: commit.clone(),
+                })?;
+
+                match new_deployment(pool, serv.id, commit, images, requested.elapsed(), timings)
+                    .await
+                {
+                    Ok(id) => Ok(Some(id)),
+                    Err(e) => {
+                        event!(Level::ERROR, "Unable to record deployment | {}", e);
+                        Ok(None)
+                    }
+                }
             }
             Err(e) => {
                 event!(
@@ -617,11 +2722,44 @@ impl Service {
                     e
                 );
                 let _ = br.send(ServiceEvent::UnknownEvent { msg: e.to_string() });
-                Err(ServiceError::Unknown)
+                Err(ServiceError::Unknown.into())
             }
         }
     }
 
+    // stops the containers and removes the live and repo directories
+    pub async fn teardown(
+        &self,
+        config: Config,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let services = match Self::get_list(std::slice::from_ref(&self.docker_host)).await {
+            Ok(lst) => lst,
+            Err(_e) => {
+                let _ = br.send(ServiceEvent::update(
+                    self.id,
+                    ServiceStatus::DiscoveryFailed,
+                ));
+                return Err(ServiceError::Discovery);
+            }
+        };
+
+        if self.is_running(&services) {
+            self.stop(config.clone(), &CancellationToken::new(), br)?;
+        }
+        // try to remove from docker
+        self.try_remove_from_docker(self.live_root(&config));
+
+        // delete the live home with its releases, and anything kept beside it
+        for kind in KEPT_KINDS {
+            let _ = std::fs::remove_dir_all(self.kept_path(&self.live_root(&config), kind));
+        }
+        self.try_delete(self.live_root(&config));
+
+        // delete service dir
+        self.delete(self.repo_root(&config))
+    }
+
     pub async fn delete_service(
         config: Config,
         pool: &SqlitePool,
@@ -632,28 +2770,7 @@ impl Service {
 
         match service {
             Ok(serv) => {
-                let services = match Self::get_list().await {
-                    Ok(lst) => lst,
-                    Err(_e) => {
-                        let _ = br.send(ServiceEvent::ServiceUpdate {
-                            id: serv.id,
-                            status: ServiceStatus::DiscoveryFailed,
-                        });
-                        return Err(ServiceError::Discovery);
-                    }
-                };
-
-                if serv.is_running(&services) {
-                    serv.stop(config.clone(), &br)?;
-                }
-                // try to remove from docker
-                serv.try_remove_from_docker(config.services_live_dir.clone());
-
-                // delete live dir
-                serv.try_delete(config.services_live_dir);
-
-                // delete service dir
-                serv.delete(config.services_repo_dir)?;
+                serv.teardown(config, &br).await?;
 
                 // delete from db
                 delete_service_entry(pool, serv.id).await?;
@@ -683,7 +2800,7 @@ impl Service {
 
         match service {
             Ok(serv) => {
-                serv.stop(config.clone(), &br)?;
+                serv.stop(config.clone(), &CancellationToken::new(), &br)?;
 
                 let _ = br.send(ServiceEvent::AllStatus);
 
@@ -0,0 +1,112 @@
+use serde_yaml::{Mapping, Value};
+use tracing::{Level, event};
+
+use super::{Service, ServiceError};
+
+// as docker accepts them: a letter or digit, then letters, digits, '_', '.' or '-'
+pub fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+// attaches a compose service to the shared networks, in whichever of the list or map forms
+// it uses. A service without networks of its own keeps the project's default one
+pub fn join(
+    compose_service: &mut Mapping,
+    target: &str,
+    networks: &[String],
+) -> Result<(), ServiceError> {
+    // host networking can't be combined with other networks
+    if compose_service.contains_key("network_mode") {
+        return Ok(());
+    }
+    let existing = compose_service
+        .entry(Value::String("networks".into()))
+        .or_insert_with(|| Value::Sequence(vec![Value::String("default".into())]));
+    match existing {
+        Value::Sequence(list) => {
+            for network in networks {
+                if !list.iter().any(|n| n.as_str() == Some(network)) {
+                    list.push(Value::String(network.clone()));
+                }
+            }
+        }
+        Value::Mapping(map) => {
+            for network in networks {
+                if !map.contains_key(network.as_str()) {
+                    map.insert(Value::String(network.clone()), Value::Null);
+                }
+            }
+        }
+        _ => {
+            return Err(ServiceError::Key(format!(
+                "{} networks (as list or map)",
+                target
+            )));
+        }
+    }
+    Ok(())
+}
+
+// declares the shared networks as external at the top of the compose file; a network the
+// repo already declares is left as it is
+pub fn declare(compose: &mut Mapping, networks: &[String]) -> Result<(), ServiceError> {
+    let declared = compose
+        .entry(Value::String("networks".into()))
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if declared.is_null() {
+        *declared = Value::Mapping(Mapping::new());
+    }
+    let Some(declared) = declared.as_mapping_mut() else {
+        return Err(ServiceError::Key("networks (as map)".into()));
+    };
+    for network in networks {
+        if !declared.contains_key(network.as_str()) {
+            let mut external = Mapping::new();
+            external.insert(Value::String("external".into()), Value::Bool(true));
+            declared.insert(Value::String(network.clone()), Value::Mapping(external));
+        }
+    }
+    Ok(())
+}
+
+impl Service {
+    // creates each of SHARED_NETWORKS on the service's docker host if it isn't there yet
+    pub fn ensure_networks(&self, networks: &[String]) -> Result<(), ServiceError> {
+        let exists = |network: &str| -> Result<bool, ServiceError> {
+            Ok(self
+                .docker()
+                .args(["network", "inspect", network])
+                .output()?
+                .status
+                .success())
+        };
+        for network in networks {
+            if exists(network)? {
+                continue;
+            }
+
+            event!(
+                Level::INFO,
+                "Creating network {} for {}",
+                network,
+                self.name
+            );
+            let output = self
+                .docker()
+                .args(["network", "create", network])
+                .output()?;
+            // another deploy may have created it in the meantime
+            if !output.status.success() && !exists(network)? {
+                event!(
+                    Level::ERROR,
+                    "NETWORK FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                return Err(ServiceError::Network(network.clone()));
+            }
+        }
+        Ok(())
+    }
+}
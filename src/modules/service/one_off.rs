@@ -0,0 +1,62 @@
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{
+    Service, ServiceError, ServiceEvent, ServiceStatus,
+    process::{self, Limit},
+};
+use crate::modules::Config;
+
+impl Service {
+    pub fn is_one_off(&self) -> bool {
+        self.one_off.is_some()
+    }
+
+    // what a finished deploy leaves the service as
+    pub fn deployed_status(&self) -> ServiceStatus {
+        match self.is_one_off() {
+            true => ServiceStatus::Completed,
+            false => ServiceStatus::Running,
+        }
+    }
+
+    // `compose run --rm` of the one-off job's compose service, streaming its output like a
+    // deploy hook's; the deploy succeeds when it exits 0
+    pub fn run_one_off(
+        &self,
+        config: Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(one_off) = &self.one_off else {
+            return Ok(());
+        };
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::RunningOneOff));
+
+        let path = self.live_path(&config);
+        let mut compose = self.compose(&path)?;
+        compose.args(["run", "--rm", "-T"]).arg(one_off);
+        let limit = Limit {
+            step: "one-off job",
+            timeout: config.timeouts.compose,
+            cancel,
+        };
+
+        let status = process::stream(compose, limit, |line| {
+            event!(Level::INFO, "ONE-OFF | {} | {}", self.name, line);
+            let _ = br.send(ServiceEvent::update(
+                self.id,
+                ServiceStatus::HookOutput(line),
+            ));
+        })?;
+        match status.code() {
+            Some(0) => Ok(()),
+            code => {
+                let code = code.map_or("a signal".to_string(), |c| c.to_string());
+                event!(Level::ERROR, "ONE-OFF FAIL | {} | {}", self.name, code);
+                Err(ServiceError::OneOffExit(code))
+            }
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    DockerServiceEntry, Service, ServiceError, ServiceEvent,
+    env::ServiceEnv,
+    script::{self, Script},
+    template,
+    timing::Timings,
+};
+use crate::modules::Config;
+
+// a release on its way through the service's pipeline
+pub struct Run<'a> {
+    pub service: &'a Service,
+    pub config: &'a Config,
+    pub env_vars: &'a [ServiceEnv],
+    pub commit: Option<&'a str>,
+    pub cancel: &'a CancellationToken,
+    pub br: &'a broadcast::Sender<ServiceEvent>,
+    pub timings: &'a mut Timings,
+    // the service's docker host as it was when the release began
+    pub containers: Vec<DockerServiceEntry>,
+    // pushed by the push step, returned with the release
+    pub images: Vec<String>,
+}
+
+// one step of a release, run in the order the service's pipeline lists it
+pub trait DeployStep: Sync {
+    // what the service's `pipeline`, or a script's `after`, calls it
+    fn name(&self) -> &str;
+
+    // a step the service isn't set up for, like a build without build_before_up, is passed
+    // over
+    fn applies(&self, _service: &Service) -> bool {
+        true
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>>;
+}
+
+struct Copy;
+struct Rewrite;
+struct PreDeploy;
+struct Build;
+struct Push;
+struct Stop;
+struct VolumeBackup;
+struct Start;
+struct Health;
+struct PostStartExec;
+struct SmokeTest;
+struct PostDeploy;
+
+// every step, in the order a service without a pipeline of its own runs them
+pub const STEPS: [&dyn DeployStep; 12] = [
+    &Copy,
+    &Rewrite,
+    &PreDeploy,
+    &Build,
+    &Push,
+    &Stop,
+    &VolumeBackup,
+    &Start,
+    &Health,
+    &PostStartExec,
+    &SmokeTest,
+    &PostDeploy,
+];
+
+// the steps every pipeline needs, and in this order before the rest: nothing else has a live
+// dir to work in until they've run
+const LEADING: [&str; 2] = ["copy", "rewrite"];
+// only make sense once the new containers are up
+const AFTER_START: [&str; 3] = ["health", "post-start-exec", "smoke-test"];
+
+pub fn find(name: &str) -> Option<&'static dyn DeployStep> {
+    STEPS.iter().find(|step| step.name() == name).copied()
+}
+
+// the names in a comma-separated pipeline, e.g. "copy, rewrite, build, start"
+fn names(pipeline: &str) -> Vec<&str> {
+    pipeline
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// why the pipeline can't run, if it can't
+pub fn check(pipeline: &str) -> Result<(), String> {
+    let names = names(pipeline);
+    if let Some(unknown) = names.iter().find(|name| find(name).is_none()) {
+        return Err(format!("unknown step '{}'", unknown));
+    }
+    if let Some((i, twice)) = names
+        .iter()
+        .enumerate()
+        .find(|(i, name)| names[..*i].contains(name))
+    {
+        return Err(format!("'{}' is listed twice, at {}", twice, i + 1));
+    }
+    if names.len() < LEADING.len() || names[..LEADING.len()] != LEADING {
+        return Err(format!("it has to start with {}", LEADING.join(", ")));
+    }
+    let Some(start) = names.iter().position(|name| *name == "start") else {
+        return Err("it has no start step".to_string());
+    };
+    if let Some(early) = names[..start]
+        .iter()
+        .find(|name| AFTER_START.contains(name))
+    {
+        return Err(format!("'{}' has to come after start", early));
+    }
+    Ok(())
+}
+
+// the names of the steps a pipeline runs, every step's when it's None
+pub fn step_names(pipeline: Option<&str>) -> Vec<&str> {
+    match pipeline {
+        Some(pipeline) => names(pipeline),
+        None => STEPS.iter().map(|step| step.name()).collect(),
+    }
+}
+
+impl Service {
+    // the steps of its pipeline, or every step when it has none, with each of `scripts` after
+    // the step it follows
+    pub fn pipeline_steps<'a>(
+        &self,
+        scripts: &'a [Script],
+    ) -> Result<Vec<&'a dyn DeployStep>, ServiceError> {
+        if let Some(pipeline) = &self.pipeline {
+            check(pipeline).map_err(ServiceError::Pipeline)?;
+        }
+        let names = step_names(self.pipeline.as_deref());
+        script::check(scripts, &names).map_err(ServiceError::Scripts)?;
+        let mut steps: Vec<&dyn DeployStep> = vec![];
+        for step in names.into_iter().filter_map(find) {
+            steps.push(step);
+            for script in scripts.iter().filter(|s| s.after == step.name()) {
+                steps.push(script);
+            }
+        }
+        Ok(steps)
+    }
+}
+
+impl DeployStep for Copy {
+    fn name(&self) -> &str {
+        "copy"
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            run.timings.time("copy", || {
+                service.copy_to_live(config.clone(), run.env_vars, run.cancel, run.br)
+            })
+        })
+    }
+}
+
+impl DeployStep for Rewrite {
+    fn name(&self) -> &str {
+        "rewrite"
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            let vars = template::variables(service, run.commit, run.env_vars);
+            let secrets: Vec<String> = service
+                .secret_files(run.env_vars)
+                .into_iter()
+                .map(|var| var.key.clone())
+                .collect();
+            run.timings.time("rewrite", || {
+                service.apply_tags(config.clone(), &vars, &secrets, run.br)
+            })?;
+            service.ensure_networks(&config.shared_networks)
+        })
+    }
+}
+
+impl DeployStep for PreDeploy {
+    fn name(&self) -> &str {
+        "pre-deploy"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.pre_deploy_cmd.is_some()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            run.timings.time("pre-deploy", || {
+                service.pre_deploy(config.clone(), run.cancel, run.br)
+            })
+        })
+    }
+}
+
+// builds while the old containers still serve traffic, unless the pipeline stops them first
+impl DeployStep for Build {
+    fn name(&self) -> &str {
+        "build"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.build_before_up
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            run.timings.time("build", || {
+                service.retry(config, run.cancel, run.br, || {
+                    service.build(config.clone(), run.cancel, run.br)
+                })
+            })
+        })
+    }
+}
+
+impl DeployStep for Push {
+    fn name(&self) -> &str {
+        "push"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.build_before_up && service.push_image
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            let commit = run.commit.ok_or(ServiceError::Push)?;
+            run.images = run.timings.time("push", || {
+                service.retry(config, run.cancel, run.br, || {
+                    service.push_images(config, commit, run.cancel, run.br)
+                })
+            })?;
+            Ok(())
+        })
+    }
+}
+
+impl DeployStep for Stop {
+    fn name(&self) -> &str {
+        "stop"
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            if !service.is_running(&run.containers) {
+                return Ok(());
+            }
+            run.timings
+                .time("stop", || service.stop(config.clone(), run.cancel, run.br))
+        })
+    }
+}
+
+// after the stop, so the snapshot is consistent
+impl DeployStep for VolumeBackup {
+    fn name(&self) -> &str {
+        "volume-backup"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.backup_volumes.is_some()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            run.timings.time("volume backup", || {
+                service.backup_volumes(config, run.cancel, run.br)
+            })
+        })
+    }
+}
+
+// starts the new containers, rolling back if they fail to start and the service is
+// health-gated; a one-off job runs to completion instead
+impl DeployStep for Start {
+    fn name(&self) -> &str {
+        "start"
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            if service.is_one_off() {
+                return run.timings.time("one-off job", || {
+                    service.run_one_off(config.clone(), run.cancel, run.br)
+                });
+            }
+            let started = run.timings.time("start", || {
+                service.start(config.clone(), run.cancel, run.br)
+            });
+            match started {
+                // a cancelled deploy stops where it is instead of rolling back
+                Err(e @ ServiceError::Cancelled) => Err(e),
+                Err(_) if service.health_grace_secs.is_some() => {
+                    service.rollback(config.clone(), run.cancel, run.br)?;
+                    Err(ServiceError::RolledBack)
+                }
+                started => started,
+            }
+        })
+    }
+}
+
+// rolls back if the new containers don't get healthy within the grace period; this and the
+// steps after it have nothing to check once a one-off job has run
+impl DeployStep for Health {
+    fn name(&self) -> &str {
+        "health"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.health_grace_secs.is_some() && !service.is_one_off()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            let grace_secs = service.health_grace_secs.unwrap_or_default();
+            let waited = Instant::now();
+            let healthy = service.wait_healthy(grace_secs, run.cancel, run.br).await;
+            run.timings.record("health", waited);
+            if !healthy? {
+                service.rollback(config.clone(), run.cancel, run.br)?;
+                return Err(ServiceError::RolledBack);
+            }
+            Ok(())
+        })
+    }
+}
+
+impl DeployStep for PostStartExec {
+    fn name(&self) -> &str {
+        "post-start-exec"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.post_start_exec.is_some() && !service.is_one_off()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            run.timings.time("post-start exec", || {
+                service.post_start_exec(config.clone(), run.cancel, run.br)
+            })
+        })
+    }
+}
+
+impl DeployStep for SmokeTest {
+    fn name(&self) -> &str {
+        "smoke-test"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.smoke_url.is_some() && !service.is_one_off()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let smoke = Instant::now();
+            let result = run.service.smoke_test(run.config, run.cancel, run.br).await;
+            run.timings.record("smoke test", smoke);
+            result
+        })
+    }
+}
+
+impl DeployStep for PostDeploy {
+    fn name(&self) -> &str {
+        "post-deploy"
+    }
+
+    fn applies(&self, service: &Service) -> bool {
+        service.post_deploy_cmd.is_some()
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let post_deploy = Instant::now();
+            let result = run
+                .service
+                .post_deploy(run.config.clone(), run.cancel, run.br)
+                .await;
+            run.timings.record("post-deploy", post_deploy);
+            result
+        })
+    }
+}
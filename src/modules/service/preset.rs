@@ -0,0 +1,93 @@
+use super::Service;
+
+// a starting point for the new-service form: the settings a kind of service usually needs,
+// and a compose override to fill in what the repo's compose file leaves out
+pub struct Preset {
+    pub slug: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    prefill: fn(&mut Service),
+    starter: &'static str,
+}
+
+pub const PRESETS: [Preset; 3] = [
+    Preset {
+        slug: "static-site",
+        name: "Static site behind nginx",
+        description: "nginx serving the repo's public/ directory",
+        prefill: |service| {
+            service.compose_name = "web".to_string();
+            service.proxy_port = Some(80);
+        },
+        starter: "services:
+  web:
+    image: nginx:alpine
+    volumes:
+      - ./public:/usr/share/nginx/html:ro
+    restart: unless-stopped
+",
+    },
+    Preset {
+        slug: "postgres-app",
+        name: "Postgres-backed app",
+        description: "an app built from the repo, with a Postgres database whose volume is backed up",
+        prefill: |service| {
+            service.compose_name = "*".to_string();
+            service.build_before_up = true;
+            service.health_grace_secs = Some(60);
+            service.backup_volumes = Some("db-data".to_string());
+            service.post_start_container = Some("app".to_string());
+        },
+        starter: "services:
+  db:
+    image: postgres:16-alpine
+    environment:
+      POSTGRES_DB: ${WRAUT_SERVICE_NAME}
+      POSTGRES_PASSWORD: ${POSTGRES_PASSWORD}
+    volumes:
+      - db-data:/var/lib/postgresql/data
+    healthcheck:
+      test: [\"CMD-SHELL\", \"pg_isready -U postgres\"]
+      interval: 10s
+    restart: unless-stopped
+  app:
+    depends_on:
+      db:
+        condition: service_healthy
+    environment:
+      DATABASE_URL: postgres://postgres:${POSTGRES_PASSWORD}@db/${WRAUT_SERVICE_NAME}
+    restart: unless-stopped
+volumes:
+  db-data:
+",
+    },
+    Preset {
+        slug: "batch-job",
+        name: "Batch job",
+        description: "a one-off job run to completion on each deploy",
+        prefill: |service| {
+            service.compose_name = "job".to_string();
+            service.build_before_up = true;
+            service.one_off = Some("job".to_string());
+        },
+        starter: "services:
+  job:
+    restart: \"no\"
+",
+    },
+];
+
+pub fn find(slug: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.slug == slug)
+}
+
+impl Preset {
+    // fills in a blank service; the starter override only when asked for, since the repo's
+    // compose file may already cover it
+    pub fn apply(&self, service: &mut Service, starter: bool) {
+        (self.prefill)(service);
+        if starter {
+            service.compose_override = Some(self.starter.to_string());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use super::{Service, duplicate, proxy};
+
+// the parts of a GitHub `pull_request` webhook event a preview needs
+#[derive(Deserialize)]
+pub struct PullRequestEvent {
+    pub action: String,
+    pub number: i64,
+    pub pull_request: PullRequest,
+}
+
+#[derive(Deserialize)]
+pub struct PullRequest {
+    pub head: GitRef,
+    pub base: GitRef,
+}
+
+#[derive(Deserialize)]
+pub struct GitRef {
+    #[serde(rename = "ref")]
+    pub branch: String,
+    pub sha: String,
+    // None once a fork's repo is deleted
+    pub repo: Option<Repo>,
+}
+
+#[derive(Deserialize)]
+pub struct Repo {
+    pub full_name: String,
+}
+
+impl PullRequestEvent {
+    // a fork's preview would run its code with the service's secrets, so forks get none
+    pub fn forked(&self) -> bool {
+        let head = self.pull_request.head.repo.as_ref().map(|r| &r.full_name);
+        let base = self.pull_request.base.repo.as_ref().map(|r| &r.full_name);
+        head.is_none() || head != base
+    }
+
+    // new commits, or a pull request opened again, are deployed to its preview
+    pub fn deploys(&self) -> bool {
+        matches!(
+            self.action.as_str(),
+            "opened" | "reopened" | "synchronize" | "ready_for_review"
+        )
+    }
+
+    pub fn closes(&self) -> bool {
+        self.action == "closed"
+    }
+}
+
+// "web-pr-12", shortening the source's name to stay a valid one; names from before they were
+// limited to ASCII are cut on a character boundary
+fn preview_name(source: &str, number: i64) -> String {
+    let suffix = format!("-pr-{}", number);
+    let keep = source
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|end| *end <= 64 - suffix.len())
+        .last()
+        .unwrap_or(0);
+    format!("{}{}", &source[..keep], suffix)
+}
+
+// under PREVIEW_DOMAIN when it's set, otherwise a subdomain of the source's own host; none
+// when the source has no access URL to go on
+fn preview_url(source: &Service, name: &str, number: i64, domain: Option<&str>) -> String {
+    match domain {
+        Some(domain) => format!("{}.{}", name.to_lowercase(), domain),
+        None => match proxy::route(&source.access_url) {
+            ("", _) => String::new(),
+            (host, _) => format!("pr-{}.{}", number, host),
+        },
+    }
+}
+
+// a service for the pull request's head branch, configured like `source` but deployed only
+// by the pull request's events: nothing schedules, polls or holds its deploys
+pub fn preview(source: &Service, event: &PullRequestEvent, domain: Option<&str>) -> Service {
+    let name = preview_name(&source.name, event.number);
+    Service {
+        access_url: preview_url(source, &name, event.number, domain),
+        name,
+        environment: Some(format!("pr-{}", event.number)),
+        branch: Some(event.pull_request.head.branch.clone()),
+        tag_pattern: None,
+        schedule: None,
+        poll_interval_secs: None,
+        watch_paths: None,
+        review_required: false,
+        requires_approval: false,
+        backup_volumes: None,
+        active: true,
+        previews: false,
+        preview_of: Some(source.id),
+        ..duplicate::duplicate(source, &[])
+    }
+}
+
+// "127.0.0.1:8080:80" -> "127.0.0.1::80", "8080:80" -> "80"
+fn unpublish_port(port: &str) -> String {
+    match port.rsplit_once(':') {
+        Some((published, target)) => match published.rsplit_once(':') {
+            Some((ip, _)) => format!("{}::{}", ip, target),
+            None => target.to_string(),
+        },
+        None => port.to_string(),
+    }
+}
+
+// frees a preview's compose service of what would clash with the service it copies: host
+// ports, which docker picks for it instead, and a fixed container name
+pub fn detach(compose_service: &mut Mapping) {
+    compose_service.remove("container_name");
+    let Some(Value::Sequence(ports)) = compose_service.get_mut("ports") else {
+        return;
+    };
+    for port in ports.iter_mut() {
+        match port {
+            Value::String(short) => *short = unpublish_port(short),
+            Value::Mapping(long) => {
+                long.remove("published");
+            }
+            _ => (),
+        }
+    }
+}
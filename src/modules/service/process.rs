@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::ServiceError;
+
+const POLL: Duration = Duration::from_millis(100);
+
+// how long each kind of pipeline command may run before it's killed
+#[derive(Clone, Debug)]
+pub struct Timeouts {
+    pub git: Duration,
+    pub copy: Duration,
+    // compose commands, image pushes and deploy hooks
+    pub compose: Duration,
+    // how long a smoke test keeps retrying
+    pub smoke: Duration,
+}
+
+// how often network-bound steps are tried, and the wait before the first retry;
+// each later retry waits twice as long
+#[derive(Clone, Debug)]
+pub struct Retry {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+// the deadline and cancellation a single command runs under
+#[derive(Clone, Copy)]
+pub struct Limit<'a> {
+    pub step: &'a str,
+    pub timeout: Duration,
+    pub cancel: &'a CancellationToken,
+}
+
+impl Limit<'_> {
+    pub fn check(&self, started: Instant) -> Result<(), ServiceError> {
+        if self.cancel.is_cancelled() {
+            return Err(ServiceError::Cancelled);
+        }
+        if started.elapsed() >= self.timeout {
+            return Err(ServiceError::Timeout(self.step.to_string()));
+        }
+        Ok(())
+    }
+}
+
+// commands run in their own process group so that killing one also takes down
+// whatever it started, like the ssh under a `git pull`
+fn spawn(command: &mut Command, limit: &Limit) -> Result<Child, ServiceError> {
+    if limit.cancel.is_cancelled() {
+        return Err(ServiceError::Cancelled);
+    }
+    Ok(command.process_group(0).spawn()?)
+}
+
+fn kill(child: &mut Child, limit: &Limit, e: ServiceError) -> ServiceError {
+    event!(Level::WARN, "Killing {} | {}", limit.step, e);
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+    e
+}
+
+fn wait(child: &mut Child, limit: &Limit, started: Instant) -> Result<ExitStatus, ServiceError> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if let Err(e) = limit.check(started) {
+            return Err(kill(child, limit, e));
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+// `Command::output` that gives up on the command once the limit expires
+pub fn output(command: Command, limit: Limit) -> Result<Output, ServiceError> {
+    // pipelines run on the async runtime; hand its other tasks, like a cancel request,
+    // to another thread while this one waits on the child
+    tokio::task::block_in_place(|| output_blocking(command, limit))
+}
+
+fn output_blocking(mut command: Command, limit: Limit) -> Result<Output, ServiceError> {
+    let started = Instant::now();
+    let mut child = spawn(
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+        &limit,
+    )?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = wait(&mut child, &limit, started)?;
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+// runs a command reading stdin from one file and writing stdout to another, for streaming
+// archives in and out of containers; only stderr is captured
+pub fn with_files(
+    command: Command,
+    stdin: Option<File>,
+    stdout: Option<File>,
+    limit: Limit,
+) -> Result<Output, ServiceError> {
+    tokio::task::block_in_place(|| with_files_blocking(command, stdin, stdout, limit))
+}
+
+fn with_files_blocking(
+    mut command: Command,
+    stdin: Option<File>,
+    stdout: Option<File>,
+    limit: Limit,
+) -> Result<Output, ServiceError> {
+    let started = Instant::now();
+    let mut child = spawn(
+        command
+            .stdin(stdin.map_or_else(Stdio::null, Stdio::from))
+            .stdout(stdout.map_or_else(Stdio::null, Stdio::from))
+            .stderr(Stdio::piped()),
+        &limit,
+    )?;
+    let stderr = drain(child.stderr.take());
+
+    let status = wait(&mut child, &limit, started)?;
+    Ok(Output {
+        status,
+        stdout: vec![],
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+// runs a command with stdout and stderr interleaved, handing over each line as it arrives
+pub fn stream(
+    command: Command,
+    limit: Limit,
+    on_line: impl FnMut(String),
+) -> Result<ExitStatus, ServiceError> {
+    tokio::task::block_in_place(|| stream_blocking(command, limit, on_line))
+}
+
+fn stream_blocking(
+    mut command: Command,
+    limit: Limit,
+    mut on_line: impl FnMut(String),
+) -> Result<ExitStatus, ServiceError> {
+    let started = Instant::now();
+    let (reader, writer) = std::io::pipe()?;
+    command
+        .stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer);
+    let mut child = spawn(&mut command, &limit)?;
+    // the command holds the pipe's write ends; drop them so reads end with the child
+    drop(command);
+
+    let (lines, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else { break };
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        match received.recv_timeout(POLL) {
+            Ok(line) => on_line(line),
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if let Err(e) = limit.check(started) {
+            return Err(kill(&mut child, &limit, e));
+        }
+    }
+
+    wait(&mut child, &limit, started)
+}
+
+// sleeps between attempts, waking early if the deploy is cancelled
+pub fn pause(duration: Duration, cancel: &CancellationToken) -> Result<(), ServiceError> {
+    let started = Instant::now();
+    tokio::task::block_in_place(|| {
+        while started.elapsed() < duration {
+            if cancel.is_cancelled() {
+                return Err(ServiceError::Cancelled);
+            }
+            std::thread::sleep(POLL);
+        }
+        Ok(())
+    })
+}
+
+// a deploy's cancellation token, how many are running, and when the first began
+type Running = (CancellationToken, usize, DateTime<Utc>);
+
+// cancellation tokens of the deploys in flight, by service id; overlapping deploys
+// of one service share a token
+#[derive(Clone, Debug, Default)]
+pub struct InFlight {
+    deploys: Arc<Mutex<HashMap<i64, Running>>>,
+}
+
+impl InFlight {
+    fn deploys(&self) -> std::sync::MutexGuard<'_, HashMap<i64, Running>> {
+        self.deploys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn begin(&self, service_id: i64) -> CancellationToken {
+        let mut deploys = self.deploys();
+        let (token, count, _) = deploys
+            .entry(service_id)
+            .or_insert_with(|| (CancellationToken::new(), 0, Utc::now()));
+        // a deploy requested after a cancel isn't cancelled with the old one
+        if token.is_cancelled() {
+            *token = CancellationToken::new();
+        }
+        *count += 1;
+        token.clone()
+    }
+
+    pub fn end(&self, service_id: i64) {
+        let mut deploys = self.deploys();
+        if let Some((_, count, _)) = deploys.get_mut(&service_id) {
+            *count -= 1;
+            if *count == 0 {
+                deploys.remove(&service_id);
+            }
+        }
+    }
+
+    pub fn contains(&self, service_id: i64) -> bool {
+        self.deploys().contains_key(&service_id)
+    }
+
+    // when the service's running deploy began, for the dashboard's elapsed timer
+    pub fn since(&self, service_id: i64) -> Option<DateTime<Utc>> {
+        self.deploys().get(&service_id).map(|(_, _, since)| *since)
+    }
+
+    // false when no deploy of the service is running
+    pub fn cancel(&self, service_id: i64) -> bool {
+        match self.deploys().get(&service_id) {
+            Some((token, _, _)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
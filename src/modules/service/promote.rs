@@ -0,0 +1,41 @@
+use sqlx::SqlitePool;
+use thiserror::Error;
+
+use super::Service;
+use crate::modules::db::{self, DBError};
+
+pub const FROM: &str = "staging";
+pub const TO: &str = "production";
+
+#[derive(Error, Debug)]
+pub enum PromoteError {
+    #[error("Only {TO} services can be promoted to")]
+    NotProduction,
+    #[error("No {FROM} service is registered for this repo")]
+    NoStaging,
+    #[error("The {FROM} service has no deployed commit to promote")]
+    NothingDeployed,
+    #[error("Unable to look up the {FROM} deployment")]
+    Db(#[from] DBError),
+}
+
+#[derive(Debug)]
+pub struct Promotion {
+    // the staging service the commit comes from
+    pub from: i64,
+    pub commit: String,
+}
+
+// the commit last deployed by the staging service of the target's repo
+pub async fn promotion(pool: &SqlitePool, target: &Service) -> Result<Promotion, PromoteError> {
+    if target.environment.as_deref() != Some(TO) {
+        return Err(PromoteError::NotProduction);
+    }
+    let from = db::service_in_environment(pool, &target.repo_url, FROM)
+        .await?
+        .ok_or(PromoteError::NoStaging)?;
+    let commit = db::last_deployed_commit(pool, from)
+        .await?
+        .ok_or(PromoteError::NothingDeployed)?;
+    Ok(Promotion { from, commit })
+}
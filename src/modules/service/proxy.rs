@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use serde_yaml::{Mapping, Value};
+
+use super::{Service, ServiceError};
+
+// the reverse proxy that routes each service's access URL to its first compose service,
+// configured through the labels wraut writes into the live compose file
+#[derive(Clone, Debug, PartialEq)]
+pub enum Proxy {
+    // read by Traefik's docker provider
+    Traefik {
+        entrypoint: String,
+        cert_resolver: Option<String>,
+    },
+    // read by caddy-docker-proxy
+    Caddy,
+    // only wraut's own tracking label is written
+    None,
+}
+
+impl FromStr for Proxy {
+    type Err = String;
+
+    // the Traefik settings are filled in from their own variables
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "traefik" => Ok(Proxy::Traefik {
+                entrypoint: "websecure".to_string(),
+                cert_resolver: Some("letsencrypt".to_string()),
+            }),
+            "caddy" => Ok(Proxy::Caddy),
+            "none" => Ok(Proxy::None),
+            other => Err(other.to_string()),
+        }
+    }
+}
+
+// the host and path prefix, if any, of an access URL such as "example.com/app"
+pub fn route(access_url: &str) -> (&str, Option<&str>) {
+    let url = access_url.trim();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    match url.split_once('/') {
+        Some((host, path)) => {
+            let path = path.trim_matches('/');
+            (host, Some(path).filter(|p| !p.is_empty()))
+        }
+        None => (url, None),
+    }
+}
+
+impl Proxy {
+    // routing labels for the service's first compose service; none without an access URL
+    pub fn labels(&self, service: &Service) -> Vec<(String, String)> {
+        let (host, path) = route(&service.access_url);
+        if host.is_empty() {
+            return vec![];
+        }
+        let name = &service.name;
+        match self {
+            Proxy::Traefik {
+                entrypoint,
+                cert_resolver,
+            } => {
+                let router = format!("traefik.http.routers.{}", name);
+                let rule = match path {
+                    Some(path) => format!("Host(`{}`) && PathPrefix(`/{}`)", host, path),
+                    None => format!("Host(`{}`)", host),
+                };
+                let mut labels = vec![
+                    ("traefik.enable".to_string(), "true".to_string()),
+                    (format!("{}.entrypoints", router), entrypoint.clone()),
+                    (format!("{}.rule", router), rule),
+                    (format!("{}.tls", router), "true".to_string()),
+                ];
+                if let Some(resolver) = cert_resolver {
+                    labels.push((format!("{}.tls.certresolver", router), resolver.clone()));
+                }
+                // containers exposing several ports need to be told which one serves
+                if let Some(port) = service.proxy_port {
+                    labels.push((format!("{}.service", router), name.clone()));
+                    labels.push((
+                        format!("traefik.http.services.{}.loadbalancer.server.port", name),
+                        port.to_string(),
+                    ));
+                }
+                labels
+            }
+            Proxy::Caddy => {
+                let upstreams = match service.proxy_port {
+                    Some(port) => format!("{{{{upstreams {}}}}}", port),
+                    None => "{{upstreams}}".to_string(),
+                };
+                match path {
+                    Some(path) => vec![
+                        ("caddy".to_string(), host.to_string()),
+                        ("caddy.handle_path".to_string(), format!("/{}*", path)),
+                        ("caddy.handle_path.reverse_proxy".to_string(), upstreams),
+                    ],
+                    None => vec![
+                        ("caddy".to_string(), host.to_string()),
+                        ("caddy.reverse_proxy".to_string(), upstreams),
+                    ],
+                }
+            }
+            Proxy::None => vec![],
+        }
+    }
+}
+
+fn label_key(label: &str) -> &str {
+    label.split_once('=').map_or(label, |(key, _)| key)
+}
+
+// adds the labels to a compose service, in whichever of the list or map forms it uses;
+// a label the repo already sets for the same key is replaced
+pub fn inject(
+    compose_service: &mut Mapping,
+    target: &str,
+    labels: &[(String, String)],
+) -> Result<(), ServiceError> {
+    let existing = compose_service
+        .entry(Value::String("labels".into()))
+        .or_insert_with(|| Value::Sequence(vec![]));
+    match existing {
+        Value::Sequence(list) => {
+            list.retain(|l| {
+                l.as_str()
+                    .is_none_or(|l| !labels.iter().any(|(key, _)| label_key(l) == key))
+            });
+            for (key, value) in labels {
+                let label = match value.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}={}", key, value),
+                };
+                list.push(Value::String(label));
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, value) in labels {
+                map.insert(Value::String(key.clone()), Value::String(value.clone()));
+            }
+        }
+        _ => {
+            return Err(ServiceError::Key(format!(
+                "{} labels (as list or map)",
+                target
+            )));
+        }
+    }
+    Ok(())
+}
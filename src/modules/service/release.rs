@@ -0,0 +1,174 @@
+use std::cmp::Ordering;
+
+// shell-style glob supporting `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Version {
+    core: Vec<u64>,
+    pre: Option<String>,
+}
+
+impl Version {
+    // accepts tags like "v1.2.3", "release-2.0" or "1.4.0-rc.1"; build metadata is ignored
+    fn parse(tag: &str) -> Option<Self> {
+        let start = tag.find(|c: char| c.is_ascii_digit())?;
+        let version = tag[start..].split('+').next()?;
+        let (core, pre) = match version.split_once('-') {
+            Some((c, p)) => (c, Some(p.to_string())),
+            None => (version, None),
+        };
+        let core = core
+            .split('.')
+            .map(|n| n.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Version { core, pre })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let width = self.core.len().max(other.core.len());
+        let pad = |v: &Vec<u64>| {
+            (0..width)
+                .map(|i| v.get(i).copied().unwrap_or(0))
+                .collect::<Vec<u64>>()
+        };
+        pad(&self.core)
+            .cmp(&pad(&other.core))
+            // a pre-release sorts below its release
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => compare_pre(a, b),
+            })
+    }
+}
+
+// dot-separated identifiers in turn, numeric ones by value and below alphanumeric ones, so
+// "rc.10" follows "rc.2"; a prefix sorts first (semver's rule 11)
+fn compare_pre(a: &str, b: &str) -> Ordering {
+    let ident = |i: &str| match i.parse::<u64>() {
+        Ok(n) => (false, n, i.to_string()),
+        Err(_) => (true, 0, i.to_string()),
+    };
+    a.split('.').map(ident).cmp(b.split('.').map(ident))
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// highest version among tags matching the pattern; pre-releases only win when nothing
+// stable matches, and non-version tags rank below both
+pub fn pick_tag<'a>(pattern: &str, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    tags.into_iter()
+        .filter(|tag| glob_match(pattern, tag))
+        .max_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+            (Some(va), Some(vb)) => (va.pre.is_none(), va)
+                .cmp(&(vb.pre.is_none(), vb))
+                .then_with(|| a.cmp(b)),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => a.cmp(b),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("v*", "v1.2.3"));
+        assert!(glob_match("v?.*", "v1.0"));
+        assert!(glob_match("*-rc*", "1.0.0-rc.1"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("v*", "release-1.0"));
+        assert!(!glob_match("v?", "v10"));
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            Version::parse("v1.2.3"),
+            Some(Version {
+                core: vec![1, 2, 3],
+                pre: None
+            })
+        );
+        assert_eq!(
+            Version::parse("release-2.0+build.5"),
+            Some(Version {
+                core: vec![2, 0],
+                pre: None
+            })
+        );
+        assert_eq!(
+            Version::parse("1.4.0-rc.1"),
+            Some(Version {
+                core: vec![1, 4, 0],
+                pre: Some("rc.1".to_string())
+            })
+        );
+        assert_eq!(Version::parse("latest"), None);
+        assert_eq!(Version::parse("v1.x"), None);
+    }
+
+    #[test]
+    fn orders_pre_releases() {
+        let v = |tag| Version::parse(tag).unwrap();
+        assert!(v("1.0.0-rc.10") > v("1.0.0-rc.2"));
+        assert!(v("1.0.0-alpha") < v("1.0.0-alpha.1"));
+        assert!(v("1.0.0-alpha.1") < v("1.0.0-alpha.beta"));
+        assert!(v("1.0.0-beta.11") < v("1.0.0-rc.1"));
+        assert!(v("1.0.0-rc.1") < v("1.0.0"));
+        assert_eq!(v("1.0").cmp(&v("1.0.0")), Ordering::Equal);
+    }
+
+    #[test]
+    fn picks_tags() {
+        let tags = ["v1.2.0", "v1.10.0", "v1.9.9", "latest"];
+        assert_eq!(pick_tag("v*", tags), Some("v1.10.0"));
+        assert_eq!(pick_tag("*", tags), Some("v1.10.0"));
+        assert_eq!(pick_tag("nightly*", tags), None);
+        // a stable release beats a newer pre-release
+        assert_eq!(pick_tag("v*", ["v2.0.0-rc.1", "v1.9.0"]), Some("v1.9.0"));
+        assert_eq!(
+            pick_tag("v*", ["v2.0.0-rc.2", "v2.0.0-rc.10"]),
+            Some("v2.0.0-rc.10")
+        );
+        assert_eq!(pick_tag("*", ["latest", "edge"]), Some("latest"));
+    }
+}
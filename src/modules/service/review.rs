@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+use super::short_commit;
+
+// a deploy held until someone approves the compose diff it would apply
+#[derive(Clone, Debug, Serialize)]
+pub struct DeployReview {
+    pub service_id: i64,
+    // what approving deploys, so later pushes don't slip in unreviewed
+    pub commit_sha: Option<String>,
+    pub diff: String,
+    pub requested_at: String,
+}
+
+impl DeployReview {
+    pub fn short_commit(&self) -> Option<&str> {
+        self.commit_sha.as_deref().map(short_commit)
+    }
+
+    // each line of the diff with the class it's shown in
+    pub fn lines(&self) -> Vec<(&'static str, &str)> {
+        self.diff
+            .lines()
+            .map(|line| {
+                let class = match line {
+                    l if l.starts_with("+++") || l.starts_with("---") => "diff-file",
+                    l if l.starts_with('+') => "diff-add",
+                    l if l.starts_with('-') => "diff-remove",
+                    l if l.starts_with("@@") => "diff-hunk",
+                    _ => "diff-context",
+                };
+                (class, line)
+            })
+            .collect()
+    }
+}
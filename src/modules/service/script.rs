@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, HashSet};
+use std::process::Command;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use tracing::{Level, event};
+
+use super::{
+    Service, ServiceError, ServiceEvent, ServiceStatus, inside_repo,
+    pipeline::{DeployStep, Run},
+    process::Limit,
+    valid_slug,
+};
+
+// a command of the service's own run as a step of its pipeline, e.g. `npm run build` after
+// the copy
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    pub name: String,
+    // the pipeline step it follows
+    pub after: String,
+    pub run: String,
+    // relative to the live dir; the live dir itself when unset
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    // COMPOSE_TIMEOUT_SECS when unset
+    pub timeout_secs: Option<u64>,
+}
+
+// e.g.
+// - name: assets
+//   after: rewrite
+//   run: npm ci && npm run build
+//   dir: frontend
+//   env: { NODE_ENV: production }
+//   timeout_secs: 600
+pub fn parse(text: &str) -> Result<Vec<Script>, String> {
+    serde_yaml::from_str::<Option<Vec<Script>>>(text)
+        .map(Option::unwrap_or_default)
+        .map_err(|e| e.to_string())
+}
+
+// why the scripts can't run in a pipeline of `steps`, if they can't
+pub fn check(scripts: &[Script], steps: &[&str]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for script in scripts {
+        if !valid_slug(&script.name) {
+            return Err(format!(
+                "'{}' isn't a valid name; use letters, digits, '-', '_' and '.'",
+                script.name
+            ));
+        }
+        if !names.insert(&script.name) {
+            return Err(format!("'{}' is listed twice", script.name));
+        }
+        if !steps.contains(&script.after.as_str()) {
+            return Err(format!(
+                "'{}' runs after '{}', which isn't in the pipeline",
+                script.name, script.after
+            ));
+        }
+        if script.run.trim().is_empty() {
+            return Err(format!("'{}' has nothing to run", script.name));
+        }
+        if script.dir.as_deref().is_some_and(|dir| !inside_repo(dir)) {
+            return Err(format!(
+                "'{}' has to run in a relative path inside the live dir",
+                script.name
+            ));
+        }
+        if script.timeout_secs == Some(0) {
+            return Err(format!("'{}' needs a timeout above 0", script.name));
+        }
+    }
+    Ok(())
+}
+
+impl Service {
+    pub fn parsed_scripts(&self) -> Result<Vec<Script>, ServiceError> {
+        match &self.scripts {
+            Some(text) => parse(text).map_err(ServiceError::Scripts),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+impl DeployStep for Script {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run<'a>(&'a self, run: &'a mut Run<'_>) -> BoxFuture<'a, Result<(), ServiceError>> {
+        Box::pin(async move {
+            let (service, config) = (run.service, run.config);
+            let step = format!("script {}", self.name);
+            let limit = Limit {
+                step: &step,
+                timeout: self
+                    .timeout_secs
+                    .map_or(config.timeouts.compose, Duration::from_secs),
+                cancel: run.cancel,
+            };
+            let mut path = service.live_path(config);
+            if let Some(dir) = &self.dir {
+                path.push(dir);
+            }
+            let _ = run.br.send(ServiceEvent::update(
+                service.id,
+                ServiceStatus::RunningScript(self.name.clone()),
+            ));
+
+            let mut sh = Command::new("sh");
+            sh.arg("-c")
+                .arg(&self.run)
+                .current_dir(path)
+                .envs(&self.env);
+            let br = run.br;
+            let ran = run
+                .timings
+                .time(&step, || service.stream_output(sh, "SCRIPT", limit, br))?;
+            if !ran {
+                event!(
+                    Level::ERROR,
+                    "SCRIPT FAIL | {} | {}",
+                    service.name,
+                    self.name
+                );
+                return Err(ServiceError::Script(self.name.clone()));
+            }
+            Ok(())
+        })
+    }
+}
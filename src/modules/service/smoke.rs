@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::Client;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{HEALTH_POLL, Service, ServiceError, ServiceEvent, ServiceStatus};
+use crate::modules::Config;
+
+// a single request gives up after this, or when the window closes if that's sooner
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Service {
+    pub fn valid_smoke_url(&self) -> bool {
+        self.smoke_url.as_deref().is_none_or(|url| {
+            reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"))
+        })
+    }
+
+    pub fn parsed_smoke_regex(&self) -> Result<Option<Regex>, ServiceError> {
+        self.smoke_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| ServiceError::SmokeRegex(e.to_string()))
+    }
+
+    // why one request didn't pass, if it didn't
+    async fn smoke_attempt(
+        &self,
+        client: &Client,
+        url: &str,
+        regex: Option<&Regex>,
+        timeout: Duration,
+    ) -> Option<String> {
+        let response = match client.get(url).timeout(timeout).send().await {
+            Ok(response) => response,
+            Err(e) => return Some(e.to_string()),
+        };
+        let status = response.status();
+        let expected = match self.smoke_status {
+            Some(expected) => i64::from(status.as_u16()) == expected,
+            None => status.is_success(),
+        };
+        if !expected {
+            return Some(format!("answered {}", status));
+        }
+        // the status is enough without a regex
+        let regex = regex?;
+        match response.text().await {
+            Ok(body) if regex.is_match(&body) => None,
+            Ok(_) => Some(format!("body doesn't match /{}/", regex)),
+            Err(e) => Some(e.to_string()),
+        }
+    }
+
+    // requests the smoke test URL until it answers as expected, for up to
+    // SMOKE_TIMEOUT_SECS; each miss is reported with the deploy's output
+    pub async fn smoke_test(
+        &self,
+        config: &Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(url) = &self.smoke_url else {
+            return Ok(());
+        };
+        let _ = br.send(ServiceEvent::update(self.id, ServiceStatus::SmokeTesting));
+        let regex = self.parsed_smoke_regex()?;
+        let client = Client::builder()
+            .build()
+            .map_err(|e| ServiceError::Smoke(e.to_string()))?;
+
+        let deadline = Instant::now() + config.timeouts.smoke;
+        loop {
+            let timeout = REQUEST_TIMEOUT.min(deadline.saturating_duration_since(Instant::now()));
+            let failed = tokio::select! {
+                failed = self.smoke_attempt(&client, url, regex.as_ref(), timeout) => failed,
+                _ = cancel.cancelled() => return Err(ServiceError::Cancelled),
+            };
+            let Some(reason) = failed else {
+                event!(Level::INFO, "Smoke test of {} passed", self.name);
+                return Ok(());
+            };
+            event!(Level::INFO, "SMOKE | {} | {}", self.name, reason);
+            let _ = br.send(ServiceEvent::update(
+                self.id,
+                ServiceStatus::HookOutput(format!("{} {}", url, reason)),
+            ));
+            if Instant::now() + HEALTH_POLL >= deadline {
+                return Err(ServiceError::Smoke(reason));
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(HEALTH_POLL) => (),
+                _ = cancel.cancelled() => return Err(ServiceError::Cancelled),
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use serde_yaml::Value;
+
+use super::{Service, env::ServiceEnv, short_commit};
+
+// the values a compose template can refer to as `${NAME}`; wraut's own win over env vars
+// of the same name, and secrets are left for compose to read from `.env` so they never
+// land in the compose file
+pub fn variables(
+    service: &Service,
+    commit: Option<&str>,
+    env_vars: &[ServiceEnv],
+) -> Vec<(String, String)> {
+    let mut vars = vec![
+        ("WRAUT_SERVICE_NAME".to_string(), service.name.clone()),
+        ("WRAUT_ACCESS_URL".to_string(), service.access_url.clone()),
+    ];
+    if let Some(commit) = commit {
+        vars.push(("WRAUT_GIT_SHA".to_string(), commit.to_string()));
+        vars.push((
+            "WRAUT_GIT_SHORT_SHA".to_string(),
+            short_commit(commit).to_string(),
+        ));
+    }
+    if let Some(environment) = &service.environment {
+        vars.push(("WRAUT_ENVIRONMENT".to_string(), environment.clone()));
+    }
+    if let Some(branch) = &service.branch {
+        vars.push(("WRAUT_BRANCH".to_string(), branch.clone()));
+    }
+    for var in env_vars.iter().filter(|var| !var.secret) {
+        if !vars.iter().any(|(key, _)| *key == var.key) {
+            vars.push((var.key.clone(), var.value.clone()));
+        }
+    }
+    vars
+}
+
+// replaces `${NAME}` for each known variable; anything else, including `$${NAME}` escapes
+// and `${NAME:-default}` forms, is left for compose's own interpolation; a substituted value's
+// own `$`s are escaped so compose doesn't interpolate them again
+fn render(template: &str, vars: &[(String, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("$$") {
+            rendered.push_str("$$");
+            rest = escaped;
+            continue;
+        }
+
+        let value = rest
+            .strip_prefix("${")
+            .and_then(|inner| inner.split_once('}'))
+            .and_then(|(name, after)| {
+                vars.iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| (value, after))
+            });
+        match value {
+            Some((value, after)) => {
+                rendered.push_str(&value.replace('$', "$$"));
+                rest = after;
+            }
+            None => {
+                rendered.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+// renders every string in a parsed compose file, the way compose interpolates values,
+// so substituted text can't change the file's structure
+pub fn render_value(value: &mut Value, vars: &[(String, String)]) {
+    match value {
+        Value::String(s) => *s = render(s, vars),
+        Value::Sequence(list) => list.iter_mut().for_each(|v| render_value(v, vars)),
+        Value::Mapping(map) => map.values_mut().for_each(|v| render_value(v, vars)),
+        Value::Tagged(tagged) => render_value(&mut tagged.value, vars),
+        _ => (),
+    }
+}
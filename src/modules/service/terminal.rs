@@ -0,0 +1,32 @@
+use std::process::Stdio;
+
+use tokio::process::{Child, Command};
+
+use super::{Service, ServiceError, network};
+use crate::modules::Config;
+
+impl Service {
+    // `compose exec` into one of the service's containers with its pipes open for a socket to
+    // drive; there's no TTY, so the shell reads a line at a time
+    pub fn shell(&self, config: &Config, container: &str) -> Result<Child, ServiceError> {
+        if self.node_id.is_some() {
+            return Err(ServiceError::TerminalOnNode);
+        }
+        // compose service names follow the same rule as network names
+        if !network::valid_name(container) {
+            return Err(ServiceError::Container(container.to_string()));
+        }
+        let path = self.live_path(config);
+        let mut compose = self.compose(&path)?;
+        compose
+            .args(["exec", "-T"])
+            .arg(container)
+            .args(["sh", "-i"]);
+        Ok(Command::from(compose)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?)
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StepTiming {
+    pub step: String,
+    pub millis: u64,
+}
+
+impl StepTiming {
+    pub fn duration(&self) -> String {
+        format_millis(self.millis)
+    }
+}
+
+// how long each pipeline step of a deploy took, in the order they ran
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Timings(Vec<StepTiming>);
+
+impl Timings {
+    // runs the step and records how long it took, whether or not it succeeded
+    pub fn time<T>(&mut self, step: &str, run: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = run();
+        self.record(step, started);
+        result
+    }
+
+    // for steps that have to be awaited
+    pub fn record(&mut self, step: &str, started: Instant) {
+        self.0.push(StepTiming {
+            step: step.to_string(),
+            millis: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    pub fn steps(self) -> Vec<StepTiming> {
+        self.0
+    }
+}
+
+// e.g. "850ms", "4.2s", "2m 05s"
+pub fn format_millis(millis: u64) -> String {
+    let duration = Duration::from_millis(millis);
+    match duration.as_secs() {
+        0 => format!("{}ms", millis),
+        secs @ 1..10 => format!("{}.{}s", secs, duration.subsec_millis() / 100),
+        secs @ 10..60 => format!("{}s", secs),
+        secs => format!("{}m {:02}s", secs / 60, secs % 60),
+    }
+}
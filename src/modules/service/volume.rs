@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{Level, event};
+
+use super::{
+    Service, ServiceError, ServiceEvent, ServiceStatus, network,
+    process::{self, Limit},
+};
+use crate::modules::{Config, format};
+
+const EXTENSION: &str = ".tar.gz";
+// a snapshot is written under this suffix and renamed once every volume is in
+const PARTIAL: &str = ".partial";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Snapshot {
+    // when it was taken, e.g. 20250101T030000.000Z; later snapshots sort after earlier ones
+    pub name: String,
+    pub volumes: Vec<String>,
+    pub bytes: u64,
+}
+
+impl Snapshot {
+    pub fn size(&self) -> String {
+        format::size(self.bytes)
+    }
+}
+
+fn service_dir(config: &Config, service_name: &str) -> PathBuf {
+    config.volume_backup_dir.join(service_name)
+}
+
+// newest first
+pub fn list(config: &Config, service_name: &str) -> Result<Vec<Snapshot>, ServiceError> {
+    let dir = service_dir(config, service_name);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut snapshots = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type()?.is_dir() || name.ends_with(PARTIAL) {
+            continue;
+        }
+        let mut volumes = vec![];
+        let mut bytes = 0;
+        for file in std::fs::read_dir(entry.path())? {
+            let file = file?;
+            let file_name = file.file_name().to_string_lossy().to_string();
+            if let Some(volume) = file_name.strip_suffix(EXTENSION) {
+                volumes.push(volume.to_string());
+                bytes += file.metadata()?.len();
+            }
+        }
+        volumes.sort();
+        snapshots.push(Snapshot {
+            name,
+            volumes,
+            bytes,
+        });
+    }
+    snapshots.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(snapshots)
+}
+
+// removes the oldest snapshots beyond VOLUME_BACKUP_KEEP
+fn trim(config: &Config, service_name: &str) -> Result<usize, ServiceError> {
+    let Some(keep) = config.volume_backup_keep else {
+        return Ok(0);
+    };
+    let dir = service_dir(config, service_name);
+    let mut removed = 0;
+    for snapshot in list(config, service_name)?.into_iter().skip(keep) {
+        std::fs::remove_dir_all(dir.join(&snapshot.name))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+impl Service {
+    pub fn backed_up_volumes(&self) -> Option<Vec<&str>> {
+        self.backup_volumes.as_deref().map(|volumes| {
+            volumes
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .collect()
+        })
+    }
+
+    // volume names follow the same rule as network names
+    pub fn valid_backup_volumes(&self) -> bool {
+        self.backed_up_volumes().is_none_or(|volumes| {
+            !volumes.is_empty() && volumes.iter().all(|v| network::valid_name(v))
+        })
+    }
+
+    // the docker volume compose made for one of the project's named volumes; None until
+    // the first `compose up` creates it
+    fn volume_id(&self, volume: &str) -> Result<Option<String>, ServiceError> {
+        let output = self
+            .docker()
+            .args(["volume", "ls", "-q", "--filter"])
+            .arg(format!(
+                "label=com.docker.compose.project={}",
+                self.project_name()
+            ))
+            .arg("--filter")
+            .arg(format!("label=com.docker.compose.volume={}", volume))
+            .output()?;
+        if !output.status.success() {
+            event!(
+                Level::ERROR,
+                "VOLUME LS FAIL | {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Err(ServiceError::Status);
+        }
+        Ok(std::str::from_utf8(&output.stdout)?
+            .lines()
+            .map(str::trim)
+            .find(|id| !id.is_empty())
+            .map(str::to_string))
+    }
+
+    // tars each of the service's backed up volumes into a new snapshot, then trims old ones;
+    // volumes that don't exist yet are skipped
+    pub fn backup_volumes(
+        &self,
+        config: &Config,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let Some(volumes) = self.backed_up_volumes() else {
+            return Ok(());
+        };
+        let _ = br.send(ServiceEvent::update(
+            self.id,
+            ServiceStatus::BackingUpVolumes,
+        ));
+
+        let dir = service_dir(config, &self.name);
+        let name = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let partial = dir.join(format!("{}{}", name, PARTIAL));
+        std::fs::create_dir_all(&partial)?;
+
+        let written = match self.snapshot(&volumes, &partial, config, cancel) {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&partial);
+                return Err(e);
+            }
+        };
+        if written == 0 {
+            event!(Level::INFO, "No volumes of {} to back up yet", self.name);
+            std::fs::remove_dir_all(&partial)?;
+            return Ok(());
+        }
+        std::fs::rename(&partial, dir.join(&name))?;
+        event!(
+            Level::INFO,
+            "Backed up {} volumes of {} to {}",
+            written,
+            self.name,
+            name
+        );
+
+        match trim(config, &self.name) {
+            Ok(0) => (),
+            Ok(removed) => event!(Level::INFO, "Removed {} old volume snapshots", removed),
+            Err(e) => event!(Level::WARN, "Unable to trim volume snapshots | {}", e),
+        }
+        Ok(())
+    }
+
+    fn snapshot(
+        &self,
+        volumes: &[&str],
+        dir: &Path,
+        config: &Config,
+        cancel: &CancellationToken,
+    ) -> Result<usize, ServiceError> {
+        let mut written = 0;
+        for volume in volumes {
+            let Some(id) = self.volume_id(volume)? else {
+                event!(
+                    Level::WARN,
+                    "Volume {} of {} doesn't exist, not backing it up",
+                    volume,
+                    self.name
+                );
+                continue;
+            };
+            let file = File::create(dir.join(format!("{}{}", volume, EXTENSION)))?;
+            let mut docker = self.docker();
+            docker
+                .args(["run", "--rm", "-v"])
+                .arg(format!("{}:/volume:ro", id))
+                .arg(&config.volume_backup_image)
+                .args(["tar", "czf", "-", "-C", "/volume", "."]);
+            let limit = Limit {
+                step: "volume backup",
+                timeout: config.timeouts.compose,
+                cancel,
+            };
+            let output = process::with_files(docker, None, Some(file), limit)?;
+            if !output.status.success() {
+                event!(
+                    Level::ERROR,
+                    "VOLUME BACKUP FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                return Err(ServiceError::VolumeBackup(volume.to_string()));
+            }
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    // stops the service, replaces the contents of each volume in the snapshot with the
+    // archived ones, and starts it again
+    pub fn restore_volumes(
+        &self,
+        config: Config,
+        snapshot: &str,
+        cancel: &CancellationToken,
+        br: &broadcast::Sender<ServiceEvent>,
+    ) -> Result<(), ServiceError> {
+        let snapshot = list(&config, &self.name)?
+            .into_iter()
+            .find(|s| s.name == snapshot)
+            .ok_or_else(|| ServiceError::NoSnapshot(snapshot.to_string()))?;
+        let dir = service_dir(&config, &self.name).join(&snapshot.name);
+
+        self.stop(config.clone(), cancel, br)?;
+        let _ = br.send(ServiceEvent::update(
+            self.id,
+            ServiceStatus::RestoringVolumes,
+        ));
+        for volume in &snapshot.volumes {
+            let id = self
+                .volume_id(volume)?
+                .ok_or_else(|| ServiceError::VolumeRestore(volume.clone()))?;
+            let file = File::open(dir.join(format!("{}{}", volume, EXTENSION)))?;
+            let mut docker = self.docker();
+            docker
+                .args(["run", "--rm", "-i", "-v"])
+                .arg(format!("{}:/volume", id))
+                .arg(&config.volume_backup_image)
+                .args([
+                    "sh",
+                    "-c",
+                    "find /volume -mindepth 1 -delete && tar xzf - -C /volume",
+                ]);
+            let limit = Limit {
+                step: "volume restore",
+                timeout: config.timeouts.compose,
+                cancel,
+            };
+            let output = process::with_files(docker, Some(file), None, limit)?;
+            if !output.status.success() {
+                event!(
+                    Level::ERROR,
+                    "VOLUME RESTORE FAIL | {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                return Err(ServiceError::VolumeRestore(volume.clone()));
+            }
+        }
+        event!(
+            Level::INFO,
+            "Restored volumes of {} from {}",
+            self.name,
+            snapshot.name
+        );
+
+        self.start(config, cancel, br)
+    }
+}
@@ -0,0 +1,96 @@
+use askama::Template;
+
+use crate::modules::{
+    AppState, db,
+    markup::{Markup, render},
+    service::Service,
+};
+
+use super::Health;
+
+const GREEN: &str = "#4c1";
+const AMBER: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+
+// a shields.io-style badge, sized by estimating the text's width in 11px Verdana
+#[derive(Template)]
+#[template(path = "statuspage/badge.svg")]
+struct BadgeTemplate {
+    label: String,
+    message: String,
+    color: &'static str,
+    label_width: usize,
+    message_width: usize,
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | '\'' | '|' | ' ' => 4,
+            'f' | 'r' | 't' | 'I' | '-' | '(' | ')' => 5,
+            'm' | 'w' | 'M' | 'W' => 10,
+            c if c.is_ascii_uppercase() || c.is_ascii_digit() => 8,
+            _ => 7,
+        })
+        .sum::<usize>()
+        + 10
+}
+
+pub fn render_badge(label: &str, message: &str, color: &'static str) -> Markup {
+    render(&BadgeTemplate {
+        label: label.to_string(),
+        message: message.to_string(),
+        color,
+        label_width: text_width(label),
+        message_width: text_width(message),
+    })
+}
+
+pub fn not_found() -> Markup {
+    render_badge("wraut", "not found", GREY)
+}
+
+pub fn unavailable() -> Markup {
+    render_badge("wraut", "unavailable", GREY)
+}
+
+// what a service's badge says: a failed deploy until the next one, otherwise its health
+// as the status page shows it. None for unknown and archived services
+pub async fn service_badge(
+    app_state: &AppState,
+    name: &str,
+) -> Result<Option<Markup>, db::DBError> {
+    let Some(service) = db::get_services(&app_state.pool, Some(false))
+        .await?
+        .into_iter()
+        .find(|s| s.name.eq_ignore_ascii_case(name))
+    else {
+        return Ok(None);
+    };
+
+    let (message, color) = match service.recorded_status() {
+        _ if !service.active => ("inactive", GREY),
+        Some(status) if status.is_failure() && !app_state.in_flight.contains(service.id) => {
+            ("deploy failed", RED)
+        }
+        _ => {
+            let containers = app_state
+                .docker_cache
+                .list(&Service::docker_hosts([&service]))
+                .await
+                .map(|mut list| {
+                    list.extend(app_state.agents.containers());
+                    list
+                });
+            match super::health(app_state, &service, &containers) {
+                Health::Operational => ("operational", GREEN),
+                Health::Degraded => ("degraded", AMBER),
+                Health::Maintenance => ("deploying", AMBER),
+                Health::Down => ("down", RED),
+                Health::Unknown => ("unknown", GREY),
+            }
+        }
+    };
+    Ok(Some(render_badge(&service.name, message, color)))
+}
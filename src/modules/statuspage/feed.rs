@@ -0,0 +1,71 @@
+use askama::Template;
+use chrono::NaiveDateTime;
+
+use crate::modules::{
+    AppState, db,
+    markup::{Markup, render},
+    service::Deployment,
+    uptime,
+};
+
+// deployments the feed lists, newest first
+const ENTRIES: i64 = 50;
+
+// a deployment of an unarchived service, as the feed tells it
+pub struct Release {
+    pub service_name: String,
+    pub environment: Option<String>,
+    pub deployment: Deployment,
+}
+
+impl Release {
+    pub fn title(&self) -> String {
+        let name = &self.service_name;
+        match (
+            self.deployment.outcome.as_str(),
+            self.deployment.short_commit(),
+        ) {
+            ("succeeded", Some(commit)) => format!("{} deployed {}", name, commit),
+            ("succeeded", None) => format!("{} deployed", name),
+            ("cancelled", _) => format!("{} deploy cancelled", name),
+            _ => format!("{} failed to deploy", name),
+        }
+    }
+
+    pub fn updated(&self) -> String {
+        rfc3339(&self.deployment.created_at)
+    }
+}
+
+// "2024-05-01 12:00:00", as sqlite stores UTC times, to "2024-05-01T12:00:00Z"
+fn rfc3339(at: &str) -> String {
+    NaiveDateTime::parse_from_str(at, uptime::TIMESTAMP)
+        .map(|at| {
+            at.and_utc()
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        })
+        .unwrap_or_else(|_| at.to_string())
+}
+
+// like the status page, it names no steps, commands or errors
+#[derive(Template)]
+#[template(path = "statuspage/feed.atom", escape = "html")]
+struct FeedTemplate {
+    releases: Vec<Release>,
+    updated: String,
+    // PUBLIC_URL, which entries link to when it's set
+    public_url: Option<String>,
+}
+
+pub async fn feed(app_state: &AppState) -> Result<Markup, db::DBError> {
+    let releases = db::get_releases(&app_state.pool, ENTRIES).await?;
+    let updated = match releases.first() {
+        Some(release) => release.updated(),
+        None => chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    };
+    Ok(render(&FeedTemplate {
+        releases,
+        updated,
+        public_url: app_state.config.public_url.clone(),
+    }))
+}
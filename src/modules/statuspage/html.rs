@@ -0,0 +1,71 @@
+use askama::Template;
+use axum::response::sse::Event;
+
+use crate::modules::{
+    assets,
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::{Health, PublicService};
+
+#[derive(Template)]
+#[template(path = "statuspage/page.html")]
+struct PageTemplate {
+    htmx_src: String,
+    htmx_sse_src: String,
+    font_css_href: String,
+}
+
+#[derive(Template)]
+#[template(path = "statuspage/table.html")]
+struct TableTemplate {
+    services: Vec<PublicService>,
+    error: bool,
+}
+
+#[derive(Template)]
+#[template(path = "statuspage/health.html")]
+struct HealthTemplate {
+    id: i64,
+    health: Health,
+}
+
+pub fn page() -> Markup {
+    render(&PageTemplate {
+        htmx_src: assets::url(assets::HTMX, assets::HTMX_CDN),
+        htmx_sse_src: assets::url(assets::HTMX_SSE, assets::HTMX_SSE_CDN),
+        font_css_href: assets::font_css_url(),
+    })
+}
+
+fn status_event(html: Markup) -> Event {
+    Event::default()
+        .event("status_event")
+        .data(html.into_string())
+}
+
+// database errors stay in the logs
+pub fn table(services: Result<Vec<PublicService>, DBError>) -> Event {
+    let template = match services {
+        Ok(services) => TableTemplate {
+            services,
+            error: false,
+        },
+        Err(_) => TableTemplate {
+            services: vec![],
+            error: true,
+        },
+    };
+    status_event(render(&template))
+}
+
+pub fn health(id: i64, health: Health) -> Event {
+    status_event(render(&HealthTemplate { id, health }))
+}
+
+pub fn shutdown() -> Event {
+    status_event(Markup::trusted(
+        "<div id=\"link-status\" class=\"warning-chip\">Reconnecting...</div>",
+    ))
+}
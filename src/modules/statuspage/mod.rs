@@ -0,0 +1,174 @@
+pub mod badge;
+pub mod feed;
+pub mod html;
+
+use std::fmt;
+
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::stream::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{
+    AppState, db,
+    service::{DockerServiceEntry, Service, ServiceError, ServiceEvent, ServiceStatus},
+    uptime::{self, Uptime},
+};
+
+// what end users are told about a service; nothing that names a step, command or error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Health {
+    Operational,
+    Degraded,
+    Maintenance,
+    Down,
+    Unknown,
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Health::Operational => write!(f, "Operational"),
+            Health::Degraded => write!(f, "Degraded"),
+            Health::Maintenance => write!(f, "Maintenance"),
+            Health::Down => write!(f, "Down"),
+            Health::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+impl Health {
+    pub fn class(&self) -> &'static str {
+        match self {
+            Health::Operational => "success",
+            Health::Degraded | Health::Maintenance => "warning",
+            Health::Down => "error",
+            Health::Unknown => "unknown",
+        }
+    }
+
+    // None when only docker can tell, like after a failed deploy that left the old
+    // version running
+    fn announced(status: &ServiceStatus) -> Option<Health> {
+        match status {
+            ServiceStatus::Running | ServiceStatus::Completed => Some(Health::Operational),
+            ServiceStatus::Degraded(_) => Some(Health::Degraded),
+            ServiceStatus::Unknown | ServiceStatus::Inactive => None,
+            s if s.is_failure() => None,
+            _ => Some(Health::Maintenance),
+        }
+    }
+}
+
+pub struct PublicService {
+    pub id: i64,
+    pub name: String,
+    pub health: Health,
+    pub uptime: Uptime,
+}
+
+// active services as end users see them
+async fn public_services(app_state: &AppState) -> Result<Vec<PublicService>, db::DBError> {
+    let services: Vec<Service> = db::get_services(&app_state.pool, Some(false))
+        .await?
+        .into_iter()
+        .filter(|s| s.active)
+        .collect();
+    let containers = app_state
+        .docker_cache
+        .list(&Service::docker_hosts(&services))
+        .await
+        .map(|mut list| {
+            list.extend(app_state.agents.containers());
+            list
+        });
+    let mut uptime = db::get_uptime_transitions(&app_state.pool, None, uptime::month_ago())
+        .await
+        .map(uptime::by_service)
+        .unwrap_or_default();
+
+    Ok(services
+        .into_iter()
+        .map(|service| PublicService {
+            id: service.id,
+            uptime: uptime.remove(&service.id).unwrap_or_default(),
+            health: health(app_state, &service, &containers),
+            name: service.name,
+        })
+        .collect())
+}
+
+fn health(
+    app_state: &AppState,
+    service: &Service,
+    containers: &Result<Vec<DockerServiceEntry>, ServiceError>,
+) -> Health {
+    let offline = service
+        .node_id
+        .is_some_and(|id| !app_state.agents.is_connected(id));
+    match containers {
+        _ if app_state.in_flight.contains(service.id) => Health::Maintenance,
+        Ok(_) if offline => Health::Unknown,
+        // a one-off job is judged by how its last run ended
+        Ok(_) if service.is_one_off() => match service.recorded_status() {
+            Some(ServiceStatus::Completed) => Health::Operational,
+            Some(s) if s.is_failure() => Health::Down,
+            _ => Health::Unknown,
+        },
+        Ok(list) => match service.container_status(list) {
+            ServiceStatus::Running => Health::Operational,
+            ServiceStatus::Degraded(_) => Health::Degraded,
+            _ => Health::Down,
+        },
+        Err(_) => Health::Unknown,
+    }
+}
+
+// the dashboard's event stream, cut down to names, health and uptime
+pub fn stream(app_state: AppState) -> impl Stream<Item = Result<Event, axum::Error>> {
+    let mut receiver = app_state.service_broadcast.subscribe();
+
+    stream! {
+        yield Ok(html::table(public_services(&app_state).await));
+
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(dropped)) => {
+                    event!(
+                        Level::WARN,
+                        "Status page stream fell behind by {} events, re-syncing",
+                        dropped
+                    );
+                    ServiceEvent::AllStatus
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if matches!(event, ServiceEvent::ServiceUpdate { .. }) {
+                app_state.docker_cache.invalidate();
+            }
+            match event {
+                ServiceEvent::ServiceUpdate { id, status, .. } => match Health::announced(&status) {
+                    Some(health) => yield Ok(html::health(id, health)),
+                    None => yield Ok(html::table(public_services(&app_state).await)),
+                },
+                ServiceEvent::AllStatus => {
+                    yield Ok(html::table(public_services(&app_state).await));
+                }
+                ServiceEvent::Shutdown => {
+                    yield Ok(html::shutdown());
+                    break;
+                }
+                ServiceEvent::Usage { .. }
+                | ServiceEvent::Drift { .. }
+                | ServiceEvent::BulkProgress { .. }
+                | ServiceEvent::DeployStarted { .. }
+                | ServiceEvent::Deployed { .. }
+                | ServiceEvent::Health { .. }
+                | ServiceEvent::JobRan { .. }
+                | ServiceEvent::UnknownEvent { .. } => (),
+            }
+        }
+    }
+}
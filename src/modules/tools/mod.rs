@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+use tracing::{Level, event};
+
+use super::Config;
+
+// set once at startup from GIT_BIN and DOCKER_BIN; until then the names are looked up on PATH
+static BINARIES: OnceLock<Binaries> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum ToolError {
+    #[error("{name} not found at '{path}' ({error}); install it or set {var} to its path")]
+    Missing {
+        name: &'static str,
+        path: String,
+        var: &'static str,
+        error: std::io::Error,
+    },
+    #[error("{0} failed | {1}")]
+    Failed(String, String),
+}
+
+#[derive(Debug)]
+struct Binaries {
+    git: PathBuf,
+    docker: PathBuf,
+}
+
+pub fn init(config: &Config) {
+    let _ = BINARIES.set(Binaries {
+        git: config.git_bin.clone(),
+        docker: config.docker_bin.clone(),
+    });
+}
+
+fn binaries() -> (&'static Path, &'static Path) {
+    match BINARIES.get() {
+        Some(b) => (&b.git, &b.docker),
+        None => (Path::new("git"), Path::new("docker")),
+    }
+}
+
+pub fn git() -> Command {
+    Command::new(binaries().0)
+}
+
+pub fn docker() -> Command {
+    Command::new(binaries().1)
+}
+
+#[derive(Debug)]
+pub struct Versions {
+    pub git: String,
+    pub docker: String,
+    pub compose: String,
+}
+
+// runs a version command, returning the first line it prints
+fn version(
+    mut command: Command,
+    name: &'static str,
+    var: &'static str,
+    args: &[&str],
+) -> Result<String, ToolError> {
+    let path = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|error| ToolError::Missing {
+            name,
+            path: path.clone(),
+            var,
+            error,
+        })?;
+    let step = format!("{} {}", path, args.join(" "));
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()),
+        false => Err(ToolError::Failed(
+            step,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+// makes sure git, docker and its compose plugin can run before anything depends on
+// them; the docker daemon itself is /readyz's concern
+pub fn preflight() -> Result<Versions, ToolError> {
+    let versions = Versions {
+        git: version(git(), "git", "GIT_BIN", &["--version"])?,
+        docker: version(docker(), "docker", "DOCKER_BIN", &["--version"])?,
+        compose: version(
+            docker(),
+            "docker",
+            "DOCKER_BIN",
+            &["compose", "version", "--short"],
+        )?,
+    };
+    event!(
+        Level::INFO,
+        "Found {} | {} | compose {}",
+        versions.git,
+        versions.docker,
+        versions.compose
+    );
+    Ok(versions)
+}
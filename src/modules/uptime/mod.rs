@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{
+    AppState, db,
+    service::{Service, ServiceEvent},
+};
+
+const TICK: Duration = Duration::from_secs(30);
+// how sqlite's datetime('now') writes timestamps
+pub const TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S";
+
+// the moment a service's containers went up or down, as observed
+#[derive(Clone, Debug)]
+pub struct Transition {
+    pub service_id: i64,
+    pub up: bool,
+    pub at: NaiveDateTime,
+}
+
+// percentage of each window the service was up; None until it's been observed
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Uptime {
+    pub day: Option<f64>,
+    pub week: Option<f64>,
+    pub month: Option<f64>,
+}
+
+impl Uptime {
+    pub fn percentages(&self) -> Vec<String> {
+        [self.day, self.week, self.month]
+            .iter()
+            .map(|percent| match percent {
+                Some(p) => format!("{:.2}%", p),
+                None => "-".to_string(),
+            })
+            .collect()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        ["24h", "7d", "30d"]
+            .iter()
+            .zip(self.percentages())
+            .map(|(window, percent)| format!("{}: {}", window, percent))
+            .collect()
+    }
+}
+
+pub fn month_ago() -> NaiveDateTime {
+    (Utc::now() - TimeDelta::days(30)).naive_utc()
+}
+
+// share of the window since `since` the service was up, given its transitions in time
+// order; a service first seen inside the window is measured from then
+fn availability(
+    transitions: &[Transition],
+    since: NaiveDateTime,
+    now: NaiveDateTime,
+) -> Option<f64> {
+    let mut up_secs = 0;
+    let mut total_secs = 0;
+    let mut current: Option<(bool, NaiveDateTime)> = None;
+    for transition in transitions {
+        let at = transition.at.max(since);
+        if let Some((up, from)) = current {
+            let span = (at - from).num_seconds();
+            total_secs += span;
+            if up {
+                up_secs += span;
+            }
+        }
+        current = Some((transition.up, at));
+    }
+
+    let (up, from) = current?;
+    let span = (now - from).num_seconds();
+    total_secs += span;
+    if up {
+        up_secs += span;
+    }
+    (total_secs > 0).then(|| up_secs as f64 * 100.0 / total_secs as f64)
+}
+
+// transitions must be ordered by service, then time, starting with the last one before
+// the 30 day window
+pub fn by_service(transitions: Vec<Transition>) -> HashMap<i64, Uptime> {
+    let now = Utc::now().naive_utc();
+    let mut grouped: HashMap<i64, Vec<Transition>> = HashMap::new();
+    for transition in transitions {
+        grouped
+            .entry(transition.service_id)
+            .or_default()
+            .push(transition);
+    }
+    grouped
+        .into_iter()
+        .map(|(id, transitions)| {
+            let window = |days| availability(&transitions, now - TimeDelta::days(days), now);
+            (
+                id,
+                Uptime {
+                    day: window(1),
+                    week: window(7),
+                    month: window(30),
+                },
+            )
+        })
+        .collect()
+}
+
+// background task that records each service going up or down
+pub async fn track(app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut interval = tokio::time::interval(TICK);
+    // the latest recorded state of each service, so only changes are written
+    let mut last: HashMap<i64, bool> =
+        match db::get_uptime_transitions(&app_state.pool, None, Utc::now().naive_utc()).await {
+            Ok(transitions) => transitions.iter().map(|t| (t.service_id, t.up)).collect(),
+            Err(e) => {
+                event!(Level::ERROR, "Unable to read uptime history | {}", e);
+                HashMap::new()
+            }
+        };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        }
+
+        let services = match db::get_services(&app_state.pool, Some(false)).await {
+            Ok(s) => s,
+            Err(e) => {
+                event!(
+                    Level::ERROR,
+                    "Uptime tracker unable to get services | {}",
+                    e
+                );
+                continue;
+            }
+        };
+        let containers = match Service::get_list(&Service::docker_hosts(&services)).await {
+            Ok(mut list) => {
+                list.extend(app_state.agents.containers());
+                list
+            }
+            Err(e) => {
+                event!(
+                    Level::WARN,
+                    "Uptime tracker unable to list containers | {}",
+                    e
+                );
+                continue;
+            }
+        };
+
+        for service in services {
+            // nothing is known about a disconnected node's containers, and a one-off job's
+            // aren't meant to stay up
+            if service.is_one_off()
+                || service
+                    .node_id
+                    .is_some_and(|id| !app_state.agents.is_connected(id))
+            {
+                continue;
+            }
+            let up = service.is_running(&containers);
+            if last.get(&service.id) == Some(&up) {
+                continue;
+            }
+            match db::new_uptime_transition(&app_state.pool, service.id, up).await {
+                Ok(_) => {
+                    // the first sighting of a service isn't a change
+                    if last.insert(service.id, up).is_some() {
+                        let _ = app_state
+                            .service_broadcast
+                            .broadcaster
+                            .send(ServiceEvent::Health { id: service.id, up });
+                    }
+                }
+                Err(e) => event!(
+                    Level::WARN,
+                    "Unable to record uptime of {} | {}",
+                    service.name,
+                    e
+                ),
+            }
+        }
+    }
+
+    event!(Level::INFO, "Uptime tracker stopped.");
+}
@@ -0,0 +1,41 @@
+use askama::Template;
+
+use crate::modules::{
+    db::DBError,
+    markup::{Markup, render},
+};
+
+use super::{Role, User};
+
+#[derive(Template)]
+#[template(path = "user/manager.html")]
+struct ManagerTemplate {
+    users: Vec<User>,
+    roles: [Role; 3],
+    error: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "user/login.html")]
+struct LoginTemplate {
+    error: Option<String>,
+}
+
+pub fn manager(users: Result<Vec<User>, DBError>, message: Option<String>) -> Markup {
+    let (users, error) = match users {
+        Ok(us) => (us, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+
+    render(&ManagerTemplate {
+        users,
+        roles: Role::ALL,
+        error,
+        message,
+    })
+}
+
+pub fn login(error: Option<String>) -> Markup {
+    render(&LoginTemplate { error })
+}
@@ -0,0 +1,174 @@
+pub mod html;
+
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use openssl::{base64, hash::MessageDigest, memcmp, pkcs5::pbkdf2_hmac, rand::rand_bytes};
+use serde::Serialize;
+use thiserror::Error;
+
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const HASH_ITERATIONS: usize = 100_000;
+const HASH_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+pub const SESSION_COOKIE: &str = "wraut_session";
+pub const SESSION_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Error, Debug)]
+pub enum UserError {
+    #[error("Usernames may only contain letters, digits, '-', '_' and '.'")]
+    Name,
+    #[error("Passwords must be at least 8 characters")]
+    Password,
+    #[error("Unknown role")]
+    Role,
+    #[error("Hashing failure")]
+    Crypto(#[from] openssl::error::ErrorStack),
+}
+
+// ordered so a higher role can do everything a lower one can
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Deployer,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Admin, Role::Deployer, Role::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Deployer => "deployer",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn can_deploy(&self) -> bool {
+        *self >= Role::Deployer
+    }
+
+    pub fn can_admin(&self) -> bool {
+        *self >= Role::Admin
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = UserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "deployer" => Ok(Role::Deployer),
+            "admin" => Ok(Role::Admin),
+            _ => Err(UserError::Role),
+        }
+    }
+}
+
+// set on every request by the auth middleware; only absent on routes outside it
+impl<S: Send + Sync> FromRequestParts<S> for Role {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Role>()
+            .copied()
+            .unwrap_or(Role::Viewer))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+}
+
+impl User {
+    pub fn valid_username(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    }
+}
+
+// stored as "pbkdf2-sha256$<iterations>$<salt>$<hash>", salt and hash base64-encoded
+pub fn hash_password(password: &str) -> Result<String, UserError> {
+    if password.chars().count() < 8 {
+        return Err(UserError::Password);
+    }
+    let mut salt = [0u8; SALT_LEN];
+    rand_bytes(&mut salt)?;
+    let mut hash = [0u8; HASH_LEN];
+    pbkdf2_hmac(
+        password.as_bytes(),
+        &salt,
+        HASH_ITERATIONS,
+        MessageDigest::sha256(),
+        &mut hash,
+    )?;
+    Ok(format!(
+        "{}${}${}${}",
+        HASH_SCHEME,
+        HASH_ITERATIONS,
+        base64::encode_block(&salt),
+        base64::encode_block(&hash)
+    ))
+}
+
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [scheme, iterations, salt, hash] = parts[..] else {
+        return false;
+    };
+    let (Ok(iterations), Ok(salt), Ok(expected)) = (
+        iterations.parse::<usize>(),
+        base64::decode_block(salt),
+        base64::decode_block(hash),
+    ) else {
+        return false;
+    };
+    if scheme != HASH_SCHEME || expected.len() != HASH_LEN {
+        return false;
+    }
+
+    let mut hash = [0u8; HASH_LEN];
+    match pbkdf2_hmac(
+        password.as_bytes(),
+        &salt,
+        iterations,
+        MessageDigest::sha256(),
+        &mut hash,
+    ) {
+        Ok(_) => memcmp::eq(&hash, &expected),
+        Err(_) => false,
+    }
+}
+
+// on Result::Ok, returns the token for the cookie and the hash to store
+pub fn new_session_token() -> Result<(String, String), UserError> {
+    let mut bytes = [0u8; 32];
+    rand_bytes(&mut bytes)?;
+    let token = hex(&bytes);
+    let token_hash = session_token_hash(&token);
+    Ok((token, token_hash))
+}
+
+pub fn session_token_hash(token: &str) -> String {
+    hex(&openssl::sha::sha256(token.as_bytes()))
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,1214 @@
+use crate::modules::{
+    AppState,
+    activity::{self, Activity},
+    audit::{self, Actor, AuditAction},
+    backup::{self, BackupError},
+    db,
+    group::{Group, Summary},
+    service::{
+        ContainerState, Deployment, DeploymentLog, Lifecycle, Service, ServiceEvent, ServiceFilter,
+        approval::{DeployApproval, Trigger},
+        compose_view::ComposeView,
+        duplicate,
+        preview::{self, PullRequestEvent},
+        promote::{self, PromoteError},
+        review::DeployReview,
+    },
+    uptime::{self, Uptime},
+    user::{self, Role},
+};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{Level, event};
+
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<db::DBError> for ApiError {
+    fn from(e: db::DBError) -> Self {
+        match e {
+            db::DBError::Sql(sqlx::Error::RowNotFound) => {
+                Self::new(StatusCode::NOT_FOUND, "Service not found")
+            }
+            e @ db::DBError::DuplicateName(_) => Self::new(StatusCode::CONFLICT, e.to_string()),
+            e => {
+                event!(Level::ERROR, "API database error | {}", e);
+                Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        }
+    }
+}
+
+impl From<BackupError> for ApiError {
+    fn from(e: BackupError) -> Self {
+        let status = match e {
+            BackupError::NotFound(_) => StatusCode::NOT_FOUND,
+            BackupError::Invalid(..) | BackupError::InMemory => StatusCode::UNPROCESSABLE_ENTITY,
+            BackupError::Io(_) | BackupError::Db(_) => {
+                event!(Level::ERROR, "{}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ServiceView {
+    #[serde(flatten)]
+    pub(super) service: Service,
+    pub(super) running: Option<bool>,
+    pub(super) containers: Option<Vec<ContainerState>>,
+}
+
+impl ServiceView {
+    // without asking docker, for callers that don't need the containers
+    pub(super) fn bare(service: Service) -> Self {
+        Self {
+            service,
+            running: None,
+            containers: None,
+        }
+    }
+
+    // viewers see how a service is doing, never how it's configured
+    fn for_role(self, role: Role) -> ServiceResponse {
+        match role.can_deploy() {
+            true => ServiceResponse::Full(Box::new(self)),
+            false => ServiceResponse::Status(StatusView {
+                id: self.service.id,
+                name: self.service.name,
+                access_url: self.service.access_url,
+                environment: self.service.environment,
+                last_status: self.service.last_status,
+                last_status_detail: self.service.last_status_detail,
+                last_status_at: self.service.last_status_at,
+                last_commit: self.service.last_commit,
+                running: self.running,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatusView {
+    id: i64,
+    name: String,
+    access_url: String,
+    environment: Option<String>,
+    last_status: Option<String>,
+    last_status_detail: Option<String>,
+    last_status_at: Option<String>,
+    last_commit: Option<String>,
+    running: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ServiceResponse {
+    Full(Box<ServiceView>),
+    Status(StatusView),
+}
+
+#[derive(Deserialize)]
+pub struct ServiceRequest {
+    name: String,
+    compose_name: String,
+    repo_url: String,
+    access_url: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    use_key: bool,
+    pre_deploy_cmd: Option<String>,
+    post_deploy_cmd: Option<String>,
+    deploy_key_id: Option<i64>,
+    schedule: Option<String>,
+    poll_interval_secs: Option<i64>,
+    tag_pattern: Option<String>,
+    clone_depth: Option<i64>,
+    compose_file: Option<String>,
+    #[serde(default)]
+    build_before_up: bool,
+    #[serde(default)]
+    push_image: bool,
+    docker_host: Option<String>,
+    node_id: Option<i64>,
+    group_id: Option<i64>,
+    environment: Option<String>,
+    branch: Option<String>,
+    proxy_port: Option<i64>,
+    compose_override: Option<String>,
+    #[serde(default)]
+    review_required: bool,
+    #[serde(default)]
+    requires_approval: bool,
+    watch_paths: Option<String>,
+    health_grace_secs: Option<i64>,
+    backup_volumes: Option<String>,
+    post_start_exec: Option<String>,
+    post_start_container: Option<String>,
+    repo_dir: Option<String>,
+    live_dir: Option<String>,
+    smoke_url: Option<String>,
+    smoke_status: Option<i64>,
+    smoke_regex: Option<String>,
+    one_off: Option<String>,
+    platform: Option<String>,
+    #[serde(default)]
+    secret_files: bool,
+    #[serde(default)]
+    previews: bool,
+    pipeline: Option<String>,
+    scripts: Option<String>,
+    // HTTPS credential; a username without a token keeps the saved token
+    git_username: Option<String>,
+    git_token: Option<String>,
+}
+
+impl From<ServiceRequest> for Service {
+    fn from(req: ServiceRequest) -> Self {
+        Service {
+            id: 0, // NOT USED
+            name: req.name,
+            compose_name: req.compose_name,
+            repo_url: req.repo_url,
+            access_url: req.access_url,
+            active: req.active,
+            use_key: req.use_key,
+            pre_deploy_cmd: req.pre_deploy_cmd,
+            post_deploy_cmd: req.post_deploy_cmd,
+            deploy_key_id: req.deploy_key_id,
+            archived: false,
+            schedule: req.schedule,
+            poll_interval_secs: req.poll_interval_secs,
+            tag_pattern: req.tag_pattern,
+            clone_depth: req.clone_depth,
+            compose_file: req.compose_file,
+            build_before_up: req.build_before_up,
+            push_image: req.push_image,
+            docker_host: req.docker_host,
+            node_id: req.node_id,
+            group_id: req.group_id,
+            environment: req.environment,
+            branch: req.branch,
+            proxy_port: req.proxy_port,
+            compose_override: req.compose_override.filter(|o| !o.trim().is_empty()),
+            review_required: req.review_required,
+            requires_approval: req.requires_approval,
+            watch_paths: req.watch_paths.filter(|p| !p.trim().is_empty()),
+            health_grace_secs: req.health_grace_secs,
+            backup_volumes: req.backup_volumes.filter(|v| !v.trim().is_empty()),
+            post_start_exec: req.post_start_exec.filter(|c| !c.trim().is_empty()),
+            post_start_container: req.post_start_container.filter(|c| !c.trim().is_empty()),
+            repo_dir: req.repo_dir.filter(|d| !d.trim().is_empty()),
+            live_dir: req.live_dir.filter(|d| !d.trim().is_empty()),
+            smoke_url: req.smoke_url.filter(|u| !u.trim().is_empty()),
+            smoke_status: req.smoke_status,
+            smoke_regex: req.smoke_regex.filter(|r| !r.is_empty()),
+            one_off: req.one_off.filter(|o| !o.trim().is_empty()),
+            platform: req.platform.filter(|p| !p.trim().is_empty()),
+            secret_files: req.secret_files,
+            previews: req.previews,
+            preview_of: None,
+            pipeline: req.pipeline.filter(|p| !p.trim().is_empty()),
+            scripts: req.scripts.filter(|s| !s.trim().is_empty()),
+            last_commit: None,
+            last_status: None,
+            last_status_detail: None,
+            last_status_at: None,
+        }
+    }
+}
+
+// `running` and `containers` are null when docker can't be queried
+pub(super) async fn view(services: Vec<Service>, app_state: &AppState) -> Vec<ServiceView> {
+    let docker_list = app_state
+        .docker_cache
+        .list(&Service::docker_hosts(&services))
+        .await
+        .ok()
+        .map(|mut list| {
+            list.extend(app_state.agents.containers());
+            list
+        });
+    services
+        .into_iter()
+        .map(|service| ServiceView {
+            running: docker_list.as_ref().map(|dl| service.is_running(dl)),
+            containers: docker_list.as_ref().map(|dl| service.containers(dl)),
+            service,
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    archived: Option<bool>,
+    group: Option<i64>,
+    environment: Option<String>,
+    search: Option<String>,
+}
+
+pub async fn list_services(
+    State(app_state): State<AppState>,
+    Query(query): Query<ListQuery>,
+    role: Role,
+) -> Result<Json<Vec<ServiceResponse>>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services");
+
+    let filter = ServiceFilter {
+        group: query.group,
+        environment: query.environment,
+        search: query.search,
+        ..Default::default()
+    };
+    let services = db::get_services(&app_state.pool, query.archived)
+        .await?
+        .into_iter()
+        .filter(|s| filter.matches(s))
+        .collect();
+    let views = view(services, &app_state).await;
+    Ok(Json(views.into_iter().map(|v| v.for_role(role)).collect()))
+}
+
+pub async fn get_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    role: Role,
+) -> Result<Json<ServiceResponse>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    match view(vec![service], &app_state).await.pop() {
+        Some(v) => Ok(Json(v.for_role(role))),
+        None => Err(ApiError::new(StatusCode::NOT_FOUND, "Service not found")),
+    }
+}
+
+// deployments, edits and health changes across services, newest first
+pub async fn activity(
+    State(app_state): State<AppState>,
+    Query(query): Query<super::ActivityQuery>,
+) -> Result<Json<Vec<Activity>>, ApiError> {
+    event!(Level::INFO, "GET /api/activity");
+
+    let limit = activity::limit(query.limit);
+    Ok(Json(
+        db::get_activity(&app_state.pool, query.service_id, limit).await?,
+    ))
+}
+
+// the service's compose file in its clone and as last deployed, with its saved override
+pub async fn service_compose(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<Json<ComposeView>, ApiError> {
+    event!(Level::INFO, "GET /api/service/:id/compose");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    Ok(Json(service.compose_view(&app_state.config)))
+}
+
+pub async fn create_service(
+    State(app_state): State<AppState>,
+    actor: Actor,
+    Json(mut request): Json<ServiceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services");
+
+    let credential = super::git_credential(
+        &app_state,
+        None,
+        request.git_username.take(),
+        request.git_token.take(),
+    )
+    .await;
+    let service = Service::from(request);
+    service
+        .validate()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    super::check_environment(&app_state.pool, &service, None)
+        .await
+        .map_err(|message| ApiError::new(StatusCode::CONFLICT, message))?;
+    super::check_compose_names(&app_state, &service, None)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let credential =
+        credential.map_err(|message| ApiError::new(StatusCode::BAD_REQUEST, message))?;
+    let id = db::new_service(&app_state.pool, service).await?;
+    db::set_git_credential(&app_state.pool, id, credential).await?;
+    let service = db::get_service(&app_state.pool, id).await?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(id),
+        AuditAction::Create,
+        audit::diff(None, Some(&service)),
+    )
+    .await;
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+
+    Ok((StatusCode::CREATED, Json(service)))
+}
+
+pub async fn update_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Json(mut request): Json<ServiceRequest>,
+) -> Result<Json<Service>, ApiError> {
+    event!(Level::INFO, "PUT /api/v1/services/:id");
+
+    let credential = super::git_credential(
+        &app_state,
+        Some(service_id),
+        request.git_username.take(),
+        request.git_token.take(),
+    )
+    .await;
+    let service = Service::from(request);
+    service
+        .validate()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    super::check_environment(&app_state.pool, &service, Some(service_id))
+        .await
+        .map_err(|message| ApiError::new(StatusCode::CONFLICT, message))?;
+    super::check_compose_names(&app_state, &service, Some(service_id))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let credential =
+        credential.map_err(|message| ApiError::new(StatusCode::BAD_REQUEST, message))?;
+    let before = db::get_service(&app_state.pool, service_id).await?;
+    db::set_git_credential(&app_state.pool, service_id, credential).await?;
+    db::update_service(&app_state.pool, service_id, service).await?;
+    let after = db::get_service(&app_state.pool, service_id).await?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Edit,
+        audit::diff(Some(&before), Some(&after)),
+    )
+    .await;
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+
+    Ok(Json(after))
+}
+
+#[derive(Deserialize)]
+pub struct DuplicateRequest {
+    // the original's name suffixed with -copy when unset
+    name: Option<String>,
+    environment: Option<String>,
+    // copy secret env vars too
+    #[serde(default)]
+    secrets: bool,
+}
+
+pub async fn duplicate_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Json(request): Json<DuplicateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/duplicate");
+
+    let source = db::get_service(&app_state.pool, service_id).await?;
+    let taken: Vec<String> = db::get_services(&app_state.pool, None)
+        .await?
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    let mut service = duplicate::duplicate(&source, &taken);
+    if let Some(name) = request.name.filter(|n| !n.trim().is_empty()) {
+        service.name = name.trim().to_string();
+    }
+    service.environment = request
+        .environment
+        .filter(|e| !e.trim().is_empty())
+        .map(|e| e.trim().to_lowercase());
+    service
+        .validate()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    super::check_environment(&app_state.pool, &service, None)
+        .await
+        .map_err(|message| ApiError::new(StatusCode::CONFLICT, message))?;
+    let credential = db::get_git_credential(&app_state.pool, service_id).await?;
+    let id = db::new_service(&app_state.pool, service).await?;
+    db::set_git_credential(&app_state.pool, id, credential).await?;
+    let service = db::get_service(&app_state.pool, id).await?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(id),
+        AuditAction::Create,
+        audit::diff(None, Some(&service)),
+    )
+    .await;
+    super::duplicate_env(&app_state, &actor, service_id, id, request.secrets).await;
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+
+    Ok((StatusCode::CREATED, Json(service)))
+}
+
+#[derive(Deserialize)]
+pub struct DeployQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn deploy_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    Query(query): Query<DeployQuery>,
+    actor: Actor,
+) -> Result<Response, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/deploy");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    if query.dry_run {
+        if app_state.in_flight.contains(service_id) {
+            return Err(ApiError::new(StatusCode::CONFLICT, "Service is busy"));
+        }
+        let dry_run = app_state
+            .dry_run(service, None)
+            .await
+            .map_err(|e| ApiError::new(StatusCode::CONFLICT, e.to_string()))?;
+        return Ok(Json(dry_run).into_response());
+    }
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Deploy,
+        json!({}),
+    )
+    .await;
+    app_state.spawn_deploy(service_id, Ok(service));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": service_id, "deploying": true })),
+    )
+        .into_response())
+}
+
+// the service's hook token stands in for API auth, so whatever can make a request can
+// deploy it and nothing else
+pub async fn deploy_hook(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /hooks/deploy/:token");
+
+    let Some(service_id) =
+        db::get_deploy_hook_service(&app_state.pool, &user::session_token_hash(&token)).await?
+    else {
+        event!(Level::WARN, "Refused deploy hook with an unknown token");
+        return Err(ApiError::new(StatusCode::NOT_FOUND, "Unknown deploy hook"));
+    };
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Deploy,
+        json!({}),
+    )
+    .await;
+    let held = service.requires_approval && !service.archived;
+    app_state.spawn_triggered(service, Trigger::Hook);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": service_id, "deploying": !held, "pending_approval": held })),
+    ))
+}
+
+// GitHub `pull_request` events for a service with previews on: opening a pull request, or
+// pushing to it, deploys its preview, and closing it deletes the preview
+pub async fn preview_hook(
+    State(app_state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    actor: Actor,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /hooks/preview/:token");
+
+    let Some(source_id) =
+        db::get_deploy_hook_service(&app_state.pool, &user::session_token_hash(&token)).await?
+    else {
+        event!(Level::WARN, "Refused preview hook with an unknown token");
+        return Err(ApiError::new(StatusCode::NOT_FOUND, "Unknown deploy hook"));
+    };
+    let source = db::get_service(&app_state.pool, source_id).await?;
+    if !source.previews || source.preview_of.is_some() || source.archived {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "Pull request previews are off for this service",
+        ));
+    }
+    // GitHub pings a hook when it's added
+    match headers.get("X-GitHub-Event").and_then(|v| v.to_str().ok()) {
+        Some("pull_request") => (),
+        other => return Ok((StatusCode::OK, Json(json!({ "ignored": other })))),
+    }
+    let event: PullRequestEvent = serde_json::from_value(payload)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    if event.forked() {
+        return Ok((StatusCode::OK, Json(json!({ "ignored": "fork" }))));
+    }
+
+    let existing = db::get_preview(&app_state.pool, source_id, event.number).await?;
+    if event.closes() {
+        let Some(id) = existing else {
+            return Ok((StatusCode::OK, Json(json!({ "ignored": "no preview" }))));
+        };
+        let service = db::get_service(&app_state.pool, id).await;
+        audit::record(
+            &app_state.pool,
+            &actor,
+            Some(id),
+            AuditAction::Delete,
+            audit::diff(service.as_ref().ok(), None),
+        )
+        .await;
+        app_state.spawn_delete(service);
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(json!({ "id": id, "deleting": true })),
+        ));
+    }
+    if !event.deploys() {
+        return Ok((StatusCode::OK, Json(json!({ "ignored": event.action }))));
+    }
+
+    let id = match existing {
+        Some(id) => id,
+        None => {
+            let service =
+                preview::preview(&source, &event, app_state.config.preview_domain.as_deref());
+            let credential = db::get_git_credential(&app_state.pool, source_id).await?;
+            let id = db::new_service(&app_state.pool, service).await?;
+            db::set_git_credential(&app_state.pool, id, credential).await?;
+            let service = db::get_service(&app_state.pool, id).await?;
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(id),
+                AuditAction::Create,
+                audit::diff(None, Some(&service)),
+            )
+            .await;
+            super::duplicate_env(&app_state, &actor, source_id, id, true).await;
+            let _ = app_state
+                .service_broadcast
+                .broadcaster
+                .send(ServiceEvent::AllStatus);
+            id
+        }
+    };
+    let ttl = app_state.config.preview_ttl.as_secs() as i64;
+    db::set_preview(&app_state.pool, id, event.number, ttl).await?;
+    let service = db::get_service(&app_state.pool, id).await?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(id),
+        AuditAction::Deploy,
+        json!({ "pull_request": event.number, "commit": event.pull_request.head.sha }),
+    )
+    .await;
+    app_state.spawn_deploy_at(id, Ok(service), Some(event.pull_request.head.sha.clone()));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": id, "deploying": true })),
+    ))
+}
+
+pub async fn promote_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/promote");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    if app_state.in_flight.contains(service_id) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "Service is busy"));
+    }
+    let promotion = promote::promotion(&app_state.pool, &service)
+        .await
+        .map_err(|e| match e {
+            PromoteError::Db(e) => ApiError::from(e),
+            e => ApiError::new(StatusCode::CONFLICT, e.to_string()),
+        })?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Promote,
+        json!({ "from": promotion.from, "commit": promotion.commit }),
+    )
+    .await;
+    app_state.spawn_deploy_at(service_id, Ok(service), Some(promotion.commit.clone()));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "id": service_id,
+            "from": promotion.from,
+            "commit": promotion.commit,
+        })),
+    ))
+}
+
+pub async fn service_status(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id/status");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    let docker_list = match service.node_id {
+        // as last reported by the node's agent
+        Some(_) => app_state.agents.containers(),
+        None => Service::get_list(std::slice::from_ref(&service.docker_host))
+            .await
+            .map_err(|e| {
+                ApiError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Unable to query docker | {}", e),
+                )
+            })?,
+    };
+
+    Ok(Json(json!({
+        "id": service.id,
+        "name": service.name,
+        "running": service.is_running(&docker_list),
+        "containers": service.containers(&docker_list),
+    })))
+}
+
+pub async fn service_deployments(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<Json<Vec<Deployment>>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id/deployments");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    Ok(Json(
+        db::get_deployments(&app_state.pool, service_id, 50).await?,
+    ))
+}
+
+// what a deploy logged, kept with its deployment whether or not it succeeded
+pub async fn deployment_log(
+    State(app_state): State<AppState>,
+    Path(deployment_id): Path<i64>,
+) -> Result<Response, ApiError> {
+    event!(Level::INFO, "GET /api/deployments/:id/log");
+
+    let kept = deployment(&app_state, deployment_id).await?;
+    Ok(download(format!("deployment-{}.log", kept.id), kept.log))
+}
+
+// the compose file the deploy left in the live dir
+pub async fn deployment_compose(
+    State(app_state): State<AppState>,
+    Path(deployment_id): Path<i64>,
+) -> Result<Response, ApiError> {
+    event!(Level::INFO, "GET /api/deployments/:id/compose");
+
+    let kept = deployment(&app_state, deployment_id).await?;
+    let compose_file = kept.compose_file.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No compose file was kept for this deployment",
+        )
+    })?;
+    Ok(download(
+        format!("deployment-{}-compose.yml", kept.id),
+        compose_file,
+    ))
+}
+
+async fn deployment(app_state: &AppState, deployment_id: i64) -> Result<DeploymentLog, ApiError> {
+    db::get_deployment_log(&app_state.pool, deployment_id)
+        .await
+        .map_err(|e| match e {
+            db::DBError::Sql(sqlx::Error::RowNotFound) => {
+                ApiError::new(StatusCode::NOT_FOUND, "Deployment not found")
+            }
+            e => e.into(),
+        })
+}
+
+fn download(file_name: String, body: String) -> Response {
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+pub async fn service_uptime(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<Json<Uptime>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id/uptime");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    let transitions =
+        db::get_uptime_transitions(&app_state.pool, Some(service_id), uptime::month_ago()).await?;
+    Ok(Json(
+        uptime::by_service(transitions)
+            .remove(&service_id)
+            .unwrap_or_default(),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct GroupView {
+    #[serde(flatten)]
+    group: Group,
+    // null when docker can't be queried
+    summary: Option<Summary>,
+}
+
+pub async fn list_groups(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<GroupView>>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/groups");
+
+    let groups = db::get_groups(&app_state.pool).await?;
+    let services = db::get_services(&app_state.pool, Some(false)).await?;
+    let docker_list = app_state
+        .docker_cache
+        .list(&Service::docker_hosts(&services))
+        .await
+        .ok()
+        .map(|mut list| {
+            list.extend(app_state.agents.containers());
+            list
+        });
+    Ok(Json(
+        groups
+            .into_iter()
+            .map(|group| GroupView {
+                summary: docker_list.as_ref().map(|dl| {
+                    Summary::of(services.iter().filter(|s| s.group_id == Some(group.id)), dl)
+                }),
+                group,
+            })
+            .collect(),
+    ))
+}
+
+pub async fn deploy_group(
+    State(app_state): State<AppState>,
+    Path(group_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/groups/:id/deploy");
+
+    if !db::get_groups(&app_state.pool)
+        .await?
+        .iter()
+        .any(|g| g.id == group_id)
+    {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, "Group not found"));
+    }
+    let filter = ServiceFilter {
+        group: Some(group_id),
+        ..Default::default()
+    };
+    let ids = app_state.deploy_all(&filter, &actor).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "deploying": ids }))))
+}
+
+#[derive(Deserialize)]
+pub struct DeployAllQuery {
+    group: Option<i64>,
+    environment: Option<String>,
+}
+
+pub async fn deploy_all(
+    State(app_state): State<AppState>,
+    Query(query): Query<DeployAllQuery>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/deploy_all");
+
+    let filter = ServiceFilter {
+        group: query.group,
+        environment: query.environment,
+        ..Default::default()
+    };
+    let ids = app_state.deploy_all(&filter, &actor).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "deploying": ids }))))
+}
+
+pub async fn service_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<Json<DeployReview>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id/review");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    db::get_deploy_review(&app_state.pool, service_id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for review",
+        ))
+}
+
+pub async fn approve_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/review/approve");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    if app_state.in_flight.contains(service_id) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "Service is busy"));
+    }
+    let review = app_state
+        .approve_review(service)
+        .await?
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for review",
+        ))?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::ApproveDeploy,
+        json!({ "commit": review.commit_sha }),
+    )
+    .await;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": service_id, "commit": review.commit_sha })),
+    ))
+}
+
+pub async fn decline_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/review/decline");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    let review = app_state
+        .decline_review(service_id)
+        .await?
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for review",
+        ))?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::DeclineDeploy,
+        json!({ "commit": review.commit_sha }),
+    )
+    .await;
+
+    Ok(Json(json!({ "id": service_id, "declined": true })))
+}
+
+pub async fn service_approval(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<Json<DeployApproval>, ApiError> {
+    event!(Level::INFO, "GET /api/v1/services/:id/approval");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    db::get_deploy_approval(&app_state.pool, service_id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for approval",
+        ))
+}
+
+pub async fn approve_deploy(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/approval/approve");
+
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    if app_state.in_flight.contains(service_id) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "Service is busy"));
+    }
+    let approval = app_state
+        .approve_deploy(service)
+        .await?
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for approval",
+        ))?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::ApproveDeploy,
+        json!({ "trigger": approval.trigger }),
+    )
+    .await;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": service_id, "deploying": true })),
+    ))
+}
+
+pub async fn decline_deploy(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/approval/decline");
+
+    db::get_service(&app_state.pool, service_id).await?;
+    let approval = app_state
+        .decline_deploy(service_id)
+        .await?
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "No deploy is waiting for approval",
+        ))?;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::DeclineDeploy,
+        json!({ "trigger": approval.trigger }),
+    )
+    .await;
+
+    Ok(Json(json!({ "id": service_id, "declined": true })))
+}
+
+pub async fn stop_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/stop");
+    lifecycle(app_state, service_id, actor, Lifecycle::Stop).await
+}
+
+pub async fn start_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/start");
+    lifecycle(app_state, service_id, actor, Lifecycle::Start).await
+}
+
+pub async fn restart_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/restart");
+    lifecycle(app_state, service_id, actor, Lifecycle::Restart).await
+}
+
+#[derive(Deserialize)]
+pub struct TearDownQuery {
+    volumes: Option<bool>,
+}
+
+pub async fn tear_down_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Query(query): Query<TearDownQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/v1/services/:id/teardown");
+    let volumes = query.volumes.unwrap_or(false);
+    lifecycle(
+        app_state,
+        service_id,
+        actor,
+        Lifecycle::TearDown { volumes },
+    )
+    .await
+}
+
+async fn lifecycle(
+    app_state: AppState,
+    service_id: i64,
+    actor: Actor,
+    action: Lifecycle,
+) -> Result<impl IntoResponse, ApiError> {
+    let service = db::get_service(&app_state.pool, service_id).await?;
+    if app_state.in_flight.contains(service_id) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "Service is busy"));
+    }
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::from(action),
+        match action {
+            Lifecycle::TearDown { volumes } => json!({ "volumes": volumes }),
+            _ => json!({}),
+        },
+    )
+    .await;
+    app_state.spawn_lifecycle(service, action);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "id": service_id, "action": action.to_string() })),
+    ))
+}
+
+const READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Serialize)]
+struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<T, E: std::fmt::Display> From<Result<T, E>> for Check {
+    fn from(result: Result<T, E>) -> Self {
+        Check {
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+// liveness: answers for as long as the server is serving
+pub async fn healthz() -> impl IntoResponse {
+    event!(Level::INFO, "GET /healthz");
+    Json(json!({ "status": "ok" }))
+}
+
+// readiness: 503 unless the database and the local docker daemon both answer, saying
+// which didn't
+pub async fn readyz(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /readyz");
+
+    let database: Check = match tokio::time::timeout(READY_TIMEOUT, db::ping(&app_state.pool)).await
+    {
+        Ok(pinged) => pinged.into(),
+        Err(e) => Err::<(), _>(e).into(),
+    };
+    let docker: Check = Service::docker_version(READY_TIMEOUT).into();
+    let (status, body) = match database.ok && docker.ok {
+        true => (StatusCode::OK, "ready"),
+        false => {
+            event!(
+                Level::WARN,
+                "Not ready | database: {:?}, docker: {:?}",
+                database.error,
+                docker.error
+            );
+            (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+        }
+    };
+    (
+        status,
+        Json(json!({ "status": body, "database": database, "docker": docker })),
+    )
+}
+
+pub async fn create_backup(
+    State(app_state): State<AppState>,
+    actor: Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/admin/backup");
+
+    let backup = backup::create(&app_state, &actor).await?;
+    Ok((StatusCode::CREATED, Json(backup)))
+}
+
+pub async fn list_backups(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "GET /api/admin/backups");
+
+    Ok(Json(backup::list(&app_state.config.backup_dir)?))
+}
+
+// staged rather than swapped under the open pool; the restore happens on the next start
+pub async fn restore_backup(
+    State(app_state): State<AppState>,
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    event!(Level::INFO, "POST /api/admin/restore/{}", name);
+
+    backup::stage(&app_state, &actor, &name).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "staged": name, "message": "restart wraut to restore this backup" })),
+    ))
+}
@@ -0,0 +1,220 @@
+use crate::modules::{
+    AppState, db, network,
+    user::{self, Role, SESSION_COOKIE, SESSION_TTL_SECS, User},
+};
+
+use axum::{
+    Form, Json,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
+    routing::MethodRouter,
+};
+use openssl::base64;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{Level, event};
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn session_cookie(token: &str, max_age: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE, token, max_age
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+// pbkdf2 is deliberately slow, so keep it off the async workers
+async fn check_login(app_state: &AppState, username: &str, password: String) -> Option<User> {
+    let (user, password_hash) = db::get_user_login(&app_state.pool, username).await.ok()?;
+    let valid =
+        tokio::task::spawn_blocking(move || user::verify_password(&password, &password_hash))
+            .await
+            .unwrap_or(false);
+    valid.then_some(user)
+}
+
+async fn request_user(app_state: &AppState, headers: &HeaderMap) -> Option<User> {
+    if let Some(token) = cookie(headers, SESSION_COOKIE) {
+        let token_hash = user::session_token_hash(token);
+        if let Ok(user) = db::get_session_user(&app_state.pool, &token_hash).await {
+            return Some(user);
+        }
+    }
+
+    // API clients authenticate per request with HTTP basic auth
+    let credentials = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode_block(credentials).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    check_login(app_state, username, password.to_string()).await
+}
+
+fn unauthenticated(request: &Request) -> Response {
+    if request.uri().path().starts_with("/api/v1") {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"wraut\"")],
+            Json(json!({ "error": "Authentication required" })),
+        )
+            .into_response();
+    }
+    if request.headers().contains_key("HX-Request") {
+        return (StatusCode::UNAUTHORIZED, [("HX-Redirect", "/login")]).into_response();
+    }
+    Redirect::to("/login").into_response()
+}
+
+// resolves the caller's user and role; with no users configured, access control is off
+pub async fn authenticate(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match request_user(&app_state, request.headers()).await {
+        Some(user) => {
+            request.extensions_mut().insert(user.role);
+            request.extensions_mut().insert(user);
+        }
+        None => match db::count_users(&app_state.pool).await {
+            Ok(0) => {
+                request.extensions_mut().insert(Role::Admin);
+            }
+            Ok(_) => return unauthenticated(&request),
+            Err(e) => {
+                event!(Level::ERROR, "Unable to check users | {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+    }
+
+    next.run(request).await
+}
+
+pub async fn require_role(State(required): State<Role>, request: Request, next: Next) -> Response {
+    // admin pages are as restricted as the changes they make
+    if required == Role::Admin && !network::allowed(&request) {
+        return network::refuse(&request);
+    }
+    let role = request
+        .extensions()
+        .get::<Role>()
+        .copied()
+        .unwrap_or(Role::Viewer);
+    if role < required {
+        event!(
+            Level::WARN,
+            "Refused {} {} for role {}",
+            request.method(),
+            request.uri().path(),
+            role
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            format!("Requires the {} role", required),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+pub fn require(role: Role, route: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    route.route_layer(middleware::from_fn_with_state(role, require_role))
+}
+
+pub async fn login_page() -> impl IntoResponse {
+    event!(Level::INFO, "GET /login");
+    user::html::login(None)
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+pub async fn login(
+    State(app_state): State<AppState>,
+    Form(login_form): Form<LoginForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /login");
+
+    let Some(user) = check_login(&app_state, &login_form.username, login_form.password).await
+    else {
+        event!(
+            Level::WARN,
+            "Failed login for '{}'",
+            login_form.username.escape_debug()
+        );
+        return (
+            StatusCode::UNAUTHORIZED,
+            user::html::login(Some("Invalid username or password".to_string())),
+        )
+            .into_response();
+    };
+
+    let session = match user::new_session_token() {
+        Ok((token, token_hash)) => {
+            db::new_session(&app_state.pool, token_hash, user.id, SESSION_TTL_SECS)
+                .await
+                .map(|_| token)
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    };
+
+    match session {
+        Ok(token) => {
+            event!(Level::INFO, "User '{}' logged in", user.username);
+            (
+                [(header::SET_COOKIE, session_cookie(&token, SESSION_TTL_SECS))],
+                Redirect::to("/"),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Unable to create session | {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                user::html::login(Some("Login failed. See logs.".to_string())),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn logout(State(app_state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    event!(Level::INFO, "POST /logout");
+
+    if let Some(token) = cookie(&headers, SESSION_COOKIE)
+        && let Err(e) = db::delete_session(&app_state.pool, &user::session_token_hash(token)).await
+    {
+        event!(Level::ERROR, "Unable to delete session | {}", e);
+    }
+
+    (
+        [
+            (header::SET_COOKIE, session_cookie("", 0)),
+            (
+                header::HeaderName::from_static("hx-redirect"),
+                HeaderValue::from_static("/login"),
+            ),
+        ],
+        "OK",
+    )
+}
@@ -0,0 +1,90 @@
+use crate::modules::AppState;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use openssl::{memcmp, rand::rand_bytes};
+use tracing::{Level, event};
+
+pub const HEADER: &str = "X-CSRF-Token";
+
+// one token per process; the dashboard page embeds it and htmx echoes it back on every request
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    if let Err(e) = rand_bytes(&mut bytes) {
+        event!(Level::ERROR, "Unable to generate CSRF token | {}", e);
+        panic!("Unable to generate CSRF token | {}", e);
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// rejects state-changing requests that don't carry the page's token
+pub async fn verify(State(app_state): State<AppState>, request: Request, next: Next) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+
+    let expected = app_state.csrf_token.as_bytes();
+    let valid = request
+        .headers()
+        .get(HEADER)
+        .map(|token| {
+            let token = token.as_bytes();
+            token.len() == expected.len() && memcmp::eq(token, expected)
+        })
+        .unwrap_or(false);
+
+    if !valid {
+        event!(
+            Level::WARN,
+            "Rejected {} {} without a valid CSRF token",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, "Invalid CSRF token").into_response();
+    }
+
+    next.run(request).await
+}
+
+// browsers send cookies with cross-site WebSocket handshakes, which carry no CSRF token;
+// a socket that acts on the server must come from a page on this host
+pub fn same_host(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+    origin
+        .to_str()
+        .ok()
+        .and_then(|o| o.split_once("://"))
+        .is_some_and(|(_, origin_host)| Some(origin_host) == host)
+}
+
+// the JSON API has no page to carry a token, so refuse anything a browser flags as cross-site;
+// non-browser clients don't send the header and pass through
+pub async fn same_origin(request: Request, next: Next) -> Response {
+    let cross_site = request
+        .headers()
+        .get("Sec-Fetch-Site")
+        .map(|site| site != "same-origin" && site != "none")
+        .unwrap_or(false);
+
+    if cross_site && !matches!(*request.method(), Method::GET | Method::HEAD) {
+        event!(
+            Level::WARN,
+            "Rejected cross-site {} {}",
+            request.method(),
+            request.uri().path()
+        );
+        return (StatusCode::FORBIDDEN, "Cross-site request refused").into_response();
+    }
+
+    next.run(request).await
+}
@@ -0,0 +1,490 @@
+use std::sync::LazyLock;
+
+use async_graphql::{
+    Context, EmptySubscription, Error, InputObject, MaybeUndefined, Object, Result, Schema,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::State;
+use serde_json::json;
+use tracing::{Level, event};
+
+use super::api::{ServiceView, view};
+use crate::modules::{
+    AppState,
+    audit::{self, Actor, AuditAction},
+    db,
+    service::{
+        ContainerState, Deployment, Service, ServiceEvent, ServiceFilter, timing::StepTiming,
+    },
+    user::Role,
+};
+
+// deep enough for services { deployments { steps } } with room to spare
+const MAX_DEPTH: usize = 8;
+// a service's deployments when no limit is asked for, and the most that can be
+const DEFAULT_DEPLOYMENTS: i64 = 10;
+const MAX_DEPLOYMENTS: i64 = 100;
+
+pub type WrautSchema = Schema<Query, Mutation, EmptySubscription>;
+
+static SCHEMA: LazyLock<WrautSchema> = LazyLock::new(|| {
+    Schema::build(Query, Mutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+});
+
+// the app state, the caller's role and who to audit travel with each request rather than
+// the schema, which is built once
+pub async fn graphql(
+    State(app_state): State<AppState>,
+    role: Role,
+    actor: Actor,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    event!(Level::INFO, "POST /api/v1/graphql");
+
+    let request = request.into_inner().data(app_state).data(role).data(actor);
+    SCHEMA.execute(request).await.into()
+}
+
+fn require(ctx: &Context<'_>, role: Role) -> Result<()> {
+    match *ctx.data::<Role>()? >= role {
+        true => Ok(()),
+        false => Err(Error::new(format!("Requires the {} role", role))),
+    }
+}
+
+fn db_error(e: db::DBError) -> Error {
+    match e {
+        db::DBError::Sql(sqlx::Error::RowNotFound) => Error::new("Service not found"),
+        e => {
+            event!(Level::ERROR, "GraphQL database error | {}", e);
+            Error::new(e.to_string())
+        }
+    }
+}
+
+// docker is only asked about services when the query selects their containers
+async fn nodes(ctx: &Context<'_>, services: Vec<Service>) -> Result<Vec<ServiceNode>> {
+    let look_ahead = ctx.look_ahead();
+    let views =
+        match look_ahead.field("running").exists() || look_ahead.field("containers").exists() {
+            true => view(services, ctx.data::<AppState>()?).await,
+            false => services.into_iter().map(ServiceView::bare).collect(),
+        };
+    Ok(views.into_iter().map(ServiceNode).collect())
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn services(
+        &self,
+        ctx: &Context<'_>,
+        archived: Option<bool>,
+        group: Option<i64>,
+        environment: Option<String>,
+        search: Option<String>,
+    ) -> Result<Vec<ServiceNode>> {
+        let app_state = ctx.data::<AppState>()?;
+        let filter = ServiceFilter {
+            group,
+            environment,
+            search,
+            ..Default::default()
+        };
+        let services = db::get_services(&app_state.pool, archived)
+            .await
+            .map_err(db_error)?
+            .into_iter()
+            .filter(|s| filter.matches(s))
+            .collect();
+        nodes(ctx, services).await
+    }
+
+    async fn service(&self, ctx: &Context<'_>, id: i64) -> Result<Option<ServiceNode>> {
+        let app_state = ctx.data::<AppState>()?;
+        let service = match db::get_service(&app_state.pool, id).await {
+            Ok(service) => service,
+            Err(db::DBError::Sql(sqlx::Error::RowNotFound)) => return Ok(None),
+            Err(e) => return Err(db_error(e)),
+        };
+        Ok(nodes(ctx, vec![service]).await?.pop())
+    }
+}
+
+// viewers may read a service's status; its configuration needs the deployer role, as it
+// does on the dashboard
+pub struct ServiceNode(ServiceView);
+
+#[Object(name = "Service")]
+impl ServiceNode {
+    async fn id(&self) -> i64 {
+        self.0.service.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.service.name
+    }
+
+    async fn compose_name(&self, ctx: &Context<'_>) -> Result<&str> {
+        require(ctx, Role::Deployer)?;
+        Ok(&self.0.service.compose_name)
+    }
+
+    async fn repo_url(&self, ctx: &Context<'_>) -> Result<&str> {
+        require(ctx, Role::Deployer)?;
+        Ok(&self.0.service.repo_url)
+    }
+
+    async fn access_url(&self) -> &str {
+        &self.0.service.access_url
+    }
+
+    async fn active(&self, ctx: &Context<'_>) -> Result<bool> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.active)
+    }
+
+    async fn archived(&self, ctx: &Context<'_>) -> Result<bool> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.archived)
+    }
+
+    async fn environment(&self) -> Option<&str> {
+        self.0.service.environment.as_deref()
+    }
+
+    async fn branch(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.branch.as_deref())
+    }
+
+    async fn group_id(&self, ctx: &Context<'_>) -> Result<Option<i64>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.group_id)
+    }
+
+    async fn node_id(&self, ctx: &Context<'_>) -> Result<Option<i64>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.node_id)
+    }
+
+    async fn schedule(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.schedule.as_deref())
+    }
+
+    async fn poll_interval_secs(&self, ctx: &Context<'_>) -> Result<Option<i64>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.poll_interval_secs)
+    }
+
+    async fn tag_pattern(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.tag_pattern.as_deref())
+    }
+
+    async fn watch_paths(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.watch_paths.as_deref())
+    }
+
+    async fn requires_approval(&self, ctx: &Context<'_>) -> Result<bool> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.requires_approval)
+    }
+
+    async fn review_required(&self, ctx: &Context<'_>) -> Result<bool> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.review_required)
+    }
+
+    async fn health_grace_secs(&self, ctx: &Context<'_>) -> Result<Option<i64>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.health_grace_secs)
+    }
+
+    async fn smoke_url(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.smoke_url.as_deref())
+    }
+
+    async fn pipeline(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.pipeline.as_deref())
+    }
+
+    async fn scripts(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.scripts.as_deref())
+    }
+
+    async fn preview_of(&self, ctx: &Context<'_>) -> Result<Option<i64>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self.0.service.preview_of)
+    }
+
+    async fn last_commit(&self) -> Option<&str> {
+        self.0.service.last_commit.as_deref()
+    }
+
+    // None until the service's pipeline has recorded a status
+    async fn status(&self) -> Option<Status<'_>> {
+        let service = &self.0.service;
+        Some(Status {
+            kind: service.last_status.as_deref()?,
+            detail: service.last_status_detail.as_deref(),
+            at: service.last_status_at.as_deref(),
+            service,
+        })
+    }
+
+    // null when docker can't be queried
+    async fn running(&self) -> Option<bool> {
+        self.0.running
+    }
+
+    async fn containers(&self, ctx: &Context<'_>) -> Result<Option<Vec<Container<'_>>>> {
+        require(ctx, Role::Deployer)?;
+        Ok(self
+            .0
+            .containers
+            .as_ref()
+            .map(|containers| containers.iter().map(Container).collect()))
+    }
+
+    // newest first
+    async fn deployments(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+    ) -> Result<Vec<DeploymentNode>> {
+        let app_state = ctx.data::<AppState>()?;
+        let deployments = db::get_deployments(
+            &app_state.pool,
+            self.0.service.id,
+            limit
+                .unwrap_or(DEFAULT_DEPLOYMENTS)
+                .clamp(1, MAX_DEPLOYMENTS),
+        )
+        .await
+        .map_err(db_error)?;
+        Ok(deployments.into_iter().map(DeploymentNode).collect())
+    }
+}
+
+pub struct Status<'a> {
+    kind: &'a str,
+    detail: Option<&'a str>,
+    at: Option<&'a str>,
+    service: &'a Service,
+}
+
+#[Object]
+impl Status<'_> {
+    async fn kind(&self) -> &str {
+        self.kind
+    }
+
+    async fn detail(&self) -> Option<&str> {
+        self.detail
+    }
+
+    // UTC, as stored
+    async fn at(&self) -> Option<&str> {
+        self.at
+    }
+
+    // as the dashboard words it
+    async fn text(&self) -> Option<String> {
+        self.service.recorded_status().map(|s| s.to_string())
+    }
+
+    async fn failed(&self) -> bool {
+        self.service
+            .recorded_status()
+            .is_some_and(|s| s.is_failure())
+    }
+}
+
+pub struct Container<'a>(&'a ContainerState);
+
+#[Object]
+impl Container<'_> {
+    async fn service(&self) -> &str {
+        &self.0.service
+    }
+
+    async fn state(&self) -> &str {
+        &self.0.state
+    }
+}
+
+pub struct DeploymentNode(Deployment);
+
+#[Object(name = "Deployment")]
+impl DeploymentNode {
+    async fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+
+    async fn commit_sha(&self) -> Option<&str> {
+        self.0.commit_sha.as_deref()
+    }
+
+    async fn images(&self) -> &[String] {
+        &self.0.images
+    }
+
+    async fn duration_ms(&self) -> Option<i64> {
+        self.0.duration_ms
+    }
+
+    async fn outcome(&self) -> &str {
+        &self.0.outcome
+    }
+
+    async fn steps(&self) -> Vec<Step<'_>> {
+        self.0.steps.iter().map(Step).collect()
+    }
+}
+
+pub struct Step<'a>(&'a StepTiming);
+
+#[Object]
+impl Step<'_> {
+    async fn step(&self) -> &str {
+        &self.0.step
+    }
+
+    async fn millis(&self) -> u64 {
+        self.0.millis
+    }
+}
+
+// settings `updateService` changes; absent ones are kept and null clears an optional one
+#[derive(InputObject)]
+pub struct ServiceInput {
+    name: Option<String>,
+    access_url: Option<String>,
+    active: Option<bool>,
+    environment: MaybeUndefined<String>,
+    branch: MaybeUndefined<String>,
+    group_id: MaybeUndefined<i64>,
+    schedule: MaybeUndefined<String>,
+    poll_interval_secs: MaybeUndefined<i64>,
+    tag_pattern: MaybeUndefined<String>,
+    watch_paths: MaybeUndefined<String>,
+    requires_approval: Option<bool>,
+    review_required: Option<bool>,
+    health_grace_secs: MaybeUndefined<i64>,
+    smoke_url: MaybeUndefined<String>,
+    pipeline: MaybeUndefined<String>,
+    scripts: MaybeUndefined<String>,
+}
+
+impl ServiceInput {
+    fn apply(self, service: &mut Service) {
+        if let Some(name) = self.name {
+            service.name = name;
+        }
+        if let Some(access_url) = self.access_url {
+            service.access_url = access_url;
+        }
+        if let Some(active) = self.active {
+            service.active = active;
+        }
+        if let Some(requires_approval) = self.requires_approval {
+            service.requires_approval = requires_approval;
+        }
+        if let Some(review_required) = self.review_required {
+            service.review_required = review_required;
+        }
+        self.environment.update_to(&mut service.environment);
+        self.branch.update_to(&mut service.branch);
+        self.group_id.update_to(&mut service.group_id);
+        self.schedule.update_to(&mut service.schedule);
+        self.poll_interval_secs
+            .update_to(&mut service.poll_interval_secs);
+        self.tag_pattern.update_to(&mut service.tag_pattern);
+        self.watch_paths.update_to(&mut service.watch_paths);
+        self.health_grace_secs
+            .update_to(&mut service.health_grace_secs);
+        self.smoke_url.update_to(&mut service.smoke_url);
+        self.pipeline.update_to(&mut service.pipeline);
+        self.scripts.update_to(&mut service.scripts);
+    }
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    // starts a deploy in the background, like POST /api/v1/services/{id}/deploy
+    async fn deploy(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        require(ctx, Role::Deployer)?;
+        let app_state = ctx.data::<AppState>()?;
+        let service = db::get_service(&app_state.pool, id)
+            .await
+            .map_err(db_error)?;
+        audit::record(
+            &app_state.pool,
+            ctx.data::<Actor>()?,
+            Some(id),
+            AuditAction::Deploy,
+            json!({}),
+        )
+        .await;
+        app_state.spawn_deploy(id, Ok(service));
+        Ok(true)
+    }
+
+    // checked and audited like PUT /api/v1/services/{id}
+    async fn update_service(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        input: ServiceInput,
+    ) -> Result<ServiceNode> {
+        require(ctx, Role::Admin)?;
+        let app_state = ctx.data::<AppState>()?;
+        let before = db::get_service(&app_state.pool, id)
+            .await
+            .map_err(db_error)?;
+        let mut service = before.clone();
+        input.apply(&mut service);
+        service.validate().map_err(|e| Error::new(e.to_string()))?;
+        super::check_environment(&app_state.pool, &service, Some(id))
+            .await
+            .map_err(Error::new)?;
+        super::check_compose_names(app_state, &service, Some(id))
+            .await
+            .map_err(|e| Error::new(e.to_string()))?;
+        db::update_service(&app_state.pool, id, service)
+            .await
+            .map_err(db_error)?;
+        let after = db::get_service(&app_state.pool, id)
+            .await
+            .map_err(db_error)?;
+        audit::record(
+            &app_state.pool,
+            ctx.data::<Actor>()?,
+            Some(id),
+            AuditAction::Edit,
+            audit::diff(Some(&before), Some(&after)),
+        )
+        .await;
+        let _ = app_state
+            .service_broadcast
+            .broadcaster
+            .send(ServiceEvent::AllStatus);
+
+        Ok(ServiceNode(ServiceView::bare(after)))
+    }
+}
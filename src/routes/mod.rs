@@ -1,13 +1,43 @@
+pub mod api;
+pub mod auth;
+pub mod csrf;
+pub mod graphql;
+pub mod ws;
+
 use crate::modules::{
-    AppState, db,
-    service::{Service, ServiceEvent, ServiceStatus},
+    AppState, activity,
+    agent::{self, Node},
+    assets::Assets,
+    audit::{self, Actor, AuditAction},
+    db,
+    deploy_key::{self, DeployKey},
+    group, maintenance,
+    markup::Markup,
+    notify::{
+        self, CHANNELS, NotificationRule, email,
+        push::{self, Channel},
+    },
+    secrets::{MASK, SecretError},
+    service::{
+        Lifecycle, Service, ServiceError, ServiceEvent, ServiceFilter,
+        adopt::{self, Unmanaged},
+        credential::GitCredential,
+        duplicate,
+        env::ServiceEnv,
+        html::{self, FieldErrors, FormChoices, ListView, Prefill},
+        job::ServiceJob,
+        preset, promote, short_commit, volume,
+    },
+    statuspage,
+    user::{self, Role, User},
 };
 
 use axum::{
-    Form,
-    extract::{Path, State},
+    Extension, Form,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::{
-        Html, IntoResponse, Sse,
+        IntoResponse, Response, Sse,
         sse::{Event, KeepAlive},
     },
 };
@@ -20,189 +50,144 @@ pub async fn status() -> impl IntoResponse {
     "OK"
 }
 
-pub async fn app() -> impl IntoResponse {
+// the dashboard's filters and page; the selects' "all" options and an empty search
+// send an empty value
+#[derive(Deserialize)]
+pub struct DashboardFilter {
+    group: Option<String>,
+    environment: Option<String>,
+    search: Option<String>,
+    status: Option<String>,
+    sort: Option<String>,
+    by_status: Option<String>,
+    page: Option<String>,
+}
+
+impl From<DashboardFilter> for ServiceFilter {
+    fn from(filter: DashboardFilter) -> Self {
+        ServiceFilter {
+            group: non_empty(filter.group).and_then(|g| g.parse().ok()),
+            environment: non_empty(filter.environment),
+            search: non_empty(filter.search).map(|s| s.trim().to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<DashboardFilter> for ListView {
+    fn from(mut filter: DashboardFilter) -> Self {
+        let status = non_empty(filter.status.take()).and_then(|s| s.parse().ok());
+        let sort = non_empty(filter.sort.take()).and_then(|s| s.parse().ok());
+        let by_status = non_empty(filter.by_status.take()).is_some();
+        let page = non_empty(filter.page.take())
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(1);
+        ListView {
+            filter: filter.into(),
+            status,
+            sort,
+            by_status,
+            page,
+        }
+    }
+}
+
+pub async fn app(
+    State(app_state): State<AppState>,
+    Query(filter): Query<DashboardFilter>,
+    role: Role,
+    user: Option<Extension<User>>,
+) -> impl IntoResponse {
     event!(Level::INFO, "GET /");
-    Html(
-        "
-        <!DOCTYPE html>
-        <html lang=\"en\">
-            <head>
-                <script src=\"https://cdn.jsdelivr.net/npm/htmx.org@2.0.8/dist/htmx.min.js\"></script>
-                <script src=\"https://cdn.jsdelivr.net/npm/htmx-ext-sse@2.2.4\" integrity=\"sha384-A986SAtodyH8eg8x8irJnYUk7i9inVQqYigD6qZ9evobksGNIXfeFvDwLSHcp31N\" crossorigin=\"anonymous\"></script>
-            </head>
-            <style>
-                @import url('https://fonts.googleapis.com/css2?family=IBM+Plex+Mono:ital,wght@0,100;0,200;0,300;0,400;0,500;0,600;0,700;1,100;1,200;1,300;1,400;1,500;1,600;1,700&display=swap');
-                @property --block-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #084b78;
-                }
-                @property --dark-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #1b2222;
-                }
-                @property --light-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #f9f5fc;
-                }
-                @property --success-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #33ca7f;
-                }
-                @property --warning-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #ffe45e;
-                }
-                @property --error-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #e02c29;
-                }
-                @property --unknown-color {
-                    syntax: \"<color>\";
-                    inherits: false;
-                    initial-value: #AAAAAA;
-                }
-                html {
-                    height: 100%;
-                    margin: 0;
-                }
-                body {
-                    background-color: var(--light-color);
-                    height: 100%;
-                    margin: 0;
-                    padding: 0;
-                    font-family: \"IBM Plex Mono\", monospace;
-                    font-weight: 400;
-                    font-style: normal;
-                }
-                input, textarea, select, button {
-                    font-family:inherit;
-                }
-                th {
-                    text-align: left;
-                }
-                .block {
-                    padding: 12px;
-                    border-radius: 4px;
-                }
-                .body {
-                    display: flex;
-                    flex-flow: column;
-                    height: 100%;
-                    background-color: var(--light-color);
-                }
-                .body .row.header {
-                    flex: 0 1 auto;
-                }
-                .body .row.content {
-                    flex: 1 1 auto;
-                }
-                .banner {
-                    padding: 12px;
-                    background-color: var(--block-color);
-                    color: var(--light-color);
-                }
-                .error {
-                    color: var(--error-color);
-                }
-                .warning {
-                    background-color: var(--warning-color);
-                }
-                .button {
-                    background-color: var(--block-color);
-                    color: var(--light-color);
-                    margin-top: 6px;
-                    cursor: pointer;
-                }
-                .form {
-                    background-color: var(--warning-color);
-                    margin-top: 6px;
-                }
-                .success-chip, .warning-chip, .error-chip, .unknown-chip {
-                    padding: 2px 6px 2px 6px;
-                    border-radius: 4px;
-                }
-                .success-chip {
-                    color: var(--light-color);
-                    background-color: var(--success-color);
-                }
-                .warning-chip {
-                    color: var(--dark-color);
-                    background-color: var(--warning-color);
-                }
-                .error-chip {
-                    color: var(--light-color);
-                    background-color: var(--error-color);
-                }
-                .unknown-chip {
-                    color: var(--dark-color);
-                    background-color: var(--unknown-color);
-                }
-                #services-list {
-                    padding: 12px;
-                }
-            </style>
-            <body hx-ext=\"sse\">
-                <div class=\"body\">
-                    <div class=\"banner row header\" style=\"display:flex;flex-direction:row;justify-content:space-between\">
-                        <div style=\"padding: 2px 0px 2px 0px;\">WRAUT</div>
-                        <div
-                            id=\"live-service-connection\"
-                            sse-connect=\"/html/live_services\"
-                            sse-swap=\"service_event\"
-                        >
-                            <!-- This is the direct target of the SSE endpoint -->
-                            Connecting...
-                        </div>
-                    </div>
-                    <table id=\"services-list\">
-                        <tr><td>Waiting connection...</td></tr>
-                    </table>
-                    <div
-                        id=\"add-service-btn\"
-                        style=\"margin:12px;border-radius:4px;cursor:pointer;\"
-                        class=\"success-chip\"
-                        hx-get=\"/html/service_form\"
-                        hx-swap=\"outerHTML\"
-                    >
-                        + Add service
-                    </div>
-                </div>
-            </body>
-        </html>
-    ",
+    html::page(
+        &app_state.csrf_token,
+        role,
+        user.map(|Extension(u)| u.username),
+        db::get_groups(&app_state.pool).await.unwrap_or_default(),
+        db::get_environments(&app_state.pool)
+            .await
+            .unwrap_or_default(),
+        filter.into(),
     )
 }
 
-pub async fn new_service_form() -> impl IntoResponse {
+pub async fn static_asset(Path(file): Path<String>) -> impl IntoResponse {
+    match Assets::get(&file) {
+        Some(asset) => (
+            [
+                (header::CONTENT_TYPE, asset.metadata.mimetype().to_string()),
+                (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            ],
+            asset.data,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PresetQuery {
+    preset: Option<String>,
+    // fill in the preset's compose override too
+    starter: Option<bool>,
+}
+
+pub async fn new_service_form(
+    State(app_state): State<AppState>,
+    Query(query): Query<PresetQuery>,
+) -> impl IntoResponse {
     event!(Level::INFO, "GET /html/service_form");
 
-    return Html(
-        "
-        <form
-            id=\"add-service-btn\"
-            hx-post=\"/api/service\"
-            hx-target=\"#services-list\"
-            style=\"margin:12px;border-radius:4px;margin-left:auto;margin-right:auto;\"
-            class=\"success-chip\"
-        >
-            <table>
-                <tr><td align=\"right\">Name:</td><td><input name=\"name\" /></td></tr>
-                <tr><td align=\"right\">Compose Name:</td><td><input name=\"compose_name\" /></td></tr>
-                <tr><td align=\"right\">Repo URL:</td><td><input name=\"repo_url\" /></td></tr>
-                <tr><td align=\"right\">Access URL:</td><td><input name=\"access_url\" /></td></tr>
-                <tr><td align=\"right\">Active:</td><td><input name=\"active\" type=\"checkbox\" value=\"true\" /></td></tr>
-                <tr><td align=\"right\">Use key:</td><td><input name=\"use_key\" type=\"checkbox\" value=\"false\" /></td></tr>
-                <tr><td align=\"center\" colspan=\"2\"><button type=\"submit\">Submit</button></td></tr>
-            </table>
-        </form>
-        ",
-    );
+    let mut service = Service::from(ServiceForm::default());
+    let preset = non_empty(query.preset).and_then(|slug| {
+        let found = preset::find(&slug);
+        if found.is_none() {
+            event!(Level::WARN, "No service preset named {}", slug);
+        }
+        found
+    });
+    let starter = query.starter.unwrap_or(false);
+    if let Some(preset) = preset {
+        preset.apply(&mut service, starter);
+    }
+
+    html::new_form(
+        service,
+        None,
+        FieldErrors::default(),
+        form_choices(&app_state, None).await,
+        preset.map_or(Prefill::Blank, |p| Prefill::Preset(p, starter)),
+    )
+}
+
+// `id` is the service being edited, whose clone offers its compose services
+async fn form_choices(app_state: &AppState, id: Option<i64>) -> FormChoices {
+    let saved = match id {
+        Some(id) => db::get_service(&app_state.pool, id).await.ok(),
+        None => None,
+    };
+    FormChoices {
+        keys: db::get_deploy_keys(&app_state.pool)
+            .await
+            .unwrap_or_default(),
+        nodes: db::get_nodes(&app_state.pool).await.unwrap_or_default(),
+        groups: db::get_groups(&app_state.pool).await.unwrap_or_default(),
+        compose_names: saved
+            .and_then(|s| s.repo_compose_names(&s.repo_path(&app_state.config)))
+            .unwrap_or_default(),
+    }
+}
+
+// sends a form back with its errors in place of itself, rather than into the services list
+fn form_errors(form: Markup, target: String) -> Response {
+    (
+        [
+            ("HX-Retarget", target),
+            ("HX-Reswap", "outerHTML".to_string()),
+        ],
+        form,
+    )
+        .into_response()
 }
 
 pub async fn edit_service_form(
@@ -215,30 +200,62 @@ pub async fn edit_service_form(
         Ok(s) => s,
         Err(e) => {
             event!(Level::ERROR, "Unable to get service from DB | {}", e);
-            return Html(
-                "<td colspan=\"2\" class=\"error\">Unable to get service information.</td>"
-                    .to_string(),
+            return Markup::trusted(
+                "<td colspan=\"2\" class=\"error\">Unable to get service information.</td>",
             );
         }
     };
 
-    return Html(format!(
-        "
-        <td colspan=\"2\">
-            <form hx-put=\"/api/service/{}\" hx-target=\"#services-list\">
-                Name: <input name=\"name\" value=\"{}\"/>
-                Repo URL: <input name=\"repo_url\" value=\"{}\"/><br />
-                Access URL: <input name=\"access_url\" value=\"{}\"/><br />
-                Active: <input name=\"active\" type=\"checkbox\" value=\"{}\" /><br />
-                <button type=\"submit\">Submit</button>
-            </form>
-        </td>
-        ",
-        service.id, service.name, service.repo_url, service.access_url, service.active,
-    ));
+    let git_username = db::get_git_credential(&app_state.pool, service_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|credential| credential.username);
+
+    html::edit_form(
+        service,
+        git_username,
+        FieldErrors::default(),
+        form_choices(&app_state, Some(service_id)).await,
+    )
 }
 
-#[derive(Deserialize)]
+// the new-service form filled in with another service's configuration
+pub async fn duplicate_service_form(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service_form/:id/duplicate");
+
+    let (source, services) = match tokio::try_join!(
+        db::get_service(&app_state.pool, service_id),
+        db::get_services(&app_state.pool, None),
+    ) {
+        Ok(found) => found,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get services from DB | {}", e);
+            return Markup::trusted(
+                "<div class=\"error\">Unable to get service information.</div>",
+            );
+        }
+    };
+    let taken: Vec<String> = services.into_iter().map(|s| s.name).collect();
+    let git_username = db::get_git_credential(&app_state.pool, service_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|credential| credential.username);
+
+    html::new_form(
+        duplicate::duplicate(&source, &taken),
+        git_username,
+        FieldErrors::default(),
+        form_choices(&app_state, None).await,
+        Prefill::Duplicate(service_id, false),
+    )
+}
+
+#[derive(Default, Deserialize)]
 pub struct ServiceForm {
     name: String,
     compose_name: String,
@@ -246,29 +263,262 @@ pub struct ServiceForm {
     access_url: String,
     active: Option<bool>,
     use_key: Option<bool>,
+    pre_deploy_cmd: Option<String>,
+    post_deploy_cmd: Option<String>,
+    deploy_key_id: Option<String>,
+    schedule: Option<String>,
+    poll_interval_secs: Option<String>,
+    tag_pattern: Option<String>,
+    clone_depth: Option<String>,
+    compose_file: Option<String>,
+    build_before_up: Option<bool>,
+    push_image: Option<bool>,
+    docker_host: Option<String>,
+    node_id: Option<String>,
+    group_id: Option<String>,
+    environment: Option<String>,
+    branch: Option<String>,
+    proxy_port: Option<String>,
+    compose_override: Option<String>,
+    review_required: Option<bool>,
+    requires_approval: Option<bool>,
+    watch_paths: Option<String>,
+    health_grace_secs: Option<String>,
+    backup_volumes: Option<String>,
+    post_start_exec: Option<String>,
+    post_start_container: Option<String>,
+    repo_dir: Option<String>,
+    live_dir: Option<String>,
+    smoke_url: Option<String>,
+    smoke_status: Option<String>,
+    smoke_regex: Option<String>,
+    one_off: Option<String>,
+    platform: Option<String>,
+    secret_files: Option<bool>,
+    previews: Option<bool>,
+    pipeline: Option<String>,
+    scripts: Option<String>,
+    git_username: Option<String>,
+    git_token: Option<String>,
+    // set when the form was filled in from another service, whose env vars are copied
+    duplicate_of: Option<String>,
+    copy_secrets: Option<bool>,
+}
+
+// empty form inputs arrive as `Some("")`; treat them as unset
+fn non_empty(field: Option<String>) -> Option<String> {
+    field.filter(|f| !f.trim().is_empty())
+}
+
+impl From<ServiceForm> for Service {
+    fn from(form: ServiceForm) -> Self {
+        Service {
+            id: 0, // NOT USED
+            name: form.name,
+            compose_name: form.compose_name,
+            repo_url: form.repo_url,
+            access_url: form.access_url,
+            active: form.active.unwrap_or(false),
+            use_key: form.use_key.unwrap_or(false),
+            pre_deploy_cmd: non_empty(form.pre_deploy_cmd),
+            post_deploy_cmd: non_empty(form.post_deploy_cmd),
+            deploy_key_id: non_empty(form.deploy_key_id).and_then(|k| k.parse().ok()),
+            archived: false,
+            schedule: non_empty(form.schedule).map(|s| s.trim().to_string()),
+            poll_interval_secs: non_empty(form.poll_interval_secs)
+                .and_then(|s| s.trim().parse().ok()),
+            tag_pattern: non_empty(form.tag_pattern).map(|s| s.trim().to_string()),
+            clone_depth: non_empty(form.clone_depth).and_then(|s| s.trim().parse().ok()),
+            compose_file: non_empty(form.compose_file).map(|s| s.trim().to_string()),
+            build_before_up: form.build_before_up.unwrap_or(false),
+            push_image: form.push_image.unwrap_or(false),
+            docker_host: non_empty(form.docker_host).map(|s| s.trim().to_string()),
+            node_id: non_empty(form.node_id).and_then(|n| n.parse().ok()),
+            group_id: non_empty(form.group_id).and_then(|g| g.parse().ok()),
+            environment: non_empty(form.environment).map(|e| e.trim().to_lowercase()),
+            branch: non_empty(form.branch).map(|b| b.trim().to_string()),
+            proxy_port: non_empty(form.proxy_port).and_then(|p| p.trim().parse().ok()),
+            compose_override: non_empty(form.compose_override),
+            review_required: form.review_required.unwrap_or(false),
+            requires_approval: form.requires_approval.unwrap_or(false),
+            watch_paths: non_empty(form.watch_paths),
+            health_grace_secs: non_empty(form.health_grace_secs)
+                .and_then(|s| s.trim().parse().ok()),
+            backup_volumes: non_empty(form.backup_volumes),
+            post_start_exec: non_empty(form.post_start_exec),
+            post_start_container: non_empty(form.post_start_container)
+                .map(|c| c.trim().to_string()),
+            repo_dir: non_empty(form.repo_dir).map(|d| d.trim().to_string()),
+            live_dir: non_empty(form.live_dir).map(|d| d.trim().to_string()),
+            smoke_url: non_empty(form.smoke_url).map(|u| u.trim().to_string()),
+            smoke_status: non_empty(form.smoke_status).and_then(|s| s.trim().parse().ok()),
+            smoke_regex: non_empty(form.smoke_regex),
+            one_off: non_empty(form.one_off).map(|o| o.trim().to_string()),
+            platform: non_empty(form.platform).map(|p| p.trim().to_lowercase()),
+            secret_files: form.secret_files.unwrap_or(false),
+            previews: form.previews.unwrap_or(false),
+            preview_of: None,
+            pipeline: non_empty(form.pipeline).map(|p| p.trim().to_string()),
+            scripts: non_empty(form.scripts),
+            last_commit: None,
+            last_status: None,
+            last_status_detail: None,
+            last_status_at: None,
+        }
+    }
+}
+
+// the HTTPS credential a service form asks for, its token encrypted; no username removes
+// it, and a blank token keeps the saved one
+async fn git_credential(
+    app_state: &AppState,
+    service_id: Option<i64>,
+    username: Option<String>,
+    token: Option<String>,
+) -> Result<Option<GitCredential>, String> {
+    let Some(username) = non_empty(username) else {
+        return Ok(None);
+    };
+    let token = match non_empty(token) {
+        Some(token) => app_state
+            .config
+            .secrets_key
+            .as_ref()
+            .ok_or(SecretError::NoKey)
+            .and_then(|key| key.encrypt(token.trim()))
+            .map_err(|e| e.to_string())?,
+        None => {
+            let saved = match service_id {
+                Some(id) => db::get_git_credential(&app_state.pool, id)
+                    .await
+                    .map_err(|e| e.to_string())?,
+                None => None,
+            };
+            saved
+                .map(|credential| credential.token)
+                .ok_or_else(|| "An HTTPS token is required with the username".to_string())?
+        }
+    };
+    Ok(Some(GitCredential {
+        username: username.trim().to_string(),
+        token,
+    }))
+}
+
+// a repo is registered at most once per environment; `id` is the service being edited
+async fn check_environment(
+    pool: &sqlx::SqlitePool,
+    service: &Service,
+    id: Option<i64>,
+) -> Result<(), String> {
+    let Some(environment) = &service.environment else {
+        return Ok(());
+    };
+    match db::service_in_environment(pool, &service.repo_url, environment).await {
+        Ok(Some(other)) if Some(other) != id => Err(format!(
+            "This repo is already registered in {} (service {})",
+            environment, other
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// compose services the repo's clone doesn't define; an edited service's clone is where its
+// saved settings put it, and a new service usually has none yet
+async fn check_compose_names(
+    app_state: &AppState,
+    service: &Service,
+    id: Option<i64>,
+) -> Result<(), ServiceError> {
+    let saved = match id {
+        Some(id) => db::get_service(&app_state.pool, id).await.ok(),
+        None => None,
+    };
+    let repo_path = saved
+        .as_ref()
+        .unwrap_or(service)
+        .repo_path(&app_state.config);
+    service.check_compose_names(&repo_path)
 }
 
 pub async fn add_new_service(
     State(app_state): State<AppState>,
-    Form(service_form): Form<ServiceForm>,
+    actor: Actor,
+    Form(mut service_form): Form<ServiceForm>,
 ) -> impl IntoResponse {
     event!(Level::INFO, "POST /api/service");
 
-    let service = Service {
-        id: 0, // NOT USED
-        name: service_form.name,
-        compose_name: service_form.compose_name,
-        repo_url: service_form.repo_url,
-        access_url: service_form.access_url,
-        active: service_form.active.unwrap_or(false),
-        use_key: service_form.use_key.unwrap_or(false),
+    let git_username = service_form.git_username.clone();
+    let duplicate_of = non_empty(service_form.duplicate_of.take()).and_then(|id| id.parse().ok());
+    let copy_secrets = service_form.copy_secrets.unwrap_or(false);
+    let prefill = match duplicate_of {
+        Some(id) => Prefill::Duplicate(id, copy_secrets),
+        None => Prefill::Blank,
     };
+    // a duplicate's blank token keeps the original's
+    let credential = git_credential(
+        &app_state,
+        duplicate_of,
+        service_form.git_username.take(),
+        service_form.git_token.take(),
+    )
+    .await;
+    let service = Service::from(service_form);
+    let mut errors = FieldErrors::from(service.field_errors());
+    if let Err(message) = check_environment(&app_state.pool, &service, None).await {
+        errors.push("environment", message);
+    }
+    if let Err(e) = check_compose_names(&app_state, &service, None).await {
+        errors.push("compose_name", e);
+    }
+    let credential = credential.unwrap_or_else(|message| {
+        errors.push("git_token", message);
+        None
+    });
+    if !errors.is_empty() {
+        let form = html::new_form(
+            service,
+            git_username,
+            errors,
+            form_choices(&app_state, None).await,
+            prefill,
+        );
+        return form_errors(form, "#add-service-btn".to_string());
+    }
 
-    match db::new_service(&app_state.pool, service).await {
-        Ok(_) => (),
+    match db::new_service(&app_state.pool, service.clone()).await {
+        Ok(id) => {
+            if let Err(e) = db::set_git_credential(&app_state.pool, id, credential).await {
+                event!(Level::ERROR, "Unable to save git credential | {}", e);
+            }
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(id),
+                AuditAction::Create,
+                audit::diff(None, Some(&service)),
+            )
+            .await;
+            if let Some(from) = duplicate_of {
+                duplicate_env(&app_state, &actor, from, id, copy_secrets).await;
+            }
+        }
+        Err(e @ db::DBError::DuplicateName(_)) => {
+            let mut errors = FieldErrors::default();
+            errors.push("name", e.to_string());
+            let form = html::new_form(
+                service,
+                git_username,
+                errors,
+                form_choices(&app_state, None).await,
+                prefill,
+            );
+            return form_errors(form, "#add-service-btn".to_string());
+        }
         Err(e) => {
             event!(Level::ERROR, "Error processing new service | {}", e);
-            return Html(
+            return Markup::trusted(
                 "<div class=\"error\">Posting new service failed. See logs and reset the page.</div>",
             ).into_response();
         }
@@ -282,28 +532,108 @@ pub async fn add_new_service(
     "OK".into_response()
 }
 
+// copies the env vars of the service a new one was duplicated from
+async fn duplicate_env(app_state: &AppState, actor: &Actor, from: i64, to: i64, secrets: bool) {
+    match db::copy_service_env(&app_state.pool, from, to, secrets).await {
+        Ok(copied) => {
+            audit::record(
+                &app_state.pool,
+                actor,
+                Some(to),
+                AuditAction::Duplicate,
+                serde_json::json!({
+                    "duplicate_of": { "before": null, "after": from },
+                    "env_vars_copied": { "before": null, "after": copied },
+                }),
+            )
+            .await
+        }
+        Err(e) => event!(
+            Level::ERROR,
+            "Unable to copy env vars of service {} | {}",
+            from,
+            e
+        ),
+    }
+}
+
 pub async fn edit_existing_service(
     State(app_state): State<AppState>,
     Path(service_id): Path<i64>,
-    Form(service_form): Form<ServiceForm>,
+    actor: Actor,
+    Form(mut service_form): Form<ServiceForm>,
 ) -> impl IntoResponse {
     event!(Level::INFO, "PUT /api/service/:id");
 
-    let service = Service {
-        id: 0, // NOT USED
-        name: service_form.name,
-        compose_name: service_form.compose_name,
-        repo_url: service_form.repo_url,
-        access_url: service_form.access_url,
-        active: service_form.active.unwrap_or(false),
-        use_key: service_form.use_key.unwrap_or(false),
-    };
+    let git_username = service_form.git_username.clone();
+    let credential = git_credential(
+        &app_state,
+        Some(service_id),
+        service_form.git_username.take(),
+        service_form.git_token.take(),
+    )
+    .await;
+    let service = Service::from(service_form);
+    let mut errors = FieldErrors::from(service.field_errors());
+    if let Err(message) = check_environment(&app_state.pool, &service, Some(service_id)).await {
+        errors.push("environment", message);
+    }
+    if let Err(e) = check_compose_names(&app_state, &service, Some(service_id)).await {
+        errors.push("compose_name", e);
+    }
+    let credential = credential.unwrap_or_else(|message| {
+        errors.push("git_token", message);
+        None
+    });
+    if !errors.is_empty() {
+        let service = Service {
+            id: service_id,
+            ..service
+        };
+        let form = html::edit_form(
+            service,
+            git_username,
+            errors,
+            form_choices(&app_state, Some(service_id)).await,
+        );
+        return form_errors(form, format!("#edit-service-{}", service_id));
+    }
+    if let Err(e) = db::set_git_credential(&app_state.pool, service_id, credential).await {
+        event!(Level::ERROR, "Unable to save git credential | {}", e);
+        return html::error_message(e.to_string()).into_response();
+    }
 
-    match db::update_service(&app_state.pool, service_id, service).await {
-        Ok(_) => (),
+    let before = db::get_service(&app_state.pool, service_id).await.ok();
+    match db::update_service(&app_state.pool, service_id, service.clone()).await {
+        Ok(_) => {
+            let after = db::get_service(&app_state.pool, service_id).await.ok();
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service_id),
+                AuditAction::Edit,
+                audit::diff(before.as_ref(), after.as_ref()),
+            )
+            .await
+        }
+        Err(e @ db::DBError::DuplicateName(_)) => {
+            let mut errors = FieldErrors::default();
+            errors.push("name", e.to_string());
+            let service = Service {
+                id: service_id,
+                ..service
+            };
+            let form = html::edit_form(
+                service,
+                git_username,
+                errors,
+                form_choices(&app_state, Some(service_id)).await,
+            );
+            return form_errors(form, format!("#edit-service-{}", service_id));
+        }
         Err(e) => {
             event!(Level::ERROR, "Error editing existing service | {}", e);
-            return Html(
+            return Markup::trusted(
                 "<div class=\"error\">Updating existing service failed. See logs and reset the page.</div>",
             ).into_response();
         }
@@ -320,96 +650,1739 @@ pub async fn edit_existing_service(
 pub async fn deploy_service(
     State(app_state): State<AppState>,
     Path(service_id): Path<i64>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    event!(Level::INFO, "GET /api/service/:id/deploy");
-    let service = db::get_service(&app_state.pool, service_id.clone()).await;
-
-    tokio::spawn(async move {
-        let status = match Service::deploy(
-            app_state.config,
-            service,
-            app_state.service_broadcast.broadcaster.clone(),
-        )
-        .await
-        {
-            Ok(_) => ServiceStatus::Running,
-            Err(e) => ServiceStatus::from_error(e),
-        };
+    event!(Level::INFO, "POST /api/service/:id/deploy");
+    let service = db::get_service(&app_state.pool, service_id).await;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Deploy,
+        serde_json::json!({}),
+    )
+    .await;
 
-        let _ = app_state
-            .service_broadcast
-            .broadcaster
-            .send(ServiceEvent::ServiceUpdate {
-                id: service_id,
-                status,
-            });
-    });
+    app_state.spawn_deploy(service_id, service);
 
     "OK"
 }
 
-pub async fn delete_service(
+pub async fn stop_service(
     State(app_state): State<AppState>,
     Path(service_id): Path<i64>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    event!(Level::INFO, "DELETE /api/service/:id");
-    let service = db::get_service(&app_state.pool, service_id.clone()).await;
-    tokio::spawn(async move {
-        Service::delete_service(
-            app_state.config,
-            &app_state.pool,
-            service,
-            app_state.service_broadcast.broadcaster,
-        )
-        .await
-    });
+    event!(Level::INFO, "POST /api/service/:id/stop");
+    lifecycle(app_state, service_id, actor, Lifecycle::Stop).await
+}
 
-    "OK"
+pub async fn start_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/start");
+    lifecycle(app_state, service_id, actor, Lifecycle::Start).await
 }
 
-pub async fn deactivate_service(
+pub async fn restart_service(
     State(app_state): State<AppState>,
     Path(service_id): Path<i64>,
+    actor: Actor,
 ) -> impl IntoResponse {
-    event!(Level::INFO, "DELETE /api/service/:id");
-    let service = db::get_service(&app_state.pool, service_id.clone()).await;
-    tokio::spawn(async move {
-        Service::deactivate_service(
-            app_state.config,
-            service,
-            app_state.service_broadcast.broadcaster,
-        )
-        .await
-    });
+    event!(Level::INFO, "POST /api/service/:id/restart");
+    lifecycle(app_state, service_id, actor, Lifecycle::Restart).await
+}
 
-    "OK"
+#[derive(Deserialize)]
+pub struct TearDownForm {
+    volumes: Option<bool>,
 }
 
-pub async fn live_services(
+pub async fn tear_down_service(
     State(app_state): State<AppState>,
-) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
-    event!(Level::INFO, "SSE /html/live_services");
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(tear_down_form): Form<TearDownForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/teardown");
+    let volumes = tear_down_form.volumes.unwrap_or(false);
+    lifecycle(
+        app_state,
+        service_id,
+        actor,
+        Lifecycle::TearDown { volumes },
+    )
+    .await
+}
 
-    let stream = app_state
-        .service_broadcast
-        .event_stream(app_state.pool.clone())
-        .await;
+async fn lifecycle(
+    app_state: AppState,
+    service_id: i64,
+    actor: Actor,
+    action: Lifecycle,
+) -> Response {
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    if app_state.in_flight.contains(service_id) {
+        return (StatusCode::CONFLICT, "Service is busy").into_response();
+    }
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::from(action),
+        match action {
+            Lifecycle::TearDown { volumes } => serde_json::json!({ "volumes": volumes }),
+            _ => serde_json::json!({}),
+        },
+    )
+    .await;
+    app_state.spawn_lifecycle(service, action);
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    "OK".into_response()
 }
 
-pub async fn all_status_request(State(app_state): State<AppState>) -> impl IntoResponse {
-    event!(Level::INFO, "GET /api/all_status");
+// deploys the commit the staging service of the same repo last deployed
+pub async fn promote_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/promote");
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    if app_state.in_flight.contains(service_id) {
+        return (StatusCode::CONFLICT, "Service is busy").into_response();
+    }
+    let promotion = match promote::promotion(&app_state.pool, &service).await {
+        Ok(p) => p,
+        Err(e) => return html::error_message(e.to_string()).into_response(),
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Promote,
+        serde_json::json!({ "from": promotion.from, "commit": promotion.commit }),
+    )
+    .await;
+    let message = format!(
+        "Promoting {} from {}",
+        short_commit(&promotion.commit),
+        promote::FROM
+    );
+    app_state.spawn_deploy_at(service_id, Ok(service), Some(promotion.commit));
 
-    match app_state
-        .service_broadcast
-        .broadcaster
-        .send(ServiceEvent::AllStatus)
-    {
-        Ok(_) => "OK",
+    Markup::text(message).into_response()
+}
+
+pub async fn service_deployments(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/deployments");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let deployments = db::get_deployments(&app_state.pool, service_id, 50).await;
+
+    html::deployments(service, deployments)
+}
+
+pub async fn service_volumes(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/volumes");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+
+    html::volumes(service, &app_state.config)
+}
+
+// replaces the service's volumes with those of a snapshot taken before an earlier deploy
+pub async fn restore_volumes(
+    State(app_state): State<AppState>,
+    Path((service_id, snapshot)): Path<(i64, String)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(
+        Level::INFO,
+        "POST /api/service/:id/volumes/:snapshot/restore"
+    );
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
         Err(e) => {
-            event!(Level::ERROR, "All status request failed | {}", e);
-            "SIGNAL FAILED"
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    if app_state.in_flight.contains(service_id) {
+        return (StatusCode::CONFLICT, "Service is busy").into_response();
+    }
+    match volume::list(&app_state.config, &service.name) {
+        Ok(snapshots) if snapshots.iter().any(|s| s.name == snapshot) => (),
+        Ok(_) => {
+            return html::error_message(ServiceError::NoSnapshot(snapshot).to_string())
+                .into_response();
         }
+        Err(e) => return html::error_message(e.to_string()).into_response(),
     }
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::RestoreVolumes,
+        serde_json::json!({ "snapshot": snapshot }),
+    )
+    .await;
+    let message = format!(
+        "Restoring the volumes of {} from {}",
+        service.name, snapshot
+    );
+    app_state.spawn_volume_restore(service, snapshot);
+
+    Markup::text(message).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct FilesQuery {
+    path: Option<String>,
+}
+
+pub async fn service_files(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    Query(query): Query<FilesQuery>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/files");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+
+    html::files(
+        service,
+        &app_state.config,
+        query.path.as_deref().unwrap_or_default(),
+    )
+}
+
+pub async fn service_compose(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/compose");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    html::compose(service, &app_state.config, None, false)
+}
+
+#[derive(Deserialize)]
+pub struct ComposeOverrideForm {
+    compose_override: Option<String>,
+}
+
+// saves just the override, for a quick fix that doesn't wait on a commit to the repo
+pub async fn save_compose_override(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(form): Form<ComposeOverrideForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/compose");
+
+    let before = match db::get_service(&app_state.pool, service_id).await {
+        Ok(service) => service,
+        Err(e) => return html::compose(Err(e), &app_state.config, None, false),
+    };
+    let service = Service {
+        compose_override: non_empty(form.compose_override),
+        ..before.clone()
+    };
+    if let Err(e) = service.check_compose_override() {
+        return html::compose(Ok(service), &app_state.config, Some(e.to_string()), false);
+    }
+    if let Err(e) = db::update_service(&app_state.pool, service_id, service).await {
+        event!(Level::ERROR, "Error saving compose override | {}", e);
+        return html::compose(Ok(before), &app_state.config, Some(e.to_string()), false);
+    }
+
+    let after = db::get_service(&app_state.pool, service_id).await;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Edit,
+        audit::diff(Some(&before), after.as_ref().ok()),
+    )
+    .await;
+    html::compose(after, &app_state.config, None, true)
+}
+
+pub async fn service_terminal(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/terminal");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let mut containers = vec![];
+    if let Ok(service) = &service {
+        let list = app_state
+            .docker_cache
+            .list(std::slice::from_ref(&service.docker_host))
+            .await
+            .unwrap_or_default();
+        for container in service.containers(&list) {
+            if container.state == "running" && !containers.contains(&container.service) {
+                containers.push(container.service);
+            }
+        }
+    }
+
+    html::terminal(service, containers)
+}
+
+pub async fn service_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/review");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let review = db::get_deploy_review(&app_state.pool, service_id).await;
+
+    html::review(service, review)
+}
+
+// deploys the commit whose compose diff was reviewed
+pub async fn approve_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/review/approve");
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    if app_state.in_flight.contains(service_id) {
+        return (StatusCode::CONFLICT, "Service is busy").into_response();
+    }
+    let review = match app_state.approve_review(service).await {
+        Ok(Some(review)) => review,
+        Ok(None) => {
+            return html::error_message("No deploy is waiting for review".to_string())
+                .into_response();
+        }
+        Err(e) => return html::error_message(e.to_string()).into_response(),
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::ApproveDeploy,
+        serde_json::json!({ "commit": review.commit_sha }),
+    )
+    .await;
+
+    Markup::text(match review.short_commit() {
+        Some(commit) => format!("Deploying {}", commit),
+        None => "Deploying".to_string(),
+    })
+    .into_response()
+}
+
+pub async fn decline_review(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/review/decline");
+    let review = match app_state.decline_review(service_id).await {
+        Ok(Some(review)) => review,
+        Ok(None) => {
+            return html::error_message("No deploy is waiting for review".to_string())
+                .into_response();
+        }
+        Err(e) => return html::error_message(e.to_string()).into_response(),
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::DeclineDeploy,
+        serde_json::json!({ "commit": review.commit_sha }),
+    )
+    .await;
+
+    Markup::text("Declined").into_response()
+}
+
+pub async fn approve_deploy(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/approval/approve");
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    if app_state.in_flight.contains(service_id) {
+        return (StatusCode::CONFLICT, "Service is busy").into_response();
+    }
+    let approval = match app_state.approve_deploy(service).await {
+        Ok(Some(approval)) => approval,
+        Ok(None) => {
+            return html::error_message("No deploy is waiting for approval".to_string())
+                .into_response();
+        }
+        Err(e) => return html::error_message(e.to_string()).into_response(),
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::ApproveDeploy,
+        serde_json::json!({ "trigger": approval.trigger }),
+    )
+    .await;
+
+    Markup::text("Deploying").into_response()
+}
+
+pub async fn decline_deploy(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/approval/decline");
+    let approval = match app_state.decline_deploy(service_id).await {
+        Ok(Some(approval)) => approval,
+        Ok(None) => {
+            return html::error_message("No deploy is waiting for approval".to_string())
+                .into_response();
+        }
+        Err(e) => return html::error_message(e.to_string()).into_response(),
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::DeclineDeploy,
+        serde_json::json!({ "trigger": approval.trigger }),
+    )
+    .await;
+
+    Markup::text("Declined").into_response()
+}
+
+pub async fn service_hook(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/hook");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let created_at = db::get_deploy_hook_created(&app_state.pool, service_id).await;
+
+    html::hook(service, created_at, None, None)
+}
+
+// creates the service's hook token, or replaces it; the token is only shown this once
+pub async fn rotate_service_hook(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/hook");
+
+    let (message, token) = match user::new_session_token() {
+        Ok((token, token_hash)) => {
+            match db::set_deploy_hook(&app_state.pool, service_id, token_hash).await {
+                Ok(_) => {
+                    audit::record(
+                        &app_state.pool,
+                        &actor,
+                        Some(service_id),
+                        AuditAction::RotateHook,
+                        serde_json::json!({}),
+                    )
+                    .await;
+                    (None, Some(token))
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "Error saving deploy hook | {}", e);
+                    (
+                        Some("Saving deploy hook failed. See logs.".to_string()),
+                        None,
+                    )
+                }
+            }
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error generating hook token | {}", e);
+            (Some("Generating token failed. See logs.".to_string()), None)
+        }
+    };
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let created_at = db::get_deploy_hook_created(&app_state.pool, service_id).await;
+    html::hook(service, created_at, token, message)
+}
+
+pub async fn delete_service_hook(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id/hook");
+
+    let message = match db::delete_deploy_hook(&app_state.pool, service_id).await {
+        Ok(_) => {
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service_id),
+                AuditAction::DeleteHook,
+                serde_json::json!({}),
+            )
+            .await;
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting deploy hook | {}", e);
+            Some("Deleting deploy hook failed. See logs.".to_string())
+        }
+    };
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let created_at = db::get_deploy_hook_created(&app_state.pool, service_id).await;
+    html::hook(service, created_at, None, message)
+}
+
+// kills the running step of the service's deploy; it fails with a cancelled status
+pub async fn cancel_deploy(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/deploy/cancel");
+    if !app_state.in_flight.cancel(service_id) {
+        return (StatusCode::NOT_FOUND, "No deployment in progress").into_response();
+    }
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::CancelDeploy,
+        serde_json::json!({}),
+    )
+    .await;
+
+    "OK".into_response()
+}
+
+pub async fn delete_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id");
+    let service = db::get_service(&app_state.pool, service_id).await;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Delete,
+        audit::diff(service.as_ref().ok(), None),
+    )
+    .await;
+    app_state.spawn_delete(service);
+
+    "OK"
+}
+
+pub async fn deactivate_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/deactivate");
+    let service = db::get_service(&app_state.pool, service_id).await;
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Deactivate,
+        serde_json::json!({}),
+    )
+    .await;
+    app_state.spawn_deactivate(service);
+
+    "OK"
+}
+
+pub async fn service_notifications(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/notifications");
+
+    notifications_panel(&app_state, service_id, None).await
+}
+
+async fn notifications_panel(
+    app_state: &AppState,
+    service_id: i64,
+    message: Option<String>,
+) -> Markup {
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let recipients = db::get_email_recipients(&app_state.pool, service_id).await;
+    let targets = db::get_push_targets(&app_state.pool, service_id).await;
+    let rules = db::get_notification_rules(&app_state.pool, service_id)
+        .await
+        .map(notify::rules);
+    html::notifications(
+        service,
+        recipients,
+        targets,
+        rules,
+        app_state.config.smtp.is_some(),
+        app_state.config.push.pushover_token.is_some(),
+        message,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct EmailRecipientForm {
+    address: String,
+}
+
+pub async fn add_email_recipient(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(form): Form<EmailRecipientForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/email");
+
+    let address = form.address.trim();
+    let message = match email::valid_address(address) {
+        false => Some(format!("'{}' isn't an email address", address)),
+        true => match db::add_email_recipient(&app_state.pool, service_id, address).await {
+            Ok(_) => {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::AddRecipient,
+                    serde_json::json!({ "email": { "before": null, "after": address } }),
+                )
+                .await;
+                None
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error adding email recipient | {}", e);
+                Some("Adding recipient failed. See logs.".to_string())
+            }
+        },
+    };
+
+    notifications_panel(&app_state, service_id, message).await
+}
+
+pub async fn delete_email_recipient(
+    State(app_state): State<AppState>,
+    Path((service_id, recipient_id)): Path<(i64, i64)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id/email/:recipient_id");
+
+    let before = db::get_email_recipients(&app_state.pool, service_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|r| r.id == recipient_id);
+    let message = match db::delete_email_recipient(&app_state.pool, service_id, recipient_id).await
+    {
+        Ok(_) => {
+            if let Some(recipient) = before {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::RemoveRecipient,
+                    serde_json::json!({ "email": { "before": recipient.address, "after": null } }),
+                )
+                .await;
+            }
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting email recipient | {}", e);
+            Some("Removing recipient failed. See logs.".to_string())
+        }
+    };
+
+    notifications_panel(&app_state, service_id, message).await
+}
+
+#[derive(Deserialize)]
+pub struct PushTargetForm {
+    channel: String,
+    target: String,
+}
+
+pub async fn add_push_target(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(form): Form<PushTargetForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/push");
+
+    let validated = form.channel.parse::<Channel>().and_then(|channel| {
+        push::validate(&app_state.config.push, channel, &form.target).map(|t| (channel, t))
+    });
+    let message = match validated {
+        Err(e) => Some(e.to_string()),
+        Ok((channel, target)) => {
+            match db::add_push_target(&app_state.pool, service_id, channel.as_str(), &target).await
+            {
+                Ok(_) => {
+                    audit::record(
+                        &app_state.pool,
+                        &actor,
+                        Some(service_id),
+                        AuditAction::AddRecipient,
+                        serde_json::json!({ channel.as_str(): { "before": null, "after": target } }),
+                    )
+                    .await;
+                    None
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "Error adding push target | {}", e);
+                    Some("Adding recipient failed. See logs.".to_string())
+                }
+            }
+        }
+    };
+
+    notifications_panel(&app_state, service_id, message).await
+}
+
+pub async fn delete_push_target(
+    State(app_state): State<AppState>,
+    Path((service_id, target_id)): Path<(i64, i64)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id/push/:target_id");
+
+    let before = db::get_push_targets(&app_state.pool, service_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|t| t.id == target_id);
+    let message = match db::delete_push_target(&app_state.pool, service_id, target_id).await {
+        Ok(_) => {
+            if let Some(target) = before {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::RemoveRecipient,
+                    serde_json::json!({ target.channel: { "before": target.target, "after": null } }),
+                )
+                .await;
+            }
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting push target | {}", e);
+            Some("Removing recipient failed. See logs.".to_string())
+        }
+    };
+
+    notifications_panel(&app_state, service_id, message).await
+}
+
+// each ticked box arrives as `rule=<channel>:<event>`
+pub async fn save_notification_rules(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(form): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/notification-rules");
+
+    let ticked = |channel: &str, event: &str| {
+        let value = format!("{}:{}", channel, event);
+        form.iter().any(|(k, v)| k == "rule" && *v == value)
+    };
+    let after: Vec<NotificationRule> = CHANNELS
+        .iter()
+        .map(|channel| NotificationRule {
+            channel: channel.to_string(),
+            deployed: ticked(channel, "deployed"),
+            deploy_failed: ticked(channel, "deploy_failed"),
+            health: ticked(channel, "health"),
+            job_failed: ticked(channel, "job_failed"),
+        })
+        .collect();
+    let before = db::get_notification_rules(&app_state.pool, service_id)
+        .await
+        .map(notify::rules)
+        .unwrap_or_default();
+
+    let mut message = None;
+    for rule in &after {
+        if let Err(e) = db::set_notification_rule(&app_state.pool, service_id, rule).await {
+            event!(Level::ERROR, "Error saving notification rule | {}", e);
+            message = Some("Saving notification rules failed. See logs.".to_string());
+            break;
+        }
+    }
+    if message.is_none() && before != after {
+        audit::record(
+            &app_state.pool,
+            &actor,
+            Some(service_id),
+            AuditAction::NotificationRules,
+            serde_json::json!({ "rules": { "before": before, "after": after } }),
+        )
+        .await;
+    }
+
+    notifications_panel(&app_state, service_id, message).await
+}
+
+// runs shown under a service's jobs
+const JOB_RUNS_SHOWN: i64 = 30;
+
+pub async fn service_jobs(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/jobs");
+
+    jobs_panel(&app_state, service_id, None).await
+}
+
+async fn jobs_panel(app_state: &AppState, service_id: i64, message: Option<String>) -> Markup {
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let jobs = db::get_service_jobs(&app_state.pool, service_id).await;
+    let runs = db::get_job_runs(&app_state.pool, service_id, JOB_RUNS_SHOWN).await;
+    html::jobs(service, jobs, runs, &app_state.running_jobs, message)
+}
+
+#[derive(Deserialize)]
+pub struct JobForm {
+    name: String,
+    schedule: String,
+    mode: String,
+    container: Option<String>,
+    command: String,
+}
+
+pub async fn add_service_job(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(form): Form<JobForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/jobs");
+
+    let job = ServiceJob {
+        id: 0, // NOT USED
+        service_id,
+        name: form.name.trim().to_string(),
+        schedule: form.schedule.trim().to_string(),
+        mode: form.mode,
+        container: non_empty(form.container).map(|c| c.trim().to_string()),
+        command: form.command.trim().to_string(),
+        active: true,
+    };
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(service) => service,
+        Err(e) => return jobs_panel(&app_state, service_id, Some(e.to_string())).await,
+    };
+    let taken = db::get_service_jobs(&app_state.pool, service_id)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .any(|j| j.name == job.name);
+
+    let errors = job.field_errors(&service);
+    let message = if let Some((_, e)) = errors.first() {
+        Some(e.to_string())
+    } else if taken {
+        Some(format!(
+            "{} already has a job named '{}'",
+            service.name, job.name
+        ))
+    } else {
+        match db::new_service_job(&app_state.pool, &job).await {
+            Ok(_) => {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::AddJob,
+                    serde_json::json!({ "job": { "before": null, "after": job } }),
+                )
+                .await;
+                None
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error adding job | {}", e);
+                Some("Adding job failed. See logs.".to_string())
+            }
+        }
+    };
+
+    jobs_panel(&app_state, service_id, message).await
+}
+
+pub async fn delete_service_job(
+    State(app_state): State<AppState>,
+    Path((service_id, job_id)): Path<(i64, i64)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id/jobs/:job_id");
+
+    let before = db::get_service_job(&app_state.pool, service_id, job_id)
+        .await
+        .ok();
+    let message = match db::delete_service_job(&app_state.pool, service_id, job_id).await {
+        Ok(_) => {
+            if let Some(job) = before {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::RemoveJob,
+                    serde_json::json!({ "job": { "before": job, "after": null } }),
+                )
+                .await;
+            }
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting job | {}", e);
+            Some("Removing job failed. See logs.".to_string())
+        }
+    };
+
+    jobs_panel(&app_state, service_id, message).await
+}
+
+#[derive(Deserialize)]
+pub struct JobActiveForm {
+    active: bool,
+}
+
+pub async fn set_service_job_active(
+    State(app_state): State<AppState>,
+    Path((service_id, job_id)): Path<(i64, i64)>,
+    actor: Actor,
+    Form(form): Form<JobActiveForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/jobs/:job_id/active");
+
+    let message =
+        match db::set_service_job_active(&app_state.pool, service_id, job_id, form.active).await {
+            Ok(_) => {
+                audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service_id),
+                AuditAction::EditJob,
+                serde_json::json!({ "active": { "before": !form.active, "after": form.active } }),
+            )
+            .await;
+                None
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error pausing or resuming job | {}", e);
+                Some("Updating job failed. See logs.".to_string())
+            }
+        };
+
+    jobs_panel(&app_state, service_id, message).await
+}
+
+pub async fn run_service_job(
+    State(app_state): State<AppState>,
+    Path((service_id, job_id)): Path<(i64, i64)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/jobs/:job_id/run");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let job = db::get_service_job(&app_state.pool, service_id, job_id).await;
+    let message = match (service, job) {
+        (Ok(service), Ok(job)) => {
+            let name = job.name.clone();
+            match app_state.spawn_job(service, job, "manual") {
+                true => {
+                    audit::record(
+                        &app_state.pool,
+                        &actor,
+                        Some(service_id),
+                        AuditAction::RunJob,
+                        serde_json::json!({ "job": name }),
+                    )
+                    .await;
+                    None
+                }
+                false => Some(format!("Job {} is already running", name)),
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => Some(e.to_string()),
+    };
+
+    jobs_panel(&app_state, service_id, message).await
+}
+
+pub async fn service_env_editor(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/service/:id/env");
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let env_vars = db::get_service_env(&app_state.pool, service_id).await;
+
+    html::env_editor(service, env_vars, None)
+}
+
+#[derive(Deserialize)]
+pub struct ServiceEnvForm {
+    key: String,
+    value: String,
+    secret: Option<bool>,
+}
+
+pub async fn set_service_env(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+    Form(env_form): Form<ServiceEnvForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/env");
+
+    let key = env_form.key.trim().to_string();
+    let secret = env_form.secret.unwrap_or(false);
+    let value = match (secret, &app_state.config.secrets_key) {
+        (false, _) => Ok(env_form.value),
+        (true, Some(k)) => k.encrypt(&env_form.value),
+        (true, None) => Err(SecretError::NoKey),
+    };
+
+    let message = match (ServiceEnv::valid_key(&key), value) {
+        (false, _) => Some(format!(
+            "Invalid variable name '{}': use letters, digits, and underscores",
+            key
+        )),
+        (true, Err(e)) => {
+            event!(Level::ERROR, "Error encrypting service env var | {}", e);
+            Some(format!("Unable to store secret | {}", e))
+        }
+        (true, Ok(v)) => {
+            let before = db::get_service_env(&app_state.pool, service_id)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .find(|var| var.key == key)
+                .map(|var| var.display_value().to_string());
+            let after = match secret {
+                true => MASK.to_string(),
+                false => v.clone(),
+            };
+            let changes = audit::env_change(&key, before, Some(after));
+            match db::set_service_env(&app_state.pool, service_id, key, v, secret).await {
+                Ok(_) => {
+                    audit::record(
+                        &app_state.pool,
+                        &actor,
+                        Some(service_id),
+                        AuditAction::SetEnv,
+                        changes,
+                    )
+                    .await;
+                    None
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "Error saving service env var | {}", e);
+                    Some("Saving variable failed. See logs.".to_string())
+                }
+            }
+        }
+    };
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let env_vars = db::get_service_env(&app_state.pool, service_id).await;
+
+    html::env_editor(service, env_vars, message)
+}
+
+pub async fn delete_service_env(
+    State(app_state): State<AppState>,
+    Path((service_id, env_id)): Path<(i64, i64)>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/service/:id/env/:env_id");
+
+    let before = db::get_service_env(&app_state.pool, service_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|var| var.id == env_id);
+    let message = match db::delete_service_env(&app_state.pool, service_id, env_id).await {
+        Ok(_) => {
+            if let Some(var) = before {
+                audit::record(
+                    &app_state.pool,
+                    &actor,
+                    Some(service_id),
+                    AuditAction::DeleteEnv,
+                    audit::env_change(&var.key, Some(var.display_value().to_string()), None),
+                )
+                .await;
+            }
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting service env var | {}", e);
+            Some("Deleting variable failed. See logs.".to_string())
+        }
+    };
+
+    let service = db::get_service(&app_state.pool, service_id).await;
+    let env_vars = db::get_service_env(&app_state.pool, service_id).await;
+
+    html::env_editor(service, env_vars, message)
+}
+
+pub async fn deploy_keys(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/deploy_keys");
+
+    deploy_key::html::manager(db::get_deploy_keys(&app_state.pool).await, None)
+}
+
+#[derive(Deserialize)]
+pub struct DeployKeyForm {
+    name: String,
+}
+
+pub async fn generate_deploy_key(
+    State(app_state): State<AppState>,
+    Form(key_form): Form<DeployKeyForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/deploy_key");
+
+    let name = key_form.name.trim().to_string();
+    let message = match DeployKey::generate(&app_state.config.deploy_keys_dir, &name) {
+        Ok((key_path, public_key)) => {
+            match db::new_deploy_key(
+                &app_state.pool,
+                name,
+                public_key,
+                key_path.to_string_lossy().to_string(),
+            )
+            .await
+            {
+                Ok(_) => None,
+                Err(e) => {
+                    event!(Level::ERROR, "Error saving deploy key | {}", e);
+                    Some("Saving key failed. See logs.".to_string())
+                }
+            }
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error generating deploy key | {}", e);
+            Some(format!("Generating key failed | {}", e))
+        }
+    };
+
+    deploy_key::html::manager(db::get_deploy_keys(&app_state.pool).await, message)
+}
+
+pub async fn delete_deploy_key(
+    State(app_state): State<AppState>,
+    Path(key_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/deploy_key/:id");
+
+    let message = match db::get_deploy_key(&app_state.pool, key_id).await {
+        Ok(key) => match db::delete_deploy_key(&app_state.pool, key_id).await {
+            Ok(_) => {
+                key.try_delete_files();
+                None
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error deleting deploy key | {}", e);
+                Some("Deleting key failed. See logs.".to_string())
+            }
+        },
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get deploy key from DB | {}", e);
+            Some("Unable to find key.".to_string())
+        }
+    };
+
+    deploy_key::html::manager(db::get_deploy_keys(&app_state.pool).await, message)
+}
+
+pub async fn nodes(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/nodes");
+
+    let nodes = db::get_nodes(&app_state.pool)
+        .await
+        .map(|ns| app_state.agents.mark(ns));
+    agent::html::manager(nodes, None, None)
+}
+
+#[derive(Deserialize)]
+pub struct NodeForm {
+    name: String,
+}
+
+pub async fn add_node(
+    State(app_state): State<AppState>,
+    Form(node_form): Form<NodeForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/node");
+
+    let name = node_form.name.trim().to_string();
+    let (message, token) = match Node::valid_name(&name) {
+        false => (
+            Some("Node names may only contain letters, digits, '-' and '_'".to_string()),
+            None,
+        ),
+        true => match user::new_session_token() {
+            Ok((token, token_hash)) => {
+                match db::new_node(&app_state.pool, name, token_hash).await {
+                    Ok(_) => (None, Some(token)),
+                    Err(e) => {
+                        event!(Level::ERROR, "Error saving node | {}", e);
+                        (Some("Saving node failed. See logs.".to_string()), None)
+                    }
+                }
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error generating node token | {}", e);
+                (Some("Generating token failed. See logs.".to_string()), None)
+            }
+        },
+    };
+
+    let nodes = db::get_nodes(&app_state.pool)
+        .await
+        .map(|ns| app_state.agents.mark(ns));
+    agent::html::manager(nodes, message, token)
+}
+
+pub async fn delete_node(
+    State(app_state): State<AppState>,
+    Path(node_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/node/:id");
+
+    let message = match db::delete_node(&app_state.pool, node_id).await {
+        Ok(_) => None,
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting node | {}", e);
+            Some("Deleting node failed. See logs.".to_string())
+        }
+    };
+
+    let nodes = db::get_nodes(&app_state.pool)
+        .await
+        .map(|ns| app_state.agents.mark(ns));
+    agent::html::manager(nodes, message, None)
+}
+
+pub async fn groups(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/groups");
+
+    group::html::manager(db::get_groups(&app_state.pool).await, None)
+}
+
+#[derive(Deserialize)]
+pub struct GroupForm {
+    name: String,
+}
+
+pub async fn add_group(
+    State(app_state): State<AppState>,
+    Form(group_form): Form<GroupForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/group");
+
+    let name = group_form.name.trim().to_string();
+    let message = match name.is_empty() {
+        true => Some("Group name is required".to_string()),
+        false => match db::new_group(&app_state.pool, name).await {
+            Ok(_) => None,
+            Err(db::DBError::Sql(sqlx::Error::Database(e))) if e.is_unique_violation() => {
+                Some("A group with that name already exists".to_string())
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Error saving group | {}", e);
+                Some("Saving group failed. See logs.".to_string())
+            }
+        },
+    };
+
+    group::html::manager(db::get_groups(&app_state.pool).await, message)
+}
+
+pub async fn delete_group(
+    State(app_state): State<AppState>,
+    Path(group_id): Path<i64>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/group/:id");
+
+    let message = match db::delete_group(&app_state.pool, group_id).await {
+        Ok(_) => {
+            let _ = app_state
+                .service_broadcast
+                .broadcaster
+                .send(ServiceEvent::AllStatus);
+            None
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error deleting group | {}", e);
+            Some("Deleting group failed. See logs.".to_string())
+        }
+    };
+
+    group::html::manager(db::get_groups(&app_state.pool).await, message)
+}
+
+pub async fn deploy_group(
+    State(app_state): State<AppState>,
+    Path(group_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/group/:id/deploy");
+
+    let filter = ServiceFilter {
+        group: Some(group_id),
+        ..Default::default()
+    };
+    match app_state.deploy_all(&filter, &actor).await {
+        Ok(_) => "OK".into_response(),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to deploy group | {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Unable to deploy group").into_response()
+        }
+    }
+}
+
+// deploys every active service, or those the dashboard is filtered to
+pub async fn deploy_all(
+    State(app_state): State<AppState>,
+    actor: Actor,
+    Form(filter): Form<DashboardFilter>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/deploy_all");
+
+    match app_state.deploy_all(&filter.into(), &actor).await {
+        Ok(_) => "OK".into_response(),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to deploy all services | {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Unable to deploy services",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn archived_services(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/archived_services");
+
+    html::archived_list(db::get_services(&app_state.pool, Some(true)).await)
+}
+
+pub async fn archive_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/archive");
+
+    match db::set_service_archived(&app_state.pool, service_id, true).await {
+        Ok(_) => {
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service_id),
+                AuditAction::Archive,
+                serde_json::json!({ "archived": { "before": false, "after": true } }),
+            )
+            .await
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Error archiving service | {}", e);
+            return "ARCHIVE FAILED";
+        }
+    }
+
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+
+    "OK"
+}
+
+pub async fn restore_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/service/:id/restore");
+
+    match db::set_service_archived(&app_state.pool, service_id, false).await {
+        Ok(_) => {
+            audit::record(
+                &app_state.pool,
+                &actor,
+                Some(service_id),
+                AuditAction::Restore,
+                serde_json::json!({ "archived": { "before": true, "after": false } }),
+            )
+            .await
+        }
+        Err(e) => event!(Level::ERROR, "Error restoring service | {}", e),
+    }
+
+    let _ = app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus);
+
+    html::archived_list(db::get_services(&app_state.pool, Some(true)).await)
+}
+
+pub async fn users(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/users");
+
+    user::html::manager(db::get_users(&app_state.pool).await, None)
+}
+
+#[derive(Deserialize)]
+pub struct UserForm {
+    username: String,
+    password: String,
+    role: String,
+}
+
+pub async fn create_user(
+    State(app_state): State<AppState>,
+    Form(user_form): Form<UserForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/user");
+
+    let username = user_form.username.trim().to_string();
+    // the first account must be able to manage the rest, so it is always an admin
+    let role = match db::count_users(&app_state.pool).await {
+        Ok(0) => Ok(Role::Admin),
+        _ => user_form.role.parse::<Role>(),
+    };
+    let password = user_form.password;
+    let password_hash = tokio::task::spawn_blocking(move || user::hash_password(&password))
+        .await
+        .unwrap_or(Err(user::UserError::Password));
+
+    let message = match (User::valid_username(&username), role, password_hash) {
+        (false, _, _) => Some(user::UserError::Name.to_string()),
+        (true, Err(e), _) | (true, _, Err(e)) => Some(e.to_string()),
+        (true, Ok(role), Ok(hash)) => {
+            match db::new_user(&app_state.pool, username, hash, role).await {
+                Ok(_) => None,
+                Err(e) => {
+                    event!(Level::ERROR, "Error saving user | {}", e);
+                    Some("Saving user failed. Is the username taken?".to_string())
+                }
+            }
+        }
+    };
+
+    user::html::manager(db::get_users(&app_state.pool).await, message)
+}
+
+pub async fn delete_user(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<i64>,
+    current: Option<Extension<User>>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "DELETE /api/user/:id");
+
+    let message = match current {
+        Some(Extension(u)) if u.id == user_id => {
+            Some("You can't delete your own account.".to_string())
+        }
+        _ => match db::delete_user(&app_state.pool, user_id).await {
+            Ok(_) => None,
+            Err(e) => {
+                event!(Level::ERROR, "Error deleting user | {}", e);
+                Some("Deleting user failed. See logs.".to_string())
+            }
+        },
+    };
+
+    user::html::manager(db::get_users(&app_state.pool).await, message)
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    service_id: Option<i64>,
+}
+
+pub async fn audit_log(
+    State(app_state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/audit_log");
+
+    audit::html::log(db::get_audit_log(&app_state.pool, query.service_id, 200).await)
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    pub service_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+pub async fn activity_feed(
+    State(app_state): State<AppState>,
+    Query(query): Query<ActivityQuery>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/activity");
+
+    let limit = activity::limit(query.limit);
+    activity::html::feed(db::get_activity(&app_state.pool, query.service_id, limit).await)
+}
+
+// sent by a browser reconnecting to a live stream
+fn last_event_id(headers: &HeaderMap) -> Option<u64> {
+    headers.get("Last-Event-ID")?.to_str().ok()?.parse().ok()
+}
+
+pub async fn live_services(
+    State(app_state): State<AppState>,
+    Query(filter): Query<DashboardFilter>,
+    role: Role,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    event!(Level::INFO, "SSE /html/live_services");
+
+    let stream = app_state.event_stream(role, filter.into(), last_event_id(&headers));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// the same events as the dashboard, narrowed to one service
+pub async fn live_service(
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    role: Role,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    event!(Level::INFO, "SSE /html/live_services/:id");
+
+    let view = ListView {
+        filter: ServiceFilter {
+            service: Some(service_id),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let stream = app_state.event_stream(role, view, last_event_id(&headers));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// public like the status page, for embedding in a repo's README
+pub async fn badge(
+    State(app_state): State<AppState>,
+    Path(file): Path<String>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /badge/:name.svg");
+
+    let found = match file.strip_suffix(".svg") {
+        Some(name) => statuspage::badge::service_badge(&app_state, name).await,
+        None => Ok(None),
+    };
+    let (status, svg) = match found {
+        Ok(Some(svg)) => (StatusCode::OK, svg),
+        Ok(None) => (StatusCode::NOT_FOUND, statuspage::badge::not_found()),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to render badge | {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                statuspage::badge::unavailable(),
+            )
+        }
+    };
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "no-cache, max-age=0"),
+        ],
+        svg.into_string(),
+    )
+}
+
+pub async fn atom_feed(State(app_state): State<AppState>) -> Response {
+    event!(Level::INFO, "GET /feed.atom");
+
+    match statuspage::feed::feed(&app_state).await {
+        Ok(feed) => (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            feed.into_string(),
+        )
+            .into_response(),
+        Err(e) => {
+            event!(Level::ERROR, "Unable to render feed | {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn statuspage() -> impl IntoResponse {
+    event!(Level::INFO, "GET /statuspage");
+    statuspage::html::page()
+}
+
+pub async fn live_statuspage(
+    State(app_state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    event!(Level::INFO, "SSE /statuspage/live");
+    Sse::new(statuspage::stream(app_state)).keep_alive(KeepAlive::default())
+}
+
+pub async fn all_status_request(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /api/all_status");
+
+    match app_state
+        .service_broadcast
+        .broadcaster
+        .send(ServiceEvent::AllStatus)
+    {
+        Ok(_) => "OK",
+        Err(e) => {
+            event!(Level::ERROR, "All status request failed | {}", e);
+            "SIGNAL FAILED"
+        }
+    }
+}
+
+pub async fn maintenance_panel(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/maintenance");
+
+    maintenance::html::panel(app_state.config.prune_schedule.clone())
+}
+
+// compose projects on the local host that no service manages
+async fn unmanaged_projects(app_state: &AppState) -> Result<Vec<Unmanaged>, String> {
+    let (entries, services) = tokio::join!(
+        app_state.docker_cache.list(&[None]),
+        db::get_services(&app_state.pool, None),
+    );
+    let entries = entries.map_err(|e| e.to_string())?;
+    let services = services.map_err(|e| e.to_string())?;
+    Ok(adopt::unmanaged(&entries, &services))
+}
+
+pub async fn adopt_panel(State(app_state): State<AppState>) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/adopt");
+
+    maintenance::html::adopt(unmanaged_projects(&app_state).await)
+}
+
+// the new-service form filled in to take over a running compose project
+pub async fn adopt_form(
+    State(app_state): State<AppState>,
+    Path(project): Path<String>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "GET /html/adopt/:project");
+
+    let found = unmanaged_projects(&app_state)
+        .await
+        .map(|projects| projects.into_iter().find(|p| p.project == project));
+    let unmanaged = match found {
+        Ok(Some(unmanaged)) => unmanaged,
+        Ok(None) => {
+            return Markup::trusted(
+                "<div class=\"error\">That project is no longer running unmanaged.</div>",
+            );
+        }
+        Err(e) => {
+            event!(Level::ERROR, "Unable to list compose projects | {}", e);
+            return Markup::trusted("<div class=\"error\">Unable to list compose projects.</div>");
+        }
+    };
+    let mut service = Service::from(ServiceForm::default());
+    unmanaged.prefill(&mut service);
+
+    html::new_form(
+        service,
+        None,
+        FieldErrors::default(),
+        form_choices(&app_state, None).await,
+        Prefill::Blank,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PruneForm {
+    dry_run: Option<bool>,
+}
+
+pub async fn prune(
+    State(app_state): State<AppState>,
+    actor: Actor,
+    Form(prune_form): Form<PruneForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/maintenance/prune");
+
+    let dry_run = prune_form.dry_run.unwrap_or(false);
+    maintenance::html::report(maintenance::run_prune(&app_state, &actor, dry_run).await)
+}
+
+// removes repo and live directories left behind by renamed or deleted services
+pub async fn remove_orphans(
+    State(app_state): State<AppState>,
+    actor: Actor,
+    Form(prune_form): Form<PruneForm>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "POST /api/maintenance/orphans");
+
+    let dry_run = prune_form.dry_run.unwrap_or(false);
+    maintenance::html::orphans(maintenance::orphans::collect(&app_state, &actor, dry_run).await)
 }
@@ -0,0 +1,331 @@
+use crate::modules::{
+    AppState,
+    agent::{Node, Report},
+    audit::{self, Actor, AuditAction},
+    db,
+    service::ServiceEvent,
+    user,
+};
+
+use axum::{
+    extract::{
+        Path, Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Child;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, event};
+
+use super::{api::view, csrf};
+
+pub async fn live_services(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    event!(Level::INFO, "WS /ws/live_services");
+
+    ws.on_upgrade(move |socket| event_socket(socket, app_state))
+}
+
+// the JSON counterpart of the SSE `AllStatus` render
+async fn all_status(app_state: &AppState) -> String {
+    match db::get_services(&app_state.pool, Some(false)).await {
+        Ok(services) => {
+            json!({ "type": "AllStatus", "services": view(services, app_state).await })
+        }
+        Err(e) => json!({ "type": "UnknownEvent", "msg": e.to_string() }),
+    }
+    .to_string()
+}
+
+async fn event_socket(mut socket: WebSocket, app_state: AppState) {
+    let mut receiver = app_state.service_broadcast.subscribe();
+
+    if socket
+        .send(Message::text(all_status(&app_state).await))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            received = receiver.recv() => {
+                let payload = match received {
+                    Ok(ServiceEvent::AllStatus) => all_status(&app_state).await,
+                    Ok(ServiceEvent::Shutdown) => {
+                        let _ = socket.send(Message::text(json!({ "type": "Shutdown" }).to_string())).await;
+                        let _ = socket.send(Message::Close(None)).await;
+                        break;
+                    }
+                    Ok(service_event) => match serde_json::to_string(&service_event) {
+                        Ok(p) => {
+                            if matches!(service_event, ServiceEvent::ServiceUpdate { .. }) {
+                                app_state.docker_cache.invalidate();
+                            }
+                            p
+                        }
+                        Err(e) => {
+                            event!(Level::ERROR, "Unable to serialize service event | {}", e);
+                            continue;
+                        }
+                    },
+                    // a full status replaces whatever was missed
+                    Err(RecvError::Lagged(dropped)) => {
+                        event!(
+                            Level::WARN,
+                            "Live services socket fell behind by {} events, re-syncing",
+                            dropped
+                        );
+                        all_status(&app_state).await
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if socket.send(Message::text(payload)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => (),
+            },
+        }
+    }
+}
+
+// agents authenticate with their node's token and then receive jobs over the socket
+pub async fn agent_connect(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    event!(Level::INFO, "WS /agent/connect");
+
+    let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let node = match db::get_node_by_token(&app_state.pool, &user::session_token_hash(token)).await
+    {
+        Ok(n) => n,
+        Err(e) => {
+            event!(Level::WARN, "Refused agent with an unknown token | {}", e);
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    };
+
+    ws.on_upgrade(move |socket| agent_socket(socket, app_state, node))
+        .into_response()
+}
+
+async fn agent_socket(mut socket: WebSocket, app_state: AppState, node: Node) {
+    event!(Level::INFO, "Node '{}' connected", node.name);
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let broadcaster = app_state.service_broadcast.broadcaster.clone();
+    let (link_id, mut jobs) = app_state.agents.connect(node.id);
+    if let Err(e) = db::touch_node(&app_state.pool, node.id, None).await {
+        event!(Level::ERROR, "Unable to update node | {}", e);
+    }
+    let _ = broadcaster.send(ServiceEvent::AllStatus);
+
+    loop {
+        tokio::select! {
+            job = jobs.recv() => {
+                // None when a newer connection from this node took over
+                let Some(job) = job else { break };
+                let payload = match serde_json::to_string(&job) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        event!(Level::ERROR, "Unable to serialize job | {}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::text(payload)).await.is_err() {
+                    break;
+                }
+            }
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+                _ => (),
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Report>(&text) {
+                    Ok(Report::Hello { hostname }) => {
+                        if let Err(e) = db::touch_node(&app_state.pool, node.id, Some(hostname)).await {
+                            event!(Level::ERROR, "Unable to update node | {}", e);
+                        }
+                    }
+                    Ok(Report::Status { service_id, status }) => {
+                        if let Some(request_id) = app_state.agents.running_job(node.id, service_id) {
+                            let _ = broadcaster.send(ServiceEvent::ServiceUpdate { id: service_id, status, request_id });
+                        }
+                    }
+                    Ok(Report::Containers { containers }) => {
+                        app_state.agents.set_containers(node.id, containers);
+                        if let Err(e) = db::touch_node(&app_state.pool, node.id, None).await {
+                            event!(Level::ERROR, "Unable to update node | {}", e);
+                        }
+                    }
+                    Ok(Report::Finished { id, outcome }) => app_state.agents.finish(node.id, id, outcome),
+                    Err(e) => event!(Level::WARN, "Unreadable report from node '{}' | {}", node.name, e),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => (),
+            },
+        }
+    }
+
+    app_state.agents.disconnect(node.id, link_id);
+    event!(Level::INFO, "Node '{}' disconnected", node.name);
+    let _ = broadcaster.send(ServiceEvent::AllStatus);
+}
+
+#[derive(Deserialize)]
+pub struct TerminalQuery {
+    // the compose service to exec into
+    container: String,
+}
+
+// a shell in one of the service's containers, for as long as the socket stays open
+pub async fn terminal(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+    Path(service_id): Path<i64>,
+    Query(query): Query<TerminalQuery>,
+    headers: HeaderMap,
+    actor: Actor,
+) -> impl IntoResponse {
+    event!(Level::INFO, "WS /ws/service/:id/terminal");
+
+    if !csrf::same_host(&headers) {
+        return (StatusCode::FORBIDDEN, "Cross-site request refused").into_response();
+    }
+    let service = match db::get_service(&app_state.pool, service_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            event!(Level::ERROR, "Unable to get service from DB | {}", e);
+            return (StatusCode::NOT_FOUND, "Service not found").into_response();
+        }
+    };
+    let child = match service.shell(&app_state.config, &query.container) {
+        Ok(c) => c,
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "Unable to open a terminal in {} | {}",
+                service.name,
+                e
+            );
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+    audit::record(
+        &app_state.pool,
+        &actor,
+        Some(service_id),
+        AuditAction::Terminal,
+        json!({ "container": query.container }),
+    )
+    .await;
+
+    ws.on_upgrade(move |socket| terminal_socket(socket, app_state, child))
+        .into_response()
+}
+
+async fn terminal_socket(mut socket: WebSocket, app_state: AppState, mut child: Child) {
+    let (Some(mut stdin), Some(mut stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        return;
+    };
+    let mut receiver = app_state.service_broadcast.subscribe();
+    let mut out = [0u8; 4096];
+    let mut err = [0u8; 4096];
+    // bytes of a character split across reads, per stream
+    let mut out_tail = Vec::new();
+    let mut err_tail = Vec::new();
+
+    loop {
+        // the prompt and error messages of a shell without a TTY go to stderr
+        let read = tokio::select! {
+            read = stdout.read(&mut out) => read.map(|n| (&out[..n], &mut out_tail)),
+            read = stderr.read(&mut err) => read.map(|n| (&err[..n], &mut err_tail)),
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    if stdin.write_all(text.as_bytes()).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => continue,
+            },
+            received = receiver.recv() => match received {
+                Ok(ServiceEvent::Shutdown) | Err(RecvError::Closed) => break,
+                _ => continue,
+            },
+        };
+        // the shell exited
+        let Ok((bytes @ [_, ..], tail)) = read else {
+            break;
+        };
+        tail.extend_from_slice(bytes);
+        let text = take_utf8(tail);
+        if text.is_empty() {
+            continue;
+        }
+        if socket.send(Message::text(text)).await.is_err() {
+            break;
+        }
+    }
+
+    let _ = socket.send(Message::Close(None)).await;
+    let _ = child.kill().await;
+}
+
+// decodes `pending`, leaving a trailing incomplete character there for the next read;
+// bytes that can never be UTF-8 become U+FFFD
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut rest = &pending[..];
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                text.push_str(&String::from_utf8_lossy(valid));
+                match e.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[len..];
+                    }
+                    // cut off mid-character
+                    None => {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    *pending = rest.to_vec();
+    text
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+# Vendors the dashboard's third-party assets so they are embedded in the binary.
+# Run from the repo root before building for hosts without outbound internet.
+set -eu
+
+cd "$(dirname "$0")"
+
+curl -fsSL -o htmx.min.js https://cdn.jsdelivr.net/npm/htmx.org@2.0.8/dist/htmx.min.js
+curl -fsSL -o htmx-ext-sse.js https://cdn.jsdelivr.net/npm/htmx-ext-sse@2.2.4
+curl -fsSL -o ibm-plex-mono-latin-400-normal.woff2 \
+    https://cdn.jsdelivr.net/npm/@fontsource/ibm-plex-mono@5.2.5/files/ibm-plex-mono-latin-400-normal.woff2